@@ -0,0 +1,45 @@
+//! `BitSet::is_disjoint` against the plain word loop it replaced, on disjoint sets so
+//! neither can stop early. `cargo bench --no-default-features --bench bitset`
+
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use wave_function_collapse::bitset::BitSet;
+
+/// Tile counts, the chunked loop only kicks in from 512 tiles (8 words)
+const SIZES: [usize; 4] = [64, 512, 2048, 8192];
+
+fn words(size: usize, indexes: impl Iterator<Item = usize>) -> Vec<u64> {
+    let mut words = vec![0u64; size.div_ceil(64)];
+
+    for index in indexes {
+        words[index / 64] |= 1 << (index % 64);
+    }
+
+    words
+}
+
+fn is_disjoint_scalar(left: &[u64], right: &[u64]) -> bool {
+    left.iter().zip(right.iter()).all(|(a, b)| a & b == 0)
+}
+
+fn is_disjoint(c: &mut Criterion) {
+    let mut group = c.benchmark_group("is_disjoint");
+
+    for size in SIZES {
+        let even = BitSet::from_indexes(size, (0..size).step_by(2));
+        let odd = BitSet::from_indexes(size, (1..size).step_by(2));
+        let even_words = words(size, (0..size).step_by(2));
+        let odd_words = words(size, (1..size).step_by(2));
+
+        group.bench_with_input(BenchmarkId::new("chunked", size), &size, |b, _| {
+            b.iter(|| black_box(&even).is_disjoint(black_box(&odd)))
+        });
+        group.bench_with_input(BenchmarkId::new("scalar", size), &size, |b, _| {
+            b.iter(|| is_disjoint_scalar(black_box(&even_words), black_box(&odd_words)))
+        });
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, is_disjoint);
+criterion_main!(benches);
@@ -0,0 +1,54 @@
+//! Explicit collapses of a cell with every tile of a large tileset still possible, with
+//! the weights looked up in a table raised by the temperature once (what the wave does)
+//! and raised on every collapse (what it did before). `cached` draws from the running
+//! totals the clones share, what the wave does without a weight modifier.
+//! `cargo bench --no-default-features --features image --bench collapse`
+
+use std::sync::Arc;
+
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use rand::SeedableRng;
+use rand_xorshift::XorShiftRng;
+use wave_function_collapse::superstate::{Collapsable, SuperState};
+use wave_function_collapse::tile::Tile;
+
+const TILES: usize = 512;
+const TEMPERATURE: f64 = 0.7;
+
+fn collapse(c: &mut Criterion) {
+    let mut group = c.benchmark_group("collapse_512");
+    let mut tiles: Vec<Tile<()>> = (0..TILES).map(|id| Tile::new(id as u64, ())).collect();
+
+    for (id, tile) in tiles.iter_mut().enumerate() {
+        tile.weight = id % 7 + 1;
+    }
+
+    let mut base = SuperState::new(tiles.into_iter().map(Arc::new).collect());
+
+    base.assign_indexes(|tile| tile.get_id() as usize);
+
+    // kept from being folded into the closures
+    let temperature = black_box(TEMPERATURE);
+    let table: Vec<f64> = base.possible.iter().map(|tile| tile.get_weight().powf(temperature.recip())).collect();
+    let mut rng = XorShiftRng::seed_from_u64(1);
+
+    group.bench_function(BenchmarkId::new("table", TILES), |b| {
+        b.iter(|| base.clone().collapse(&mut rng, |index, _| table[index]))
+    });
+
+    group.bench_function(BenchmarkId::new("cached", TILES), |b| {
+        b.iter(|| base.clone().collapse_weights(&mut rng, &table))
+    });
+
+    group.bench_function(BenchmarkId::new("powf", TILES), |b| {
+        b.iter(|| {
+            base.clone()
+                .collapse(&mut rng, |_, tile| tile.get_weight().powf(temperature.recip()))
+        })
+    });
+
+    group.finish();
+}
+
+criterion_group!(benches, collapse);
+criterion_main!(benches);
@@ -0,0 +1,94 @@
+//! Solving a 200x200 grid from scratch, collapse by collapse. With the
+//! `threaded` feature the wavefronts are checked on a single thread and on the default
+//! pool, compare the two for the speedup and a build without it for the overhead.
+//! `cargo bench --no-default-features --features image,threaded --bench propagation`
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use enum_map::enum_map;
+use wave_function_collapse::grid::{Direction, Grid};
+use wave_function_collapse::superstate::SuperState;
+use wave_function_collapse::tile::Tile;
+use wave_function_collapse::wave::{Wave, WaveBuilder};
+
+const SIZE: usize = 200;
+/// Edge colors of the tileset, every combination is a tile so it never contradicts
+const COLORS: usize = 3;
+const SEED: u64 = 1;
+
+/// Tiles with one of `COLORS` colors on every edge, neighbors match when the touching
+/// edges have the same color, like the synthetic tilesets of the `bench` subcommand
+fn edge_tiles() -> Vec<Tile<()>> {
+    let edges: Vec<_> = (0..COLORS.pow(4))
+        .map(|id| {
+            let edge = |n: u32| id / COLORS.pow(n) % COLORS;
+
+            enum_map! {
+                Direction::Up => edge(0),
+                Direction::Right => edge(1),
+                Direction::Down => edge(2),
+                Direction::Left => edge(3),
+            }
+        })
+        .collect();
+
+    let mut tiles: Vec<Tile<()>> = (0..edges.len()).map(|id| Tile::new(id as u64, ())).collect();
+
+    for (tile, edge) in tiles.iter_mut().zip(edges.iter()) {
+        for (id, other) in edges.iter().enumerate() {
+            for (direction, color) in edge {
+                if other[direction.invert()] == *color {
+                    tile.neighbors[direction].insert(id as u64);
+                }
+            }
+        }
+    }
+
+    tiles
+}
+
+fn solve(mut wave: Wave<Tile<()>>) -> Wave<Tile<()>> {
+    while !wave.done() {
+        wave.tick();
+    }
+
+    wave
+}
+
+fn propagation(c: &mut Criterion) {
+    let mut group = c.benchmark_group("solve_200x200");
+    let base = SuperState::new(edge_tiles().into_iter().map(Arc::new).collect());
+    let builder = || WaveBuilder::new(Grid::new(SIZE, SIZE, &mut |_, _| base.clone())).seed(SEED);
+
+    // a whole solve takes close to a minute on a slow machine, take criterion's fewest
+    // samples and warm up with a single one
+    group.sample_size(10);
+    group.warm_up_time(Duration::from_secs(1));
+
+    #[cfg(not(feature = "threaded"))]
+    group.bench_function(BenchmarkId::new("sequential", 1), |b| b.iter(|| solve(builder().build())));
+
+    #[cfg(feature = "threaded")]
+    {
+        let pools = [
+            ("single", rayon::ThreadPoolBuilder::new().num_threads(1).build().unwrap()),
+            ("default", rayon::ThreadPoolBuilder::new().build().unwrap()),
+        ];
+
+        for (name, pool) in pools {
+            let pool = Arc::new(pool);
+            let threads = pool.current_num_threads();
+
+            group.bench_with_input(BenchmarkId::new(name, threads), &threads, |b, _| {
+                b.iter(|| solve(builder().thread_pool(pool.clone()).build()))
+            });
+        }
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, propagation);
+criterion_main!(benches);
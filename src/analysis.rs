@@ -0,0 +1,381 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt::Write;
+use std::sync::Arc;
+
+use enum_map::EnumMap;
+
+use crate::grid::{Direction, Grid, Size};
+use crate::superstate::{Collapsable, SuperState};
+use crate::tile::Tile;
+use crate::wave::{Propagation, WaveBuilder};
+
+/// Heaviest tiles listed in the report
+const TOP_TILES: usize = 5;
+/// Ticks per cell before a trial solve is given up on
+const TRIAL_TICKS: usize = 100;
+
+/// Rough guess at how hard a tileset is to solve, from how many neighbors tiles allow
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Difficulty {
+    Easy,
+    Moderate,
+    Hard,
+}
+
+/// A tile that can't be placed anywhere, no tile that can still be placed fits on
+/// either side of it along one axis
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DeadTile {
+    pub id: u64,
+    /// Up for the vertical axis, Left for the horizontal one
+    pub axis: Direction,
+}
+
+impl DeadTile {
+    pub fn describe<F: Fn(u64) -> String>(&self, name: F) -> String {
+        let sides = match self.axis {
+            Direction::Up | Direction::Down => "above or below",
+            Direction::Left | Direction::Right => "left or right of",
+        };
+
+        format!("{} can never be placed, no tile fits {sides} it", name(self.id))
+    }
+}
+
+/// Finds tiles that can never be placed on a grid of `size`. Without a neighbor on one
+/// side a tile can still go along the border, it's dead when it has neither side of
+/// an axis the grid is more than one cell long in. Neighbors have to allow each other,
+/// and tiles next to dead tiles only count the tiles that are left, so removing dead
+/// tiles doesn't make other tiles dead.
+pub fn dead_tiles<T: Clone + Sync + Send>(tiles: &[Tile<T>], size: &Size) -> Vec<DeadTile> {
+    let lookup: HashMap<u64, &Tile<T>> = tiles.iter().map(|tile| (tile.get_id(), tile)).collect();
+    let mut alive: HashSet<u64> = lookup.keys().copied().collect();
+    let mut dead = Vec::new();
+    let mut axes = Vec::new();
+
+    if size.height > 1 {
+        axes.push(Direction::Up);
+    }
+
+    if size.width > 1 {
+        axes.push(Direction::Left);
+    }
+
+    let fits = |alive: &HashSet<u64>, tile: &Tile<T>, direction: Direction| {
+        tile.neighbors[direction].iter().any(|id| {
+            alive.contains(id)
+                && lookup[id].neighbors[direction.invert()].contains(&tile.get_id())
+        })
+    };
+
+    loop {
+        let found: Vec<DeadTile> = tiles
+            .iter()
+            .filter(|tile| alive.contains(&tile.get_id()))
+            .filter_map(|tile| {
+                axes.iter()
+                    .find(|axis| !fits(&alive, tile, **axis) && !fits(&alive, tile, axis.invert()))
+                    .map(|axis| DeadTile {
+                        id: tile.get_id(),
+                        axis: *axis,
+                    })
+            })
+            .collect();
+
+        if found.is_empty() {
+            break;
+        }
+
+        for tile in &found {
+            alive.remove(&tile.id);
+        }
+
+        dead.extend(found);
+    }
+
+    dead
+}
+
+/// Numbers describing a tileset, computed without solving anything
+#[derive(Debug, Clone)]
+pub struct TilesetStats {
+    pub tiles: usize,
+    pub total_weight: usize,
+    pub min_weight: usize,
+    pub median_weight: usize,
+    pub max_weight: usize,
+    /// Shannon entropy of the weights in bits, the most a single cell can hold
+    pub weight_entropy: f64,
+    /// Heaviest tiles by id, heaviest first
+    pub heaviest: Vec<(u64, usize)>,
+    /// Average share of the tileset a tile allows as neighbor in each direction
+    pub density: EnumMap<Direction, f64>,
+    /// Average number of neighbors a tile allows in each direction
+    pub branching: EnumMap<Direction, f64>,
+    /// Tiles that allow exactly one neighbor in each direction
+    pub forced: EnumMap<Direction, usize>,
+    /// Forced tiles whose only neighbor is forced the same way, placing one of those
+    /// settles a line of cells at once
+    pub chained: EnumMap<Direction, usize>,
+    /// Tiles that allow no neighbor at all in each direction
+    pub closed: EnumMap<Direction, usize>,
+    pub dead: Vec<DeadTile>,
+}
+
+impl TilesetStats {
+    /// `size` is the grid the tileset would be used for, see [`dead_tiles`]
+    pub fn new<T: Clone + Sync + Send>(tiles: &[Tile<T>], size: &Size) -> Self {
+        let mut weights: Vec<(u64, usize)> = tiles
+            .iter()
+            .map(|tile| (tile.get_id(), tile.weight))
+            .collect();
+
+        weights.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+
+        let total_weight = weights.iter().map(|(_, weight)| weight).sum::<usize>();
+        let weight_entropy = weights
+            .iter()
+            .map(|(_, weight)| *weight as f64 / total_weight.max(1) as f64)
+            .filter(|p| *p > 0.0)
+            .map(|p| -p * p.log2())
+            .sum();
+
+        let lookup: HashMap<u64, &Tile<T>> = tiles.iter().map(|tile| (tile.get_id(), tile)).collect();
+        let mut density = EnumMap::default();
+        let mut branching = EnumMap::default();
+        let mut closed = EnumMap::default();
+        let mut forced = EnumMap::default();
+        let mut chained = EnumMap::default();
+
+        for tile in tiles {
+            for (direction, neighbors) in &tile.neighbors {
+                density[direction] += neighbors.len() as f64 / (tiles.len() * tiles.len()) as f64;
+                branching[direction] += neighbors.len() as f64 / tiles.len() as f64;
+
+                if neighbors.is_empty() {
+                    closed[direction] += 1;
+                }
+
+                if neighbors.len() == 1 {
+                    forced[direction] += 1;
+
+                    let next = neighbors.iter().next().and_then(|id| lookup.get(id));
+
+                    if next.is_some_and(|next| next.neighbors[direction].len() == 1) {
+                        chained[direction] += 1;
+                    }
+                }
+            }
+        }
+
+        Self {
+            tiles: tiles.len(),
+            total_weight,
+            min_weight: weights.last().map_or(0, |(_, weight)| *weight),
+            median_weight: weights
+                .get(weights.len() / 2)
+                .map_or(0, |(_, weight)| *weight),
+            max_weight: weights.first().map_or(0, |(_, weight)| *weight),
+            weight_entropy,
+            heaviest: weights.into_iter().take(TOP_TILES).collect(),
+            density,
+            branching,
+            forced,
+            chained,
+            closed,
+            dead: dead_tiles(tiles, size),
+        }
+    }
+
+    /// Based on the sparsest direction, tilesets where tiles allow few neighbors run
+    /// into contradictions more often
+    pub fn difficulty(&self) -> Difficulty {
+        let sparsest = self.density.values().copied().fold(f64::INFINITY, f64::min);
+
+        match sparsest {
+            d if d >= 0.3 => Difficulty::Easy,
+            d if d >= 0.1 => Difficulty::Moderate,
+            _ => Difficulty::Hard,
+        }
+    }
+
+    /// Human readable report, `name` turns tile ids into something the user can find
+    pub fn describe<F: Fn(u64) -> String>(&self, name: F) -> String {
+        let mut report = String::new();
+
+        // writing to a string can't fail
+        let _ = writeln!(report, "Tiles:          {}", self.tiles);
+        let _ = writeln!(
+            report,
+            "Weights:        {} total, {} min, {} median, {} max",
+            self.total_weight, self.min_weight, self.median_weight, self.max_weight
+        );
+        let _ = writeln!(report, "Weight entropy: {:.2} bits", self.weight_entropy);
+        let _ = writeln!(report, "Heaviest:");
+
+        for (id, weight) in &self.heaviest {
+            let _ = writeln!(report, "  {weight:>6}  {}", name(*id));
+        }
+
+        let _ = writeln!(report, "Adjacency:");
+
+        for (direction, density) in &self.density {
+            let _ = writeln!(
+                report,
+                "  {:<6} {:>5.1}% of the tileset allowed on average, {} tiles without neighbors",
+                format!("{direction:?}"),
+                density * 100.0,
+                self.closed[direction]
+            );
+        }
+
+        let _ = writeln!(report, "Branching:");
+
+        for (direction, branching) in &self.branching {
+            let _ = writeln!(
+                report,
+                "  {:<6} {branching:>6.1} neighbors on average, {} tiles forced, {} in chains",
+                format!("{direction:?}"),
+                self.forced[direction],
+                self.chained[direction]
+            );
+        }
+
+        let _ = writeln!(report, "Dead tiles:     {}", self.dead.len());
+
+        for tile in &self.dead {
+            let _ = writeln!(report, "  {}", tile.describe(&name));
+        }
+
+        let _ = write!(report, "Difficulty:     {:?}", self.difficulty());
+
+        report
+    }
+}
+
+/// Outcome of solving a few small grids, how often a tileset runs into contradictions
+/// tends to carry over to larger grids
+#[derive(Debug, Clone)]
+pub struct SolveEstimate {
+    pub size: Size,
+    pub runs: usize,
+    /// Runs that never had to roll back
+    pub clean: usize,
+    /// Runs given up on after too many ticks
+    pub unfinished: usize,
+    pub rollbacks: usize,
+}
+
+impl SolveEstimate {
+    /// Solves `runs` grids of `size` with consecutive seeds starting at `seed`
+    pub fn new<T: Clone + Sync + Send>(
+        tiles: &[Tile<T>],
+        size: Size,
+        runs: usize,
+        seed: u64,
+    ) -> Self {
+        let base_state = SuperState::new(tiles.iter().cloned().map(Arc::new).collect());
+        let mut estimate = Self {
+            size,
+            runs,
+            clean: 0,
+            unfinished: 0,
+            rollbacks: 0,
+        };
+
+        for run in 0..runs as u64 {
+            let grid = Grid::new(size.width, size.height, &mut |_, _| base_state.clone());
+            let mut wave = WaveBuilder::new(grid).seed(seed.wrapping_add(run)).build();
+            let mut ticks = 0;
+
+            while !wave.done() && ticks < TRIAL_TICKS * size.width * size.height {
+                wave.tick();
+                ticks += 1;
+            }
+
+            estimate.rollbacks += wave.rollbacks();
+            estimate.clean += (wave.rollbacks() == 0) as usize;
+            estimate.unfinished += !wave.done() as usize;
+        }
+
+        estimate
+    }
+
+    /// Rollbacks per thousand cells
+    pub fn rollback_rate(&self) -> f64 {
+        let cells = (self.runs * self.size.width * self.size.height).max(1);
+
+        self.rollbacks as f64 * 1000.0 / cells as f64
+    }
+
+    pub fn describe(&self) -> String {
+        format!(
+            "{} runs of {}x{}: {:.1} rollbacks per 1000 cells, {} without rollbacks, {} unfinished",
+            self.runs,
+            self.size.width,
+            self.size.height,
+            self.rollback_rate(),
+            self.clean,
+            self.unfinished
+        )
+    }
+}
+
+/// How far picking a tile spread through the grid over a solve, see
+/// `Wave::propagation`. Stiff tilesets, where a tile leaves its neighbors few options,
+/// narrow many cells per pick.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PropagationStats {
+    pub collapses: usize,
+    /// Collapses that ended in a contradiction
+    pub contradicted: usize,
+    pub mean_cells: f64,
+    pub median_cells: usize,
+    /// Cells that nine in ten collapses narrowed at most
+    pub p90_cells: usize,
+    pub max_cells: usize,
+    pub mean_reach: f64,
+    pub max_reach: usize,
+}
+
+impl PropagationStats {
+    pub fn new(propagation: &[Propagation]) -> Self {
+        let mut cells: Vec<usize> = propagation.iter().map(|propagation| propagation.cells).collect();
+        let count = propagation.len().max(1) as f64;
+        // nearest rank, the smallest value at least `share` of the collapses don't exceed
+        let percentile = |cells: &[usize], share: f64| {
+            let rank = (share * cells.len() as f64).ceil() as usize;
+
+            cells.get(rank.saturating_sub(1)).copied().unwrap_or(0)
+        };
+
+        cells.sort_unstable();
+
+        Self {
+            collapses: propagation.len(),
+            contradicted: propagation.iter().filter(|propagation| propagation.contradicted).count(),
+            mean_cells: cells.iter().sum::<usize>() as f64 / count,
+            median_cells: percentile(&cells, 0.5),
+            p90_cells: percentile(&cells, 0.9),
+            max_cells: cells.last().copied().unwrap_or(0),
+            mean_reach: propagation.iter().map(|propagation| propagation.reach).sum::<usize>() as f64 / count,
+            max_reach: propagation.iter().map(|propagation| propagation.reach).max().unwrap_or(0),
+        }
+    }
+
+    pub fn describe(&self) -> String {
+        format!(
+            "Collapses:      {} picked, {} contradicted\n\
+             Cells narrowed: {:.1} mean, {} median, {} 90th percentile, {} max\n\
+             Reach:          {:.1} mean, {} max",
+            self.collapses,
+            self.contradicted,
+            self.mean_cells,
+            self.median_cells,
+            self.p90_cells,
+            self.max_cells,
+            self.mean_reach,
+            self.max_reach
+        )
+    }
+}
@@ -0,0 +1,46 @@
+use image::DynamicImage;
+use clap::Args;
+use tracing::{info, warn};
+
+use wave_function_collapse::grid::{Grid, Size};
+use wave_function_collapse::superstate::Collapsable;
+use wave_function_collapse::tile::Tile;
+use wave_function_collapse::usage::AdjacencyUsage;
+
+use crate::load_image;
+use crate::output;
+
+#[derive(Debug, Args)]
+pub struct AnalyzeOpt {
+    #[arg(value_parser = load_image, help = "Input image the tileset is taken from")]
+    input: DynamicImage,
+
+    #[arg(short, long, help = "Input image grid size")]
+    input_size: usize,
+
+    #[arg(value_parser = load_image, help = "Output image generated from the input")]
+    output: DynamicImage,
+}
+
+/// Reads an output back into tiles and prints which rules of the tileset it used as
+/// JSON, see `output::adjacency_json`
+pub fn run(opt: &AnalyzeOpt) {
+    let size = Size::uniform(opt.input_size);
+    let tiles = Tile::from_image(&opt.input, &size);
+    let ids = Tile::ids(&opt.output, &size);
+    let known: Vec<u64> = tiles.iter().map(|tile| tile.get_id()).collect();
+    let cells = Grid::new(ids.width(), ids.height(), &mut |x, y| {
+        ids.get(x, y).copied().filter(|id| known.contains(id))
+    });
+    let unknown = cells.iter().filter(|(_, _, cell)| cell.is_none()).count();
+
+    info!("{} unique tiles found, read a {}x{} output", tiles.len(), ids.width(), ids.height());
+
+    if unknown > 0 {
+        warn!("{unknown} cells of the output aren't tiles of the input, they're skipped");
+    }
+
+    let usage = AdjacencyUsage::new(&cells, &tiles);
+
+    println!("{}", serde_json::to_string_pretty(&output::adjacency_json(&usage, &tiles)).unwrap());
+}
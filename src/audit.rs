@@ -0,0 +1,110 @@
+//! Checks that a seed always gives the same generation. Two waves built the same way are
+//! solved side by side and their recordings compared as they go, so a hash map iterated
+//! in a different order or a thread finishing first shows up at the step it changed
+//! something instead of as a slightly different output.
+
+use std::fmt;
+use std::io::{self, Write};
+use std::sync::{Arc, Mutex};
+
+use crate::recorder::{Event, Recorder, Replay};
+use crate::superstate::Collapsable;
+use crate::wave::Wave;
+
+/// Where two runs of the same seed went different ways
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Divergence {
+    /// Events both runs agreed on before this one
+    pub step: usize,
+    /// None when that run had nothing more to do
+    pub first: Option<Event>,
+    pub second: Option<Event>,
+}
+
+impl fmt::Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let describe = |event: &Option<Event>| match event {
+            Some(event) => format!("{event:?}"),
+            None => "nothing".to_string(),
+        };
+
+        write!(
+            f,
+            "Runs diverged after {} events, the first did {} where the second did {}",
+            self.step,
+            describe(&self.first),
+            describe(&self.second)
+        )
+    }
+}
+
+/// Recording kept in memory so both logs can be read while the waves are running
+#[derive(Clone, Default)]
+struct Log(Arc<Mutex<Vec<u8>>>);
+
+impl Write for Log {
+    fn write(&mut self, buffer: &[u8]) -> io::Result<usize> {
+        self.0.lock().unwrap().extend_from_slice(buffer);
+        Ok(buffer.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Solves `first` and `second`, waves built the same way with the same seed, a tick at a
+/// time each and stops at the first event where they differ. Ok with the amount of
+/// events once both are done. Replaces any recording the waves had, and like solving it
+/// doesn't return for a wave that can't be solved.
+pub fn audit<T>(first: &mut Wave<T>, second: &mut Wave<T>) -> Result<usize, Divergence>
+where
+    T: Collapsable,
+{
+    let (first_log, second_log) = (Log::default(), Log::default());
+
+    first.start_recording(Recorder::new(Box::new(first_log.clone())));
+    second.start_recording(Recorder::new(Box::new(second_log.clone())));
+
+    // bytes known to be the same in both logs
+    let mut checked = 0;
+
+    loop {
+        if !first.done() {
+            first.tick();
+        }
+
+        if !second.done() {
+            second.tick();
+        }
+
+        let first_bytes = first_log.0.lock().unwrap();
+        let second_bytes = second_log.0.lock().unwrap();
+
+        if first_bytes.len() != second_bytes.len() || first_bytes[checked..] != second_bytes[checked..] {
+            return Err(divergence(&first_bytes, &second_bytes));
+        }
+
+        checked = first_bytes.len();
+
+        if first.done() && second.done() {
+            return Ok(events(&first_bytes).len());
+        }
+    }
+}
+
+fn events(log: &[u8]) -> Vec<Event> {
+    // both logs were written in full by this process, they can't be cut off or corrupt
+    Replay::new(log).unwrap().map(Result::unwrap).collect()
+}
+
+fn divergence(first: &[u8], second: &[u8]) -> Divergence {
+    let (first, second) = (events(first), events(second));
+    let step = first.iter().zip(&second).take_while(|(a, b)| a == b).count();
+
+    Divergence {
+        step,
+        first: first.get(step).cloned(),
+        second: second.get(step).cloned(),
+    }
+}
@@ -0,0 +1,122 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use image::{DynamicImage, GenericImageView};
+use rand::rngs::OsRng;
+use rand::Rng;
+use clap::Args;
+use tracing::{error, info, warn};
+
+use wave_function_collapse::grid::{Grid, Size};
+use wave_function_collapse::superstate::SuperState;
+use wave_function_collapse::tile::Tile;
+use wave_function_collapse::wave::WaveBuilder;
+
+use crate::load_image;
+use crate::output::OutputFormat;
+
+#[derive(Debug, Args)]
+pub struct BatchOpt {
+    #[arg(value_parser = load_image, help = "Input image")]
+    input: DynamicImage,
+
+    #[arg(short, long, help = "Input image grid size")]
+    input_size: usize,
+
+    #[arg(help = "Directory to write the outputs to, named after their seed")]
+    output: PathBuf,
+
+    #[arg(short, long, default_value = "20x20", help = "Output image grid size")]
+    output_size: Size,
+
+    #[arg(short, long, default_value = "10", help = "Outputs to generate")]
+    count: usize,
+
+    #[arg(short, long, help = "Seed of the first output, the others count up from it")]
+    seed: Option<u64>,
+
+    #[arg(long, help = "Skip outputs that are the same as an earlier one and keep going until there are --count unique ones")]
+    unique: bool,
+
+    #[arg(long, help = "Stop after n seeds even when there aren't enough unique outputs, 10 times --count by default")]
+    max_attempts: Option<usize>,
+
+    #[arg(long, help = "Skip a seed once it ran into more than n contradictions")]
+    max_rollbacks: Option<usize>,
+}
+
+/// Generates outputs for consecutive seeds. Tiny tilesets tend to repeat themselves,
+/// every output is fingerprinted and duplicates are flagged or, with `--unique`, skipped.
+pub fn run(opt: &BatchOpt) {
+    if let Err(e) = fs::create_dir_all(&opt.output) {
+        error!("Failed to create {}: {e}", opt.output.display());
+        return;
+    }
+
+    let tiles = Tile::from_image(&opt.input, &Size::uniform(opt.input_size));
+    let (tile_width, tile_height) = tiles[0].value.image().dimensions();
+    let base_state = SuperState::new(tiles.iter().cloned().map(Arc::new).collect());
+    let first_seed = opt.seed.unwrap_or_else(|| OsRng.gen());
+    let max_attempts = opt.max_attempts.unwrap_or(opt.count * 10);
+
+    info!("{} unique tiles found, starting at seed {first_seed}", tiles.len());
+
+    // seed of the first output with a fingerprint
+    let mut seen: HashMap<u64, u64> = HashMap::new();
+    let mut written = 0;
+    let mut duplicates = 0;
+    let mut failed = 0;
+
+    for attempt in 0..max_attempts {
+        if opt.unique && seen.len() == opt.count || !opt.unique && written == opt.count {
+            break;
+        }
+
+        let seed = first_seed.wrapping_add(attempt as u64);
+        let grid = Grid::new(
+            opt.output_size.width,
+            opt.output_size.height,
+            &mut |_, _| base_state.clone(),
+        );
+        let mut wave = WaveBuilder::new(grid).seed(seed).build();
+
+        while !wave.done() && opt.max_rollbacks.is_none_or(|limit| wave.rollbacks() <= limit) {
+            wave.tick();
+        }
+
+        let Some(fingerprint) = wave.fingerprint() else {
+            warn!("Seed {seed} gave up after {} contradictions", wave.rollbacks());
+            failed += 1;
+            continue;
+        };
+
+        if let Some(original) = seen.get(&fingerprint) {
+            duplicates += 1;
+
+            if opt.unique {
+                info!("Seed {seed} is the same as seed {original}, skipping it");
+                continue;
+            }
+
+            warn!("Seed {seed} is the same as seed {original}");
+        } else {
+            seen.insert(fingerprint, seed);
+        }
+
+        let path = opt.output.join(format!("{seed}.png"));
+        let canvas = Tile::compose(&wave.grid, tile_width, tile_height);
+
+        match OutputFormat::Png.save(canvas, &path, 100) {
+            Ok(()) => written += 1,
+            Err(e) => error!("Failed to write {}: {e}", path.display()),
+        }
+    }
+
+    info!("Wrote {written} outputs, {} unique, {duplicates} duplicates and {failed} failed seeds", seen.len());
+
+    if opt.unique && seen.len() < opt.count {
+        warn!("Only found {} of {} unique outputs in {max_attempts} seeds", seen.len(), opt.count);
+    }
+}
@@ -0,0 +1,210 @@
+use std::fs;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Instant;
+
+use enum_map::enum_map;
+use rand::{Rng, SeedableRng};
+use rand_xorshift::XorShiftRng;
+use serde_json::json;
+use clap::Args;
+use tracing::info;
+
+use wave_function_collapse::grid::{Direction, Grid};
+use wave_function_collapse::superstate::SuperState;
+use wave_function_collapse::tile::Tile;
+use wave_function_collapse::wave::WaveBuilder;
+
+/// Edge colors per tileset, a full tileset has colors^4 tiles
+const EDGE_COLORS: [usize; 3] = [2, 3, 4];
+/// Percentage of tiles kept, sparse tilesets run into contradictions
+const DENSITIES: [usize; 2] = [100, 60];
+
+#[derive(Debug, Clone, Copy)]
+pub enum ReportFormat {
+    Json,
+    Csv,
+}
+
+impl FromStr for ReportFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "json" => Ok(Self::Json),
+            "csv" => Ok(Self::Csv),
+            _ => Err(format!("Unknown report format: {s}")),
+        }
+    }
+}
+
+#[derive(Debug, Args)]
+pub struct BenchOpt {
+    #[arg(long, default_value = "json", value_parser = crate::one_of::<ReportFormat>(&["json", "csv"]), help = "Report format")]
+    format: ReportFormat,
+
+    #[arg(long, default_value = "1", help = "Seed used for every run")]
+    seed: u64,
+
+    #[arg(
+        long,
+        value_delimiter = ',',
+        default_value = "20,50,100",
+        help = "Comma separated output grid sizes, large grids show how propagation scales with threads"
+    )]
+    sizes: Vec<usize>,
+}
+
+struct Run {
+    size: usize,
+    tiles: usize,
+    density: usize,
+    seconds: f64,
+    rollbacks: usize,
+    /// Peak resident memory of the process during the run, what it held before included
+    peak_rss_kb: Option<u64>,
+}
+
+impl Run {
+    fn cells_per_sec(&self) -> f64 {
+        (self.size * self.size) as f64 / self.seconds
+    }
+}
+
+/// Runs the standard matrix of grid sizes and synthetic tilesets and prints a report
+pub fn run(opt: &BenchOpt) {
+    let mut runs = Vec::new();
+    #[cfg(feature = "threaded")]
+    let pool = Arc::new(rayon::ThreadPoolBuilder::new().build().expect("the default pool starts"));
+
+    for colors in EDGE_COLORS {
+        for density in DENSITIES {
+            let tiles = edge_tiles(colors, density, opt.seed);
+
+            for size in opt.sizes.iter().copied() {
+                info!("Running {size}x{size} with {} tiles ({density}%)", tiles.len());
+
+                // the peak is kept per process, without a reset every run reports the largest so far
+                let measured = reset_peak_rss();
+                let base_state = SuperState::new(tiles.iter().cloned().map(Arc::new).collect());
+                let grid = Grid::new(size, size, &mut |_, _| base_state.clone());
+                let start = Instant::now();
+                let builder = WaveBuilder::new(grid).seed(opt.seed);
+                #[cfg(feature = "threaded")]
+                let builder = builder.thread_pool(pool.clone());
+                let mut wave = builder.build();
+
+                while !wave.done() {
+                    wave.tick();
+                }
+
+                runs.push(Run {
+                    size,
+                    tiles: tiles.len(),
+                    density,
+                    seconds: start.elapsed().as_secs_f64(),
+                    rollbacks: wave.rollbacks(),
+                    peak_rss_kb: measured.then(peak_rss_kb).flatten(),
+                });
+            }
+        }
+    }
+
+    match opt.format {
+        ReportFormat::Json => print_json(&runs),
+        ReportFormat::Csv => print_csv(&runs),
+    }
+}
+
+/// Tiles with one of `colors` colors on every edge, neighbors match when the touching
+/// edges have the same color. Dropping tiles from the full set makes it harder to solve.
+fn edge_tiles(colors: usize, density: usize, seed: u64) -> Vec<Tile<()>> {
+    let mut rng = XorShiftRng::seed_from_u64(seed);
+    let mut edges = Vec::new();
+
+    for id in 0..colors.pow(4) {
+        let edge = |n: u32| id / colors.pow(n) % colors;
+
+        if id == 0 || rng.gen_range(0..100) < density {
+            edges.push(enum_map! {
+                Direction::Up => edge(0),
+                Direction::Right => edge(1),
+                Direction::Down => edge(2),
+                Direction::Left => edge(3),
+            });
+        }
+    }
+
+    let mut output: Vec<Tile<()>> = (0..edges.len())
+        .map(|id| Tile::new(id as u64, ()))
+        .collect();
+
+    for (tile, edge) in output.iter_mut().zip(edges.iter()) {
+        for (id, other) in edges.iter().enumerate() {
+            for (direction, color) in edge {
+                if other[direction.invert()] == *color {
+                    tile.neighbors[direction].insert(id as u64);
+                }
+            }
+        }
+    }
+
+    output
+}
+
+/// Sets the peak resident memory back to the current one, false where the kernel
+/// doesn't allow it (Linux 4.0 and up does)
+fn reset_peak_rss() -> bool {
+    fs::write("/proc/self/clear_refs", "5").is_ok()
+}
+
+/// Peak resident memory of the process since the last `reset_peak_rss`, only available
+/// on Linux
+fn peak_rss_kb() -> Option<u64> {
+    let status = fs::read_to_string("/proc/self/status").ok()?;
+    let line = status.lines().find(|line| line.starts_with("VmHWM:"))?;
+
+    line.split_whitespace().nth(1)?.parse().ok()
+}
+
+fn print_json(runs: &[Run]) {
+    let runs: Vec<_> = runs
+        .iter()
+        .map(|run| {
+            json!({
+                "size": format!("{0}x{0}", run.size),
+                "tiles": run.tiles,
+                "density": run.density,
+                "seconds": run.seconds,
+                "cells_per_sec": run.cells_per_sec(),
+                "rollbacks": run.rollbacks,
+                "peak_rss_kb": run.peak_rss_kb,
+            })
+        })
+        .collect();
+
+    let report = json!({
+        "version": env!("CARGO_PKG_VERSION"),
+        "threaded": cfg!(feature = "threaded"),
+        "runs": runs,
+    });
+
+    println!("{}", serde_json::to_string_pretty(&report).unwrap());
+}
+
+fn print_csv(runs: &[Run]) {
+    println!("size,tiles,density,seconds,cells_per_sec,rollbacks,peak_rss_kb");
+
+    for run in runs {
+        println!(
+            "{0}x{0},{1},{2},{3:.4},{4:.1},{5},{6}",
+            run.size,
+            run.tiles,
+            run.density,
+            run.seconds,
+            run.cells_per_sec(),
+            run.rollbacks,
+            run.peak_rss_kb.map(|kb| kb.to_string()).unwrap_or_default(),
+        );
+    }
+}
@@ -0,0 +1,74 @@
+//! Large scale structure for outputs that would otherwise look the same everywhere. The
+//! grid is split into regions before solving and every region only gets the tiles of
+//! its own palette, like a desert next to a forest.
+
+use std::collections::HashSet;
+use std::hash::Hash;
+
+use rand::{Rng, SeedableRng};
+use rand_xorshift::XorShiftRng;
+
+use crate::constraint::Constraint;
+use crate::grid::{Grid, Position};
+use crate::superstate::{Collapsable, SuperState};
+
+/// Keeps every cell to the tiles in the palette of its region. `I` identifies tiles.
+#[derive(Debug, Clone)]
+pub struct Biomes<I> {
+    /// Region of every cell
+    regions: Grid<usize>,
+    /// Palette of every region
+    assigned: Vec<usize>,
+    palettes: Vec<HashSet<I>>,
+}
+
+impl<I> Biomes<I>
+where
+    I: Eq + Hash,
+{
+    /// Splits a grid of `width` by `height` into `count` voronoi regions around random
+    /// cells, every region gets one of `palettes` at random
+    pub fn voronoi(width: usize, height: usize, count: usize, palettes: Vec<HashSet<I>>, seed: u64) -> Self {
+        assert!(!palettes.is_empty(), "Biomes need at least one palette");
+
+        let mut rng = XorShiftRng::seed_from_u64(seed);
+        let centers: Vec<Position> = (0..count.max(1))
+            .map(|_| Position::new(rng.gen_range(0..width), rng.gen_range(0..height)))
+            .collect();
+        let assigned = centers.iter().map(|_| rng.gen_range(0..palettes.len())).collect();
+        let regions = Grid::new(width, height, &mut |x, y| {
+            let distance = |center: &Position| x.abs_diff(center.x).pow(2) + y.abs_diff(center.y).pow(2);
+
+            (0..centers.len()).min_by_key(|index| distance(&centers[*index])).unwrap()
+        });
+
+        Self {
+            regions,
+            assigned,
+            palettes,
+        }
+    }
+
+    pub fn regions(&self) -> &Grid<usize> {
+        &self.regions
+    }
+
+    /// Tiles allowed at `x`, `y`, nothing outside the grid
+    pub fn palette(&self, x: usize, y: usize) -> Option<&HashSet<I>> {
+        let region = self.regions.get(x, y)?;
+
+        Some(&self.palettes[self.assigned[*region]])
+    }
+}
+
+impl<I, T> Constraint<T> for Biomes<I>
+where
+    I: Eq + Hash + Send,
+    T: Collapsable<Identifier = I>,
+{
+    fn filter(&self, position: &Position, candidates: &mut SuperState<T>) {
+        if let Some(palette) = self.palette(position.x, position.y) {
+            candidates.retain(|tile| palette.contains(&tile.get_id()));
+        }
+    }
+}
@@ -0,0 +1,69 @@
+/// Number of words compared per step in `is_disjoint`
+const LANES: usize = 4;
+
+/// Fixed size set of small integers, used for sets of dense tile indexes
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BitSet {
+    words: Vec<u64>,
+}
+
+impl BitSet {
+    pub fn new(size: usize) -> Self {
+        Self {
+            words: vec![0; size.div_ceil(64)],
+        }
+    }
+
+    pub fn from_indexes<I: IntoIterator<Item = usize>>(size: usize, indexes: I) -> Self {
+        let mut output = Self::new(size);
+
+        for index in indexes {
+            output.insert(index);
+        }
+
+        output
+    }
+
+    #[inline]
+    pub fn insert(&mut self, index: usize) {
+        self.words[index / 64] |= 1 << (index % 64);
+    }
+
+    #[inline]
+    pub fn contains(&self, index: usize) -> bool {
+        self.words[index / 64] & (1 << (index % 64)) != 0
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.words.iter().all(|word| *word == 0)
+    }
+
+    /// Large sets are compared a few words at a time so the compiler can vectorize the
+    /// comparison, small sets (the common case) stay on the plain loop.
+    #[inline]
+    pub fn is_disjoint(&self, other: &Self) -> bool {
+        if self.words.len() < LANES * 2 {
+            return Self::is_disjoint_scalar(&self.words, &other.words);
+        }
+
+        let left = self.words.chunks_exact(LANES);
+        let right = other.words.chunks_exact(LANES);
+        let rest = Self::is_disjoint_scalar(left.remainder(), right.remainder());
+
+        rest && left.zip(right).all(|(a, b)| {
+            let mut overlap = 0;
+
+            for lane in 0..LANES {
+                overlap |= a[lane] & b[lane];
+            }
+
+            overlap == 0
+        })
+    }
+
+    #[inline]
+    fn is_disjoint_scalar(left: &[u64], right: &[u64]) -> bool {
+        left.iter().zip(right.iter()).all(|(a, b)| a & b == 0)
+    }
+}
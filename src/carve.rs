@@ -0,0 +1,161 @@
+//! Rivers, roads and other paths that have to run unbroken across the output. A random
+//! walk between the endpoints is marked out before solving and the cells along it only
+//! get tiles of the path's class, the solver fills in the rest around them.
+
+use std::str::FromStr;
+
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+use rand_xorshift::XorShiftRng;
+
+use crate::constraint::Constraint;
+use crate::grid::{Direction, Grid, Position};
+use crate::superstate::{Collapsable, SuperState};
+
+/// Chance for every step of a walk to go anywhere instead of towards the end, higher
+/// makes for more winding paths
+const WANDER: f64 = 0.35;
+
+const SIDES: [Direction; 4] = [Direction::Up, Direction::Right, Direction::Down, Direction::Left];
+
+/// Where a path runs
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Route {
+    Between(Position, Position),
+    /// From a random cell on one border to a random cell on the opposite border
+    Across,
+}
+
+impl FromStr for Route {
+    type Err = String;
+
+    /// Parses `x,y:x,y` or `across`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.eq_ignore_ascii_case("across") {
+            return Ok(Route::Across);
+        }
+
+        let (from, to) = s
+            .split_once(':')
+            .ok_or(format!("invalid route: {}, expected x,y:x,y or across", s))?;
+
+        Ok(Route::Between(from.parse()?, to.parse()?))
+    }
+}
+
+/// Keeps the cells along carved paths to the tiles of one class, see `Route`
+pub struct Carve<T> {
+    corridor: Grid<bool>,
+    radius: usize,
+    rng: XorShiftRng,
+    class: Box<dyn Fn(&T) -> bool + Send>,
+}
+
+impl<T> Carve<T>
+where
+    T: Collapsable,
+{
+    /// Nothing carved yet for a grid of `width` by `height`, `class` tells the tiles a
+    /// path may be made of
+    pub fn new<F>(width: usize, height: usize, seed: u64, class: F) -> Self
+    where
+        F: Fn(&T) -> bool + Send + 'static,
+    {
+        Self {
+            corridor: Grid::new(width, height, &mut |_, _| false),
+            radius: 0,
+            rng: XorShiftRng::seed_from_u64(seed),
+            class: Box::new(class),
+        }
+    }
+
+    /// Widens the paths carved after this by `radius` cells on every side
+    pub fn radius(mut self, radius: usize) -> Self {
+        self.radius = radius;
+        self
+    }
+
+    /// Carves a path along `route`, endpoints outside the grid are clamped to it
+    pub fn route(mut self, route: Route) -> Self {
+        let (width, height) = (self.corridor.width(), self.corridor.height());
+        let clamp = |position: Position| Position::new(position.x.min(width - 1), position.y.min(height - 1));
+        let (from, to) = match route {
+            Route::Between(from, to) => (clamp(from), clamp(to)),
+            Route::Across if self.rng.gen_bool(0.5) => (
+                Position::new(0, self.rng.gen_range(0..height)),
+                Position::new(width - 1, self.rng.gen_range(0..height)),
+            ),
+            Route::Across => (
+                Position::new(self.rng.gen_range(0..width), 0),
+                Position::new(self.rng.gen_range(0..width), height - 1),
+            ),
+        };
+
+        for step in self.walk(from, to) {
+            self.mark(step);
+        }
+
+        self
+    }
+
+    /// Cells the paths run through
+    pub fn corridor(&self) -> &Grid<bool> {
+        &self.corridor
+    }
+
+    /// Cells from `from` to `to`, every step goes towards the end unless it wanders off.
+    /// The walk drifts towards the end, so it always gets there.
+    fn walk(&mut self, from: Position, to: Position) -> Vec<Position> {
+        let (width, height) = (self.corridor.width(), self.corridor.height());
+        let mut position = from;
+        let mut steps = vec![from];
+
+        while position != to {
+            let toward: Vec<Direction> = SIDES
+                .into_iter()
+                .filter(|direction| {
+                    position
+                        .step(*direction)
+                        .is_some_and(|next| next.manhattan(&to) < position.manhattan(&to))
+                })
+                .collect();
+            let direction = if self.rng.gen_bool(WANDER) {
+                *SIDES.choose(&mut self.rng).unwrap()
+            } else {
+                *toward.choose(&mut self.rng).unwrap()
+            };
+
+            if let Some(next) = position.step(direction).filter(|next| next.within(width, height)) {
+                position = next;
+                steps.push(next);
+            }
+        }
+
+        steps
+    }
+
+    fn mark(&mut self, center: Position) {
+        let radius = self.radius as isize;
+
+        for dy in -radius..=radius {
+            for dx in -radius..=radius {
+                if let Some(position) = center.checked_offset(dx, dy) {
+                    if let Some(cell) = self.corridor.get_mut(position.x, position.y) {
+                        *cell = true;
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl<T> Constraint<T> for Carve<T>
+where
+    T: Collapsable,
+{
+    fn filter(&self, position: &Position, candidates: &mut SuperState<T>) {
+        if self.corridor.get(position.x, position.y) == Some(&true) {
+            candidates.retain(|tile| (self.class)(tile));
+        }
+    }
+}
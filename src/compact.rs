@@ -0,0 +1,168 @@
+use crate::grid::Grid;
+
+/// Width and height of the chunks, runs don't cross chunk borders so changing a cell only
+/// re-encodes its own chunk
+const CHUNK_SIZE: usize = 16;
+
+/// Grid storage for huge maps that are mostly the same cell, like an ocean with a few
+/// islands. Cells are kept in chunks as runs of equal values, a chunk of one value takes
+/// up a single run no matter its size. Reads are a binary search in the chunk, writes
+/// re-encode the chunk, so it's meant for grids that are mostly read.
+#[derive(Debug, Clone)]
+pub struct CompactGrid<T>
+where
+    T: Clone + Eq,
+{
+    /// Runs per chunk in row-major order as (end, value), `end` being the index in the
+    /// chunk right after the run
+    chunks: Vec<Vec<(usize, T)>>,
+    width: usize,
+    height: usize,
+}
+
+impl<T> CompactGrid<T>
+where
+    T: Clone + Eq,
+{
+    pub fn new(width: usize, height: usize, value: T) -> Self {
+        let mut grid = Self {
+            chunks: Vec::new(),
+            width,
+            height,
+        };
+
+        grid.chunks = (0..grid.chunk_count())
+            .map(|chunk| vec![(grid.chunk_area(chunk), value.clone())])
+            .collect();
+
+        grid
+    }
+
+    pub fn from_grid(grid: &Grid<T>) -> Self {
+        let mut output = Self {
+            chunks: Vec::new(),
+            width: grid.width(),
+            height: grid.height(),
+        };
+
+        output.chunks = (0..output.chunk_count())
+            .map(|chunk| {
+                let cells = output.chunk_cells(chunk).map(|(x, y)| grid.get(x, y).unwrap().clone());
+
+                encode(cells)
+            })
+            .collect();
+
+        output
+    }
+
+    pub fn to_grid(&self) -> Grid<T> {
+        Grid::new(self.width, self.height, &mut |x, y| self.get(x, y).unwrap().clone())
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn size(&self) -> usize {
+        self.width * self.height
+    }
+
+    /// Runs stored over all chunks, a rough measure of how well the grid compresses
+    pub fn runs(&self) -> usize {
+        self.chunks.iter().map(Vec::len).sum()
+    }
+
+    pub fn get(&self, x: usize, y: usize) -> Option<&T> {
+        let (chunk, index) = self.locate(x, y)?;
+        let runs = &self.chunks[chunk];
+
+        Some(&runs[runs.partition_point(|(end, _)| *end <= index)].1)
+    }
+
+    pub fn set(&mut self, x: usize, y: usize, value: T) -> Result<(), &'static str> {
+        let (chunk, index) = self.locate(x, y).ok_or("Cell out of range")?;
+
+        if *self.get(x, y).unwrap() == value {
+            return Ok(());
+        }
+
+        let mut cells = decode(&self.chunks[chunk]);
+
+        cells[index] = value;
+        self.chunks[chunk] = encode(cells.into_iter());
+
+        Ok(())
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (usize, usize, &T)> {
+        (0..self.height).flat_map(move |y| (0..self.width).map(move |x| (x, y, self.get(x, y).unwrap())))
+    }
+
+    fn chunk_columns(&self) -> usize {
+        self.width.div_ceil(CHUNK_SIZE)
+    }
+
+    fn chunk_count(&self) -> usize {
+        self.chunk_columns() * self.height.div_ceil(CHUNK_SIZE)
+    }
+
+    /// Top left and size of a chunk, chunks on the right and bottom edge can be smaller
+    fn chunk_bounds(&self, chunk: usize) -> (usize, usize, usize, usize) {
+        let x = chunk % self.chunk_columns() * CHUNK_SIZE;
+        let y = chunk / self.chunk_columns() * CHUNK_SIZE;
+
+        (x, y, CHUNK_SIZE.min(self.width - x), CHUNK_SIZE.min(self.height - y))
+    }
+
+    fn chunk_area(&self, chunk: usize) -> usize {
+        let (_, _, width, height) = self.chunk_bounds(chunk);
+
+        width * height
+    }
+
+    fn chunk_cells(&self, chunk: usize) -> impl Iterator<Item = (usize, usize)> {
+        let (x, y, width, height) = self.chunk_bounds(chunk);
+
+        (y..y + height).flat_map(move |cy| (x..x + width).map(move |cx| (cx, cy)))
+    }
+
+    /// Chunk of a cell and its index in the chunk
+    fn locate(&self, x: usize, y: usize) -> Option<(usize, usize)> {
+        if x >= self.width || y >= self.height {
+            return None;
+        }
+
+        let chunk = x / CHUNK_SIZE + y / CHUNK_SIZE * self.chunk_columns();
+        let (_, _, width, _) = self.chunk_bounds(chunk);
+
+        Some((chunk, x % CHUNK_SIZE + y % CHUNK_SIZE * width))
+    }
+}
+
+fn encode<T: Eq>(cells: impl Iterator<Item = T>) -> Vec<(usize, T)> {
+    let mut runs: Vec<(usize, T)> = Vec::new();
+
+    for (index, value) in cells.enumerate() {
+        match runs.last_mut() {
+            Some((end, last)) if *last == value => *end = index + 1,
+            _ => runs.push((index + 1, value)),
+        }
+    }
+
+    runs
+}
+
+fn decode<T: Clone>(runs: &[(usize, T)]) -> Vec<T> {
+    let mut cells = Vec::with_capacity(runs.last().map_or(0, |(end, _)| *end));
+
+    for (end, value) in runs {
+        cells.resize(*end, value.clone());
+    }
+
+    cells
+}
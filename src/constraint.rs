@@ -0,0 +1,24 @@
+//! Rules that look further than the neighbors of a cell, like how often a tile may be
+//! used or where it may go. The wave asks every constraint to filter every cell once at
+//! the start, then whenever it propagates a cell and right before collapsing it, so a
+//! rule that depends on cells far away is checked at the latest when it matters.
+
+use crate::grid::Position;
+use crate::superstate::{Collapsable, SuperState};
+
+pub trait Constraint<T>: Send
+where
+    T: Collapsable,
+{
+    /// Removes the tiles that aren't allowed at `position` from `candidates`, removing
+    /// all of them is a contradiction
+    fn filter(&self, position: &Position, candidates: &mut SuperState<T>);
+
+    /// A cell is down to a single tile, either collapsed by the solver or as a result of
+    /// propagating
+    fn on_collapse(&mut self, _position: &Position, _tile: &T) {}
+
+    /// The wave jumped to another state (a rollback, restore or restart), afterwards
+    /// `on_collapse` is called again for every cell that's still collapsed
+    fn reset(&mut self) {}
+}
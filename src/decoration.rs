@@ -0,0 +1,82 @@
+//! A second pass on top of a solved wave, like trees that only grow on grass. The
+//! decorations are solved as a wave of their own, with every cell limited to what fits
+//! the base tile under it.
+
+use std::collections::{HashMap, HashSet};
+use std::hash::Hash;
+
+use crate::constraint::Constraint;
+use crate::grid::{Grid, Position};
+use crate::superstate::{Collapsable, SuperState};
+use crate::wave::WaveBuilder;
+
+/// Keeps every cell to the decorations that fit the base tile under it, see `layer`.
+/// `B` identifies base tiles and `I` decorations.
+#[derive(Debug, Clone)]
+pub struct BaseLayer<B: Clone, I> {
+    base: Grid<Option<B>>,
+    allowed: HashMap<B, HashSet<I>>,
+}
+
+impl<B, I, D> Constraint<D> for BaseLayer<B, I>
+where
+    B: Eq + Hash + Clone + Send,
+    I: Eq + Hash + Send,
+    D: Collapsable<Identifier = I>,
+{
+    fn filter(&self, position: &Position, candidates: &mut SuperState<D>) {
+        let Some(Some(tile)) = self.base.get(position.x, position.y) else {
+            return;
+        };
+        let allowed = &self.allowed[tile];
+
+        candidates.retain(|decoration| allowed.contains(&decoration.get_id()));
+    }
+}
+
+/// Starts a wave the size of `base` for a layer on top of it, every cell only gets the
+/// `decorations` that `allowed` accepts on the base tile under it. Cells of `base` that
+/// aren't collapsed allow every decoration. Fails with the first cell where nothing is
+/// allowed, the layer could never be solved.
+pub fn layer<T, D, F>(
+    base: &Grid<SuperState<T>>,
+    decorations: &SuperState<D>,
+    allowed: F,
+) -> Result<WaveBuilder<D>, Position>
+where
+    T: Collapsable,
+    D: Collapsable,
+    T::Identifier: 'static,
+    D::Identifier: 'static,
+    F: Fn(&T, &D) -> bool,
+{
+    let mut lookup: HashMap<T::Identifier, HashSet<D::Identifier>> = HashMap::new();
+
+    for (x, y, cell) in base {
+        let Some(tile) = cell.collapsed() else {
+            continue;
+        };
+        let fits = lookup.entry(tile.get_id()).or_insert_with(|| {
+            decorations
+                .possible
+                .iter()
+                .filter(|decoration| allowed(tile, decoration))
+                .map(|decoration| decoration.get_id())
+                .collect()
+        });
+
+        if fits.is_empty() {
+            return Err(Position::new(x, y));
+        }
+    }
+
+    let constraint = BaseLayer {
+        base: Grid::new(base.width(), base.height(), &mut |x, y| {
+            base.get(x, y).and_then(|cell| cell.collapsed()).map(|tile| tile.get_id())
+        }),
+        allowed: lookup,
+    };
+    let grid = Grid::new(base.width(), base.height(), &mut |_, _| decorations.clone());
+
+    Ok(WaveBuilder::new(grid).constraint(constraint))
+}
@@ -0,0 +1,278 @@
+//! Exact backend for the `exact` feature. The grid is encoded as a SAT problem and
+//! handed to an embedded CDCL solver (batsat, a port of MiniSat), so unlike the wave it
+//! either finds a solution or proves there is none. Solving is exponential in the worst
+//! case, it's meant for small grids the wave keeps failing on.
+//!
+//! Every cell gets a variable per tile it can be. A cell takes exactly one of its tiles,
+//! and a tile in a cell needs one of the tiles it fits with in every neighbor (the
+//! support encoding). `Options::max_counts` adds a cardinality constraint per tile.
+//! The wave's own `Constraint`s, like carved paths or biomes, aren't part of the model.
+
+use batsat::{lbool, Callbacks, ClauseKind, Lit, Solver, SolverInterface, SolverOpts};
+use rand::distributions::{Distribution, WeightedIndex};
+use rand::Rng;
+
+use crate::bitset::BitSet;
+use crate::grid::{Direction, Grid, Neighbors};
+
+pub use crate::topology::Failure;
+
+#[derive(Debug, Clone)]
+pub struct Options {
+    /// Conflicts the solver may run into before giving up
+    pub conflicts: usize,
+    /// Most cells a tile may take up as (dense index, count), on top of the neighbor rules
+    pub max_counts: Vec<(usize, usize)>,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            conflicts: 1_000_000,
+            max_counts: Vec::new(),
+        }
+    }
+}
+
+/// Stops the solver once it learnt a clause for every conflict it may run into
+struct Budget {
+    conflicts: usize,
+}
+
+impl Callbacks for Budget {
+    fn on_new_clause(&mut self, _clause: &[Lit], kind: ClauseKind) {
+        if kind == ClauseKind::Learnt {
+            self.conflicts = self.conflicts.saturating_sub(1);
+        }
+    }
+
+    fn stop(&self) -> bool {
+        self.conflicts == 0
+    }
+}
+
+/// Finds a dense tile index for every cell out of `domains`, the possible indexes per
+/// cell. `rules` and `weights` are indexed by dense index. Every cell leans towards a
+/// tile drawn by weight from its domain, so different rngs give different solutions.
+pub fn solve<R: Rng>(
+    domains: Grid<Vec<usize>>,
+    rules: &[Neighbors<BitSet>],
+    weights: &[f64],
+    options: &Options,
+    rng: &mut R,
+) -> Result<Grid<usize>, Failure> {
+    let opts = SolverOpts {
+        random_seed: rng.gen_range(1.0..f64::from(i32::MAX)),
+        rnd_init_act: true,
+        ..Default::default()
+    };
+    let mut solver = Solver::new(opts, Budget { conflicts: options.conflicts });
+    let width = domains.width();
+
+    // a variable per possible tile of every cell, positive for the tile drawn by weight
+    let vars: Vec<Vec<Lit>> = domains
+        .iter()
+        .map(|(_, _, domain)| {
+            let preferred = WeightedIndex::new(domain.iter().map(|tile| weights[*tile]))
+                .map(|distribution| distribution.sample(rng))
+                .ok();
+
+            (0..domain.len())
+                .map(|index| Lit::new(solver.new_var(lbool::new(preferred == Some(index)), true), true))
+                .collect()
+        })
+        .collect();
+
+    for cell in &vars {
+        add_clause(&mut solver, cell.clone());
+        at_most(&mut solver, cell, 1);
+    }
+
+    // both tiles have to allow each other
+    let fits = |tile: usize, direction: Direction, neighbor: usize| {
+        rules[tile][direction].contains(neighbor) && rules[neighbor][direction.invert()].contains(tile)
+    };
+
+    for (x, y, domain) in &domains {
+        for (direction, neighbor) in domains.get_neighbor_positions(x, y) {
+            let Some(neighbor) = neighbor else {
+                continue;
+            };
+            let others = domains.get(neighbor.x, neighbor.y).unwrap();
+            let other_vars = &vars[neighbor.x + neighbor.y * width];
+
+            for (tile, var) in domain.iter().zip(&vars[x + y * width]) {
+                let supports = others
+                    .iter()
+                    .zip(other_vars)
+                    .filter(|(other, _)| fits(*tile, direction, **other))
+                    .map(|(_, other_var)| *other_var);
+
+                add_clause(&mut solver, std::iter::once(!*var).chain(supports).collect());
+            }
+        }
+    }
+
+    for (tile, count) in &options.max_counts {
+        let uses: Vec<Lit> = domains
+            .iter()
+            .zip(&vars)
+            .filter_map(|((_, _, domain), cell)| Some(cell[domain.iter().position(|other| other == tile)?]))
+            .collect();
+
+        at_most(&mut solver, &uses, *count);
+    }
+
+    let result = solver.solve_limited(&[]);
+
+    if result == lbool::FALSE {
+        return Err(Failure::Unsolvable);
+    }
+
+    if result != lbool::TRUE {
+        return Err(Failure::GaveUp);
+    }
+
+    Ok(Grid::new(width, domains.height(), &mut |x, y| {
+        let domain = domains.get(x, y).unwrap();
+        let chosen = vars[x + y * width]
+            .iter()
+            .position(|var| solver.value_lit(*var) == lbool::TRUE)
+            .expect("every cell takes one of its tiles");
+
+        domain[chosen]
+    }))
+}
+
+fn add_clause(solver: &mut Solver<Budget>, mut clause: Vec<Lit>) {
+    // false once the clauses contradict each other, solving reports that
+    solver.add_clause_reuse(&mut clause);
+}
+
+/// At most `count` of `lits` are true, a sequential counter (Sinz 2005) with a
+/// variable for every literal and count up to `count`
+fn at_most(solver: &mut Solver<Budget>, lits: &[Lit], count: usize) {
+    if lits.len() <= count {
+        return;
+    }
+
+    if count == 0 {
+        for lit in lits {
+            add_clause(solver, vec![!*lit]);
+        }
+
+        return;
+    }
+
+    // counters[i][j]: at least j + 1 of the first i + 1 literals are true
+    let counters: Vec<Vec<Lit>> = (0..lits.len() - 1)
+        .map(|_| (0..count).map(|_| Lit::new(solver.new_var_default(), true)).collect())
+        .collect();
+
+    for (i, lit) in lits.iter().enumerate() {
+        if i < counters.len() {
+            add_clause(solver, vec![!*lit, counters[i][0]]);
+        }
+
+        if i == 0 {
+            for counter in &counters[0][1..] {
+                add_clause(solver, vec![!*counter]);
+            }
+
+            continue;
+        }
+
+        let previous = &counters[i - 1];
+
+        // one more true literal on top of `count` already is too many
+        add_clause(solver, vec![!*lit, !previous[count - 1]]);
+
+        if i == counters.len() {
+            continue;
+        }
+
+        for j in 0..count {
+            add_clause(solver, vec![!previous[j], counters[i][j]]);
+
+            if j > 0 {
+                add_clause(solver, vec![!*lit, !previous[j - 1], counters[i][j]]);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use enum_map::enum_map;
+    use rand::SeedableRng;
+    use rand_xorshift::XorShiftRng;
+
+    /// Rules for `count` tiles where only different tiles sit next to each other
+    fn distinct(count: usize) -> Vec<Neighbors<BitSet>> {
+        (0..count)
+            .map(|tile| {
+                let others = BitSet::from_indexes(count, (0..count).filter(|other| *other != tile));
+
+                enum_map! { _ => others.clone() }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn solutions_follow_the_rules() {
+        let rules = distinct(3);
+        let mut rng = XorShiftRng::seed_from_u64(0);
+
+        for _ in 0..8 {
+            let domains = Grid::new(6, 6, &mut |_, _| vec![0, 1, 2]);
+            let solution = solve(domains, &rules, &[1.0; 3], &Options::default(), &mut rng).unwrap();
+
+            for (x, y, tile) in &solution {
+                for (_, neighbor) in solution.get_neighbors(x, y) {
+                    assert_ne!(Some(tile), neighbor);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn max_counts_limit_how_often_a_tile_is_used() {
+        // a checkerboard of two tiles has to use each of them on half the cells
+        let rules = distinct(2);
+        let mut rng = XorShiftRng::seed_from_u64(0);
+        let domains = || Grid::new(4, 4, &mut |_, _| vec![0, 1]);
+        let count = |solution: &Grid<usize>| solution.iter().filter(|(_, _, tile)| **tile == 0).count();
+
+        let free = solve(domains(), &rules, &[1.0, 1.0], &Options::default(), &mut rng).unwrap();
+
+        assert_eq!(count(&free), 8);
+
+        // three tiles, the third one only fits where the others leave room
+        let rules = distinct(3);
+        let options = Options {
+            max_counts: vec![(0, 3)],
+            ..Options::default()
+        };
+        let limited = solve(
+            Grid::new(4, 4, &mut |_, _| vec![0, 1, 2]),
+            &rules,
+            &[100.0, 1.0, 1.0],
+            &options,
+            &mut rng,
+        )
+        .unwrap();
+
+        assert!(count(&limited) <= 3);
+
+        let options = Options {
+            max_counts: vec![(0, 7)],
+            ..Options::default()
+        };
+
+        assert!(matches!(
+            solve(domains(), &distinct(2), &[1.0, 1.0], &options, &mut rng),
+            Err(Failure::Unsolvable)
+        ));
+    }
+}
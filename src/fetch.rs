@@ -0,0 +1,87 @@
+use std::env;
+use std::fs;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use wave_function_collapse::sprite::{FNV_OFFSET, FNV_PRIME};
+
+/// Largest accepted download
+const MAX_SIZE: u64 = 32 * 1024 * 1024;
+const TIMEOUT: Duration = Duration::from_secs(30);
+
+pub fn is_url(s: &str) -> bool {
+    s.starts_with("http://") || s.starts_with("https://")
+}
+
+/// Downloads `url` into the cache directory and returns the path, a cached copy is
+/// used as is. Delete the cache directory to fetch again.
+pub fn fetch(url: &str) -> Result<PathBuf, String> {
+    let path = cache_path(url)?;
+
+    // inputs are parsed before logging is set up, so this can't log
+    if path.exists() {
+        return Ok(path);
+    }
+
+    let response = ureq::AgentBuilder::new()
+        .timeout(TIMEOUT)
+        .build()
+        .get(url)
+        .call()
+        .map_err(|e| format!("Failed to download {e}"))?;
+
+    let too_large = || format!("{url} is larger than {MAX_SIZE} bytes");
+
+    if let Some(length) = response.header("Content-Length") {
+        if length.parse::<u64>().is_ok_and(|length| length > MAX_SIZE) {
+            return Err(too_large());
+        }
+    }
+
+    let mut body = Vec::new();
+
+    response
+        .into_reader()
+        .take(MAX_SIZE + 1)
+        .read_to_end(&mut body)
+        .map_err(|e| format!("Failed to download {url}: {e}"))?;
+
+    if body.len() as u64 > MAX_SIZE {
+        return Err(too_large());
+    }
+
+    // written next to the final path first, so an interrupted write is never cached
+    let partial = path.with_extension("part");
+
+    fs::write(&partial, body)
+        .and_then(|_| fs::rename(&partial, &path))
+        .map_err(|e| format!("Failed to cache {url}: {e}"))?;
+
+    Ok(path)
+}
+
+/// `$XDG_CACHE_HOME` or `~/.cache`, the file is named after an FNV-1a hash of the url,
+/// which stays the same between builds so the cache does too, and keeps its extension
+/// so the format can still be told from it
+fn cache_path(url: &str) -> Result<PathBuf, String> {
+    let directory = env::var_os("XDG_CACHE_HOME")
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| Path::new(&home).join(".cache")))
+        .ok_or("No cache directory, set XDG_CACHE_HOME")?
+        .join(env!("CARGO_PKG_NAME"));
+
+    fs::create_dir_all(&directory).map_err(|e| format!("Failed to create {}: {e}", directory.display()))?;
+
+    let hash = url
+        .bytes()
+        .fold(FNV_OFFSET, |hash, byte| (hash ^ byte as u64).wrapping_mul(FNV_PRIME));
+    let name = url.split(['?', '#']).next().unwrap_or(url);
+    let mut path = directory.join(format!("{hash:016x}"));
+
+    if let Some(extension) = Path::new(name).extension() {
+        path.set_extension(extension);
+    }
+
+    Ok(path)
+}
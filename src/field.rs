@@ -0,0 +1,86 @@
+//! Smooth randomness to vary tile weights over the output, like mountains that gather
+//! where an elevation map is high instead of being sprinkled everywhere. Fields only
+//! nudge the weights, the neighbor rules still decide what can go where.
+
+use std::collections::HashSet;
+use std::hash::Hash;
+
+use noise::{Fbm, MultiFractal, NoiseFn, Perlin};
+
+use crate::grid::Position;
+use crate::superstate::Collapsable;
+
+/// Layers of finer noise on top of the hills, more gives rougher edges
+const OCTAVES: usize = 3;
+
+/// Scales the weight of some tiles by perlin noise over the grid. Fields with the same
+/// seed and scale share their noise, so one can favor mountains where another keeps
+/// water away. `I` identifies tiles.
+#[derive(Debug, Clone)]
+pub struct NoiseField<I> {
+    noise: Fbm<Perlin>,
+    /// Cells per noise feature, larger gives wider hills
+    scale: f64,
+    /// Weight multiplier where the noise peaks, its inverse where the noise bottoms out
+    strength: f64,
+    tiles: HashSet<I>,
+}
+
+impl<I> NoiseField<I>
+where
+    I: Eq + Hash,
+{
+    pub fn new(seed: u32, tiles: HashSet<I>) -> Self {
+        Self {
+            noise: Fbm::new(seed).set_octaves(OCTAVES),
+            scale: 8.0,
+            strength: 4.0,
+            tiles,
+        }
+    }
+
+    pub fn scale(mut self, scale: f64) -> Self {
+        self.scale = scale;
+        self
+    }
+
+    /// Below 1 pushes the tiles away from the peaks instead
+    pub fn strength(mut self, strength: f64) -> Self {
+        self.strength = strength;
+        self
+    }
+
+    /// Noise at `position`, between -1 and 1
+    pub fn sample(&self, position: &Position) -> f64 {
+        let point = [position.x as f64 / self.scale, position.y as f64 / self.scale];
+
+        self.noise.get(point).clamp(-1.0, 1.0)
+    }
+
+    /// What the weight of tile `id` is multiplied by at `position`, 1 for tiles the
+    /// field doesn't cover
+    pub fn multiplier(&self, position: &Position, id: &I) -> f64 {
+        match self.tiles.contains(id) {
+            true => self.strength.powf(self.sample(position)),
+            false => 1.0,
+        }
+    }
+}
+
+/// Weight modifier for `WaveBuilder::weight_modifier` that stacks `fields` on top of the
+/// weights of the tiles
+pub fn weights<T>(fields: Vec<NoiseField<T::Identifier>>) -> impl Fn(&Position, &T) -> f64 + Send + Sync + 'static
+where
+    T: Collapsable,
+    T::Identifier: 'static,
+{
+    move |position, tile| {
+        let id = tile.get_id();
+
+        fields
+            .iter()
+            .map(|field| field.multiplier(position, &id))
+            .product::<f64>()
+            * tile.get_weight()
+    }
+}
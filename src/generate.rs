@@ -0,0 +1,1438 @@
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use image::{DynamicImage, GenericImageView, Pixel};
+use indicatif::{HumanDuration, ProgressBar, ProgressStyle};
+use rand::rngs::OsRng;
+use rand::Rng;
+use tracing::subscriber::NoSubscriber;
+use tracing::{debug, error, info, trace, warn};
+
+use wave_function_collapse::analysis::{dead_tiles, SolveEstimate, TilesetStats};
+use wave_function_collapse::audit;
+use wave_function_collapse::biome::Biomes;
+use wave_function_collapse::carve::Carve;
+use wave_function_collapse::decoration;
+#[cfg(feature = "exact")]
+use wave_function_collapse::exact;
+#[cfg(feature = "noise")]
+use wave_function_collapse::field::{self, NoiseField};
+#[cfg(feature = "gpu")]
+use wave_function_collapse::gpu;
+use wave_function_collapse::grid::{Grid, Position, Size};
+use wave_function_collapse::overlay::Overlay;
+#[cfg(feature = "threaded")]
+use rayon::{ThreadPool, ThreadPoolBuilder};
+#[cfg(feature = "threaded")]
+use wave_function_collapse::portfolio;
+use wave_function_collapse::postprocess::PostProcess;
+use wave_function_collapse::preprocess::{downscale, flatten, Preprocess};
+use wave_function_collapse::profiling::{self, Phase};
+use wave_function_collapse::recorder::{self, Recorder, Replay};
+use wave_function_collapse::sprite::Sprite;
+#[cfg(any(feature = "sdl2", feature = "tui", feature = "live"))]
+use {
+    image::{Rgb, Rgba},
+    wave_function_collapse::style::CellStyle,
+};
+use wave_function_collapse::superstate::{Collapsable, SuperState};
+use wave_function_collapse::tile::{Boundary, Extraction, Provenance, Tile, TileIssue, Variants};
+use wave_function_collapse::usage::AdjacencyUsage;
+use wave_function_collapse::wave::{Progress, RollbackSchedule, Wave, WaveBuilder};
+
+#[cfg(feature = "sdl2")]
+use {
+    crate::visual::{self, SdlDraw},
+    wave_function_collapse::history::EditHistory,
+    wave_function_collapse::wave::Rollback,
+};
+#[cfg(feature = "live")]
+use crate::live;
+#[cfg(feature = "tui")]
+use crate::tui;
+use crate::output::{self, OutputFormat};
+use crate::{Animation, Input, Opt};
+
+/// Largest grid solved by --estimate, and how many times
+const ESTIMATE_SIZE: usize = 16;
+const ESTIMATE_RUNS: usize = 8;
+
+fn report_failure(dir: Option<&Path>, wfc: &Wave<Tile<Sprite>>, tiles: &[Tile<Sprite>], reason: &str) {
+    let Some(dir) = dir else {
+        return;
+    };
+    let (tile_width, tile_height) = tiles[0].value.image().dimensions();
+
+    match output::write_failure_report(dir, wfc, tile_width, tile_height, reason) {
+        Ok(()) => info!("Wrote a failure report to {}", dir.display()),
+        Err(e) => error!("Failed to write failure report: {e}"),
+    }
+}
+
+/// Solves the tiles of `overlay` as a layer on top of the finished wave, every tile of
+/// the overlay only goes on the input tiles it's drawn over
+fn decorate(
+    input: &DynamicImage,
+    overlay: &DynamicImage,
+    input_size: usize,
+    wfc: &Wave<Tile<Sprite>>,
+    max_rollbacks: Option<usize>,
+) -> Result<Wave<Tile<Sprite>>, String> {
+    let size = Size::uniform(input_size);
+    let pairs = Tile::pairs(input, overlay, &size)?;
+    let decorations = SuperState::new(Tile::from_image(overlay, &size).into_iter().map(Arc::new).collect());
+    let mut layer = decoration::layer(&wfc.grid, &decorations, |tile, decoration| {
+        pairs.contains(&(tile.get_id(), decoration.get_id()))
+    })
+    .map_err(|position| format!("No decoration fits the tile at {}x{}", position.x, position.y))?
+    .seed(wfc.seed())
+    .build();
+
+    while !layer.done() {
+        if max_rollbacks.is_some_and(|limit| layer.rollbacks() > limit) {
+            return Err(format!("Gave up after {} contradictions", layer.rollbacks()));
+        }
+
+        layer.tick();
+    }
+
+    Ok(layer)
+}
+
+fn progress_message(progress: &Progress) -> String {
+    let eta = match progress.eta() {
+        Some(eta) => HumanDuration(eta).to_string(),
+        None => "?".to_string(),
+    };
+
+    format!("{:.0}/s, {eta} left, {} rollbacks", progress.net_rate, progress.rollbacks)
+}
+
+fn load_replay(path: &PathBuf, wfc: &Wave<Tile<Sprite>>) -> io::Result<Replay<BufReader<File>>> {
+    let replay = Replay::new(BufReader::new(File::open(path)?))?;
+
+    if replay.header != wfc.header() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("Recording is for {:?}, not {:?}", replay.header, wfc.header()),
+        ));
+    }
+
+    Ok(replay)
+}
+
+/// Applies recorded events up to and including the next explicit collapse, or a single
+/// event when `once` is set. Returns false once the recording has run out.
+fn replay_tick<R: io::Read>(wfc: &mut Wave<Tile<Sprite>>, replay: &mut Replay<R>, once: bool) -> bool {
+    for event in replay.by_ref() {
+        let event = match event {
+            Ok(event) => event,
+            Err(e) => {
+                error!("Failed to read recording: {e}");
+                return false;
+            }
+        };
+
+        wfc.apply(&event);
+
+        if once || matches!(event, recorder::Event::Collapse(..)) {
+            return true;
+        }
+    }
+
+    false
+}
+
+/// Ways of watching a generation while it runs, any of them can be on at once
+#[derive(Debug, Clone, Copy)]
+pub struct Views {
+    pub tui: bool,
+    pub live: bool,
+    pub visual: bool,
+}
+
+/// What stays the same when the input is reloaded with `--watch`
+pub struct Settings {
+    pub seed: u64,
+    /// Local file the input was loaded from, an url has none
+    source: Option<PathBuf>,
+    views: Views,
+    output_format: Option<OutputFormat>,
+    crop: Option<output::Crop>,
+    extraction: Extraction,
+    preprocess: Preprocess,
+    pub overlay: Overlay,
+    #[cfg(feature = "threaded")]
+    pool: Arc<ThreadPool>,
+}
+
+impl Settings {
+    /// Checks the options go together
+    pub fn new(opt: &Opt, source: Option<&Path>, views: Views) -> Result<Self, String> {
+        let output_format = match &opt.output {
+            Some(path) => match OutputFormat::resolve(path, opt.format)? {
+                format if opt.stream_output && format != OutputFormat::Png => {
+                    return Err(format!("Only PNG output can be streamed, {} isn't a PNG", path.display()));
+                }
+                format => Some(format),
+            },
+            None => None,
+        };
+
+        let crop = match (opt.crop, opt.trim_border) {
+            (Some(crop), _) => Some(crop),
+            (None, Some(border)) => Some(output::Crop::trim(&opt.output_size, border)),
+            (None, None) => None,
+        };
+
+        if let Some(crop) = crop {
+            crop.check(&opt.output_size)?;
+        }
+
+        // only the winner is known once it's done, there'd be nothing to watch
+        #[cfg(feature = "threaded")]
+        if opt.portfolio.is_some() && (views.tui || views.live || views.visual) {
+            return Err("--portfolio can't be combined with --visual, --tui or --live".into());
+        }
+
+        // a project file fills these in as defaults, which clap doesn't check conflicts for
+        #[cfg(feature = "threaded")]
+        if opt.portfolio.is_some() && (opt.record.is_some() || opt.replay.is_some()) {
+            return Err("--portfolio can't be combined with --record or --replay".into());
+        }
+
+        if !opt.carve.is_empty() && opt.carve_tiles.is_empty() {
+            return Err("--carve needs --carve-tiles to know what to carve the paths from".into());
+        }
+
+        if opt.biomes.is_some() && opt.biome_tiles.is_empty() {
+            return Err("--biomes needs at least one --biome-tiles".into());
+        }
+
+        if opt.decorations.is_some() && matches!(opt.input, Some(Input::Config(_) | Input::Animation(_))) {
+            return Err("--decorations needs a still image input to line up with".into());
+        }
+
+        if opt.watch {
+            if views.tui || views.live {
+                return Err("--watch can't be combined with --tui or --live".into());
+            }
+
+            if !source.is_some_and(|path| path.exists()) {
+                return Err("--watch needs a local input file".into());
+            }
+        }
+
+        let seed = opt.seed.unwrap_or_else(|| OsRng.gen());
+
+        #[cfg(feature = "threaded")]
+        let pool = ThreadPoolBuilder::new()
+            .num_threads(opt.threads.unwrap_or(0))
+            .build()
+            .map_err(|e| format!("Failed to start the thread pool: {e}"))?;
+
+        Ok(Self {
+            seed,
+            source: source.map(Path::to_path_buf),
+            views,
+            output_format,
+            crop,
+            extraction: Extraction {
+                boundary: match opt.mirror_edges {
+                    true => Boundary::Mirror,
+                    false => Boundary::Drop,
+                },
+                symmetry: opt.symmetry,
+                shared_weights: opt.shared_weights,
+            },
+            preprocess: Preprocess {
+                background: opt.flatten.map(|color| color.to_rgb()),
+                downscale: opt.downscale,
+                colors: opt.quantize.map(usize::from),
+            },
+            overlay: Overlay {
+                grid_lines: opt.grid_lines,
+                labels: opt.labels,
+            },
+            #[cfg(feature = "threaded")]
+            pool: Arc::new(pool),
+        })
+    }
+}
+
+/// How a generation ended
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Outcome {
+    /// The outputs were written, a watch carries on with the next change
+    Written,
+    /// There's nothing more to do: the options only asked for a report or a view was closed
+    Finished,
+    /// The wave couldn't be set up, solved or written
+    Failed,
+}
+
+/// Runs the generations of a session, the window stays open from one to the next
+pub struct Generator<'a> {
+    opt: &'a Opt,
+    settings: &'a Settings,
+    #[cfg(feature = "sdl2")]
+    sdl_draw: Option<SdlDraw>,
+}
+
+impl<'a> Generator<'a> {
+    pub fn new(opt: &'a Opt, settings: &'a Settings) -> Self {
+        Self {
+            opt,
+            settings,
+            #[cfg(feature = "sdl2")]
+            sdl_draw: None,
+        }
+    }
+
+    /// Solves the input and writes every output asked for
+    pub fn run(&mut self, input: &Input) -> Outcome {
+        let opt = self.opt;
+        let settings = self.settings;
+        let preprocess = &settings.preprocess;
+        let current = match input {
+            Input::Image(image) if !preprocess.is_empty() => Cow::Owned(Input::Image(preprocess.apply(image))),
+            Input::Animation(animation) if !preprocess.is_empty() => Cow::Owned(Input::Animation(Animation {
+                frames: animation.frames.iter().map(|frame| preprocess.apply(frame)).collect(),
+                delays: animation.delays.clone(),
+            })),
+            current => Cow::Borrowed(current),
+        };
+        let tileset = Tileset::new(opt, &current, settings.source.as_deref(), &settings.extraction);
+
+        if opt.dry_run || opt.estimate {
+            describe_tileset(opt, &tileset, settings.seed);
+            return Outcome::Finished;
+        }
+
+        let factory = match WaveFactory::new(opt, settings, &tileset) {
+            Ok(factory) => factory,
+            Err(e) => {
+                error!("{e}");
+                return Outcome::Failed;
+            }
+        };
+
+        if opt.audit {
+            audit(&factory, settings.seed);
+            return Outcome::Finished;
+        }
+
+        #[cfg(feature = "sdl2")]
+        let solved = solve(opt, settings, &tileset, &factory, &mut self.sdl_draw);
+
+        #[cfg(not(feature = "sdl2"))]
+        let solved = solve(opt, settings, &tileset, &factory);
+
+        let wfc = match solved {
+            Ok(wfc) => wfc,
+            Err(outcome) => return outcome,
+        };
+
+        let decorations = solve_decorations(opt, &wfc, &current);
+
+        #[cfg(feature = "sdl2")]
+        if let Some(draw) = self.sdl_draw.as_mut().filter(|_| opt.scrub && wfc.done()) {
+            info!("Scrubbing through {} collapses", wfc.collapse_order().count());
+
+            draw.scrub(&wfc);
+        }
+
+        #[cfg(feature = "sdl2")]
+        if let Some(delay) = opt.hold {
+            info!("Waiting for {} seconds", delay);
+
+            std::thread::sleep(Duration::from_secs_f32(delay));
+        }
+
+        info!("Drawing output");
+
+        let written = write_output(opt, settings, &wfc, &tileset, &current, decorations.as_ref());
+
+        write_reports(opt, &wfc, &tileset);
+
+        match written {
+            Ok(()) => Outcome::Written,
+            Err(e) => {
+                error!("Failed to write {}: {e}", opt.output.as_ref().unwrap().display());
+                Outcome::Failed
+            }
+        }
+    }
+
+    /// Drains the window's events, true once it was closed. Always false without one.
+    pub fn closed(&mut self) -> bool {
+        #[cfg(feature = "sdl2")]
+        if let Some(draw) = self.sdl_draw.as_mut() {
+            return draw.quit_requested();
+        }
+
+        false
+    }
+}
+
+/// The tiles of an input, checked and pruned
+pub struct Tileset {
+    pub tiles: Vec<Tile<Sprite>>,
+    variants: Variants,
+    pub provenance: HashMap<u64, Provenance>,
+    /// `tags` of every tile of a config, empty for images
+    #[cfg(feature = "noise")]
+    tags: HashMap<u64, Vec<String>>,
+}
+
+impl Tileset {
+    /// Extracts the tiles and reports the broken ones and the ones that can never be
+    /// placed, exits on any of them with `--strict`
+    fn new(opt: &Opt, input: &Input, source: Option<&Path>, extraction: &Extraction) -> Self {
+        let timer = profiling::time(Phase::Extraction);
+        let (mut tiles, variants) = match input {
+            Input::Image(value) => (
+                Tile::from_image_with(value, &Size::uniform(opt.input_size.unwrap()), extraction),
+                Variants::new(),
+            ),
+            Input::Animation(value) => Tile::from_frames(&value.frames, &Size::uniform(opt.input_size.unwrap()), extraction),
+            Input::Config(value) => (Tile::from_config(value), Variants::new()),
+        };
+
+        let provenance = provenance(input, source, &tiles, opt.input_size, extraction);
+
+        drop(timer);
+
+        info!("{} unique tiles found", tiles.len());
+
+        // the config lines up with the tiles until they get pruned
+        #[cfg(feature = "noise")]
+        let tags: HashMap<u64, Vec<String>> = match input {
+            Input::Config(configs) => tiles
+                .iter()
+                .zip(configs)
+                .map(|(tile, config)| (tile.get_id(), config.tags().to_vec()))
+                .collect(),
+            Input::Image(_) | Input::Animation(_) => HashMap::new(),
+        };
+
+        let issues = Tile::validate(&tiles);
+
+        if !issues.is_empty() {
+            let names = tile_names(&provenance);
+            let name = |id: u64| names.get(&id).cloned().unwrap_or_else(|| format!("Tile {id}"));
+
+            for issue in &issues {
+                match issue {
+                    _ if opt.strict => error!("{}", issue.describe(name)),
+                    TileIssue::Unsolvable(_) => warn!("{}", issue.describe(name)),
+                    _ => debug!("{}", issue.describe(name)),
+                }
+            }
+        }
+
+        let invalid_neighbors = tiles
+            .iter()
+            .map(|t| t.neighbors.len())
+            .filter(|c| *c != 4)
+            .collect::<Vec<usize>>();
+
+        if opt.strict && !invalid_neighbors.is_empty() {
+            error!(
+                "Found {} tiles with invalid amount of neighbors: {:?}",
+                invalid_neighbors.len(),
+                invalid_neighbors
+            );
+        }
+
+        if opt.strict && (!issues.is_empty() || !invalid_neighbors.is_empty()) {
+            error!("Tileset failed strict validation");
+            profiling::finish();
+            std::process::exit(1);
+        }
+
+        if !invalid_neighbors.is_empty() {
+            warn!(
+                "Found {} tiles with invalid amount of neighbors: {:?}",
+                invalid_neighbors.len(),
+                invalid_neighbors
+            );
+
+            tiles.retain(|t| t.neighbors.len() == 4);
+
+            warn!("Retained {} tiles", tiles.len());
+        }
+
+        let dead = dead_tiles(&tiles, &opt.output_size);
+
+        if !dead.is_empty() {
+            let names = tile_names(&provenance);
+            let name = |id: u64| names.get(&id).cloned().unwrap_or_else(|| format!("Tile {id}"));
+
+            for tile in &dead {
+                match opt.prune {
+                    true => debug!("{}", tile.describe(name)),
+                    false => warn!("{}", tile.describe(name)),
+                }
+            }
+
+            if opt.prune {
+                tiles.retain(|tile| dead.iter().all(|dead| dead.id != tile.get_id()));
+
+                info!("Pruned {} tiles that can never be placed", dead.len());
+            } else {
+                warn!("Found {} tiles that can never be placed, --prune removes them", dead.len());
+            }
+        }
+
+        Self {
+            tiles,
+            variants,
+            provenance,
+            #[cfg(feature = "noise")]
+            tags,
+        }
+    }
+}
+
+/// `--dry-run` and `--estimate`, prints what's known about the tiles without solving
+fn describe_tileset(opt: &Opt, tileset: &Tileset, seed: u64) {
+    let names = tile_names(&tileset.provenance);
+    let name = |id: u64| names.get(&id).cloned().unwrap_or_else(|| format!("Tile {id}"));
+
+    println!("{}", TilesetStats::new(&tileset.tiles, &opt.output_size).describe(name));
+
+    if opt.estimate {
+        let size = Size {
+            width: opt.output_size.width.min(ESTIMATE_SIZE),
+            height: opt.output_size.height.min(ESTIMATE_SIZE),
+        };
+
+        // contradictions are what's being measured, no need to warn about each
+        let estimate = tracing::subscriber::with_default(NoSubscriber::default(), || {
+            SolveEstimate::new(&tileset.tiles, size, ESTIMATE_RUNS, seed)
+        });
+
+        println!("Estimate:       {}", estimate.describe());
+    }
+}
+
+/// Builds the waves of a tileset as the options ask, restarts and portfolios build
+/// more of them with other seeds
+struct WaveFactory<'a> {
+    opt: &'a Opt,
+    settings: &'a Settings,
+    base_state: SuperState<Tile<Sprite>>,
+    carve_ids: HashSet<u64>,
+    biome_palettes: Vec<HashSet<u64>>,
+    #[cfg(feature = "noise")]
+    noise_tiles: Vec<HashSet<u64>>,
+}
+
+impl<'a> WaveFactory<'a> {
+    /// Looks up the tiles the options name by index or tag
+    fn new(opt: &'a Opt, settings: &'a Settings, tileset: &Tileset) -> Result<Self, String> {
+        let tiles = &tileset.tiles;
+
+        // the exact solver takes the indexes as they are, the wave orders tiles by id too
+        #[cfg(feature = "exact")]
+        if let Some((index, _)) = opt.exact_max_count.iter().find(|(index, _)| *index >= tiles.len()) {
+            return Err(format!("--exact-max-count only goes up to {}, got {index}", tiles.len() - 1));
+        }
+
+        // every wave starts the recording over, a path that can't be written fails here
+        if let Some(path) = &opt.record {
+            File::create(path).map_err(|e| format!("Failed to create recording: {e}"))?;
+        }
+
+        Ok(Self {
+            opt,
+            settings,
+            base_state: SuperState::new(tiles.iter().cloned().map(Arc::new).collect()),
+            carve_ids: tile_ids(tiles, &opt.carve_tiles).map_err(|e| format!("--carve-tiles {e}"))?,
+            biome_palettes: opt
+                .biome_tiles
+                .iter()
+                .map(|indexes| tile_ids(tiles, indexes))
+                .collect::<Result<_, _>>()
+                .map_err(|e| format!("--biome-tiles {e}"))?,
+            #[cfg(feature = "noise")]
+            noise_tiles: opt
+                .noise
+                .iter()
+                .map(|weight| noise_ids(tiles, &tileset.tags, &weight.tiles))
+                .collect::<Result<_, _>>()
+                .map_err(|e| format!("--noise {e}"))?,
+        })
+    }
+
+    fn build(&self, seed: u64) -> Wave<Tile<Sprite>> {
+        let opt = self.opt;
+        let views = self.settings.views;
+        let grid = Grid::new(
+            opt.output_size.width,
+            opt.output_size.height,
+            &mut |_, _| self.base_state.clone(),
+        );
+        let mut builder = WaveBuilder::new(grid)
+            .seed(seed)
+            .area_seeds(opt.area_seeds)
+            .diagonals(opt.diagonals)
+            .temperature(opt.temperature)
+            .track_rollbacks(views.visual || views.live)
+            .rollback_schedule(RollbackSchedule {
+                initial_step: opt.rollback_step,
+                growth_percent: opt.rollback_growth,
+                reset_threshold: opt.rollback_reset,
+                restart_unit: opt.restart_unit,
+                repair_steps: opt.repair_steps,
+            });
+
+        if !opt.carve.is_empty() {
+            let class = self.carve_ids.clone();
+            let carve = Carve::new(opt.output_size.width, opt.output_size.height, seed, move |tile: &Tile<Sprite>| {
+                class.contains(&tile.get_id())
+            })
+            .radius(opt.carve_radius);
+
+            builder = builder.constraint(opt.carve.iter().fold(carve, |carve, route| carve.route(*route)));
+        }
+
+        if let Some(count) = opt.biomes {
+            let biomes = Biomes::voronoi(
+                opt.output_size.width,
+                opt.output_size.height,
+                count,
+                self.biome_palettes.clone(),
+                seed,
+            );
+
+            builder = builder.constraint(biomes);
+        }
+
+        #[cfg(feature = "noise")]
+        if !opt.noise.is_empty() {
+            let fields = opt
+                .noise
+                .iter()
+                .zip(&self.noise_tiles)
+                .map(|(weight, ids)| {
+                    NoiseField::new(seed as u32, ids.clone())
+                        .scale(weight.scale)
+                        .strength(weight.strength)
+                })
+                .collect();
+
+            builder = builder.weight_modifier(field::weights(fields));
+        }
+
+        #[cfg(feature = "threaded")]
+        {
+            builder = builder.thread_pool(self.settings.pool.clone());
+        }
+
+        #[cfg(feature = "gpu")]
+        if opt.gpu {
+            builder = builder.gpu(gpu::MIN_FRONT);
+        }
+
+        if let Some(path) = &opt.record {
+            match File::create(path) {
+                Ok(file) => {
+                    info!("Recording to {}", path.display());
+
+                    builder = builder.recorder(Recorder::new(Box::new(BufWriter::new(file))));
+                }
+                Err(e) => error!("Failed to create recording, solving without it: {e}"),
+            }
+        }
+
+        builder.build()
+    }
+}
+
+/// `--audit`, solves the seed twice and exits with an error when the runs differ
+fn audit(factory: &WaveFactory, seed: u64) {
+    info!("Solving seed {seed} twice to check both runs do the same");
+
+    match audit::audit(&mut factory.build(seed), &mut factory.build(seed)) {
+        Ok(events) => println!("Both runs did the same {events} steps"),
+        Err(divergence) => {
+            error!("{divergence}");
+            profiling::finish();
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Solves a wave of the tileset while keeping every view up to date, Err is
+/// `Outcome::Finished` once a view is closed and `Outcome::Failed` when the solver gives up
+fn solve(
+    opt: &Opt,
+    settings: &Settings,
+    tileset: &Tileset,
+    factory: &WaveFactory,
+    #[cfg(feature = "sdl2")] sdl_draw: &mut Option<SdlDraw>,
+) -> Result<Wave<Tile<Sprite>>, Outcome> {
+    let tiles = &tileset.tiles;
+    #[cfg(feature = "threaded")]
+    let wfc = match opt.portfolio.filter(|count| *count > 1) {
+        Some(count) => race(opt, settings, tiles, factory, count).ok_or(Outcome::Failed)?,
+        None => factory.build(settings.seed),
+    };
+    #[cfg(not(feature = "threaded"))]
+    let wfc = factory.build(settings.seed);
+    let progress = progress_bar(wfc.grid.size() as u64, settings.views.tui);
+
+    #[cfg(any(feature = "sdl2", feature = "tui", feature = "live"))]
+    let style = match opt.contradiction_color {
+        Some(Rgba([r, g, b, _])) => CellStyle::new(opt.palette).contradiction_color(Rgb([r, g, b])),
+        None => CellStyle::new(opt.palette),
+    };
+
+    #[cfg(feature = "live")]
+    let mut live_view = match &opt.live {
+        Some(bind) => match live::LiveView::start(bind, tiles, &style, wfc.grid.width(), wfc.grid.height()) {
+            Ok(view) => Some(view),
+            Err(e) => {
+                error!("Failed to start live view: {e}");
+                return Err(Outcome::Failed);
+            }
+        },
+        None => None,
+    };
+
+    let mut replay = match &opt.replay {
+        Some(path) => match load_replay(path, &wfc) {
+            Ok(replay) => Some(replay),
+            Err(e) => {
+                error!("Failed to load recording: {e}");
+                return Err(Outcome::Failed);
+            }
+        },
+        None => None,
+    };
+
+    #[cfg(feature = "tui")]
+    let mut tui_view = settings.views.tui.then(|| tui::Tui::start(style.clone(), settings.seed));
+
+    #[cfg(feature = "sdl2")]
+    visual::open_window(opt, settings, tileset, &style, sdl_draw);
+
+    let mut limits = Limits::new(opt);
+    let replaying = replay.is_some();
+
+    // a session keeps going after the wave is done, there might be more to edit
+    #[cfg(feature = "sdl2")]
+    let editing = opt.session.is_some();
+    #[cfg(not(feature = "sdl2"))]
+    let editing = false;
+
+    let shared = Mutex::new(Shared {
+        wave: wfc,
+        editing,
+        edited: false,
+        closed: false,
+        #[cfg(feature = "sdl2")]
+        rollbacks: Vec::new(),
+    });
+    let frame_wanted = AtomicBool::new(false);
+
+    // rollbacks take collapses back, the bar stays at the furthest the wave got
+    let mut furthest = 0;
+
+    #[cfg(feature = "sdl2")]
+    let step_time = match opt.slow {
+        true => Some(Duration::from_secs(1) / 60),
+        false => opt.steps_per_second.map(|rate| Duration::from_secs_f64(rate.recip())),
+    };
+    #[cfg(not(feature = "sdl2"))]
+    let step_time: Option<Duration> = None;
+    let mut next_step = Instant::now();
+
+    let mut solver = || -> Result<(), Outcome> {
+        loop {
+            // the window only needs the wave for a moment, it gets it before the next tick
+            while frame_wanted.load(Ordering::Acquire) {
+                thread::yield_now();
+            }
+
+            let mut guard = shared.lock().unwrap();
+            let state = &mut *guard;
+            let wfc = &mut state.wave;
+
+            if state.closed {
+                return Err(Outcome::Finished);
+            }
+
+            if !replaying && wfc.done() && !state.editing {
+                break;
+            }
+
+            if std::mem::take(&mut state.edited) {
+                furthest = wfc.progress().collapsed as u64;
+            }
+
+            let status = wfc.progress();
+
+            furthest = furthest.max(status.collapsed as u64);
+            progress.set_position(furthest);
+            progress.set_message(progress_message(&status));
+
+            // an edit might have finished the wave, a session waits for more edits
+            if !replaying && wfc.done() {
+                drop(guard);
+                thread::sleep(Duration::from_millis(16));
+                continue;
+            }
+
+            #[cfg(feature = "tui")]
+            if let Some(view) = tui_view.as_mut() {
+                match view.update(wfc).unwrap() {
+                    tui::Action::Quit => return Err(Outcome::Finished),
+                    tui::Action::Restart if !replaying => {
+                        restart(wfc, view, factory);
+                        continue;
+                    }
+                    _ => {}
+                }
+            }
+
+            // a throttled solver waits for its next step without holding on to the wave
+            if let Some(step_time) = step_time {
+                let now = Instant::now();
+
+                if now < next_step {
+                    drop(guard);
+                    thread::sleep(next_step - now);
+                    continue;
+                }
+
+                next_step = now + step_time;
+            }
+
+            let slow = step_time.is_some();
+
+            if let Some(events) = replay.as_mut() {
+                if !replay_tick(wfc, events, slow) {
+                    break;
+                }
+            } else if slow {
+                wfc.tick_once();
+            } else {
+                wfc.tick();
+            }
+
+            // a replay does what the recorded run did, limits included
+            let checked = match replay {
+                Some(_) => Ok(()),
+                None => limits.check(wfc),
+            };
+
+            if let Err(reason) = checked {
+                report_failure(opt.failure_report.as_deref(), wfc, tiles, reason);
+                return Err(Outcome::Failed);
+            }
+
+            #[cfg(any(feature = "sdl2", feature = "live"))]
+            let rollbacks = wfc.take_rollbacks();
+
+            #[cfg(feature = "live")]
+            if let Some(view) = live_view.as_mut() {
+                view.flash(&rollbacks, wfc.grid.width());
+                view.update(&wfc.grid);
+            }
+
+            #[cfg(feature = "sdl2")]
+            state.rollbacks.extend(rollbacks);
+
+            #[cfg(feature = "tui")]
+            if let Some(view) = tui_view.as_mut().filter(|_| !replaying && wfc.done()) {
+                if let tui::Action::Restart = view.finish(wfc).unwrap() {
+                    restart(wfc, view, factory);
+                }
+            }
+        }
+
+        let wfc = &mut shared.lock().unwrap().wave;
+
+        // a replay can't be restarted, it only gets to show the result
+        #[cfg(feature = "tui")]
+        if let Some(view) = tui_view.as_mut().filter(|_| replaying) {
+            view.finish(wfc).unwrap();
+        }
+
+        #[cfg(feature = "live")]
+        if let Some(view) = live_view.as_mut() {
+            view.flush(&wfc.grid);
+        }
+
+        if let Err(e) = wfc.finish_recording() {
+            error!("Failed to write recording: {e}");
+        }
+
+        progress.finish();
+
+        Ok(())
+    };
+
+    // the solver runs at its own pace next to the window, which samples the wave once a
+    // frame and hands it the edits
+    #[cfg(feature = "sdl2")]
+    let solved = match sdl_draw.as_mut() {
+        Some(draw) => thread::scope(|scope| {
+            let solver = scope.spawn(solver);
+
+            watch(opt, draw, &shared, &frame_wanted, replaying, &solver);
+
+            solver.join().unwrap()
+        }),
+        None => solver(),
+    };
+    #[cfg(not(feature = "sdl2"))]
+    let solved = solver();
+
+    #[cfg(feature = "tui")]
+    drop(tui_view);
+
+    solved?;
+
+    let wfc = shared.into_inner().unwrap().wave;
+
+    #[cfg(feature = "sdl2")]
+    if let Some(draw) = sdl_draw.as_mut() {
+        visual::update_canvas(&wfc, draw);
+    }
+
+    Ok(wfc)
+}
+
+/// The wave and what the solver and the window tell each other about it
+struct Shared {
+    wave: Wave<Tile<Sprite>>,
+    /// Set while a session waits for edits, the solver keeps going after the wave is done
+    editing: bool,
+    /// Set by the window when an edit went through
+    edited: bool,
+    /// Set by the window once it's closed, the solver stops at its next tick
+    closed: bool,
+    /// Rollbacks the window hasn't flashed yet
+    #[cfg(feature = "sdl2")]
+    rollbacks: Vec<Rollback>,
+}
+
+/// Draws the wave and applies the window's edits once a frame until the solver stops
+#[cfg(feature = "sdl2")]
+fn watch<T>(
+    opt: &Opt,
+    draw: &mut SdlDraw,
+    shared: &Mutex<Shared>,
+    frame_wanted: &AtomicBool,
+    replaying: bool,
+    solver: &thread::ScopedJoinHandle<T>,
+) {
+    let mut history = EditHistory::new(visual::EDIT_HISTORY);
+
+    while !solver.is_finished() {
+        let frame = Instant::now();
+
+        frame_wanted.store(true, Ordering::Release);
+
+        {
+            let mut guard = shared.lock().unwrap();
+            let state = &mut *guard;
+
+            frame_wanted.store(false, Ordering::Release);
+            draw.flash(std::mem::take(&mut state.rollbacks));
+
+            match visual::apply_actions(opt, draw, &mut state.wave, &mut history, replaying, &mut state.editing) {
+                Some(edited) => state.edited |= edited,
+                None => {
+                    state.closed = true;
+                    return;
+                }
+            }
+
+            if !replaying {
+                history.solving(&state.wave);
+            }
+
+            visual::draw_canvas(&state.wave, draw);
+        }
+
+        draw.present();
+        thread::sleep(visual::FRAME_TIME.saturating_sub(frame.elapsed()));
+    }
+}
+
+fn progress_bar(len: u64, hidden: bool) -> ProgressBar {
+    let progress = match hidden {
+        true => ProgressBar::hidden(),
+        false => ProgressBar::new(len),
+    };
+
+    progress.enable_steady_tick(Duration::from_millis(200));
+    progress.set_style(
+        ProgressStyle::default_bar()
+            .template("{spinner:.green} [{elapsed_precise}] [{bar:40.cyan/blue}] {pos:>5}/{len} {msg}")
+            .unwrap()
+            .progress_chars("#>-"),
+    );
+
+    progress
+}
+
+/// What `--exact` and `--max-rollbacks` hold a wave to, checked after every tick
+struct Limits<'a> {
+    opt: &'a Opt,
+    #[cfg(feature = "exact")]
+    exact_tried: bool,
+}
+
+impl<'a> Limits<'a> {
+    fn new(opt: &'a Opt) -> Self {
+        Self {
+            opt,
+            #[cfg(feature = "exact")]
+            exact_tried: false,
+        }
+    }
+
+    /// Hands the wave to the exact solver once it ran into enough contradictions, Err
+    /// is why the wave gave up for the failure report
+    fn check(&mut self, wfc: &mut Wave<Tile<Sprite>>) -> Result<(), &'static str> {
+        #[cfg(feature = "exact")]
+        if !self.exact_tried && self.opt.exact.is_some_and(|limit| wfc.rollbacks() >= limit) {
+            self.exact_tried = true;
+            switch_to_exact(self.opt, wfc)?;
+        }
+
+        if !wfc.done() && self.opt.max_rollbacks.is_some_and(|limit| wfc.rollbacks() > limit) {
+            error!("Giving up after {} contradictions", wfc.rollbacks());
+            return Err("too many contradictions");
+        }
+
+        Ok(())
+    }
+}
+
+/// `--portfolio`, solves `count` seeds in parallel and returns the first one to finish.
+/// Every seed is held to the same limits as a single wave, None when all of them gave up.
+#[cfg(feature = "threaded")]
+fn race(
+    opt: &Opt,
+    settings: &Settings,
+    tiles: &[Tile<Sprite>],
+    factory: &WaveFactory,
+    count: usize,
+) -> Option<Wave<Tile<Sprite>>> {
+    let seed = settings.seed;
+
+    info!("Solving with {count} seeds in parallel");
+
+    let waves = (0..count as u64).map(|index| factory.build(seed.wrapping_add(index))).collect();
+    let limits = || {
+        let mut limits = Limits::new(opt);
+
+        move |wave: &mut Wave<Tile<Sprite>>| limits.check(wave)
+    };
+
+    match portfolio::solve_first(waves, limits) {
+        Ok((index, wave)) => {
+            info!("Seed {} finished first", seed.wrapping_add(index as u64));
+            Some(wave)
+        }
+        Err(failures) => {
+            error!("Every seed gave up");
+
+            // the report is on the seed that got the furthest
+            if let Some((wave, reason)) = failures.iter().min_by_key(|(wave, _)| wave.remaining()) {
+                report_failure(opt.failure_report.as_deref(), wave, tiles, reason);
+            }
+
+            None
+        }
+    }
+}
+
+/// Hands the wave to the exact solver for `--exact`, Err when it proves there's no
+/// solution
+#[cfg(feature = "exact")]
+fn switch_to_exact(opt: &Opt, wfc: &mut Wave<Tile<Sprite>>) -> Result<(), &'static str> {
+    info!("Switching to the exact solver after {} contradictions", wfc.rollbacks());
+
+    let options = exact::Options {
+        max_counts: opt.exact_max_count.clone(),
+        ..Default::default()
+    };
+
+    match wfc.solve_exact(&options) {
+        Ok(()) => info!("The exact solver found a solution"),
+        Err(exact::Failure::GaveUp) => warn!("The exact solver gave up, carrying on"),
+        Err(exact::Failure::Unsolvable) => {
+            error!("The grid has no solution");
+            return Err("the exact solver proved the grid has no solution");
+        }
+    }
+
+    Ok(())
+}
+
+/// `--decorations`, solved once the wave is done
+fn solve_decorations(opt: &Opt, wfc: &Wave<Tile<Sprite>>, input: &Input) -> Option<Wave<Tile<Sprite>>> {
+    let (Some(overlay), Input::Image(image)) = (&opt.decorations, input) else {
+        return None;
+    };
+
+    if !wfc.done() {
+        return None;
+    }
+
+    info!("Solving decorations");
+
+    // the overlay has to line up with the input, transparency and colors are its own
+    let overlay = match opt.downscale {
+        1 => Cow::Borrowed(overlay),
+        factor => Cow::Owned(DynamicImage::ImageRgba8(downscale(&overlay.to_rgba8(), factor))),
+    };
+
+    match decorate(image, &overlay, opt.input_size.unwrap(), wfc, opt.max_rollbacks) {
+        Ok(layer) => Some(layer),
+        Err(e) => {
+            error!("Failed to solve the decorations: {e}");
+            None
+        }
+    }
+}
+
+/// Writes the output image, cropped, decorated and post-processed as asked
+fn write_output(
+    opt: &Opt,
+    settings: &Settings,
+    wfc: &Wave<Tile<Sprite>>,
+    tileset: &Tileset,
+    input: &Input,
+    decorations: Option<&Wave<Tile<Sprite>>>,
+) -> Result<(), String> {
+    let (Some(output), Some(format)) = (&opt.output, settings.output_format) else {
+        return Ok(());
+    };
+    let _timer = profiling::time(Phase::Rendering);
+    let variants = &tileset.variants;
+    let overlay = settings.overlay;
+    let (tile_width, tile_height) = tileset.tiles[0].value.image().dimensions();
+
+    trace!("Tile size: {tile_width}x{tile_height}");
+
+    let (grid, decorations) = match settings.crop {
+        Some(crop) => (
+            Cow::Owned(crop.apply(&wfc.grid)),
+            decorations.map(|layer| Cow::Owned(crop.apply(&layer.grid))),
+        ),
+        None => (
+            Cow::Borrowed(&wfc.grid),
+            decorations.map(|layer| Cow::Borrowed(&layer.grid)),
+        ),
+    };
+
+    if opt.stream_output {
+        if !variants.is_empty() {
+            warn!("Streamed outputs aren't animated, writing the first frame");
+        }
+
+        return stream_output(opt, &overlay, output, &grid, decorations.as_deref(), (tile_width, tile_height));
+    }
+
+    let render = |frame: usize| {
+        let mut canvas = Tile::compose_frame(&grid, variants, frame, tile_width, tile_height);
+
+        if let Some(layer) = &decorations {
+            image::imageops::overlay(&mut canvas, &Tile::compose(layer, tile_width, tile_height), 0, 0);
+        }
+
+        for filter in &opt.post {
+            trace!("Running {filter:?}");
+
+            filter.apply(&mut canvas);
+        }
+
+        if let Some(matte) = opt.matte {
+            flatten(&mut canvas, matte.to_rgb());
+        }
+
+        overlay.apply(&mut canvas, &grid, tile_width, tile_height);
+
+        if opt.scale > 1 {
+            trace!("Scaling output by {}", opt.scale);
+
+            canvas = image::imageops::resize(
+                &canvas,
+                canvas.width() * opt.scale,
+                canvas.height() * opt.scale,
+                image::imageops::FilterType::Nearest,
+            );
+        }
+
+        canvas
+    };
+    let animation = match input {
+        Input::Animation(animation) if !variants.is_empty() => Some(animation),
+        _ => None,
+    };
+
+    trace!("Writing output");
+
+    match animation {
+        Some(animation) if format == OutputFormat::Png => {
+            let (width, height) = (
+                grid.width() as u32 * tile_width * opt.scale,
+                grid.height() as u32 * tile_height * opt.scale,
+            );
+            let frames = (0..animation.frames.len()).map(render);
+
+            output::write_frames(output, width, height, frames, &animation.delays)
+        }
+        Some(_) => {
+            warn!("{format:?} can't hold an animation, writing the first frame");
+            format.save(render(0), output, opt.quality)
+        }
+        None => format.save(render(0), output, opt.quality),
+    }
+}
+
+/// `--stream-output`, composes and writes the output a row of tiles at a time
+fn stream_output(
+    opt: &Opt,
+    overlay: &Overlay,
+    output: &Path,
+    grid: &Grid<SuperState<Tile<Sprite>>>,
+    decorations: Option<&Grid<SuperState<Tile<Sprite>>>>,
+    (tile_width, tile_height): (u32, u32),
+) -> Result<(), String> {
+    trace!("Streaming output");
+
+    let bands = (0..grid.height()).map(|row| {
+        let rows = row..row + 1;
+        let mut band = Tile::compose_rows(grid, rows.clone(), tile_width, tile_height);
+
+        if let Some(layer) = decorations {
+            let layer = Tile::compose_rows(layer, rows.clone(), tile_width, tile_height);
+
+            image::imageops::overlay(&mut band, &layer, 0, 0);
+        }
+
+        if let Some(matte) = opt.matte {
+            flatten(&mut band, matte.to_rgb());
+        }
+
+        overlay.apply_rows(&mut band, grid, rows, tile_width, tile_height);
+        band
+    });
+    let (width, height) = (
+        grid.width() as u32 * tile_width,
+        grid.height() as u32 * tile_height,
+    );
+
+    output::write_bands(output, width, height, opt.scale, bands)
+}
+
+/// Writes the reports about the solve next to the output, the indexed image, tile and
+/// adjacency usage, propagation stats, the collapse animation and heatmap
+fn write_reports(opt: &Opt, wfc: &Wave<Tile<Sprite>>, tileset: &Tileset) {
+    let tiles = &tileset.tiles;
+
+    if let Some(path) = &opt.indexed {
+        info!("Writing indexed image to {}", path.display());
+
+        if let Err(e) = output::write_indexed(&wfc.grid, tiles, &tileset.provenance, path, opt.indexed_cell_size) {
+            error!("Failed to write {}: {e}", path.display());
+        }
+    }
+
+    if opt.usage.is_some() || opt.usage_chart.is_some() {
+        let usage = output::tile_usage(wfc, tiles);
+
+        if let Some(format) = opt.usage {
+            output::print_usage(&usage, format);
+        }
+
+        if let Some(path) = &opt.usage_chart {
+            info!("Writing usage chart to {}", path.display());
+
+            if let Err(e) = output::write_usage_chart(&usage, path) {
+                error!("Failed to write {}: {e}", path.display());
+            }
+        }
+    }
+
+    if let Some(format) = opt.propagation {
+        output::print_propagation(wfc.propagation(), format);
+    }
+
+    if let Some(path) = &opt.adjacency {
+        info!("Writing adjacency usage to {}", path.display());
+
+        let usage = AdjacencyUsage::of_grid(&wfc.grid, tiles);
+        let written = File::create(path)
+            .map_err(|e| e.to_string())
+            .and_then(|file| {
+                serde_json::to_writer_pretty(BufWriter::new(file), &output::adjacency_json(&usage, tiles))
+                    .map_err(|e| e.to_string())
+            });
+
+        if let Err(e) = written {
+            error!("Failed to write {}: {e}", path.display());
+        }
+    }
+
+    if let Some(path) = opt.animation.as_ref().filter(|_| wfc.done()) {
+        info!("Writing animation to {}", path.display());
+
+        let (tile_width, tile_height) = tiles[0].value.image().dimensions();
+        let playback = output::Playback {
+            stride: opt.frame_stride.unwrap_or(wfc.grid.width()),
+            delay: opt.frame_delay,
+            hold: opt.final_hold,
+            tint: opt.order_overlay,
+        };
+
+        if let Err(e) = output::write_animation(wfc, tile_width, tile_height, path, &playback) {
+            error!("Failed to write {}: {e}", path.display());
+        }
+    }
+
+    if let Some(path) = opt.order_heatmap.as_ref().filter(|_| wfc.done()) {
+        info!("Writing collapse order to {}", path.display());
+
+        let (tile_width, tile_height) = tiles[0].value.image().dimensions();
+
+        if let Err(e) = output::write_order_heatmap(wfc, tile_width, tile_height, path) {
+            error!("Failed to write {}: {e}", path.display());
+        }
+    }
+}
+
+/// Ids of the tiles at dense `indexes`, the indexes `--labels` shows
+fn tile_ids(tiles: &[Tile<Sprite>], indexes: &[usize]) -> Result<HashSet<u64>, String> {
+    let mut ids: Vec<u64> = tiles.iter().map(|tile| tile.get_id()).collect();
+
+    ids.sort();
+
+    indexes
+        .iter()
+        .map(|index| {
+            ids.get(*index)
+                .copied()
+                .ok_or_else(|| format!("only goes up to {}, got {index}", ids.len() - 1))
+        })
+        .collect()
+}
+
+/// Ids of the tiles a noise field covers, by dense index or by the tags in the config
+#[cfg(feature = "noise")]
+fn noise_ids(tiles: &[Tile<Sprite>], tags: &HashMap<u64, Vec<String>>, names: &[String]) -> Result<HashSet<u64>, String> {
+    let mut ids = HashSet::new();
+
+    for name in names {
+        if let Ok(index) = name.parse::<usize>() {
+            ids.extend(tile_ids(tiles, &[index])?);
+            continue;
+        }
+
+        let tagged: Vec<u64> = tiles
+            .iter()
+            .map(|tile| tile.get_id())
+            .filter(|id| tags.get(id).is_some_and(|tags| tags.contains(name)))
+            .collect();
+
+        if tagged.is_empty() {
+            return Err(format!("no tile is tagged {name}"));
+        }
+
+        ids.extend(tagged);
+    }
+
+    Ok(ids)
+}
+
+/// Where every tile was learned from, the config entry or the first place it shows up in
+/// the input. Config tiles line up with the config until they get pruned.
+fn provenance(
+    input: &Input,
+    source: Option<&Path>,
+    tiles: &[Tile<Sprite>],
+    input_size: Option<usize>,
+    extraction: &Extraction,
+) -> HashMap<u64, Provenance> {
+    let mut provenance = match input {
+        Input::Image(image) => Tile::provenance(image, &Size::uniform(input_size.unwrap()), extraction),
+        Input::Animation(animation) => {
+            Tile::frame_provenance(&animation.frames, &Size::uniform(input_size.unwrap()), extraction)
+        }
+        Input::Config(configs) => {
+            return tiles
+                .iter()
+                .zip(configs)
+                .map(|(tile, config)| {
+                    let origin = Provenance {
+                        source: Some(config.image().to_path_buf()),
+                        position: None,
+                        turns: 0,
+                        flipped: false,
+                    };
+
+                    (tile.get_id(), origin)
+                })
+                .collect()
+        }
+    };
+
+    for origin in provenance.values_mut() {
+        origin.source = source.map(Path::to_path_buf);
+    }
+
+    provenance
+}
+
+/// Names tiles after where they come from
+fn tile_names(provenance: &HashMap<u64, Provenance>) -> HashMap<u64, String> {
+    provenance
+        .iter()
+        .map(|(id, origin)| {
+            let name = match (origin.position, &origin.source) {
+                (Some(Position { x, y }), _) => format!("Tile at column {x}, row {y} of the input"),
+                (None, Some(source)) => format!("Tile {}", source.display()),
+                (None, None) => format!("Tile {id}"),
+            };
+            let transform = match (origin.flipped, origin.turns) {
+                (false, 0) => String::new(),
+                (false, turns) => format!(", turned {}°", turns as u32 * 90),
+                (true, 0) => ", mirrored".to_string(),
+                (true, turns) => format!(", mirrored and turned {}°", turns as u32 * 90),
+            };
+
+            (*id, name + &transform)
+        })
+        .collect()
+}
+
+/// Starts over with a new seed, a recording is started over as well
+#[cfg(feature = "tui")]
+fn restart(wfc: &mut Wave<Tile<Sprite>>, view: &mut tui::Tui, factory: &WaveFactory) {
+    let seed = OsRng.gen();
+
+    // flush before the file is truncated by the new recorder
+    if let Err(e) = wfc.finish_recording() {
+        error!("Failed to write recording: {e}");
+    }
+
+    *wfc = factory.build(seed);
+    view.restart(seed);
+}
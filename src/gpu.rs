@@ -0,0 +1,246 @@
+//! GPU propagation for the `gpu` feature. Every cell is a bitmask of the tiles it can
+//! still be, kept in one GPU buffer next to the rules as bitmasks per tile and side. A
+//! compute shader (`gpu.wgsl`) narrows every cell against its neighbors in parallel,
+//! sweeps repeat until one changes nothing. That's the same fixpoint propagating on the
+//! CPU ends in, only reached by sweeping the whole grid, so it pays off on huge grids
+//! where a single collapse spreads far. See `WaveBuilder::gpu`.
+
+use std::sync::mpsc;
+
+use enum_map::Enum;
+use wgpu::util::DeviceExt;
+
+use crate::bitset::BitSet;
+use crate::grid::{Direction, Neighbors};
+
+/// Queued cells at which the wave hands propagation over to the GPU, below it the
+/// CPU only touches the cells that change and is faster than sweeping everything
+pub const MIN_FRONT: usize = 4096;
+/// Sweeps dispatched between checks whether the last ones changed anything, checking
+/// waits for the GPU to finish
+const SWEEPS_PER_CHECK: usize = 16;
+const WORKGROUP_SIZE: u32 = 64;
+/// Most workgroups a dispatch can have along one dimension
+const MAX_GROUPS: u32 = 65535;
+
+/// Device and buffers for sweeping grids of one size with one set of rules
+pub struct Propagator {
+    device: wgpu::Device,
+    queue: wgpu::Queue,
+    pipeline: wgpu::ComputePipeline,
+    bind_group: wgpu::BindGroup,
+    cells: wgpu::Buffer,
+    changed: wgpu::Buffer,
+    /// Where `cells` and `changed` are copied to be read back, in that order
+    readback: wgpu::Buffer,
+    /// u32 words per cell
+    words: usize,
+    cell_count: usize,
+    groups: (u32, u32),
+}
+
+impl Propagator {
+    /// Sets up the first GPU that supports compute shaders for a `width` by `height`
+    /// grid. `rules` are the wave's, the tiles allowed on every side of every dense
+    /// tile index.
+    pub fn new(rules: &[Neighbors<BitSet>], width: usize, height: usize) -> Result<Self, String> {
+        let instance = wgpu::Instance::default();
+        let adapter = pollster::block_on(instance.request_adapter(&wgpu::RequestAdapterOptions {
+            power_preference: wgpu::PowerPreference::HighPerformance,
+            ..Default::default()
+        }))
+        .ok_or("no GPU adapter found")?;
+
+        if !adapter
+            .get_downlevel_capabilities()
+            .flags
+            .contains(wgpu::DownlevelFlags::COMPUTE_SHADERS)
+        {
+            return Err(format!("{} can't run compute shaders", adapter.get_info().name));
+        }
+
+        let (device, queue) = pollster::block_on(adapter.request_device(
+            &wgpu::DeviceDescriptor {
+                label: Some("wfc"),
+                required_limits: adapter.limits(),
+                ..Default::default()
+            },
+            None,
+        ))
+        .map_err(|e| e.to_string())?;
+
+        let words = rules.len().div_ceil(32).max(1);
+        let cell_count = width * height;
+        let cells_size = (cell_count * words * 4) as u64;
+
+        if cells_size > device.limits().max_storage_buffer_binding_size as u64 {
+            return Err(format!("a {width}x{height} grid doesn't fit in a GPU buffer"));
+        }
+
+        let mut rule_words = vec![0u32; rules.len() * Direction::LENGTH * words];
+
+        for (tile, sides) in rules.iter().enumerate() {
+            for (direction, allowed) in sides {
+                let offset = (tile * Direction::LENGTH + direction.into_usize()) * words;
+
+                for other in (0..rules.len()).filter(|other| allowed.contains(*other)) {
+                    rule_words[offset + other / 32] |= 1 << (other % 32);
+                }
+            }
+        }
+
+        let groups = (cell_count as u32).div_ceil(WORKGROUP_SIZE).max(1);
+        let groups = (groups.min(MAX_GROUPS), groups.div_ceil(MAX_GROUPS));
+        let params = [width as u32, height as u32, words as u32, groups.0 * WORKGROUP_SIZE];
+
+        let storage = wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::COPY_SRC;
+        let params = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("params"),
+            contents: &bytes(&params),
+            usage: wgpu::BufferUsages::UNIFORM,
+        });
+        let rules = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("rules"),
+            contents: &bytes(&rule_words),
+            usage: wgpu::BufferUsages::STORAGE,
+        });
+        let cells = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("cells"),
+            size: cells_size,
+            usage: storage,
+            mapped_at_creation: false,
+        });
+        let changed = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("changed"),
+            size: 4,
+            usage: storage,
+            mapped_at_creation: false,
+        });
+        let readback = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("readback"),
+            size: cells_size + 4,
+            usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("sweep"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("gpu.wgsl").into()),
+        });
+        let pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some("sweep"),
+            layout: None,
+            module: &module,
+            entry_point: Some("sweep"),
+            compilation_options: Default::default(),
+            cache: None,
+        });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("sweep"),
+            layout: &pipeline.get_bind_group_layout(0),
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: params.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: cells.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: rules.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: changed.as_entire_binding(),
+                },
+            ],
+        });
+
+        Ok(Self {
+            device,
+            queue,
+            pipeline,
+            bind_group,
+            cells,
+            changed,
+            readback,
+            words,
+            cell_count,
+            groups,
+        })
+    }
+
+    /// u32 words every cell takes up in the masks `propagate` works on
+    pub fn words(&self) -> usize {
+        self.words
+    }
+
+    /// Narrows `masks`, `words` per cell in row-major order with bit `n` standing for
+    /// the tile with dense index `n`, until every tile left has something to sit next
+    /// to on every side. Cells that end up empty are contradictions.
+    pub fn propagate(&mut self, masks: &mut [u32]) -> Result<(), String> {
+        assert_eq!(masks.len(), self.cell_count * self.words, "masks for another grid size");
+
+        let cells_size = self.cells.size();
+
+        self.queue.write_buffer(&self.cells, 0, &bytes(masks));
+
+        loop {
+            self.queue.write_buffer(&self.changed, 0, &bytes(&[0]));
+
+            let mut encoder = self.device.create_command_encoder(&Default::default());
+
+            // a pass per sweep, the next sweep sees everything the last one wrote
+            for _ in 0..SWEEPS_PER_CHECK {
+                let mut pass = encoder.begin_compute_pass(&Default::default());
+
+                pass.set_pipeline(&self.pipeline);
+                pass.set_bind_group(0, &self.bind_group, &[]);
+                pass.dispatch_workgroups(self.groups.0, self.groups.1, 1);
+            }
+
+            encoder.copy_buffer_to_buffer(&self.changed, 0, &self.readback, cells_size, 4);
+            self.queue.submit([encoder.finish()]);
+
+            if self.read(cells_size..cells_size + 4)?[0] == 0 {
+                break;
+            }
+        }
+
+        let mut encoder = self.device.create_command_encoder(&Default::default());
+
+        encoder.copy_buffer_to_buffer(&self.cells, 0, &self.readback, 0, cells_size);
+        self.queue.submit([encoder.finish()]);
+        masks.copy_from_slice(&self.read(0..cells_size)?);
+
+        Ok(())
+    }
+
+    /// Waits for the GPU and reads `range` of the readback buffer
+    fn read(&self, range: std::ops::Range<u64>) -> Result<Vec<u32>, String> {
+        let slice = self.readback.slice(range);
+        let (sender, receiver) = mpsc::channel();
+
+        slice.map_async(wgpu::MapMode::Read, move |result| {
+            let _ = sender.send(result);
+        });
+        self.device.poll(wgpu::Maintain::Wait);
+        receiver.recv().map_err(|e| e.to_string())?.map_err(|e| e.to_string())?;
+
+        let words = slice
+            .get_mapped_range()
+            .chunks_exact(4)
+            .map(|word| u32::from_le_bytes(word.try_into().unwrap()))
+            .collect();
+
+        self.readback.unmap();
+
+        Ok(words)
+    }
+}
+
+fn bytes(words: &[u32]) -> Vec<u8> {
+    words.iter().flat_map(|word| word.to_le_bytes()).collect()
+}
@@ -0,0 +1,128 @@
+// Propagation sweep for `gpu.rs`. One invocation per cell drops the tiles that have
+// nothing to sit next to on some side, like `SuperState::allowed` does on the CPU.
+// Cells are narrowed in place: tiles only ever go away, so a neighbor read halfway
+// through a sweep is never smaller than it ends up, and a sweep that changes nothing
+// has read every cell as it is.
+
+struct Params {
+    width: u32,
+    height: u32,
+    // u32 words per cell and per rule
+    words: u32,
+    // invocations per row of workgroups, dispatches are 2D for grids over 4M cells
+    row: u32,
+}
+
+@group(0) @binding(0) var<uniform> params: Params;
+// tile bits of every cell, row-major
+@group(0) @binding(1) var<storage, read_write> cells: array<atomic<u32>>;
+// tiles allowed next to every tile, indexed by (tile * 4 + direction)
+@group(0) @binding(2) var<storage, read> rules: array<u32>;
+// words changed since the host last cleared it
+@group(0) @binding(3) var<storage, read_write> changed: atomic<u32>;
+
+// index of the neighbor in `direction` (in the order of grid::Direction), -1 past
+// the edge of the grid
+fn neighbor(x: u32, y: u32, direction: u32) -> i32 {
+    switch direction {
+        case 0u: {
+            if y == 0u {
+                return -1;
+            }
+            return i32((y - 1u) * params.width + x);
+        }
+        case 1u: {
+            if x + 1u >= params.width {
+                return -1;
+            }
+            return i32(y * params.width + x + 1u);
+        }
+        case 2u: {
+            if y + 1u >= params.height {
+                return -1;
+            }
+            return i32((y + 1u) * params.width + x);
+        }
+        default: {
+            if x == 0u {
+                return -1;
+            }
+            return i32(y * params.width + x - 1u);
+        }
+    }
+}
+
+fn is_empty(cell: u32) -> bool {
+    for (var word = 0u; word < params.words; word++) {
+        if atomicLoad(&cells[cell * params.words + word]) != 0u {
+            return false;
+        }
+    }
+
+    return true;
+}
+
+// whether `tile` allows any of the tiles left in `cell` on its side `direction`
+fn fits(tile: u32, direction: u32, cell: u32) -> bool {
+    let rule = (tile * 4u + direction) * params.words;
+
+    for (var word = 0u; word < params.words; word++) {
+        if (rules[rule + word] & atomicLoad(&cells[cell * params.words + word])) != 0u {
+            return true;
+        }
+    }
+
+    return false;
+}
+
+@compute @workgroup_size(64)
+fn sweep(@builtin(global_invocation_id) id: vec3<u32>) {
+    let cell = id.y * params.row + id.x;
+
+    if cell >= params.width * params.height {
+        return;
+    }
+
+    let x = cell % params.width;
+    let y = cell / params.width;
+    var neighbors: array<i32, 4>;
+
+    for (var direction = 0u; direction < 4u; direction++) {
+        let other = neighbor(x, y, direction);
+
+        // an empty neighbor constrains nothing, like on the CPU
+        if other >= 0 && is_empty(u32(other)) {
+            neighbors[direction] = -1;
+        } else {
+            neighbors[direction] = other;
+        }
+    }
+
+    for (var word = 0u; word < params.words; word++) {
+        let index = cell * params.words + word;
+        let tiles = atomicLoad(&cells[index]);
+        var keep = tiles;
+        var left = tiles;
+
+        while left != 0u {
+            let bit = firstTrailingBit(left);
+            let tile = word * 32u + bit;
+
+            left &= left - 1u;
+
+            for (var direction = 0u; direction < 4u; direction++) {
+                let other = neighbors[direction];
+
+                if other >= 0 && !fits(tile, direction, u32(other)) {
+                    keep &= ~(1u << bit);
+                    break;
+                }
+            }
+        }
+
+        if keep != tiles {
+            atomicAnd(&cells[index], keep);
+            atomicAdd(&changed, 1u);
+        }
+    }
+}
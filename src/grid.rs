@@ -1,7 +1,14 @@
 use core::str::FromStr;
+use std::fmt;
 use std::mem;
 use enum_map::{enum_map, Enum, EnumMap};
 
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+#[cfg(feature = "threaded")]
+use rayon::prelude::{IndexedParallelIterator, IntoParallelRefIterator, ParallelIterator};
+
 #[derive(Debug, PartialEq, Eq, Hash, Clone, Copy, Enum)]
 pub enum Direction {
     Up,
@@ -21,10 +28,154 @@ impl Direction {
     }
 }
 
-pub type Position = (usize, usize);
+/// The sides and the corners, for tilesets where diagonal neighbors matter as well
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy, Enum)]
+pub enum Direction8 {
+    Up,
+    UpRight,
+    Right,
+    DownRight,
+    Down,
+    DownLeft,
+    Left,
+    UpLeft,
+}
+
+impl Direction8 {
+    pub const DIAGONALS: [Direction8; 4] = [
+        Direction8::UpRight,
+        Direction8::DownRight,
+        Direction8::DownLeft,
+        Direction8::UpLeft,
+    ];
+
+    pub fn invert(&self) -> Self {
+        match self {
+            Direction8::Up => Direction8::Down,
+            Direction8::UpRight => Direction8::DownLeft,
+            Direction8::Right => Direction8::Left,
+            Direction8::DownRight => Direction8::UpLeft,
+            Direction8::Down => Direction8::Up,
+            Direction8::DownLeft => Direction8::UpRight,
+            Direction8::Left => Direction8::Right,
+            Direction8::UpLeft => Direction8::DownRight,
+        }
+    }
+
+    pub fn is_diagonal(&self) -> bool {
+        Self::DIAGONALS.contains(self)
+    }
+
+    /// Step along x and y, y grows downwards
+    pub fn offset(&self) -> (isize, isize) {
+        match self {
+            Direction8::Up => (0, -1),
+            Direction8::UpRight => (1, -1),
+            Direction8::Right => (1, 0),
+            Direction8::DownRight => (1, 1),
+            Direction8::Down => (0, 1),
+            Direction8::DownLeft => (-1, 1),
+            Direction8::Left => (-1, 0),
+            Direction8::UpLeft => (-1, -1),
+        }
+    }
+}
+
+impl From<Direction> for Direction8 {
+    fn from(direction: Direction) -> Self {
+        match direction {
+            Direction::Up => Direction8::Up,
+            Direction::Right => Direction8::Right,
+            Direction::Down => Direction8::Down,
+            Direction::Left => Direction8::Left,
+        }
+    }
+}
+
+/// Cell in a grid, ordered by x and then y
+#[derive(Debug, Default, PartialEq, Eq, Hash, PartialOrd, Ord, Clone, Copy)]
+pub struct Position {
+    pub x: usize,
+    pub y: usize,
+}
+
+impl Position {
+    pub fn new(x: usize, y: usize) -> Self {
+        Self { x, y }
+    }
+
+    /// Moved by `dx`, `dy`, None when that goes below zero
+    pub fn checked_offset(&self, dx: isize, dy: isize) -> Option<Self> {
+        Some(Self::new(self.x.checked_add_signed(dx)?, self.y.checked_add_signed(dy)?))
+    }
+
+    /// One step in `direction`, None when that goes below zero
+    pub fn step(&self, direction: Direction) -> Option<Self> {
+        self.step8(direction.into())
+    }
+
+    pub fn step8(&self, direction: Direction8) -> Option<Self> {
+        let (dx, dy) = direction.offset();
+
+        self.checked_offset(dx, dy)
+    }
+
+    /// Whether the position is in a grid of `width` by `height`
+    pub fn within(&self, width: usize, height: usize) -> bool {
+        self.x < width && self.y < height
+    }
+
+    /// Row-major index in a grid `width` wide
+    pub fn index(&self, width: usize) -> usize {
+        self.x + self.y * width
+    }
+
+    /// Steps between two cells when moving along the sides
+    pub fn manhattan(&self, other: &Self) -> usize {
+        self.x.abs_diff(other.x) + self.y.abs_diff(other.y)
+    }
+
+    /// Steps between two cells when moving along the corners as well
+    pub fn chebyshev(&self, other: &Self) -> usize {
+        self.x.abs_diff(other.x).max(self.y.abs_diff(other.y))
+    }
+}
+
+impl fmt::Display for Position {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}, {}", self.x, self.y)
+    }
+}
+
+impl FromStr for Position {
+    type Err = String;
+
+    /// Parses `x,y`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (raw_x, raw_y) = s.split_once(',').ok_or(format!("invalid position: {}", s))?;
+        let x = raw_x.trim().parse::<usize>().map_err(|_| format!("invalid x: {}", raw_x))?;
+        let y = raw_y.trim().parse::<usize>().map_err(|_| format!("invalid y: {}", raw_y))?;
+
+        Ok(Self::new(x, y))
+    }
+}
+
+impl From<(usize, usize)> for Position {
+    fn from((x, y): (usize, usize)) -> Self {
+        Self::new(x, y)
+    }
+}
+
+impl From<Position> for (usize, usize) {
+    fn from(position: Position) -> Self {
+        (position.x, position.y)
+    }
+}
 
 pub type Neighbors<T> = EnumMap<Direction, T>;
 
+pub type Neighbors8<T> = EnumMap<Direction8, T>;
+
 #[derive(Debug, Copy, Clone)]
 pub struct Size {
     pub width: usize,
@@ -63,6 +214,8 @@ impl Size {
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(try_from = "RawGrid<T>"))]
 pub struct Grid<T>
 where
     T: Clone,
@@ -72,6 +225,37 @@ where
     height: usize,
 }
 
+/// Deserialized grid before the size is checked against the data
+#[cfg(feature = "serde")]
+#[derive(Deserialize)]
+struct RawGrid<T> {
+    data: Vec<T>,
+    width: usize,
+    height: usize,
+}
+
+#[cfg(feature = "serde")]
+impl<T: Clone> TryFrom<RawGrid<T>> for Grid<T> {
+    type Error = String;
+
+    fn try_from(raw: RawGrid<T>) -> Result<Self, Self::Error> {
+        if raw.data.len() != raw.width * raw.height {
+            return Err(format!(
+                "{} cells don't make a {}x{} grid",
+                raw.data.len(),
+                raw.width,
+                raw.height
+            ));
+        }
+
+        Ok(Self {
+            data: raw.data,
+            width: raw.width,
+            height: raw.height,
+        })
+    }
+}
+
 pub struct GridIter<'a, T>
 where
     T: Clone,
@@ -110,7 +294,40 @@ where
         self.width * self.height
     }
 
-    pub fn iter(&self) -> GridIter<T> {
+    /// One line per row with a character per cell, for storing and diffing grids as
+    /// plain text
+    pub fn to_text<F: FnMut(&T) -> char>(&self, mut format: F) -> String {
+        self.rows()
+            .map(|row| row.iter().map(&mut format).collect::<String>())
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    /// Reads a grid written by `to_text`, `parse` returns None for characters it doesn't
+    /// know. Every line has to be as long as the first one.
+    pub fn from_text<F: FnMut(char) -> Option<T>>(text: &str, mut parse: F) -> Result<Self, String> {
+        let lines: Vec<&str> = text.lines().filter(|line| !line.is_empty()).collect();
+        let width = lines.first().map_or(0, |line| line.chars().count());
+        let mut data = Vec::with_capacity(width * lines.len());
+
+        for (y, line) in lines.iter().enumerate() {
+            if line.chars().count() != width {
+                return Err(format!("row {y} isn't {width} cells wide"));
+            }
+
+            for (x, c) in line.chars().enumerate() {
+                data.push(parse(c).ok_or(format!("unknown cell '{c}' at {x}, {y}"))?);
+            }
+        }
+
+        Ok(Self {
+            data,
+            width,
+            height: lines.len(),
+        })
+    }
+
+    pub fn iter(&self) -> GridIter<'_, T> {
         GridIter {
             grid: self,
             pos: 0,
@@ -119,6 +336,44 @@ where
         }
     }
 
+    /// Rows from top to bottom
+    pub fn rows(&self) -> impl Iterator<Item = &[T]> {
+        self.data.chunks(self.width.max(1))
+    }
+
+    pub fn rows_mut(&mut self) -> impl Iterator<Item = &mut [T]> {
+        self.data.chunks_mut(self.width.max(1))
+    }
+
+    /// Columns from left to right, each from top to bottom
+    pub fn cols(&self) -> impl Iterator<Item = impl Iterator<Item = &T>> {
+        (0..self.width).map(move |x| self.data[x..].iter().step_by(self.width))
+    }
+
+    /// Every `width` by `height` area that fits in the grid in row-major order, areas
+    /// overlap like `slice::windows`
+    pub fn windows(&self, width: usize, height: usize) -> impl Iterator<Item = GridView<'_, T>> {
+        let columns = (self.width + 1).saturating_sub(width.max(1));
+        let rows = (self.height + 1).saturating_sub(height.max(1));
+
+        (0..rows).flat_map(move |y| (0..columns).map(move |x| self.slice(x, y, width, height)))
+    }
+
+    /// Parallel `iter` on the current rayon pool, the order of the cells is kept when
+    /// collecting
+    #[cfg(feature = "threaded")]
+    pub fn par_iter(&self) -> impl IndexedParallelIterator<Item = (usize, usize, &T)>
+    where
+        T: Sync,
+    {
+        let width = self.width;
+
+        self.data
+            .par_iter()
+            .enumerate()
+            .map(move |(index, value)| (index % width, index / width, value))
+    }
+
     pub fn get(&self, x: usize, y: usize) -> Option<&T> {
         let index = x + (y * self.width);
 
@@ -177,42 +432,34 @@ where
         y: usize,
         direction: Direction,
     ) -> Option<Position> {
-        match direction {
-            Direction::Up => {
-                if y == 0 {
-                    None
-                } else {
-                    Some((x, y - 1))
-                }
-            }
-            Direction::Down => {
-                if y + 1 >= self.height {
-                    None
-                } else {
-                    Some((x, y + 1))
-                }
-            }
-            Direction::Left => {
-                if x == 0 {
-                    None
-                } else {
-                    Some((x - 1, y))
-                }
-            }
-            Direction::Right => {
-                if x + 1 >= self.width {
-                    None
-                } else {
-                    Some((x + 1, y))
-                }
-            }
+        Position::new(x, y)
+            .step(direction)
+            .filter(|position| position.within(self.width, self.height))
+    }
+
+    pub fn get_neighbor_position8(&self, x: usize, y: usize, direction: Direction8) -> Option<Position> {
+        Position::new(x, y)
+            .step8(direction)
+            .filter(|position| position.within(self.width, self.height))
+    }
+
+    pub fn get_neighbor_positions8(&self, x: usize, y: usize) -> Neighbors8<Option<Position>> {
+        enum_map! { direction => self.get_neighbor_position8(x, y, direction) }
+    }
+
+    /// Like `get_neighbors` with the corners included
+    pub fn get_neighbors8(&self, x: usize, y: usize) -> Neighbors8<Option<&T>> {
+        enum_map! {
+            direction => self
+                .get_neighbor_position8(x, y, direction)
+                .and_then(|position| self.get(position.x, position.y)),
         }
     }
 
     pub fn get_neighbor(&self, x: usize, y: usize, direction: Direction) -> Option<&T> {
-        let (lx, ly) = self.get_neighbor_position(x, y, direction)?;
+        let position = self.get_neighbor_position(x, y, direction)?;
 
-        self.get(lx, ly)
+        self.get(position.x, position.y)
     }
 
     pub fn width(&self) -> usize {
@@ -223,15 +470,88 @@ where
         self.height
     }
 
-    pub fn slice(&self, x: usize, y: usize, width: usize, height: usize) -> Grid<&T> {
+    /// Borrowed view of the area at `x`, `y`, clipped to the grid
+    pub fn slice(&self, x: usize, y: usize, width: usize, height: usize) -> GridView<'_, T> {
+        GridView {
+            grid: self,
+            x,
+            y,
+            width: width.min(self.width.saturating_sub(x)),
+            height: height.min(self.height.saturating_sub(y)),
+        }
+    }
+
+    /// Like `slice`, but the cells can be changed in place
+    pub fn slice_mut(&mut self, x: usize, y: usize, width: usize, height: usize) -> GridViewMut<'_, T> {
+        let start = x.min(self.width);
+        let end = x.saturating_add(width).min(self.width);
+        let rows = self
+            .data
+            .chunks_mut(self.width.max(1))
+            .skip(y)
+            .take(height)
+            .map(|row| &mut row[start..end])
+            .collect();
+
+        GridViewMut { rows, x, y }
+    }
+
+    /// Copy of the area at `x`, `y`, clipped to the grid
+    pub fn crop(&self, x: usize, y: usize, width: usize, height: usize) -> Self {
         Grid::new(
-            width.min(self.width() - x), 
-            height.min(self.height() - y), 
-            &mut |x, y| self.get(x, y).unwrap()
+            width.min(self.width.saturating_sub(x)),
+            height.min(self.height.saturating_sub(y)),
+            &mut |cx, cy| self.get(x + cx, y + cy).unwrap().clone(),
         )
     }
 
-    pub fn chunked(&self, chunk_width: usize, chunk_height: usize) -> Vec<Grid<&T>> {
+    /// Copies `other` over this grid with its top left at `x`, `y`, whatever falls
+    /// outside is left out
+    pub fn paste(&mut self, other: &Grid<T>, x: usize, y: usize) {
+        for (ox, oy, value) in other {
+            if x + ox < self.width && y + oy < self.height {
+                self.set(x + ox, y + oy, value.clone()).unwrap();
+            }
+        }
+    }
+
+    /// Turned a quarter clockwise
+    pub fn rotate90(&self) -> Self {
+        Grid::new(self.height, self.width, &mut |x, y| {
+            self.get(y, self.height - 1 - x).unwrap().clone()
+        })
+    }
+
+    pub fn rotate180(&self) -> Self {
+        Grid::new(self.width, self.height, &mut |x, y| {
+            self.get(self.width - 1 - x, self.height - 1 - y).unwrap().clone()
+        })
+    }
+
+    /// Turned a quarter counterclockwise
+    pub fn rotate270(&self) -> Self {
+        Grid::new(self.height, self.width, &mut |x, y| {
+            self.get(self.width - 1 - y, x).unwrap().clone()
+        })
+    }
+
+    /// Mirrored left to right
+    pub fn flip_horizontal(&self) -> Self {
+        Grid::new(self.width, self.height, &mut |x, y| {
+            self.get(self.width - 1 - x, y).unwrap().clone()
+        })
+    }
+
+    /// Mirrored top to bottom
+    pub fn flip_vertical(&self) -> Self {
+        Grid::new(self.width, self.height, &mut |x, y| {
+            self.get(x, self.height - 1 - y).unwrap().clone()
+        })
+    }
+
+    /// Views of `chunk_width` by `chunk_height` covering the grid, column by column.
+    /// Chunks on the right and bottom edge are smaller when the grid doesn't divide evenly.
+    pub fn chunked(&self, chunk_width: usize, chunk_height: usize) -> Vec<GridView<'_, T>> {
         let mut output = vec![];
 
         for x in (0..self.width()).step_by(chunk_width) {
@@ -240,7 +560,141 @@ where
             }
         }
 
-        return output;
+        output
+    }
+
+    /// Like `chunked`, but the chunks can be changed in place, each by its own thread
+    /// if need be
+    pub fn chunked_mut(&mut self, chunk_width: usize, chunk_height: usize) -> Vec<GridViewMut<'_, T>> {
+        let columns = self.width.div_ceil(chunk_width);
+        let rows = self.height.div_ceil(chunk_height);
+        let mut output: Vec<GridViewMut<T>> = (0..columns)
+            .flat_map(|cx| {
+                (0..rows).map(move |cy| GridViewMut {
+                    rows: Vec::new(),
+                    x: cx * chunk_width,
+                    y: cy * chunk_height,
+                })
+            })
+            .collect();
+
+        for (y, row) in self.data.chunks_mut(self.width.max(1)).enumerate() {
+            for (cx, part) in row.chunks_mut(chunk_width).enumerate() {
+                output[cx * rows + y / chunk_height].rows.push(part);
+            }
+        }
+
+        output
+    }
+}
+
+/// Borrowed area of a grid, positions are relative to the top left of the area
+pub struct GridView<'a, T>
+where
+    T: Clone,
+{
+    grid: &'a Grid<T>,
+    x: usize,
+    y: usize,
+    width: usize,
+    height: usize,
+}
+
+impl<T> Clone for GridView<'_, T>
+where
+    T: Clone,
+{
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for GridView<'_, T> where T: Clone {}
+
+impl<'a, T> GridView<'a, T>
+where
+    T: Clone,
+{
+    /// Top left of the view in the grid it was taken from
+    pub fn offset(&self) -> Position {
+        Position::new(self.x, self.y)
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn get(&self, x: usize, y: usize) -> Option<&'a T> {
+        if x >= self.width || y >= self.height {
+            return None;
+        }
+
+        self.grid.get(self.x + x, self.y + y)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (usize, usize, &'a T)> + 'a {
+        let view = *self;
+
+        (0..view.height).flat_map(move |y| (0..view.width).map(move |x| (x, y, view.get(x, y).unwrap())))
+    }
+
+    pub fn to_grid(&self) -> Grid<T> {
+        Grid::new(self.width, self.height, &mut |x, y| self.get(x, y).unwrap().clone())
+    }
+}
+
+/// Mutably borrowed area of a grid, positions are relative to the top left of the area.
+/// Views from `chunked_mut` don't overlap, so they can be used side by side.
+pub struct GridViewMut<'a, T> {
+    rows: Vec<&'a mut [T]>,
+    x: usize,
+    y: usize,
+}
+
+impl<'a, T> GridViewMut<'a, T> {
+    /// Top left of the view in the grid it was taken from
+    pub fn offset(&self) -> Position {
+        Position::new(self.x, self.y)
+    }
+
+    pub fn width(&self) -> usize {
+        self.rows.first().map_or(0, |row| row.len())
+    }
+
+    pub fn height(&self) -> usize {
+        self.rows.len()
+    }
+
+    pub fn get(&self, x: usize, y: usize) -> Option<&T> {
+        self.rows.get(y)?.get(x)
+    }
+
+    pub fn get_mut(&mut self, x: usize, y: usize) -> Option<&mut T> {
+        self.rows.get_mut(y)?.get_mut(x)
+    }
+
+    pub fn set(&mut self, x: usize, y: usize, value: T) -> Result<(), &'static str> {
+        *self.get_mut(x, y).ok_or("Cell out of range")? = value;
+
+        Ok(())
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (usize, usize, &T)> {
+        self.rows
+            .iter()
+            .enumerate()
+            .flat_map(|(y, row)| row.iter().enumerate().map(move |(x, value)| (x, y, value)))
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (usize, usize, &mut T)> + use<'_, 'a, T> {
+        self.rows
+            .iter_mut()
+            .enumerate()
+            .flat_map(|(y, row)| row.iter_mut().enumerate().map(move |(x, value)| (x, y, value)))
     }
 }
 
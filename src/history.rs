@@ -0,0 +1,117 @@
+use crate::grid::Position;
+use crate::superstate::Collapsable;
+use crate::session::Session;
+use crate::wave::{Snapshot, Wave};
+
+/// Something done to a wave that can be undone
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Edit<I> {
+    Pin(Position, I),
+    Unpin(Position),
+    /// A saved session replaced the whole wave
+    Load,
+    /// Everything the solver collapsed on its own between two other edits
+    Solve,
+}
+
+/// Undo and redo for edits to a wave. Every edit is a transaction with a snapshot of
+/// the wave from before it, undoing an edit restores that snapshot. The history is kept
+/// next to the wave rather than owning it, so the wave can still be drawn and ticked as
+/// usual.
+pub struct EditHistory<T>
+where
+    T: Collapsable,
+{
+    undo: Vec<(Edit<T::Identifier>, Snapshot<T>)>,
+    redo: Vec<(Edit<T::Identifier>, Snapshot<T>)>,
+    /// Edits kept for undoing, every one holds a copy of the grid
+    limit: usize,
+}
+
+impl<T> EditHistory<T>
+where
+    T: Collapsable,
+{
+    pub fn new(limit: usize) -> Self {
+        Self {
+            undo: Vec::new(),
+            redo: Vec::new(),
+            limit: limit.max(1),
+        }
+    }
+
+    pub fn pin(&mut self, wave: &mut Wave<T>, x: usize, y: usize, id: T::Identifier) -> Result<(), &'static str> {
+        let before = wave.snapshot();
+
+        wave.pin(x, y, id.clone())?;
+        self.push(Edit::Pin(Position::new(x, y), id), before);
+
+        Ok(())
+    }
+
+    pub fn unpin(&mut self, wave: &mut Wave<T>, x: usize, y: usize) -> Result<(), &'static str> {
+        let before = wave.snapshot();
+
+        wave.unpin(x, y)?;
+        self.push(Edit::Unpin(Position::new(x, y)), before);
+
+        Ok(())
+    }
+
+    pub fn load(&mut self, wave: &mut Wave<T>, session: Session) -> Result<(), &'static str> {
+        let before = wave.snapshot();
+
+        wave.load_session(session)?;
+        self.push(Edit::Load, before);
+
+        Ok(())
+    }
+
+    /// Call before the solver ticks, the ticks up to the next edit are undone as one.
+    /// Doesn't drop the redo history, redoing jumps back over what the solver did.
+    pub fn solving(&mut self, wave: &Wave<T>) {
+        if !matches!(self.undo.last(), Some((Edit::Solve, _))) {
+            let redo = std::mem::take(&mut self.redo);
+
+            self.push(Edit::Solve, wave.snapshot());
+            self.redo = redo;
+        }
+    }
+
+    /// Reverts the last edit along with what the solver did after it, None when there's
+    /// nothing left to undo. A solver that keeps running would otherwise only ever undo
+    /// its own last few ticks.
+    pub fn undo(&mut self, wave: &mut Wave<T>) -> Option<Edit<T::Identifier>> {
+        let (mut edit, mut before) = self.undo.pop()?;
+
+        if edit == Edit::Solve {
+            if let Some(previous) = self.undo.pop() {
+                (edit, before) = previous;
+            }
+        }
+
+        self.redo.push((edit.clone(), wave.snapshot()));
+        wave.restore(before);
+
+        Some(edit)
+    }
+
+    /// Applies the last undone edit again, None when there's nothing to redo
+    pub fn redo(&mut self, wave: &mut Wave<T>) -> Option<Edit<T::Identifier>> {
+        let (edit, after) = self.redo.pop()?;
+
+        self.undo.push((edit.clone(), wave.snapshot()));
+        wave.restore(after);
+
+        Some(edit)
+    }
+
+    fn push(&mut self, edit: Edit<T::Identifier>, before: Snapshot<T>) {
+        if self.undo.len() >= self.limit {
+            self.undo.remove(0);
+        }
+
+        self.undo.push((edit, before));
+        self.redo.clear();
+    }
+}
@@ -0,0 +1,47 @@
+pub mod analysis;
+pub mod audit;
+pub mod biome;
+pub mod bitset;
+pub mod carve;
+pub mod compact;
+pub mod constraint;
+pub mod decoration;
+#[cfg(feature = "noise")]
+pub mod field;
+#[cfg(feature = "exact")]
+pub mod exact;
+#[cfg(feature = "gpu")]
+pub mod gpu;
+pub mod grid;
+pub mod history;
+#[cfg(feature = "image")]
+pub mod overlay;
+#[cfg(feature = "threaded")]
+pub mod portfolio;
+#[cfg(feature = "image")]
+pub mod postprocess;
+#[cfg(feature = "image")]
+pub mod preprocess;
+pub mod profiling;
+pub mod recorder;
+#[cfg(feature = "samples")]
+pub mod samples;
+pub mod seam;
+pub mod session;
+pub mod sortedset;
+#[cfg(feature = "image")]
+pub mod sprite;
+#[cfg(feature = "image")]
+pub mod style;
+pub mod superstate;
+#[cfg(feature = "async")]
+pub mod task;
+pub mod tile;
+pub mod topology;
+pub mod usage;
+pub mod wave;
+
+#[cfg(feature = "python")]
+mod python;
+#[cfg(feature = "wasm")]
+mod wasm;
@@ -0,0 +1,240 @@
+use std::io::Cursor;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use image::{GenericImageView, ImageOutputFormat, Rgb, RgbaImage};
+use serde_json::json;
+use tiny_http::{Header, Request, Response, Server, StatusCode};
+use tracing::{info, warn};
+use tungstenite::handshake::derive_accept_key;
+use tungstenite::protocol::Role;
+use tungstenite::{Message, WebSocket};
+
+use wave_function_collapse::grid::Grid;
+use wave_function_collapse::sprite::Sprite;
+use wave_function_collapse::style::CellStyle;
+use wave_function_collapse::superstate::{Collapsable, SuperState};
+use wave_function_collapse::tile::Tile;
+use wave_function_collapse::wave::Rollback;
+
+const VIEWER: &str = include_str!("live.html");
+
+/// Cell values above this are uncollapsed, the low byte holds how far along they are
+const UNCOLLAPSED: u16 = 0xFE00;
+const CONTRADICTION: u16 = 0xFFFF;
+/// Minimum time between two updates, diffing the grid is linear in its size
+const UPDATE_INTERVAL: Duration = Duration::from_millis(33);
+
+type Socket = WebSocket<Box<dyn tiny_http::ReadWrite + Send>>;
+
+struct Shared {
+    header: String,
+    atlas: Vec<u8>,
+    cells: Vec<u16>,
+    clients: Vec<Socket>,
+}
+
+/// Streams cell updates to browsers over a WebSocket. `GET /` serves a small viewer,
+/// `/atlas.png` the tiles in dense index order and `/ws` the updates: a JSON header
+/// followed by binary messages of (u32 cell index, u16 value) pairs, little endian.
+/// Values below `UNCOLLAPSED` are tile indexes. Rollbacks are sent as JSON with the
+/// index of the contradicting cell and the indexes of the cells it touched.
+pub struct LiveView {
+    shared: Arc<Mutex<Shared>>,
+    last_update: Instant,
+}
+
+impl LiveView {
+    pub fn start(
+        bind: &str,
+        tiles: &[Tile<Sprite>],
+        style: &CellStyle,
+        width: usize,
+        height: usize,
+    ) -> Result<Self, String> {
+        let server = Server::http(bind).map_err(|e| e.to_string())?;
+
+        // dense indexes follow tile id order
+        let mut tiles: Vec<&Tile<Sprite>> = tiles.iter().collect();
+        tiles.sort_by_key(|tile| tile.get_id());
+
+        let (tile_width, tile_height) = tiles[0].value.image().dimensions();
+        let mut atlas = RgbaImage::new(tile_width * tiles.len() as u32, tile_height);
+
+        for (index, tile) in tiles.iter().enumerate() {
+            image::imageops::overlay(&mut atlas, tile.value.image(), index as i64 * tile_width as i64, 0);
+        }
+
+        let mut png = Vec::new();
+
+        atlas
+            .write_to(&mut Cursor::new(&mut png), ImageOutputFormat::Png)
+            .map_err(|e| e.to_string())?;
+
+        // the viewer only knows how far along a cell is, so it gets the gradient
+        let gradient: Vec<String> = (0..=255)
+            .map(|progress| hex(style.gradient(progress as f32 / 255.0)))
+            .collect();
+        let header = json!({
+            "width": width,
+            "height": height,
+            "tile_width": tile_width,
+            "tile_height": tile_height,
+            "tiles": tiles.len(),
+            "gradient": gradient,
+            "contradiction": hex(style.contradiction()),
+        });
+
+        let shared = Arc::new(Mutex::new(Shared {
+            header: header.to_string(),
+            atlas: png,
+            cells: vec![UNCOLLAPSED; width * height],
+            clients: Vec::new(),
+        }));
+
+        info!("Live view on http://{bind}");
+
+        let handle = shared.clone();
+
+        thread::spawn(move || {
+            for request in server.incoming_requests() {
+                handle_request(request, &handle);
+            }
+        });
+
+        Ok(Self {
+            shared,
+            last_update: Instant::now(),
+        })
+    }
+
+    /// Sends the cells that changed, at most once every `UPDATE_INTERVAL`
+    pub fn update<T: Collapsable>(&mut self, grid: &Grid<SuperState<T>>) {
+        if self.last_update.elapsed() >= UPDATE_INTERVAL {
+            self.flush(grid);
+        }
+    }
+
+    /// Tells the viewers to highlight the cells of `rollbacks`
+    pub fn flash(&mut self, rollbacks: &[Rollback], width: usize) {
+        if rollbacks.is_empty() {
+            return;
+        }
+
+        let mut shared = self.shared.lock().unwrap();
+
+        for rollback in rollbacks {
+            let message = json!({
+                "rollback": {
+                    "contradiction": rollback.contradiction.index(width),
+                    "cells": rollback.cells.iter().map(|cell| cell.index(width)).collect::<Vec<_>>(),
+                }
+            });
+
+            shared
+                .clients
+                .retain_mut(|client| client.send(Message::text(message.to_string())).is_ok());
+        }
+    }
+
+    /// Sends every cell that changed since the last update
+    pub fn flush<T: Collapsable>(&mut self, grid: &Grid<SuperState<T>>) {
+        self.last_update = Instant::now();
+
+        let mut shared = self.shared.lock().unwrap();
+        let mut message = Vec::new();
+
+        for (index, (_, _, cell)) in grid.iter().enumerate() {
+            let value = cell_value(cell);
+
+            if shared.cells[index] != value {
+                shared.cells[index] = value;
+                message.extend_from_slice(&(index as u32).to_le_bytes());
+                message.extend_from_slice(&value.to_le_bytes());
+            }
+        }
+
+        if message.is_empty() {
+            return;
+        }
+
+        shared.clients.retain_mut(|client| {
+            client.send(Message::binary(message.clone())).is_ok()
+        });
+    }
+}
+
+fn cell_value<T: Collapsable>(cell: &SuperState<T>) -> u16 {
+    match cell.entropy() {
+        0 => CONTRADICTION,
+        1 => cell.indexes()[0] as u16,
+        entropy => {
+            let progress = 255 - entropy * 255 / cell.base_entropy().max(1);
+
+            UNCOLLAPSED | progress as u16
+        }
+    }
+}
+
+fn handle_request(request: Request, shared: &Mutex<Shared>) {
+    let response = match request.url() {
+        "/" => Response::from_string(VIEWER).with_header(header("Content-Type", "text/html")),
+        "/atlas.png" => Response::from_data(shared.lock().unwrap().atlas.clone())
+            .with_header(header("Content-Type", "image/png")),
+        "/ws" => return upgrade(request, shared),
+        _ => Response::from_string("Not found").with_status_code(404),
+    };
+
+    if let Err(e) = request.respond(response) {
+        warn!("Failed to send response: {e}");
+    }
+}
+
+fn upgrade(request: Request, shared: &Mutex<Shared>) {
+    let key = request
+        .headers()
+        .iter()
+        .find(|h| h.field.equiv("Sec-WebSocket-Key"))
+        .map(|h| derive_accept_key(h.value.as_bytes()));
+
+    let key = match key {
+        Some(key) => key,
+        None => {
+            let _ = request.respond(Response::from_string("Expected a WebSocket").with_status_code(400));
+            return;
+        }
+    };
+
+    let response = Response::empty(StatusCode(101))
+        .with_header(header("Upgrade", "websocket"))
+        .with_header(header("Connection", "Upgrade"))
+        .with_header(header("Sec-WebSocket-Accept", &key));
+
+    let stream = request.upgrade("websocket", response);
+    let mut socket = WebSocket::from_raw_socket(stream, Role::Server, None);
+    let mut shared = shared.lock().unwrap();
+
+    let mut state = Vec::with_capacity(shared.cells.len() * 6);
+
+    for (index, value) in shared.cells.iter().enumerate() {
+        state.extend_from_slice(&(index as u32).to_le_bytes());
+        state.extend_from_slice(&value.to_le_bytes());
+    }
+
+    let sent = socket.send(Message::text(shared.header.clone()))
+        .and_then(|_| socket.send(Message::binary(state)));
+
+    match sent {
+        Ok(_) => shared.clients.push(socket),
+        Err(e) => warn!("Failed to send initial state: {e}"),
+    }
+}
+
+fn hex(Rgb([r, g, b]): Rgb<u8>) -> String {
+    format!("#{r:02x}{g:02x}{b:02x}")
+}
+
+fn header(name: &str, value: &str) -> Header {
+    Header::from_bytes(name.as_bytes(), value.as_bytes()).unwrap()
+}
@@ -1,52 +1,55 @@
-mod grid;
-mod sprite;
-mod superstate;
-mod tile;
-mod wave;
-
-use image::{io::Reader as ImageReader, DynamicImage, GenericImageView};
-use image::{ImageError, RgbaImage};
-
-use indicatif::ProgressBar;
-use indicatif::ProgressStyle;
-use log::warn;
-use log::{info, trace};
-use rand::rngs::OsRng;
-use rand::Rng;
-
-use simplelog::{ColorChoice, Config, TermLogger, TerminalMode};
+#[cfg(feature = "image")]
+mod analyze;
+#[cfg(feature = "image")]
+mod batch;
+#[cfg(feature = "image")]
+mod bench;
+#[cfg(feature = "net")]
+mod fetch;
+mod generate;
+#[cfg(feature = "live")]
+mod live;
+mod output;
+mod project;
+#[cfg(feature = "serve")]
+mod serve;
+#[cfg(feature = "tui")]
+mod tui;
+#[cfg(feature = "sdl2")]
+mod visual;
+mod watch;
+
+use image::{io::Reader as ImageReader, DynamicImage, Rgba};
+use image::codecs::{gif::GifDecoder, png::PngDecoder};
+use image::{AnimationDecoder, ImageError, ImageFormat};
+
+use tracing::{error, info};
+
+use tracing_subscriber::filter::{EnvFilter, LevelFilter};
 use std::fmt::Debug;
+use std::str::FromStr;
 use std::fs::File;
 use std::io::BufReader;
 use std::path::PathBuf;
-use std::time::Duration;
-use std::{io, usize};
-use structopt::clap::Shell;
-use structopt::StructOpt;
-use structopt_flags::{LogLevel, QuietVerbose};
-use tile::TileConfig;
-
-use grid::Size;
-use superstate::SuperState;
-use tile::Tile;
-
-use crate::grid::Grid;
-use wave::Wave;
-
+use std::process::ExitCode;
+use std::io;
+use clap::builder::{BoolishValueParser, PossibleValuesParser, RangedU64ValueParser, TypedValueParser};
+use clap::{value_parser, ArgAction, ArgMatches, Args, CommandFactory, FromArgMatches, Parser, Subcommand};
+use clap_complete::Shell;
+
+use wave_function_collapse::carve::Route;
+use wave_function_collapse::grid::Size;
+use generate::{Generator, Outcome, Settings, Views};
+use output::{OutputFormat, UsageFormat};
+use wave_function_collapse::postprocess::Filter;
+#[cfg(feature = "profiling")]
+use wave_function_collapse::profiling;
+use wave_function_collapse::style::parse_color;
+#[cfg(any(feature = "sdl2", feature = "tui", feature = "live"))]
+use wave_function_collapse::style::Palette;
 #[cfg(feature = "sdl2")]
-use {
-    sdl2::video::FullscreenType,
-    sdl2::event::Event,
-    sdl2::keyboard::Keycode,
-    sdl2::pixels::{Color, PixelFormatEnum},
-    sdl2::rect::Rect,
-    sdl2::render::{Canvas, Texture},
-    sdl2::video::Window,
-    sdl2::EventPump,
-    sprite::Sprite,
-    std::collections::HashMap,
-    superstate::Collapsable,
-};
+use wave_function_collapse::grid::Position;
+use wave_function_collapse::tile::{Symmetry, TileConfig};
 
 fn load_image(s: &str) -> Result<DynamicImage, ImageError> {
     let path = PathBuf::from(s);
@@ -55,6 +58,37 @@ fn load_image(s: &str) -> Result<DynamicImage, ImageError> {
     Ok(image)
 }
 
+/// Frames of an animated GIF or PNG, `None` for anything else including animations of
+/// a single frame
+fn load_animation(s: &str) -> Option<Animation> {
+    let reader = BufReader::new(File::open(s).ok()?);
+    let frames = match ImageFormat::from_path(s).ok()? {
+        ImageFormat::Gif => GifDecoder::new(reader).ok()?.into_frames(),
+        ImageFormat::Png => PngDecoder::new(reader).ok().filter(|decoder| decoder.is_apng())?.apng().into_frames(),
+        _ => return None,
+    }
+    .collect_frames()
+    .ok()?;
+
+    if frames.len() < 2 {
+        return None;
+    }
+
+    let delays = frames
+        .iter()
+        .map(|frame| {
+            let (numerator, denominator) = frame.delay().numer_denom_ms();
+
+            (numerator / denominator.max(1)).min(u16::MAX as u32) as u16
+        })
+        .collect();
+
+    Some(Animation {
+        frames: frames.into_iter().map(|frame| DynamicImage::ImageRgba8(frame.into_buffer())).collect(),
+        delays,
+    })
+}
+
 fn load_config(s: &str) -> Result<Vec<TileConfig>, String> {
     let path = PathBuf::from(s);
     let file = File::open(path).map_err(|e| format!("Failed to open config file: {}", e))?;
@@ -65,125 +99,372 @@ fn load_config(s: &str) -> Result<Vec<TileConfig>, String> {
     Ok(configs)
 }
 
-fn load_input(s: &str) -> Result<Input, &'static str> {
-    if let Ok(image) = load_image(s) {
+/// Lets every option be set from a `{prefix}_{OPTION}` environment variable as well,
+/// e.g. `WFC_OUTPUT_SIZE=40x40` or `WFC_BATCH_COUNT=5` for the batch subcommand. The
+/// command line wins over the environment, the environment over the defaults.
+fn with_env(command: clap::Command, prefix: &str) -> clap::Command {
+    command
+        .mut_args(|arg| {
+            let name = format!("{prefix}_{}", arg.get_id().as_str().to_uppercase());
+
+            match arg.get_action() {
+                // -vv and -q have no sensible single value
+                ArgAction::Count => arg,
+                // flags also take 1/0, yes/no and on/off from the environment
+                ArgAction::SetTrue => arg.env(name).value_parser(BoolishValueParser::new()),
+                _ => arg.env(name),
+            }
+        })
+        .mut_subcommands(|command| {
+            let prefix = format!("{prefix}_{}", command.get_name().to_uppercase());
+
+            with_env(command, &prefix)
+        })
+}
+
+/// Parser for options that take one of `names`, read with the type's `FromStr`
+fn one_of<T>(names: &'static [&'static str]) -> impl TypedValueParser<Value = T>
+where
+    T: FromStr + Clone + Send + Sync + 'static,
+    T::Err: Debug,
+{
+    PossibleValuesParser::new(names).map(|name| name.parse().unwrap())
+}
+
+#[cfg(feature = "sdl2")]
+fn parse_rate(s: &str) -> Result<f64, String> {
+    match s.parse::<f64>() {
+        Ok(rate) if rate > 0.0 && rate.is_finite() => Ok(rate),
+        _ => Err(format!("{s} isn't a number above zero")),
+    }
+}
+
+fn parse_temperature(s: &str) -> Result<f64, String> {
+    match s.parse::<f64>() {
+        Ok(temperature) if temperature > 0.0 && temperature.is_finite() => Ok(temperature),
+        _ => Err(format!("{s} isn't a number above zero")),
+    }
+}
+
+fn load_input(s: &str) -> Result<Input, String> {
+    #[cfg(feature = "net")]
+    if fetch::is_url(s) {
+        let path = fetch::fetch(s)?;
+
+        return load_input(&path.to_string_lossy());
+    }
+
+    if let Some(animation) = load_animation(s) {
+        Ok(Input::Animation(animation))
+    } else if let Ok(image) = load_image(s) {
         Ok(Input::Image(image))
     } else if let Ok(configs) = load_config(s) {
         Ok(Input::Config(configs))
     } else {
-        Err("Failed to load input")
+        Err("Failed to load input".into())
     }
 }
 
-#[cfg(feature = "sdl2")]
-struct SdlDraw {
-    canvas: Canvas<Window>,
-    events: EventPump,
-    pub textures: HashMap<u64, Texture>,
+/// Logs to stderr at the level set by -v/-q, `RUST_LOG` style directives in the
+/// environment take precedence (e.g. `RUST_LOG=wave_function_collapse::wave=trace`)
+fn init_logging(level: LevelFilter) {
+    let filter = EnvFilter::builder()
+        .with_default_directive(level.into())
+        .from_env_lossy();
+
+    tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .with_writer(io::stderr)
+        .init();
 }
 
-#[cfg(feature = "sdl2")]
-impl SdlDraw {
-    pub fn new(size: Size, tiles: &[Tile<Sprite>], vsync: bool, fullscreen: bool) -> Self {
-        let context = sdl2::init().unwrap();
-        let video = context.video().unwrap();
-
-        let mut window = video
-            .window(
-                "Wave Function Collapse",
-                size.width as u32,
-                size.height as u32,
-            )
-            .position_centered()
-            .build()
-            .map_err(|e| e.to_string())
-            .unwrap();
-
-        if fullscreen {
-            window.set_fullscreen(FullscreenType::True).unwrap();
-        }
-
-        if window.fullscreen_state() != FullscreenType::Off {
-            context.mouse().show_cursor(false);
-        }
-
-        let mut builder = window.into_canvas().target_texture();
+#[derive(Debug, Clone)]
+enum Input {
+    Image(DynamicImage),
+    Animation(Animation),
+    Config(Vec<TileConfig>),
+}
 
-        if vsync {
-            builder = builder.present_vsync();
-        }
+/// Frames of an animated input, every frame is the size of the whole image
+#[derive(Debug, Clone)]
+struct Animation {
+    frames: Vec<DynamicImage>,
+    /// Milliseconds every frame stays up
+    delays: Vec<u16>,
+}
 
-        let canvas = builder.build().map_err(|e| e.to_string()).unwrap();
+/// Noise field asked for on the command line, see `NoiseField`
+#[cfg(feature = "noise")]
+#[derive(Debug, Clone)]
+struct NoiseWeight {
+    /// Tile indexes or config tags
+    tiles: Vec<String>,
+    scale: f64,
+    strength: f64,
+}
 
-        let events = context.event_pump().unwrap();
-        let texture_creator = canvas.texture_creator();
-        let mut textures = HashMap::new();
+#[derive(Debug, Subcommand)]
+enum Command {
+    #[command(about = "Run a fixed matrix of grid sizes and tilesets and report throughput")]
+    Bench(bench::BenchOpt),
 
-        for tile in tiles {
-            if textures.contains_key(&tile.get_id()) {
-                continue;
-            }
+    #[command(about = "Generate outputs for a range of seeds, flagging or skipping duplicates")]
+    Batch(batch::BatchOpt),
 
-            let rgba = tile.value.image.to_rgba8();
-            let (width, height) = tile.value.image.dimensions();
+    #[command(about = "Report which neighbor rules of a tileset an output image used and which it never did")]
+    Analyze(analyze::AnalyzeOpt),
 
-            let mut texture = texture_creator
-                .create_texture_streaming(PixelFormatEnum::RGBA32, width, height)
-                .map_err(|e| e.to_string())
-                .unwrap();
+    #[cfg(feature = "serve")]
+    #[command(about = "Serve generations over HTTP")]
+    Serve(serve::ServeOpt),
+}
 
-            texture
-                .with_lock(None, |buffer: &mut [u8], _: usize| {
-                    buffer.copy_from_slice(&rgba);
-                })
-                .unwrap();
+/// -v and -q, counted
+#[derive(Debug, Args)]
+struct Verbosity {
+    #[arg(
+        short,
+        long,
+        action = ArgAction::Count,
+        global = true,
+        conflicts_with = "quiet",
+        help = "Log more, -v for info, -vv for debug and -vvv for everything"
+    )]
+    verbose: u8,
 
-            textures.insert(tile.get_id(), texture);
-        }
+    #[arg(
+        short,
+        long,
+        action = ArgAction::Count,
+        global = true,
+        help = "Log less, -q for errors only and -qq for nothing"
+    )]
+    quiet: u8,
+}
 
-        Self {
-            canvas,
-            events,
-            textures,
+impl Verbosity {
+    /// Warnings and up by default
+    fn level(&self) -> LevelFilter {
+        match self.verbose.min(3) as i8 - self.quiet.min(2) as i8 {
+            -2 => LevelFilter::OFF,
+            -1 => LevelFilter::ERROR,
+            0 => LevelFilter::WARN,
+            1 => LevelFilter::INFO,
+            2 => LevelFilter::DEBUG,
+            _ => LevelFilter::TRACE,
         }
     }
 }
 
-#[derive(Debug)]
-enum Input {
-    Image(DynamicImage),
-    Config(Vec<TileConfig>),
-}
-
-#[derive(Debug, StructOpt)]
-#[structopt(
+#[derive(Debug, Parser)]
+#[command(
     name = "Wave Function Collapse",
-    about = "Generate images using wfc from input images"
+    about = "Generate images using wfc from input images",
+    subcommand_negates_reqs = true
 )]
 struct Opt {
-    #[structopt(flatten)]
-    verbose: QuietVerbose,
+    #[command(subcommand)]
+    command: Option<Command>,
 
-    #[structopt(parse(try_from_str=load_input), help = "Input", required_unless="completions")]
+    #[command(flatten)]
+    verbose: Verbosity,
+
+    #[arg(value_parser = load_input, help = "Input", required_unless_present_any = ["completions", "print_config", "project"])]
     input: Option<Input>,
 
-    #[structopt(
-        parse(try_from_str),
+    #[arg(
         short,
         long,
-        required_if("input", "config"),
+        required_if_eq("input", "config"),
         help = "Input image grid size"
     )]
     input_size: Option<usize>,
 
-    #[structopt(
-        parse(from_os_str),
+    #[arg(
+        long,
+        default_value = "1",
+        value_parser = value_parser!(u32).range(1..),
+        help = "Average every square of this many input pixels into one before extracting tiles, the input size counts the smaller pixels"
+    )]
+    downscale: u32,
+
+    #[arg(
+        long,
+        value_parser = value_parser!(u16).range(2..=256),
+        help = "Reduce the input to at most this many colors before extracting tiles"
+    )]
+    quantize: Option<u16>,
+
+    #[arg(
+        long,
+        value_parser = parse_color,
+        help = "Draw the input over this color before extracting tiles so nothing is partly transparent, as rrggbb in hex"
+    )]
+    flatten: Option<Rgba<u8>>,
+
+    #[arg(
+        long,
+        help = "Reflect the input at its borders when learning neighbors, so tiles that only show up on an edge still get neighbors past it"
+    )]
+    mirror_edges: bool,
+
+    #[arg(
+        long,
+        default_value = "none",
+        value_parser = one_of::<Symmetry>(&Symmetry::NAMES),
+        ignore_case = true,
+        help = "Also learn tiles from the mirror image (mirror), the rotations (rotate) or both (all) of the input"
+    )]
+    symmetry: Symmetry,
+
+    #[arg(
+        long,
+        help = "Give every rotated or mirrored variant of a tile the weight of the tile in the input, so tiles that look the same turned around aren't picked more often"
+    )]
+    shared_weights: bool,
+
+    #[arg(
         help = "Output image",
     )]
     output: Option<PathBuf>,
 
-    #[structopt(
-        parse(try_from_str),
+    #[arg(
+        long,
+        value_parser = one_of::<OutputFormat>(&OutputFormat::NAMES),
+        ignore_case = true,
+        help = "Output image format, taken from the output extension by default"
+    )]
+    format: Option<OutputFormat>,
+
+    #[arg(
+        long,
+        default_value = "90",
+        value_parser = value_parser!(u8).range(1..=100),
+        help = "JPEG quality, 1 to 100"
+    )]
+    quality: u8,
+
+    #[arg(
+        long,
+        value_parser = parse_color,
+        help = "Draw the output over this color instead of leaving empty and see-through tiles transparent, as rrggbb in hex. JPEG has no transparency and uses black without it"
+    )]
+    matte: Option<Rgba<u8>>,
+
+    #[arg(
+        long,
+        help = "Write the PNG output a row of cells at a time instead of drawing it whole in memory, for huge outputs"
+    )]
+    stream_output: bool,
+
+    #[arg(
+        long,
+        conflicts_with = "stream_output",
+        help = "Run a filter on the output image before it's saved, quantize[:colors], dither[:colors] or outline[:rrggbb], can be repeated"
+    )]
+    post: Vec<Filter>,
+
+    #[arg(long, help = "Only draw the cells in x,y,w,h to the output image")]
+    crop: Option<output::Crop>,
+
+    #[arg(
+        long,
+        conflicts_with = "crop",
+        help = "Leave n cells along every edge out of the output image, e.g. a constrained margin"
+    )]
+    trim_border: Option<usize>,
+
+    #[arg(
+        long,
+        help = "Also write an indexed PNG with a palette entry per tile and a JSON mapping of the entries to tiles and where they came from next to it"
+    )]
+    indexed: Option<PathBuf>,
+
+    #[arg(
+        long,
+        default_value = "1",
+        value_parser = value_parser!(u32).range(1..),
+        help = "Pixels per cell side in the indexed PNG"
+    )]
+    indexed_cell_size: u32,
+
+    #[arg(
+        long,
+        help = "Also write the generation as a lossless animated PNG"
+    )]
+    animation: Option<PathBuf>,
+
+    #[arg(
+        long,
+        value_parser = RangedU64ValueParser::<usize>::new().range(1..),
+        help = "Collapses per animation frame, a row of the grid by default"
+    )]
+    frame_stride: Option<usize>,
+
+    #[arg(long, default_value = "40", help = "Milliseconds per animation frame")]
+    frame_delay: u16,
+
+    #[arg(
+        long,
+        default_value = "2000",
+        help = "Milliseconds the finished output stays up before the animation loops"
+    )]
+    final_hold: u16,
+
+    #[arg(
+        long,
+        requires = "animation",
+        help = "Tint every cell of the animation by when it collapsed, from dark to bright"
+    )]
+    order_overlay: bool,
+
+    #[arg(
+        long,
+        help = "Also write a PNG coloring every cell by when it collapsed, from dark to bright"
+    )]
+    order_heatmap: Option<PathBuf>,
+
+    #[arg(
+        long,
+        value_parser = one_of::<UsageFormat>(&["table", "json"]),
+        ignore_case = true,
+        help = "Print how often every tile was placed against what its weight would give it"
+    )]
+    usage: Option<UsageFormat>,
+
+    #[arg(
+        long,
+        value_parser = one_of::<UsageFormat>(&["table", "json"]),
+        ignore_case = true,
+        help = "Print how many cells every tile the solver picked narrowed down and how far they were"
+    )]
+    propagation: Option<UsageFormat>,
+
+    #[arg(
+        long,
+        help = "Also write the tile usage as a bar chart PNG"
+    )]
+    usage_chart: Option<PathBuf>,
+
+    #[arg(
+        long,
+        help = "Also write which neighbor rules the output used and which it never did as JSON"
+    )]
+    adjacency: Option<PathBuf>,
+
+    #[arg(
+        long,
+        default_value = "1",
+        value_parser = value_parser!(u32).range(1..),
+        help = "Scale the output image up n times, keeping pixels sharp"
+    )]
+    scale: u32,
+
+    #[cfg(feature = "profiling")]
+    #[arg(long, help = "Write phase timings as a chrome tracing file")]
+    profile: Option<PathBuf>,
+
+    #[arg(
         short,
         long,
         default_value = "20x20",
@@ -191,245 +472,450 @@ struct Opt {
     )]
     output_size: Size,
 
-    #[cfg(not(feature = "threaded"))]
-    #[structopt(parse(try_from_str), short, long, help = "Random seed")]
+    #[arg(short, long, help = "Random seed")]
     seed: Option<u64>,
 
+    #[arg(
+        long,
+        default_value = "1",
+        help = "Explicit collapses to roll back on a contradiction"
+    )]
+    rollback_step: usize,
+
+    #[arg(
+        long,
+        default_value = "50",
+        help = "Hundredths of a collapse added to the rollback for each repeated contradiction, 50 undoes one more every other time"
+    )]
+    rollback_growth: usize,
+
+    #[arg(long, help = "Restore a checkpoint or reset once a rollback grows past n steps")]
+    rollback_reset: Option<usize>,
+
+    #[arg(
+        long,
+        help = "Restart with a new seed after n contradictions, scaled by the Luby sequence (1, 1, 2, 1, 1, 2, 4, ...)"
+    )]
+    restart_unit: Option<usize>,
+
+    #[arg(
+        long,
+        help = "Try n min-conflicts moves on the contradicting cells before restoring a checkpoint or resetting"
+    )]
+    repair_steps: Option<usize>,
+
+    #[cfg(feature = "threaded")]
+    #[arg(
+        long,
+        conflicts_with_all = &["record", "replay"],
+        help = "Solve with n seeds in parallel and keep the first one to finish"
+    )]
+    portfolio: Option<usize>,
+
+    #[cfg(feature = "threaded")]
+    #[arg(long, help = "Threads propagation runs on, as many as there are cores by default")]
+    threads: Option<usize>,
+
+    #[cfg(feature = "gpu")]
+    #[arg(long, help = "Propagate on the GPU when a collapse spreads far, for huge outputs")]
+    gpu: bool,
+
+    #[cfg(feature = "exact")]
+    #[arg(
+        long,
+        help = "Switch to the exact solver after n contradictions, for small grids that keep failing"
+    )]
+    exact: Option<usize>,
+
+    #[cfg(feature = "exact")]
+    #[arg(
+        long,
+        value_parser = parse_max_count,
+        help = "Most cells a tile (index as shown by --labels) may take up once the exact solver takes over, as index:count, can be repeated"
+    )]
+    exact_max_count: Vec<(usize, usize)>,
+
+    #[arg(long, help = "Give up once the solver ran into more than n contradictions")]
+    max_rollbacks: Option<usize>,
+
+    #[arg(
+        long,
+        help = "Write the partial output, heatmaps and a report to this directory when generation fails"
+    )]
+    failure_report: Option<PathBuf>,
+
+    #[arg(
+        long,
+        help = "Carve a path of --carve-tiles from x,y to x,y, or `across` the output between opposite borders, can be repeated"
+    )]
+    carve: Vec<Route>,
+
+    #[arg(
+        long,
+        value_delimiter = ',',
+        help = "Comma separated tile indexes (as shown by --labels) carved paths are made of"
+    )]
+    carve_tiles: Vec<usize>,
+
+    #[arg(long, default_value = "0", help = "Widen carved paths by n cells on every side")]
+    carve_radius: usize,
+
+    #[arg(long, help = "Split the output into n regions that each only use the tiles of one --biome-tiles")]
+    biomes: Option<usize>,
+
+    #[arg(
+        long,
+        value_parser = parse_indexes,
+        help = "Comma separated tile indexes (as shown by --labels) a biome is made of, can be repeated"
+    )]
+    biome_tiles: Vec<Vec<usize>>,
+
+    #[cfg(feature = "noise")]
+    #[arg(
+        long,
+        value_parser = parse_noise,
+        help = "Scale the weight of tiles by perlin noise, as tiles[:scale[:strength]] where tiles are comma separated indexes (as shown by --labels) or config tags. Strength multiplies the weight where the noise peaks (4 by default, below 1 pushes the tiles away instead), scale is the cells per hill (8 by default). All fields share one noise map, can be repeated"
+    )]
+    noise: Vec<NoiseWeight>,
+
+    #[arg(
+        long,
+        value_parser = load_image,
+        help = "Image drawn over the input, its tiles are solved as a second layer that only puts them on the tiles they're drawn over"
+    )]
+    decorations: Option<DynamicImage>,
+
+    #[arg(
+        long,
+        help = "Give every disconnected area its own rng, so changes in one area don't change the others"
+    )]
+    area_seeds: bool,
+
+    #[arg(long, help = "Also match the corners of tiles extracted from an image, not just the sides")]
+    diagonals: bool,
+
+    #[arg(
+        long,
+        default_value = "1",
+        value_parser = parse_temperature,
+        help = "Below 1 common tiles win more often, above 1 rare tiles catch up"
+    )]
+    temperature: f64,
+
+    #[cfg(any(feature = "sdl2", feature = "tui", feature = "live"))]
+    #[arg(
+        long,
+        default_value = "teal",
+        value_parser = one_of::<Palette>(&Palette::NAMES),
+        ignore_case = true,
+        help = "Colors for cells that haven't collapsed yet"
+    )]
+    palette: Palette,
+
+    #[cfg(any(feature = "sdl2", feature = "tui", feature = "live"))]
+    #[arg(
+        long,
+        value_parser = parse_color,
+        help = "Color for cells without any tiles left, as rrggbb in hex"
+    )]
+    contradiction_color: Option<Rgba<u8>>,
+
+    #[arg(long, help = "Draw lines between the cells of the output and the window")]
+    grid_lines: bool,
+
+    #[arg(long, help = "Label collapsed cells with their tile index where it fits")]
+    labels: bool,
+
+    #[cfg(feature = "live")]
+    #[arg(long, help = "Stream the generation to a browser, e.g. --live 127.0.0.1:8080")]
+    live: Option<String>,
+
+    #[cfg(feature = "tui")]
+    #[arg(long, help = "Show the generation in the terminal, logging is silenced unless RUST_LOG is set")]
+    tui: bool,
+
+    #[arg(
+        long,
+        help = "Fail on tiles that can't be placed everywhere or tilesets that can't be solved"
+    )]
+    strict: bool,
+
+    #[arg(
+        long,
+        conflicts_with = "watch",
+        help = "Print statistics about the tileset and exit without generating anything"
+    )]
+    dry_run: bool,
+
+    #[arg(
+        long,
+        conflicts_with = "watch",
+        help = "Like --dry-run, and also solve a few small grids to estimate the rollback rate"
+    )]
+    estimate: bool,
+
+    #[arg(
+        long,
+        conflicts_with_all = &["watch", "record", "replay"],
+        help = "Solve the seed twice side by side and fail at the first step where the runs differ"
+    )]
+    audit: bool,
+
+    #[arg(long, help = "Remove tiles that can never be placed before generating")]
+    prune: bool,
+
+    #[arg(
+        long,
+        conflicts_with = "replay",
+        help = "Generate again with the same seed whenever the input changes"
+    )]
+    watch: bool,
+
+    #[arg(long, help = "Record every solver action to a file")]
+    record: Option<PathBuf>,
+
+    #[arg(
+        long,
+        conflicts_with = "record",
+        help = "Replay a recording instead of solving, needs the same input and output size"
+    )]
+    replay: Option<PathBuf>,
+
     #[cfg(feature = "sdl2")]
-    #[structopt(short = "V", long, help = "Open a window to show the generation")]
+    #[arg(short = 'V', long, help = "Open a window to show the generation")]
     visual: bool,
 
     #[cfg(feature = "sdl2")]
-    #[structopt(long, help = "Render every step during visualisation")]
+    #[arg(
+        long,
+        value_parser = parse_rate,
+        help = "Throttle the solver to n steps per second, so every step can be followed in the window"
+    )]
+    steps_per_second: Option<f64>,
+
+    #[cfg(feature = "sdl2")]
+    #[arg(long, conflicts_with = "steps_per_second", help = "Shorthand for --steps-per-second 60")]
     slow: bool,
 
     #[cfg(feature = "sdl2")]
-    #[structopt(long, help = "Turns on vsync")]
+    #[arg(long, help = "Turns on vsync")]
     vsync: bool,
 
     #[cfg(feature = "sdl2")]
-    #[structopt(long, help = "Hold the image for n seconds after finishing")]
+    #[arg(long, help = "Hold the image for n seconds after finishing")]
     hold: Option<f32>,
 
     #[cfg(feature = "sdl2")]
-    #[structopt(short, long, help = "Runs the application in full screen")]
+    #[arg(
+        long,
+        requires = "visual",
+        help = "Play the generation back once it's done, left and right step through it (shift for a row at a time), home and end jump and space plays it. Closing the window carries on."
+    )]
+    scrub: bool,
+
+    #[cfg(feature = "sdl2")]
+    #[arg(short, long, help = "Runs the application in full screen")]
     fullscreen: bool,
 
-    #[structopt(long, possible_values= &Shell::variants(), case_insensitive = true, help = "Generate shell completions and exit")]
-    completions: Option<Shell>,
-}
+    #[cfg(feature = "sdl2")]
+    #[arg(
+        long,
+        conflicts_with = "fullscreen",
+        help = "Full screen at the desktop resolution, without switching video modes"
+    )]
+    borderless: bool,
 
-#[cfg(feature = "image")]
-fn main() {
-    use std::sync::Arc;
+    #[cfg(feature = "sdl2")]
+    #[arg(
+        long,
+        default_value = "0",
+        value_parser = value_parser!(i32).range(0..),
+        help = "Display to open the window on, 0 is the primary one"
+    )]
+    display: i32,
 
-    let opt: Opt = Opt::from_args();
+    #[cfg(feature = "sdl2")]
+    #[arg(
+        long,
+        help = "Window position as x,y from the top left of the display, centered by default"
+    )]
+    window_position: Option<Position>,
 
-    if let Some(shell) = opt.completions {
-        Opt::clap().gen_completions_to(env!("CARGO_PKG_NAME"), shell, &mut io::stdout());
-        return;
-    }
+    #[cfg(feature = "sdl2")]
+    #[arg(
+        long,
+        default_value = "808080",
+        value_parser = parse_color,
+        help = "Window background, also shown through transparent tiles, as rrggbb in hex"
+    )]
+    background: Rgba<u8>,
 
-    TermLogger::init(
-        opt.verbose.get_level_filter(),
-        Config::default(),
-        TerminalMode::Mixed,
-        ColorChoice::Auto,
-    )
-    .unwrap();
-
-    let mut tiles = match &opt.input.unwrap() {
-        Input::Image(value) => Tile::from_image(value, &Size::uniform(opt.input_size.unwrap())),
-        Input::Config(value) => Tile::from_config(value),
-    };
+    #[cfg(feature = "sdl2")]
+    #[arg(
+        long,
+        value_parser = parse_color,
+        help = "Outline collapsed cells in the window, as rrggbb or rrggbbaa in hex"
+    )]
+    border_color: Option<Rgba<u8>>,
 
-    info!("{} unique tiles found", tiles.len());
+    #[cfg(feature = "sdl2")]
+    #[arg(
+        long,
+        requires = "visual",
+        conflicts_with = "replay",
+        help = "Session file to save to with ctrl+s and load from with ctrl+o, keeps the window open until it's closed"
+    )]
+    session: Option<PathBuf>,
 
-    let invalid_neighbors = tiles
-        .iter()
-        .map(|t| t.neighbors.len())
-        .filter(|c| *c != 4)
-        .collect::<Vec<usize>>();
+    #[arg(long, value_enum, ignore_case = true, help = "Generate shell completions and exit")]
+    completions: Option<Shell>,
 
-    if !invalid_neighbors.is_empty() {
-        warn!(
-            "Found {} tiles with invalid amount of neighbors: {:?}",
-            invalid_neighbors.len(),
-            invalid_neighbors
-        );
+    #[arg(long, help = "Print every option as it would be used for this run as JSON and exit")]
+    print_config: bool,
 
-        tiles.retain(|t| t.neighbors.len() == 4);
+    #[arg(
+        long,
+        help = "Take the defaults of the options from a JSON file written by --print-config, the environment and command line override them"
+    )]
+    project: Option<PathBuf>,
+}
 
-        warn!("Retained {} tiles", tiles.len());
-    }
+#[cfg(feature = "image")]
+fn main() -> ExitCode {
+    let mut command = with_env(Opt::command(), "WFC");
 
-    let base_state = SuperState::new(tiles.iter().cloned().map(Arc::new).collect());
-    let grid = Grid::new(
-        opt.output_size.width,
-        opt.output_size.height,
-        &mut |_, _| base_state.clone(),
-    );
-    let seed = {
-        #[cfg(not(feature = "threaded"))]
-        {opt.seed.unwrap_or(OsRng.gen())}
-
-        #[cfg(feature = "threaded")]
-        {OsRng.gen()}
-    };
+    if let Some(path) = project::path() {
+        command = project::apply(command.clone(), &path)
+            .unwrap_or_else(|e| command.error(clap::error::ErrorKind::Io, e).exit());
+    }
 
-    info!("Using seed: {}", seed);
+    let matches = command.get_matches_mut();
+    let mut opt = Opt::from_arg_matches(&matches).unwrap_or_else(|e| e.exit());
 
-    let max_progress = grid.size() as u64;
-    let progress = ProgressBar::new(grid.size() as u64);
-    let mut wfc = Wave::new(grid, seed);
+    if opt.print_config {
+        println!("{:#}", project::dump(&command, &matches));
+        return ExitCode::SUCCESS;
+    }
 
-    progress.enable_steady_tick(Duration::from_millis(200));
-    progress.set_style(
-        ProgressStyle::default_bar()
-            .template(
-                "{spinner:.green} [{elapsed_precise}] [{bar:40.cyan/blue}] {pos:>5}/{len} {per_sec:>12}",
+    // the project file only fills in defaults, which clap doesn't count as given
+    if opt.input.is_none() && opt.completions.is_none() && opt.command.is_none() {
+        command
+            .error(
+                clap::error::ErrorKind::MissingRequiredArgument,
+                "the project has no input, pass one or add it to the project",
             )
-            .unwrap()
-            .progress_chars("#>-"),
-    );
+            .exit();
+    }
 
-    #[cfg(feature = "sdl2")]
-    let mut sdl_draw = if opt.visual {
-        let (tile_width, tile_height) = tiles[0].value.image.dimensions();
-        let mut size = opt.output_size;
+    if let Some(shell) = opt.completions {
+        clap_complete::generate(shell, &mut Opt::command(), env!("CARGO_PKG_NAME"), &mut io::stdout());
+        return ExitCode::SUCCESS;
+    }
 
-        assert_eq!(tile_width, tile_height);
+    #[cfg(feature = "tui")]
+    let tui = opt.tui;
 
-        size.scale(tile_width.try_into().unwrap());
+    #[cfg(not(feature = "tui"))]
+    let tui = false;
 
-        Some(SdlDraw::new(size, &tiles, opt.vsync, opt.fullscreen))
-    } else {
-        None
-    };
+    #[cfg(feature = "live")]
+    let live = opt.live.is_some();
 
-    while !wfc.done() {
-        progress.set_position(max_progress - wfc.remaining() as u64);
-
-        #[cfg(feature = "sdl2")]
-        if let Some(draw) = sdl_draw.as_mut() {
-            for event in draw.events.poll_iter() {
-                match event {
-                    Event::Quit { .. }
-                    | Event::KeyDown {
-                        keycode: Some(Keycode::Escape),
-                        ..
-                    } => return,
-                    _ => {}
-                }
-            }
+    #[cfg(not(feature = "live"))]
+    let live = false;
 
-            update_canvas(&wfc, draw);
-        }
+    #[cfg(feature = "sdl2")]
+    let visual = opt.visual;
 
-        #[cfg(feature = "sdl2")]
-        if opt.slow {
-            wfc.tick_once();
-        } else {
-            wfc.tick();
-        }
+    #[cfg(not(feature = "sdl2"))]
+    let visual = false;
 
-        #[cfg(not(feature = "sdl2"))]
-        wfc.tick();
+    // log lines would end up in the middle of the terminal ui
+    init_logging(if tui {
+        LevelFilter::OFF
+    } else {
+        opt.verbose.level()
+    });
+
+    // written on the way out, whichever way that is
+    #[cfg(feature = "profiling")]
+    let _trace = opt.profile.as_deref().map(profiling::trace_to);
+
+    match &opt.command {
+        Some(Command::Bench(bench_opt)) => bench::run(bench_opt),
+        Some(Command::Batch(batch_opt)) => batch::run(batch_opt),
+        Some(Command::Analyze(analyze_opt)) => analyze::run(analyze_opt),
+        #[cfg(feature = "serve")]
+        Some(Command::Serve(serve_opt)) => serve::run(serve_opt),
+        None => return generate_input(&mut opt, &matches, Views { tui, live, visual }),
     }
 
-    #[cfg(feature = "sdl2")]
-    if let Some(draw) = sdl_draw.as_mut() {
-        update_canvas(&wfc, draw);
-    }
+    ExitCode::SUCCESS
+}
 
-    progress.finish();
+/// Generates from the input of `opt`, failing when the output can't be set up or written
+#[cfg(feature = "image")]
+fn generate_input(opt: &mut Opt, matches: &ArgMatches, views: Views) -> ExitCode {
+    // the input is reloaded from its source on changes, an url can't be watched
+    let source = matches.get_raw("input").and_then(|mut values| values.next()).map(PathBuf::from);
+    let settings = match Settings::new(opt, source.as_deref(), views) {
+        Ok(settings) => settings,
+        Err(e) => {
+            error!("{e}");
+            return ExitCode::FAILURE;
+        }
+    };
+    let input = opt.input.take().unwrap();
 
-    #[cfg(feature = "sdl2")]
-    if let Some(delay) = opt.hold {
-        info!("Waiting for {} seconds", delay);
+    info!("Using seed: {}", settings.seed);
 
-        std::thread::sleep(Duration::from_secs_f32(delay));
-    }
+    let mut generator = Generator::new(opt, &settings);
+    let outcome = match source.as_deref().filter(|_| opt.watch) {
+        Some(source) => watch::run(generator, input, source),
+        None => generator.run(&input),
+    };
 
-    info!("Drawing output");
-    if opt.output.is_none() {
-        return;
+    match outcome {
+        Outcome::Failed => ExitCode::FAILURE,
+        Outcome::Written | Outcome::Finished => ExitCode::SUCCESS,
     }
+}
 
-    // drawing
-    let (tile_width, tile_height) = tiles[0].value.image.dimensions();
-
-    trace!("Tile size: {tile_width}x{tile_height}");
 
-    let mut canvas = RgbaImage::new(
-        opt.output_size.width as u32 * tile_width,
-        opt.output_size.height as u32 * tile_height,
-    );
+#[cfg(feature = "exact")]
+fn parse_max_count(s: &str) -> Result<(usize, usize), String> {
+    let (index, count) = s.split_once(':').ok_or(format!("{s} isn't index:count"))?;
 
-    for (x, y, cell) in &wfc.grid {
-        if let Some(t) = cell.collapsed() {
-            image::imageops::overlay(
-                &mut canvas,
-                &t.value.image,
-                x as i64 * tile_width as i64,
-                y as i64 * tile_height as i64,
-            );
-        }
-    }
-
-    trace!("Writing output");
+    Ok((
+        index.trim().parse().map_err(|_| format!("{index} isn't a tile index"))?,
+        count.trim().parse().map_err(|_| format!("{count} isn't a count"))?,
+    ))
+}
 
-    canvas.save(opt.output.unwrap().as_path()).unwrap();
+fn parse_indexes(s: &str) -> Result<Vec<usize>, String> {
+    s.split(',')
+        .map(|index| index.trim().parse().map_err(|_| format!("{index} isn't a tile index")))
+        .collect()
 }
 
-// todo only draw updated
-#[cfg(feature = "sdl2")]
-fn update_canvas(wfc: &Wave<Tile<Sprite>>, context: &mut SdlDraw) {
-    use sdl2::render::BlendMode;
-
-    let (tile_width, tile_height) = wfc.grid.get(0, 0).unwrap().possible[0]
-        .value
-        .image
-        .dimensions();
-
-    context.canvas.clear();
-    context.canvas.set_blend_mode(BlendMode::Blend);
-
-    for (x, y, cell) in &wfc.grid {
-
-        let rect = Rect::new(
-            x as i32 * tile_width as i32,
-            y as i32 * tile_height as i32,
-            tile_width,
-            tile_height,
-        );
-
-        if let Some(tile) = cell.collapsed() {
-            // todo streamline
-            let texture = context.textures.get(&tile.get_id()).unwrap();
-
-            context.canvas.set_draw_color(Color::GRAY);
-            context.canvas.fill_rect(rect).unwrap();
-            context.canvas.copy(texture, None, Some(rect)).unwrap();
-        } else {
-            let mut color = if cell.entropy() > 0 {
-                let ratio = cell.entropy() as f32 / cell.base_entropy() as f32;
-                let value = (255.0 * (1.0 - ratio)) as u8;
-
-                Color::RGB(0, value / 3, value / 2)
-            } else {
-                Color::RED
-            };
-
-
-            if wfc.data.get(x, y).map(|x| x.is_some()).unwrap_or(false) {
-                color.r = 40;
-            }
+/// Parses `tiles[:scale[:strength]]`, see `--noise`
+#[cfg(feature = "noise")]
+fn parse_noise(s: &str) -> Result<NoiseWeight, String> {
+    let mut parts = s.split(':');
+    let tiles: Vec<String> = parts.next().unwrap().split(',').map(|tile| tile.trim().to_string()).collect();
+    let mut number = |default: f64| match parts.next() {
+        None => Ok(default),
+        Some(part) => match part.parse::<f64>() {
+            Ok(value) if value > 0.0 && value.is_finite() => Ok(value),
+            _ => Err(format!("{part} isn't a number above zero")),
+        },
+    };
+    let scale = number(8.0)?;
+    let strength = number(4.0)?;
 
-            context.canvas.set_draw_color(color);
-            context.canvas.fill_rect(rect).unwrap();
-        }
+    if tiles.iter().any(String::is_empty) {
+        return Err(format!("{s} is missing tiles"));
     }
 
-    context.canvas.present();
+    Ok(NoiseWeight { tiles, scale, strength })
 }
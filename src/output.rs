@@ -0,0 +1,778 @@
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::str::FromStr;
+
+use image::codecs::webp::{WebPEncoder, WebPQuality};
+use image::{ColorType, DynamicImage, GenericImageView, ImageOutputFormat, Rgb, Rgba, RgbaImage};
+use serde_json::{json, Value};
+
+use wave_function_collapse::analysis::PropagationStats;
+use wave_function_collapse::grid::{Grid, Position, Size};
+use wave_function_collapse::preprocess::flatten;
+use wave_function_collapse::sprite::Sprite;
+use wave_function_collapse::style::{CellStyle, Palette};
+use wave_function_collapse::superstate::{Collapsable, SuperState};
+use wave_function_collapse::tile::{Provenance, Tile};
+use wave_function_collapse::usage::{AdjacencyUsage, RuleUsage};
+use wave_function_collapse::wave::{Propagation, Wave};
+
+/// Collapses listed in a failure report, counting back from the last one
+const REPORT_HISTORY: usize = 64;
+/// Perceptually even, so equal steps in the collapse order look equally far apart
+const ORDER_PALETTE: Palette = Palette::Viridis;
+/// Pixels of the longest bar in a usage chart
+const CHART_BAR: u32 = 400;
+/// Pixels between a tile and its bar in a usage chart
+const CHART_GAP: u32 = 4;
+
+/// How the tile usage report is printed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UsageFormat {
+    Table,
+    Json,
+}
+
+impl FromStr for UsageFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "table" => Ok(Self::Table),
+            "json" => Ok(Self::Json),
+            _ => Err(format!("Unknown usage format: {s}")),
+        }
+    }
+}
+
+/// Area of the grid that makes it into the output image, in cells
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Crop {
+    pub x: usize,
+    pub y: usize,
+    pub width: usize,
+    pub height: usize,
+}
+
+impl Crop {
+    /// Everything but `border` cells along every edge of a grid of `size`
+    pub fn trim(size: &Size, border: usize) -> Self {
+        Self {
+            x: border,
+            y: border,
+            width: size.width.saturating_sub(border * 2),
+            height: size.height.saturating_sub(border * 2),
+        }
+    }
+
+    /// Fails when the area is empty or doesn't fit in a grid of `size`
+    pub fn check(&self, size: &Size) -> Result<(), String> {
+        if self.width == 0 || self.height == 0 {
+            return Err(format!("Cropping to {}x{} leaves nothing of the output", self.width, self.height));
+        }
+
+        if self.x + self.width > size.width || self.y + self.height > size.height {
+            return Err(format!(
+                "Cropping {}x{} at {},{} goes past the {}x{} output",
+                self.width, self.height, self.x, self.y, size.width, size.height
+            ));
+        }
+
+        Ok(())
+    }
+
+    pub fn apply<T: Clone>(&self, grid: &Grid<T>) -> Grid<T> {
+        grid.crop(self.x, self.y, self.width, self.height)
+    }
+}
+
+impl FromStr for Crop {
+    type Err = String;
+
+    /// Parses `x,y,w,h`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let values: Vec<usize> = s
+            .split(',')
+            .map(|value| value.trim().parse().map_err(|_| format!("invalid number: {value}")))
+            .collect::<Result<_, _>>()?;
+
+        match values[..] {
+            [x, y, width, height] => Ok(Self { x, y, width, height }),
+            _ => Err(format!("invalid crop: {s}, expected x,y,w,h")),
+        }
+    }
+}
+
+/// How often a tile was placed next to how often its weight alone would place it, the
+/// neighbor rules make up the difference
+#[derive(Debug, Clone)]
+pub struct TileUsage<'a> {
+    /// Dense index, as `--labels` shows it
+    pub index: usize,
+    pub tile: &'a Tile<Sprite>,
+    pub placed: usize,
+    pub expected: f64,
+}
+
+/// Usage of every tile in dense index order, including the ones that weren't placed
+pub fn tile_usage<'a>(wave: &Wave<Tile<Sprite>>, tiles: &'a [Tile<Sprite>]) -> Vec<TileUsage<'a>> {
+    let mut tiles: Vec<&Tile<Sprite>> = tiles.iter().collect();
+
+    tiles.sort_by_key(|tile| tile.get_id());
+    tiles.dedup_by_key(|tile| tile.get_id());
+
+    let placements = wave.placements();
+    let placed: usize = placements.values().sum();
+    let total_weight: f64 = tiles.iter().map(|tile| tile.get_weight()).sum();
+
+    tiles
+        .into_iter()
+        .enumerate()
+        .map(|(index, tile)| TileUsage {
+            index,
+            tile,
+            placed: placements.get(&tile.get_id()).copied().unwrap_or(0),
+            expected: placed as f64 * tile.get_weight() / total_weight.max(f64::EPSILON),
+        })
+        .collect()
+}
+
+/// Prints the usage report to stdout, the table lists the most placed tiles first
+pub fn print_usage(usage: &[TileUsage], format: UsageFormat) {
+    let placed: usize = usage.iter().map(|tile| tile.placed).sum();
+    let share = |count: f64| 100.0 * count / placed.max(1) as f64;
+
+    match format {
+        UsageFormat::Table => {
+            let mut rows: Vec<&TileUsage> = usage.iter().collect();
+
+            rows.sort_by(|a, b| b.placed.cmp(&a.placed).then(a.index.cmp(&b.index)));
+            println!("{:>5}  {:>7}  {:>7}  {:>8}  {:>7}", "index", "placed", "share", "expected", "weight");
+
+            for row in rows {
+                println!(
+                    "{:>5}  {:>7}  {:>6.1}%  {:>7.1}%  {:>7}",
+                    row.index,
+                    row.placed,
+                    share(row.placed as f64),
+                    share(row.expected),
+                    row.tile.weight
+                );
+            }
+        }
+        UsageFormat::Json => {
+            let tiles: Vec<_> = usage
+                .iter()
+                .map(|row| {
+                    json!({
+                        "index": row.index,
+                        // as a string, javascript can't hold every u64
+                        "id": row.tile.get_id().to_string(),
+                        "weight": row.tile.weight,
+                        "placed": row.placed,
+                        "expected": row.expected,
+                    })
+                })
+                .collect();
+            let report = json!({
+                "placed": placed,
+                "tiles": tiles,
+            });
+
+            println!("{}", serde_json::to_string_pretty(&report).unwrap());
+        }
+    }
+}
+
+/// Prints how far every tile the solver picked spread, as a summary of the distribution
+/// or as JSON with every pick
+pub fn print_propagation(propagation: &[Propagation], format: UsageFormat) {
+    let stats = PropagationStats::new(propagation);
+
+    match format {
+        UsageFormat::Table => println!("{}", stats.describe()),
+        UsageFormat::Json => {
+            let picks: Vec<_> = propagation
+                .iter()
+                .map(|propagation| {
+                    json!({
+                        "x": propagation.position.x,
+                        "y": propagation.position.y,
+                        "cells": propagation.cells,
+                        "reach": propagation.reach,
+                        "contradicted": propagation.contradicted,
+                    })
+                })
+                .collect();
+            let report = json!({
+                "collapses": stats.collapses,
+                "contradicted": stats.contradicted,
+                "cells": {
+                    "mean": stats.mean_cells,
+                    "median": stats.median_cells,
+                    "p90": stats.p90_cells,
+                    "max": stats.max_cells,
+                },
+                "reach": {
+                    "mean": stats.mean_reach,
+                    "max": stats.max_reach,
+                },
+                "picks": picks,
+            });
+
+            println!("{}", serde_json::to_string_pretty(&report).unwrap());
+        }
+    }
+}
+
+/// Writes the usage as a bar chart, a row per tile in dense index order with the tile
+/// followed by a bar in its average color. The line across the bar marks where the
+/// weight alone would put it.
+pub fn write_usage_chart(usage: &[TileUsage], path: &Path) -> Result<(), String> {
+    let Some((tile_width, tile_height)) = usage.first().map(|row| row.tile.value.image().dimensions()) else {
+        return Err("There are no tiles to chart".into());
+    };
+    let longest = usage
+        .iter()
+        .map(|row| (row.placed as f64).max(row.expected))
+        .fold(1.0, f64::max);
+    let length = |count: f64| (count / longest * CHART_BAR as f64).round() as u32;
+    let mut chart = RgbaImage::from_pixel(
+        tile_width + CHART_GAP + CHART_BAR + 1,
+        usage.len() as u32 * (tile_height + CHART_GAP),
+        Rgba([255, 255, 255, 255]),
+    );
+
+    for (row, tile) in usage.iter().enumerate() {
+        let top = row as u32 * (tile_height + CHART_GAP);
+        let [r, g, b] = tile.tile.value.average_color().0;
+
+        image::imageops::overlay(&mut chart, tile.tile.value.image(), 0, top as i64);
+
+        for x in 0..length(tile.placed as f64) {
+            for y in 0..tile_height {
+                chart.put_pixel(tile_width + CHART_GAP + x, top + y, Rgba([r, g, b, 255]));
+            }
+        }
+
+        let marker = tile_width + CHART_GAP + length(tile.expected).min(CHART_BAR);
+
+        for y in 0..tile_height {
+            chart.put_pixel(marker, top + y, Rgba([0, 0, 0, 255]));
+        }
+    }
+
+    OutputFormat::Png.save(chart, path, 100)
+}
+
+/// Adjacency usage as JSON with tiles as dense indexes (as `--labels` shows them): how
+/// often every used rule came up, the allowed rules that never did and the pairs no rule
+/// allows
+pub fn adjacency_json(usage: &AdjacencyUsage, tiles: &[Tile<Sprite>]) -> Value {
+    let mut ids: Vec<u64> = tiles.iter().map(|tile| tile.get_id()).collect();
+
+    ids.sort();
+    ids.dedup();
+
+    let pair = |rule: &RuleUsage| {
+        // pairs read from an image may have tiles the tileset doesn't
+        let index = |id: u64| ids.binary_search(&id).ok();
+
+        json!({
+            "tile": index(rule.tile),
+            "direction": format!("{:?}", rule.direction).to_lowercase(),
+            "neighbor": index(rule.neighbor),
+        })
+    };
+    let counted = |rule: &RuleUsage| {
+        let mut value = pair(rule);
+
+        value["count"] = json!(rule.count);
+        value
+    };
+
+    json!({
+        "allowed": usage.rules.iter().filter(|rule| rule.allowed).count(),
+        "used": usage.used().map(counted).collect::<Vec<_>>(),
+        "dead": usage.dead().map(pair).collect::<Vec<_>>(),
+        "disallowed": usage.disallowed().map(counted).collect::<Vec<_>>(),
+    })
+}
+
+/// Image formats the output can be written as
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputFormat {
+    Png,
+    Jpeg,
+    Bmp,
+    Tiff,
+    Qoi,
+    Tga,
+    WebP,
+}
+
+impl OutputFormat {
+    pub const NAMES: [&'static str; 7] = ["png", "jpeg", "bmp", "tiff", "qoi", "tga", "webp"];
+
+    /// Picks the format from `format` if given, otherwise from the extension of `path`
+    pub fn resolve(path: &Path, format: Option<OutputFormat>) -> Result<Self, String> {
+        let format = match format {
+            Some(format) => format,
+            None => path
+                .extension()
+                .and_then(|extension| extension.to_str())
+                .ok_or_else(|| format!("{} has no extension, pass --format", path.display()))?
+                .parse()?,
+        };
+
+        Ok(format)
+    }
+
+    /// Writes the image, JPEG has no alpha channel so it's drawn over black. WebP is
+    /// lossless like PNG, `quality` only applies to JPEG.
+    pub fn save(self, mut image: RgbaImage, path: &Path, quality: u8) -> Result<(), String> {
+        let mut writer = BufWriter::new(File::create(path).map_err(|e| e.to_string())?);
+        let encoder = match self {
+            OutputFormat::Png => ImageOutputFormat::Png,
+            OutputFormat::Jpeg => ImageOutputFormat::Jpeg(quality),
+            OutputFormat::Bmp => ImageOutputFormat::Bmp,
+            OutputFormat::Tiff => ImageOutputFormat::Tiff,
+            OutputFormat::Qoi => ImageOutputFormat::Qoi,
+            OutputFormat::Tga => ImageOutputFormat::Tga,
+            // the encoder behind ImageOutputFormat::WebP is always lossy
+            OutputFormat::WebP => {
+                let (width, height) = image.dimensions();
+
+                return WebPEncoder::new_with_quality(writer, WebPQuality::lossless())
+                    .encode(&image, width, height, ColorType::Rgba8)
+                    .map_err(|e| e.to_string());
+            }
+        };
+        let image = match self {
+            OutputFormat::Jpeg => {
+                flatten(&mut image, Rgb([0, 0, 0]));
+
+                DynamicImage::ImageRgb8(DynamicImage::ImageRgba8(image).to_rgb8())
+            }
+            _ => DynamicImage::ImageRgba8(image),
+        };
+
+        image.write_to(&mut writer, encoder).map_err(|e| e.to_string())
+    }
+}
+
+impl FromStr for OutputFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "png" => Ok(OutputFormat::Png),
+            "jpeg" | "jpg" => Ok(OutputFormat::Jpeg),
+            "bmp" => Ok(OutputFormat::Bmp),
+            "tiff" | "tif" => Ok(OutputFormat::Tiff),
+            "qoi" => Ok(OutputFormat::Qoi),
+            "tga" => Ok(OutputFormat::Tga),
+            "webp" => Ok(OutputFormat::WebP),
+            _ => Err(format!(
+                "Unknown output format {s}, expected one of {:?}",
+                OutputFormat::NAMES
+            )),
+        }
+    }
+}
+
+/// Writes a PNG of `width`x`height` pixels from bands of rows drawn one after the other,
+/// scaled up by `scale`. Only one band is held in memory at a time, unlike `save`.
+pub fn write_bands(
+    path: &Path,
+    width: u32,
+    height: u32,
+    scale: u32,
+    bands: impl Iterator<Item = RgbaImage>,
+) -> Result<(), String> {
+    let file = File::create(path).map_err(|e| e.to_string())?;
+    let mut encoder = png::Encoder::new(BufWriter::new(file), width * scale, height * scale);
+
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+
+    let mut writer = encoder
+        .write_header()
+        .and_then(|writer| writer.into_stream_writer())
+        .map_err(|e| e.to_string())?;
+    let mut line = Vec::with_capacity((width * scale * 4) as usize);
+    let mut written = 0;
+
+    for band in bands {
+        for row in band.rows() {
+            line.clear();
+
+            for pixel in row {
+                for _ in 0..scale {
+                    line.extend_from_slice(&pixel.0);
+                }
+            }
+
+            for _ in 0..scale {
+                writer.write_all(&line).map_err(|e| e.to_string())?;
+            }
+        }
+
+        written += band.height();
+    }
+
+    if written != height {
+        return Err(format!("Drew {written} of {height} rows"));
+    }
+
+    writer.finish().map_err(|e| e.to_string())
+}
+
+/// Writes an indexed PNG with a palette entry per tile, in dense index order (tiles
+/// ordered by id), and every cell as a `cell_size` square of its tile's entry. The
+/// palette colors are the average tile colors, `<path>.json` maps entries to tile ids and
+/// where the tiles came from. Cells that aren't collapsed get an extra transparent entry.
+pub fn write_indexed(
+    grid: &Grid<SuperState<Tile<Sprite>>>,
+    tiles: &[Tile<Sprite>],
+    provenance: &HashMap<u64, Provenance>,
+    path: &Path,
+    cell_size: u32,
+) -> Result<(), String> {
+    let mut tiles: Vec<&Tile<Sprite>> = tiles.iter().collect();
+
+    tiles.sort_by_key(|tile| tile.get_id());
+    tiles.dedup_by_key(|tile| tile.get_id());
+
+    let uncollapsed = grid.iter().any(|(_, _, cell)| cell.collapsed().is_none());
+    let entries = tiles.len() + uncollapsed as usize;
+
+    if entries > 256 {
+        return Err(format!("An indexed image holds up to 256 colors, {entries} are needed"));
+    }
+
+    let mut palette = Vec::with_capacity(entries * 3);
+
+    for tile in &tiles {
+        palette.extend_from_slice(&tile.value.average_color().0);
+    }
+
+    let width = grid.width() as u32 * cell_size;
+    let height = grid.height() as u32 * cell_size;
+    let mut data = vec![0u8; (width * height) as usize];
+
+    for (x, y, cell) in grid {
+        let entry = match cell.collapsed() {
+            Some(_) => cell.indexes()[0] as u8,
+            None => tiles.len() as u8,
+        };
+
+        for row in 0..cell_size {
+            let start = ((y as u32 * cell_size + row) * width + x as u32 * cell_size) as usize;
+
+            data[start..start + cell_size as usize].fill(entry);
+        }
+    }
+
+    let file = File::create(path).map_err(|e| e.to_string())?;
+    let mut encoder = png::Encoder::new(BufWriter::new(file), width, height);
+
+    encoder.set_color(png::ColorType::Indexed);
+    encoder.set_depth(png::BitDepth::Eight);
+
+    let mut transparency: Vec<u8> = tiles.iter().map(|tile| tile.value.average_alpha()).collect();
+
+    if uncollapsed {
+        palette.extend_from_slice(&[0, 0, 0]);
+        transparency.push(0);
+    }
+
+    if transparency.iter().any(|alpha| *alpha < 255) {
+        encoder.set_trns(transparency);
+    }
+
+    encoder.set_palette(palette);
+    encoder
+        .write_header()
+        .and_then(|mut writer| writer.write_image_data(&data))
+        .map_err(|e| e.to_string())?;
+
+    let mapping = json!({
+        "cell_size": cell_size,
+        "uncollapsed": uncollapsed.then_some(tiles.len()),
+        "tiles": tiles
+            .iter()
+            .enumerate()
+            .map(|(index, tile)| {
+                let [r, g, b] = tile.value.average_color().0;
+
+                json!({
+                    "index": index,
+                    // as a string, javascript can't hold every u64
+                    "id": tile.get_id().to_string(),
+                    "weight": tile.weight,
+                    "color": format!("#{r:02x}{g:02x}{b:02x}"),
+                    "origin": provenance.get(&tile.get_id()).map(|origin| json!({
+                        "source": origin.source.as_ref().map(|source| source.display().to_string()),
+                        "x": origin.position.map(|position| position.x),
+                        "y": origin.position.map(|position| position.y),
+                        "turns": origin.turns,
+                        "flipped": origin.flipped,
+                    })),
+                })
+            })
+            .collect::<Vec<_>>(),
+    });
+    let mapping_path = path.with_extension("json");
+    let file = File::create(&mapping_path).map_err(|e| e.to_string())?;
+
+    serde_json::to_writer_pretty(BufWriter::new(file), &mapping).map_err(|e| e.to_string())
+}
+
+/// How an animation written by `write_animation` plays
+#[derive(Debug, Clone, Copy)]
+pub struct Playback {
+    /// Collapses drawn per frame
+    pub stride: usize,
+    /// Milliseconds per frame
+    pub delay: u16,
+    /// Milliseconds the last frame stays up before the animation loops
+    pub hold: u16,
+    /// Tints every cell by when it collapsed, like `write_order_heatmap`
+    pub tint: bool,
+}
+
+/// Writes the generation as a lossless animated PNG: tiles appear in the order their
+/// cells collapsed, a frame per `playback.stride` collapses, and the finished output stays
+/// up for `playback.hold` milliseconds before it loops. Every frame only covers the
+/// cells that changed since the one before.
+pub fn write_animation(
+    wave: &Wave<Tile<Sprite>>,
+    tile_width: u32,
+    tile_height: u32,
+    path: &Path,
+    playback: &Playback,
+) -> Result<(), String> {
+    let Playback { stride, delay, hold, tint } = *playback;
+    let order: Vec<Position> = wave.collapse_order().map(|(position, _)| position).collect();
+    let chunks: Vec<&[Position]> = order.chunks(stride.max(1)).collect();
+    let style = CellStyle::new(ORDER_PALETTE);
+    let last = order.len().saturating_sub(1).max(1) as f32;
+    let width = wave.grid.width() as u32 * tile_width;
+    let height = wave.grid.height() as u32 * tile_height;
+    let mut canvas = RgbaImage::new(width, height);
+
+    let file = File::create(path).map_err(|e| e.to_string())?;
+    let mut encoder = png::Encoder::new(BufWriter::new(file), width, height);
+
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    // the blank first frame, then a frame per chunk
+    encoder
+        .set_animated(chunks.len() as u32 + 1, 0)
+        .and_then(|_| encoder.set_frame_delay(delay, 1000))
+        .and_then(|_| encoder.set_blend_op(png::BlendOp::Source))
+        .and_then(|_| encoder.set_dispose_op(png::DisposeOp::None))
+        .map_err(|e| e.to_string())?;
+
+    let mut writer = encoder.write_header().map_err(|e| e.to_string())?;
+
+    if chunks.is_empty() {
+        writer.set_frame_delay(hold, 1000).map_err(|e| e.to_string())?;
+    }
+
+    writer.write_image_data(&canvas).map_err(|e| e.to_string())?;
+
+    for (index, chunk) in chunks.iter().enumerate() {
+        for (offset, position) in chunk.iter().enumerate() {
+            let (x, y) = (position.x as u32 * tile_width, position.y as u32 * tile_height);
+
+            if let Some(tile) = wave.grid.get(position.x, position.y).and_then(|cell| cell.collapsed()) {
+                image::imageops::overlay(&mut canvas, tile.value.image(), x as i64, y as i64);
+            }
+
+            if tint {
+                let rank = index * stride.max(1) + offset;
+                let color = style.gradient(rank as f32 / last);
+
+                for (dx, dy) in (0..tile_height).flat_map(|dy| (0..tile_width).map(move |dx| (dx, dy))) {
+                    if let Some(pixel) = canvas.get_pixel_mut_checked(x + dx, y + dy) {
+                        *pixel = blend(*pixel, color);
+                    }
+                }
+            }
+        }
+
+        let left = chunk.iter().map(|position| position.x).min().unwrap_or(0) as u32;
+        let top = chunk.iter().map(|position| position.y).min().unwrap_or(0) as u32;
+        let right = chunk.iter().map(|position| position.x + 1).max().unwrap_or(1) as u32;
+        let bottom = chunk.iter().map(|position| position.y + 1).max().unwrap_or(1) as u32;
+        let (x, y) = (left * tile_width, top * tile_height);
+        let (frame_width, frame_height) = ((right - left) * tile_width, (bottom - top) * tile_height);
+        let frame = image::imageops::crop_imm(&canvas, x, y, frame_width, frame_height).to_image();
+        let frame_delay = match index + 1 == chunks.len() {
+            true => hold,
+            false => delay,
+        };
+
+        writer
+            .reset_frame_position()
+            .and_then(|_| writer.set_frame_dimension(frame_width, frame_height))
+            .and_then(|_| writer.set_frame_position(x, y))
+            .and_then(|_| writer.set_frame_delay(frame_delay, 1000))
+            .and_then(|_| writer.write_image_data(&frame))
+            .map_err(|e| e.to_string())?;
+    }
+
+    writer.finish().map_err(|e| e.to_string())
+}
+
+/// Writes the order the cells were collapsed in as a gradient from the first cell to the
+/// last. Cells collapsed more than once, after a backtrack or a repair, count where they
+/// were collapsed last and cells that never collapsed stay transparent.
+pub fn write_order_heatmap(
+    wave: &Wave<Tile<Sprite>>,
+    tile_width: u32,
+    tile_height: u32,
+    path: &Path,
+) -> Result<(), String> {
+    let mut ranks = Grid::new(wave.grid.width(), wave.grid.height(), &mut |_, _| None);
+    let mut count = 0;
+
+    for (rank, (position, _)) in wave.collapse_order().enumerate() {
+        ranks.set(position.x, position.y, Some(rank))?;
+        count = rank + 1;
+    }
+
+    let style = CellStyle::new(ORDER_PALETTE);
+    let last = count.saturating_sub(1).max(1) as f32;
+    let mut image = heatmap(&ranks, tile_width, tile_height, |rank: &Option<usize>| {
+        style.gradient(rank.unwrap_or(0) as f32 / last)
+    });
+
+    for (x, y, pixel) in image.enumerate_pixels_mut() {
+        if let Some(None) = ranks.get((x / tile_width) as usize, (y / tile_height) as usize) {
+            *pixel = Rgba([0, 0, 0, 0]);
+        }
+    }
+
+    OutputFormat::Png.save(image, path, 100)
+}
+
+/// Mixes `color` half into `pixel`, leaving its alpha alone
+fn blend(pixel: Rgba<u8>, color: Rgb<u8>) -> Rgba<u8> {
+    let mix = |channel: usize| ((pixel[channel] as u16 + color[channel] as u16) / 2) as u8;
+
+    Rgba([mix(0), mix(1), mix(2), pixel[3]])
+}
+
+/// Writes whole frames as a looping animated PNG, every frame stays up for its delay in
+/// milliseconds
+pub fn write_frames(
+    path: &Path,
+    width: u32,
+    height: u32,
+    frames: impl ExactSizeIterator<Item = RgbaImage>,
+    delays: &[u16],
+) -> Result<(), String> {
+    let file = File::create(path).map_err(|e| e.to_string())?;
+    let mut encoder = png::Encoder::new(BufWriter::new(file), width, height);
+
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder
+        .set_animated(frames.len() as u32, 0)
+        .and_then(|_| encoder.set_blend_op(png::BlendOp::Source))
+        .and_then(|_| encoder.set_dispose_op(png::DisposeOp::None))
+        .map_err(|e| e.to_string())?;
+
+    let mut writer = encoder.write_header().map_err(|e| e.to_string())?;
+
+    for (frame, delay) in frames.zip(delays) {
+        writer
+            .set_frame_delay(*delay, 1000)
+            .and_then(|_| writer.write_image_data(&frame))
+            .map_err(|e| e.to_string())?;
+    }
+
+    writer.finish().map_err(|e| e.to_string())
+}
+
+/// Writes what's needed to find out why a generation failed into `dir`: the partial
+/// output, heatmaps of what's left per cell and of where contradictions happened, and
+/// a report.json with the seed, the last contradiction and the last collapses.
+pub fn write_failure_report(
+    dir: &Path,
+    wave: &Wave<Tile<Sprite>>,
+    tile_width: u32,
+    tile_height: u32,
+    reason: &str,
+) -> Result<(), String> {
+    fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+
+    let save = |image: RgbaImage, name: &str| OutputFormat::Png.save(image, &dir.join(name), 100);
+    let style = CellStyle::new(Palette::Heat);
+
+    save(Tile::compose(&wave.grid, tile_width, tile_height), "partial.png")?;
+
+    let entropy = heatmap(&wave.grid, tile_width, tile_height, |cell| match cell.entropy() {
+        0 => style.contradiction(),
+        entropy => style.gradient(entropy as f32 / cell.base_entropy() as f32),
+    });
+
+    save(entropy, "entropy.png")?;
+
+    let contradictions = wave.contradictions();
+    let most = contradictions.iter().map(|(_, _, count)| *count).max().unwrap_or(0).max(1);
+    let contradictions = heatmap(contradictions, tile_width, tile_height, |count| {
+        style.gradient(*count as f32 / most as f32)
+    });
+
+    save(contradictions, "contradictions.png")?;
+
+    let history: Vec<_> = wave
+        .collapse_order()
+        .rev()
+        .take(REPORT_HISTORY)
+        .map(|(position, explicit)| json!({ "x": position.x, "y": position.y, "explicit": explicit }))
+        .collect();
+    let progress = wave.progress();
+    let report = json!({
+        "reason": reason,
+        "seed": wave.seed(),
+        "width": wave.grid.width(),
+        "height": wave.grid.height(),
+        "collapsed": progress.collapsed,
+        "rollbacks": progress.rollbacks,
+        "last_contradiction": wave
+            .last_contradiction()
+            .map(|position| json!({ "x": position.x, "y": position.y })),
+        // newest first
+        "history": history,
+    });
+    let file = File::create(dir.join("report.json")).map_err(|e| e.to_string())?;
+
+    serde_json::to_writer_pretty(BufWriter::new(file), &report).map_err(|e| e.to_string())
+}
+
+/// Image with a cell of `cell_width` by `cell_height` pixels in the color of every value
+fn heatmap<T, F>(grid: &Grid<T>, cell_width: u32, cell_height: u32, color: F) -> RgbaImage
+where
+    T: Clone,
+    F: Fn(&T) -> Rgb<u8>,
+{
+    let mut image = RgbaImage::new(grid.width() as u32 * cell_width, grid.height() as u32 * cell_height);
+
+    for (x, y, value) in grid {
+        let [r, g, b] = color(value).0;
+
+        for dy in 0..cell_height {
+            for dx in 0..cell_width {
+                image.put_pixel(x as u32 * cell_width + dx, y as u32 * cell_height + dy, Rgba([r, g, b, 255]));
+            }
+        }
+    }
+
+    image
+}
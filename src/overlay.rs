@@ -0,0 +1,190 @@
+use std::ops::Range;
+
+use image::{Pixel, Rgba, RgbaImage};
+
+use crate::grid::Grid;
+use crate::sprite::Sprite;
+use crate::superstate::SuperState;
+use crate::tile::Tile;
+
+const GRID_LINE: Rgba<u8> = Rgba([0, 0, 0, 96]);
+const LABEL: Rgba<u8> = Rgba([255, 255, 255, 255]);
+const LABEL_BACKGROUND: Rgba<u8> = Rgba([0, 0, 0, 160]);
+
+/// 3x5 glyphs, one bit per pixel from the top left, row by row. Letters are upper case
+/// only, lower case is drawn with the same glyphs.
+fn glyph(c: char) -> u16 {
+    match c.to_ascii_uppercase() {
+        '0' => 0b111_101_101_101_111,
+        '1' => 0b010_110_010_010_111,
+        '2' => 0b111_001_111_100_111,
+        '3' => 0b111_001_111_001_111,
+        '4' => 0b101_101_111_001_001,
+        '5' => 0b111_100_111_001_111,
+        '6' => 0b111_100_111_101_111,
+        '7' => 0b111_001_001_001_001,
+        '8' => 0b111_101_111_101_111,
+        '9' => 0b111_101_111_001_111,
+        'A' => 0b010_101_111_101_101,
+        'B' => 0b110_101_110_101_110,
+        'C' => 0b011_100_100_100_011,
+        'D' => 0b110_101_101_101_110,
+        'E' => 0b111_100_110_100_111,
+        'F' => 0b111_100_110_100_100,
+        'G' => 0b011_100_101_101_011,
+        'H' => 0b101_101_111_101_101,
+        'I' => 0b111_010_010_010_111,
+        'J' => 0b001_001_001_101_010,
+        'K' => 0b101_101_110_101_101,
+        'L' => 0b100_100_100_100_111,
+        'M' => 0b101_111_111_101_101,
+        'N' => 0b110_101_101_101_101,
+        'O' => 0b010_101_101_101_010,
+        'P' => 0b110_101_110_100_100,
+        'Q' => 0b010_101_101_110_011,
+        'R' => 0b110_101_110_101_101,
+        'S' => 0b011_100_010_001_110,
+        'T' => 0b111_010_010_010_010,
+        'U' => 0b101_101_101_101_111,
+        'V' => 0b101_101_101_101_010,
+        'W' => 0b101_101_111_111_101,
+        'X' => 0b101_101_010_101_101,
+        'Y' => 0b101_101_010_010_010,
+        'Z' => 0b111_001_010_100_111,
+        '.' => 0b000_000_000_000_010,
+        ':' => 0b000_010_000_010_000,
+        '/' => 0b001_001_010_100_100,
+        '%' => 0b101_001_010_100_101,
+        '-' => 0b000_000_111_000_000,
+        // anything else is left blank
+        _ => 0,
+    }
+}
+
+/// Debugging aids drawn over the output, labels are the dense tile indexes (tiles
+/// ordered by id) as used by the live view, the server and the python bindings
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Overlay {
+    pub grid_lines: bool,
+    pub labels: bool,
+}
+
+impl Overlay {
+    pub fn apply(
+        &self,
+        image: &mut RgbaImage,
+        grid: &Grid<SuperState<Tile<Sprite>>>,
+        tile_width: u32,
+        tile_height: u32,
+    ) {
+        self.apply_rows(image, grid, 0..grid.height(), tile_width, tile_height);
+    }
+
+    /// Same as `apply` for an image of `rows` only, as drawn by `Tile::compose_rows`
+    pub fn apply_rows(
+        &self,
+        image: &mut RgbaImage,
+        grid: &Grid<SuperState<Tile<Sprite>>>,
+        rows: Range<usize>,
+        tile_width: u32,
+        tile_height: u32,
+    ) {
+        if self.grid_lines {
+            for x in (tile_width..image.width()).step_by(tile_width as usize) {
+                for y in 0..image.height() {
+                    image.get_pixel_mut(x, y).blend(&GRID_LINE);
+                }
+            }
+
+            // the first row of the band gets a line unless it's the first of the grid
+            let first = match rows.start {
+                0 => tile_height,
+                _ => 0,
+            };
+
+            for y in (first..image.height()).step_by(tile_height as usize) {
+                for x in 0..image.width() {
+                    image.get_pixel_mut(x, y).blend(&GRID_LINE);
+                }
+            }
+        }
+
+        if self.labels {
+            for y in rows.clone() {
+                for x in 0..grid.width() {
+                    let Some(label) = grid
+                        .get(x, y)
+                        .and_then(|cell| Text::label(cell, tile_width, tile_height))
+                    else {
+                        continue;
+                    };
+                    let left = x as u32 * tile_width;
+                    let top = (y - rows.start) as u32 * tile_height;
+
+                    for (dx, dy) in label.background() {
+                        image.get_pixel_mut(left + dx, top + dy).blend(&LABEL_BACKGROUND);
+                    }
+
+                    for (dx, dy) in label.pixels() {
+                        image.put_pixel(left + dx, top + dy, LABEL);
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// A line of text in the 3x5 font on a dark background, scaled up by `scale`. Offsets
+/// are relative to the top left of the background.
+pub struct Text {
+    glyphs: Vec<u16>,
+    scale: u32,
+}
+
+impl Text {
+    pub fn new(text: &str, scale: u32) -> Self {
+        Self {
+            glyphs: text.chars().map(glyph).collect(),
+            scale: scale.max(1),
+        }
+    }
+
+    /// Tile index of a collapsed cell, scaled up for larger tiles. None when the cell
+    /// isn't collapsed or the label doesn't fit in the tile.
+    pub fn label(cell: &SuperState<Tile<Sprite>>, tile_width: u32, tile_height: u32) -> Option<Self> {
+        cell.collapsed()?;
+
+        let label = Self::new(&cell.indexes()[0].to_string(), tile_height / 16);
+        let (width, height) = label.size();
+
+        (width <= tile_width && height <= tile_height).then_some(label)
+    }
+
+    pub fn size(&self) -> (u32, u32) {
+        (
+            (self.glyphs.len() as u32 * 4 + 1) * self.scale,
+            7 * self.scale,
+        )
+    }
+
+    pub fn background(&self) -> impl Iterator<Item = (u32, u32)> {
+        let (width, height) = self.size();
+
+        (0..height).flat_map(move |y| (0..width).map(move |x| (x, y)))
+    }
+
+    pub fn pixels(&self) -> impl Iterator<Item = (u32, u32)> + '_ {
+        let scale = self.scale;
+
+        self.glyphs.iter().enumerate().flat_map(move |(position, glyph)| {
+            (0..15)
+                .filter(move |bit| glyph & (1 << (14 - bit)) != 0)
+                .flat_map(move |bit| {
+                    let x = (position as u32 * 4 + 1 + bit % 3) * scale;
+                    let y = (1 + bit / 3) * scale;
+
+                    (0..scale).flat_map(move |dy| (0..scale).map(move |dx| (x + dx, y + dy)))
+                })
+        })
+    }
+}
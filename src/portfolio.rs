@@ -0,0 +1,71 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::thread;
+
+use tracing::debug;
+
+use crate::superstate::Collapsable;
+use crate::wave::Wave;
+
+/// Runs every wave on its own thread and returns the index and wave of the first one
+/// to finish, the others stop at their next tick. Waves usually differ only by seed,
+/// one of them tends to get through a contradiction-prone tileset a lot sooner.
+///
+/// `limits` makes the check every wave runs after each of its ticks, a wave gives up
+/// when its check fails. Once all of them gave up, Err holds every wave in order along
+/// with the reason it gave up.
+#[allow(clippy::type_complexity)]
+pub fn solve_first<T, L, C, E>(waves: Vec<Wave<T>>, limits: L) -> Result<(usize, Wave<T>), Vec<(Wave<T>, E)>>
+where
+    T: Collapsable,
+    L: Fn() -> C + Sync,
+    C: FnMut(&mut Wave<T>) -> Result<(), E>,
+    E: Send,
+{
+    let finished = AtomicBool::new(false);
+    let winner = Mutex::new(None);
+    let failures = Mutex::new(Vec::new());
+
+    thread::scope(|scope| {
+        for (index, mut wave) in waves.into_iter().enumerate() {
+            let finished = &finished;
+            let winner = &winner;
+            let failures = &failures;
+            let limits = &limits;
+
+            scope.spawn(move || {
+                let mut check = limits();
+
+                while !wave.done() {
+                    if finished.load(Ordering::Relaxed) {
+                        debug!("Wave {index} stopped with {} cells left", wave.remaining());
+                        return;
+                    }
+
+                    wave.tick();
+
+                    if let Err(reason) = check(&mut wave) {
+                        debug!("Wave {index} gave up with {} cells left", wave.remaining());
+                        failures.lock().unwrap().push((index, wave, reason));
+                        return;
+                    }
+                }
+
+                if !finished.swap(true, Ordering::Relaxed) {
+                    *winner.lock().unwrap() = Some((index, wave));
+                }
+            });
+        }
+    });
+
+    match winner.into_inner().unwrap() {
+        Some(winner) => Ok(winner),
+        None => {
+            let mut failures = failures.into_inner().unwrap();
+
+            failures.sort_by_key(|(index, _, _)| *index);
+
+            Err(failures.into_iter().map(|(_, wave, reason)| (wave, reason)).collect())
+        }
+    }
+}
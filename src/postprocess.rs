@@ -0,0 +1,207 @@
+//! Steps run on the composed output before it's saved, so the usual pixel art touches
+//! don't need a second tool. Anything implementing `PostProcess` can be run, `Filter`
+//! holds the built in ones the command line offers.
+
+use std::collections::HashMap;
+use std::str::FromStr;
+
+use image::imageops::colorops::ColorMap;
+use image::{Rgba, RgbaImage};
+
+/// Changes the composed output in place
+pub trait PostProcess {
+    fn apply(&self, image: &mut RgbaImage);
+}
+
+impl<F: Fn(&mut RgbaImage)> PostProcess for F {
+    fn apply(&self, image: &mut RgbaImage) {
+        self(image)
+    }
+}
+
+/// Built in post processing steps
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Filter {
+    /// Reduces the colors to a palette of at most this many, picked by median cut
+    Quantize(usize),
+    /// Same as `Quantize` with Floyd-Steinberg dithering to hide the banding
+    Dither(usize),
+    /// Draws the color on transparent pixels next to opaque ones
+    Outline(Rgba<u8>),
+}
+
+impl Filter {
+    pub const NAMES: [&'static str; 3] = ["quantize", "dither", "outline"];
+}
+
+impl FromStr for Filter {
+    type Err = String;
+
+    /// Parses `quantize[:colors]`, `dither[:colors]` or `outline[:rrggbb]`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (name, argument) = match s.split_once(':') {
+            Some((name, argument)) => (name, Some(argument)),
+            None => (s, None),
+        };
+        let colors = || match argument.map(str::parse::<usize>) {
+            None => Ok(16),
+            Some(Ok(colors @ 2..=256)) => Ok(colors),
+            Some(_) => Err(format!("invalid color count in {s}, expected 2 to 256")),
+        };
+
+        match name.to_lowercase().as_str() {
+            "quantize" => Ok(Filter::Quantize(colors()?)),
+            "dither" => Ok(Filter::Dither(colors()?)),
+            "outline" => Ok(Filter::Outline(match argument {
+                Some(color) => crate::style::parse_color(color)?,
+                None => Rgba([0, 0, 0, 255]),
+            })),
+            _ => Err(format!("Unknown filter {s}, expected one of {:?}", Filter::NAMES)),
+        }
+    }
+}
+
+impl PostProcess for Filter {
+    fn apply(&self, image: &mut RgbaImage) {
+        match *self {
+            Filter::Quantize(colors) => {
+                let palette = Palette::median_cut(image, colors);
+
+                for pixel in image.pixels_mut() {
+                    palette.map_color(pixel);
+                }
+            }
+            Filter::Dither(colors) => {
+                let palette = Palette::median_cut(image, colors);
+
+                image::imageops::dither(image, &palette);
+            }
+            Filter::Outline(color) => outline(image, color),
+        }
+    }
+}
+
+fn outline(image: &mut RgbaImage, color: Rgba<u8>) {
+    let (width, height) = image.dimensions();
+    let opaque = |x: u32, y: u32| image.get_pixel(x, y)[3] > 0;
+    let edges: Vec<(u32, u32)> = image
+        .enumerate_pixels()
+        .filter(|(x, y, pixel)| {
+            pixel[3] == 0
+                && ((*x > 0 && opaque(x - 1, *y))
+                    || (*y > 0 && opaque(*x, y - 1))
+                    || (x + 1 < width && opaque(x + 1, *y))
+                    || (y + 1 < height && opaque(*x, y + 1)))
+        })
+        .map(|(x, y, _)| (x, y))
+        .collect();
+
+    for (x, y) in edges {
+        image.put_pixel(x, y, color);
+    }
+}
+
+/// Colors an image is reduced to, alpha is left as it is
+struct Palette {
+    colors: Vec<[u8; 3]>,
+}
+
+impl Palette {
+    /// Splits the colors of the opaque pixels at the median of their widest channel
+    /// until there are `count` groups, every group becomes its average color
+    fn median_cut(image: &RgbaImage, count: usize) -> Self {
+        let mut histogram: HashMap<[u8; 3], u32> = HashMap::new();
+
+        for pixel in image.pixels().filter(|pixel| pixel[3] > 0) {
+            *histogram.entry([pixel[0], pixel[1], pixel[2]]).or_default() += 1;
+        }
+
+        let mut boxes: Vec<Vec<([u8; 3], u32)>> = vec![histogram.into_iter().collect()];
+
+        while boxes.len() < count {
+            let Some((index, channel)) = boxes
+                .iter()
+                .enumerate()
+                .filter(|(_, colors)| colors.len() > 1)
+                .map(|(index, colors)| (index, widest(colors)))
+                .max_by_key(|(_, (_, range))| *range)
+                .map(|(index, (channel, _))| (index, channel))
+            else {
+                break;
+            };
+            let mut colors = boxes.swap_remove(index);
+
+            colors.sort_unstable_by_key(|(color, _)| color[channel]);
+
+            let total: u32 = colors.iter().map(|(_, count)| count).sum();
+            let mut seen = 0;
+            let split = colors
+                .iter()
+                .position(|(_, count)| {
+                    seen += count;
+                    seen * 2 >= total
+                })
+                .unwrap()
+                .clamp(1, colors.len() - 1);
+            let upper = colors.split_off(split);
+
+            boxes.push(colors);
+            boxes.push(upper);
+        }
+
+        let colors = boxes
+            .iter()
+            .filter(|colors| !colors.is_empty())
+            .map(|colors| {
+                let total: u64 = colors.iter().map(|(_, count)| *count as u64).sum();
+
+                [0, 1, 2].map(|channel| {
+                    let sum: u64 = colors.iter().map(|(color, count)| color[channel] as u64 * *count as u64).sum();
+
+                    (sum / total) as u8
+                })
+            })
+            .collect();
+
+        Self { colors }
+    }
+}
+
+/// Channel with the largest spread of values and how large it is
+fn widest(colors: &[([u8; 3], u32)]) -> (usize, u8) {
+    (0..3)
+        .map(|channel| {
+            let values = colors.iter().map(|(color, _)| color[channel]);
+            let range = values.clone().max().unwrap() - values.min().unwrap();
+
+            (channel, range)
+        })
+        .max_by_key(|(_, range)| *range)
+        .unwrap()
+}
+
+impl ColorMap for Palette {
+    type Color = Rgba<u8>;
+
+    fn index_of(&self, color: &Rgba<u8>) -> usize {
+        let distance = |candidate: &[u8; 3]| -> u32 {
+            (0..3).map(|channel| (candidate[channel] as i32 - color[channel] as i32).pow(2) as u32).sum()
+        };
+
+        (0..self.colors.len()).min_by_key(|index| distance(&self.colors[*index])).unwrap_or(0)
+    }
+
+    fn lookup(&self, index: usize) -> Option<Rgba<u8>> {
+        self.colors.get(index).map(|[r, g, b]| Rgba([*r, *g, *b, 255]))
+    }
+
+    fn has_lookup(&self) -> bool {
+        true
+    }
+
+    fn map_color(&self, color: &mut Rgba<u8>) {
+        if let Some([r, g, b]) = self.colors.get(self.index_of(color)) {
+            *color = Rgba([*r, *g, *b, color[3]]);
+        }
+    }
+}
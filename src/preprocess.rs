@@ -0,0 +1,98 @@
+//! Clean up run on an input image before its tiles are extracted. Noisy inputs (photos,
+//! scaled up or compressed pixel art) hardly repeat a tile, which makes for a huge
+//! tileset that barely connects. Flattening, downscaling and quantizing them first
+//! brings the tiles that only differ by noise back together.
+
+use image::{DynamicImage, Rgb, Rgba, RgbaImage};
+
+use crate::postprocess::{Filter, PostProcess};
+
+/// Transforms for an input image, run in the order of the fields
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Preprocess {
+    /// Draws the image over this color so no pixel is left partly transparent
+    pub background: Option<Rgb<u8>>,
+    /// Averages every square of this many pixels into one, 1 leaves the size alone
+    pub downscale: u32,
+    /// Reduces the colors to a palette of at most this many
+    pub colors: Option<usize>,
+}
+
+impl Default for Preprocess {
+    fn default() -> Self {
+        Self {
+            background: None,
+            downscale: 1,
+            colors: None,
+        }
+    }
+}
+
+impl Preprocess {
+    /// Whether `apply` would leave images as they are
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    pub fn apply(&self, image: &DynamicImage) -> DynamicImage {
+        let mut image = image.to_rgba8();
+
+        if let Some(background) = self.background {
+            flatten(&mut image, background);
+        }
+
+        if self.downscale > 1 {
+            image = downscale(&image, self.downscale);
+        }
+
+        if let Some(colors) = self.colors {
+            Filter::Quantize(colors).apply(&mut image);
+        }
+
+        DynamicImage::ImageRgba8(image)
+    }
+}
+
+/// Blends every pixel over `background`, leaving the image opaque
+pub fn flatten(image: &mut RgbaImage, background: Rgb<u8>) {
+    for pixel in image.pixels_mut() {
+        let alpha = pixel[3] as u32;
+        let blend = |channel: usize| ((pixel[channel] as u32 * alpha + background[channel] as u32 * (255 - alpha)) / 255) as u8;
+
+        *pixel = Rgba([blend(0), blend(1), blend(2), 255]);
+    }
+}
+
+/// Averages every `factor` by `factor` square of pixels into one, pixels past the last
+/// whole square are dropped. Colors are weighted by their alpha so transparent pixels
+/// don't darken their neighbors.
+pub fn downscale(image: &RgbaImage, factor: u32) -> RgbaImage {
+    let area = (factor * factor) as u64;
+
+    RgbaImage::from_fn(image.width() / factor, image.height() / factor, |x, y| {
+        let mut sums = [0u64; 4];
+
+        for dy in 0..factor {
+            for dx in 0..factor {
+                let pixel = image.get_pixel(x * factor + dx, y * factor + dy);
+                let alpha = pixel[3] as u64;
+
+                for channel in 0..3 {
+                    sums[channel] += pixel[channel] as u64 * alpha;
+                }
+
+                sums[3] += alpha;
+            }
+        }
+
+        match sums[3] {
+            0 => Rgba([0, 0, 0, 0]),
+            alpha => Rgba([
+                (sums[0] / alpha) as u8,
+                (sums[1] / alpha) as u8,
+                (sums[2] / alpha) as u8,
+                (alpha / area) as u8,
+            ]),
+        }
+    })
+}
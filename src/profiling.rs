@@ -0,0 +1,178 @@
+//! Phase timers for the `profiling` feature. Without the feature timers compile down to
+//! nothing, so they can stay in hot paths.
+
+#[cfg(feature = "profiling")]
+use {
+    enum_map::{Enum, EnumMap},
+    tracing::{error, info},
+    serde_json::json,
+    std::fs::File,
+    std::io::BufWriter,
+    std::path::{Path, PathBuf},
+    std::sync::Mutex,
+    std::time::{Duration, Instant},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "profiling", derive(Enum))]
+pub enum Phase {
+    Extraction,
+    Propagation,
+    Selection,
+    Collapse,
+    Rollback,
+    Rendering,
+}
+
+#[cfg(feature = "profiling")]
+impl Phase {
+    /// Phases that run often enough that only their totals are kept
+    fn counted_only(&self) -> bool {
+        matches!(self, Phase::Propagation | Phase::Selection | Phase::Collapse)
+    }
+}
+
+#[cfg(feature = "profiling")]
+struct Profile {
+    origin: Instant,
+    totals: EnumMap<Phase, (usize, Duration)>,
+    spans: Vec<(Phase, Duration, Duration)>,
+}
+
+#[cfg(feature = "profiling")]
+static PROFILE: Mutex<Option<Profile>> = Mutex::new(None);
+
+/// Where `finish` writes the chrome trace
+#[cfg(feature = "profiling")]
+static TRACE: Mutex<Option<PathBuf>> = Mutex::new(None);
+
+/// Records the time between its creation and drop against a phase.
+/// Nested timers are inclusive, a rollback during propagation counts towards both.
+#[must_use]
+pub struct Timer {
+    #[cfg(feature = "profiling")]
+    phase: Phase,
+    #[cfg(feature = "profiling")]
+    start: Instant,
+}
+
+#[inline]
+pub fn time(phase: Phase) -> Timer {
+    #[cfg(not(feature = "profiling"))]
+    let _ = phase;
+
+    Timer {
+        #[cfg(feature = "profiling")]
+        phase,
+        #[cfg(feature = "profiling")]
+        start: Instant::now(),
+    }
+}
+
+impl Drop for Timer {
+    #[inline]
+    fn drop(&mut self) {
+        #[cfg(feature = "profiling")]
+        {
+            let elapsed = self.start.elapsed();
+            let mut profile = PROFILE.lock().unwrap();
+            let profile = profile.get_or_insert_with(|| Profile {
+                origin: self.start,
+                totals: EnumMap::default(),
+                spans: Vec::new(),
+            });
+
+            let total = &mut profile.totals[self.phase];
+
+            total.0 += 1;
+            total.1 += elapsed;
+
+            if !self.phase.counted_only() {
+                let offset = self.start.saturating_duration_since(profile.origin);
+
+                profile.spans.push((self.phase, offset, elapsed));
+            }
+        }
+    }
+}
+
+/// Logs the per-phase totals and writes them, together with the spans of the less
+/// frequent phases, as a chrome tracing file (chrome://tracing or ui.perfetto.dev)
+#[cfg(feature = "profiling")]
+pub fn write_chrome_trace(path: &Path) -> std::io::Result<()> {
+    let profile = PROFILE.lock().unwrap();
+    let profile = match profile.as_ref() {
+        Some(profile) => profile,
+        None => return Ok(()),
+    };
+
+    let events: Vec<_> = profile
+        .spans
+        .iter()
+        .map(|(phase, offset, duration)| {
+            json!({
+                "name": format!("{phase:?}"),
+                "ph": "X",
+                "pid": 0,
+                "tid": 0,
+                "ts": offset.as_micros() as u64,
+                "dur": duration.as_micros() as u64,
+            })
+        })
+        .collect();
+
+    let mut totals = serde_json::Map::new();
+
+    for (phase, (count, duration)) in &profile.totals {
+        info!("{phase:?}: {count} calls, {duration:?}");
+
+        totals.insert(
+            format!("{phase:?}"),
+            json!({ "calls": count, "micros": duration.as_micros() as u64 }),
+        );
+    }
+
+    let writer = BufWriter::new(File::create(path)?);
+    let trace = json!({
+        "traceEvents": events,
+        "displayTimeUnit": "ms",
+        "otherData": totals,
+    });
+
+    serde_json::to_writer(writer, &trace)?;
+
+    Ok(())
+}
+
+/// Writes the chrome trace when dropped, so it's there however the run ends
+#[cfg(feature = "profiling")]
+#[must_use]
+pub struct TraceGuard;
+
+#[cfg(feature = "profiling")]
+impl Drop for TraceGuard {
+    fn drop(&mut self) {
+        finish();
+    }
+}
+
+/// Has the chrome trace written to `path` once the guard is dropped or `finish` is called
+#[cfg(feature = "profiling")]
+pub fn trace_to(path: &Path) -> TraceGuard {
+    *TRACE.lock().unwrap() = Some(path.to_owned());
+
+    TraceGuard
+}
+
+/// Writes the chrome trace given to `trace_to` unless it already was, for exits that
+/// skip the guard
+pub fn finish() {
+    #[cfg(feature = "profiling")]
+    if let Some(path) = TRACE.lock().unwrap().take() {
+        info!("Writing profile to {}", path.display());
+
+        if let Err(e) = write_chrome_trace(&path) {
+            error!("Failed to write profile to {}: {e}", path.display());
+        }
+    }
+}
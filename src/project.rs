@@ -0,0 +1,130 @@
+use std::ffi::OsStr;
+use std::fs::File;
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+
+use clap::{ArgAction, ArgMatches, Command};
+use serde_json::{json, Map, Value};
+
+/// Options that only make sense on the command line, they're left out of the dump
+const SKIPPED: [&str; 3] = ["print_config", "project", "completions"];
+
+/// Cargo features and whether this build has them, options of missing features don't
+/// exist in the build
+const FEATURES: [(&str, bool); 12] = [
+    ("image", cfg!(feature = "image")),
+    ("sdl2", cfg!(feature = "sdl2")),
+    ("threaded", cfg!(feature = "threaded")),
+    ("profiling", cfg!(feature = "profiling")),
+    ("serve", cfg!(feature = "serve")),
+    ("live", cfg!(feature = "live")),
+    ("tui", cfg!(feature = "tui")),
+    ("net", cfg!(feature = "net")),
+    ("exact", cfg!(feature = "exact")),
+    ("async", cfg!(feature = "async")),
+    ("noise", cfg!(feature = "noise")),
+    ("samples", cfg!(feature = "samples")),
+];
+
+/// Path given to `--project`, looked up before parsing as the file can fill in
+/// required options
+pub fn path() -> Option<PathBuf> {
+    let mut args = std::env::args_os().skip(1);
+
+    while let Some(arg) = args.next() {
+        if arg == "--project" {
+            return args.next().map(PathBuf::from);
+        }
+
+        if let Some(path) = arg.to_str().and_then(|arg| arg.strip_prefix("--project=")) {
+            return Some(PathBuf::from(path));
+        }
+    }
+
+    std::env::var_os("WFC_PROJECT").map(PathBuf::from)
+}
+
+/// Every option of `command` as it was resolved from the command line, the environment,
+/// a project file or its default, keyed by the option's name in snake case. Options
+/// that weren't given are null, a missing seed stays random.
+pub fn dump(command: &Command, matches: &ArgMatches) -> Value {
+    let mut options = Map::new();
+
+    for arg in command.get_arguments() {
+        let id = arg.get_id().as_str();
+
+        if SKIPPED.contains(&id) {
+            continue;
+        }
+
+        let value = match arg.get_action() {
+            ArgAction::SetTrue => Value::Bool(matches.get_flag(id)),
+            ArgAction::Append => match matches.get_raw(id) {
+                Some(values) => values.map(lossy).collect(),
+                None => Value::Array(Vec::new()),
+            },
+            ArgAction::Set => match matches.get_raw(id).and_then(|mut values| values.next()) {
+                Some(value) => lossy(value),
+                None => Value::Null,
+            },
+            // help, version and the -v/-q counts
+            _ => continue,
+        };
+
+        options.insert(id.to_string(), value);
+    }
+
+    let features: Vec<&str> = FEATURES
+        .iter()
+        .filter(|(_, enabled)| *enabled)
+        .map(|(name, _)| *name)
+        .collect();
+
+    json!({
+        "features": features,
+        "options": options,
+    })
+}
+
+fn lossy(value: &OsStr) -> Value {
+    Value::String(value.to_string_lossy().into_owned())
+}
+
+/// Makes the options of a project file, as written by `dump`, the defaults of
+/// `command`. The environment and the command line still override them. Options the
+/// build doesn't have (e.g. of a missing feature) are skipped.
+pub fn apply(mut command: Command, path: &Path) -> Result<Command, String> {
+    let file = File::open(path).map_err(|e| format!("Failed to open {}: {e}", path.display()))?;
+    let project: Value = serde_json::from_reader(BufReader::new(file))
+        .map_err(|e| format!("Failed to parse {}: {e}", path.display()))?;
+    let options = project
+        .get("options")
+        .and_then(Value::as_object)
+        .ok_or(format!("{} has no options", path.display()))?;
+
+    for (name, value) in options {
+        let known = command.get_arguments().any(|arg| arg.get_id() == name.as_str());
+
+        if !known || SKIPPED.contains(&name.as_str()) {
+            continue;
+        }
+
+        let values: Vec<String> = match value {
+            Value::Null => continue,
+            Value::String(value) => vec![value.clone()],
+            Value::Array(values) => values.iter().map(text).collect(),
+            value => vec![text(value)],
+        };
+
+        command = command.mut_arg(name, |arg| arg.default_values(values));
+    }
+
+    Ok(command)
+}
+
+fn text(value: &Value) -> String {
+    match value {
+        Value::String(value) => value.clone(),
+        value => value.to_string(),
+    }
+}
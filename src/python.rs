@@ -0,0 +1,160 @@
+//! Python bindings, built with the `python` feature (e.g. `maturin develop --features python`)
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::BufReader;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use image::io::Reader as ImageReader;
+use image::GenericImageView;
+use numpy::{PyArray1, PyArray2, PyArrayMethods};
+use pyo3::exceptions::{PyIOError, PyValueError};
+use pyo3::prelude::*;
+
+use crate::grid::{Grid, Size};
+use crate::sprite::Sprite;
+use crate::superstate::{Collapsable, SuperState};
+use crate::tile::{Tile, TileConfig};
+use crate::wave::{Wave, WaveBuilder};
+
+/// Tiles extracted from an input image or config, shared between waves
+#[pyclass(name = "TileSet")]
+#[derive(Clone)]
+pub struct PyTileSet {
+    tiles: Vec<Tile<Sprite>>,
+}
+
+#[pymethods]
+impl PyTileSet {
+    #[staticmethod]
+    fn from_image(path: PathBuf, tile_size: usize) -> PyResult<Self> {
+        let image = ImageReader::open(path)
+            .map_err(|e| PyIOError::new_err(e.to_string()))?
+            .decode()
+            .map_err(|e| PyValueError::new_err(e.to_string()))?;
+
+        Self::new(Tile::from_image(&image, &Size::uniform(tile_size)))
+    }
+
+    #[staticmethod]
+    fn from_config(path: PathBuf) -> PyResult<Self> {
+        let file = File::open(path).map_err(|e| PyIOError::new_err(e.to_string()))?;
+        let configs: Vec<TileConfig> = serde_json::from_reader(BufReader::new(file))
+            .map_err(|e| PyValueError::new_err(e.to_string()))?;
+
+        Self::new(Tile::from_config(&configs))
+    }
+
+    fn __len__(&self) -> usize {
+        self.tiles.len()
+    }
+}
+
+impl PyTileSet {
+    fn new(tiles: Vec<Tile<Sprite>>) -> PyResult<Self> {
+        if tiles.is_empty() {
+            return Err(PyValueError::new_err("No tiles found"));
+        }
+
+        Ok(Self { tiles })
+    }
+}
+
+/// A wave over a tileset, tiles are referred to by their position in the tileset
+#[pyclass(name = "Wave", unsendable)]
+pub struct PyWave {
+    wave: Wave<Tile<Sprite>>,
+    lookup: HashMap<u64, i32>,
+    tile_size: (u32, u32),
+}
+
+#[pymethods]
+impl PyWave {
+    #[new]
+    #[pyo3(signature = (tileset, width, height, seed = 0))]
+    fn new(tileset: &PyTileSet, width: usize, height: usize, seed: u64) -> Self {
+        let base_state = SuperState::new(tileset.tiles.iter().cloned().map(Arc::new).collect());
+        let grid = Grid::new(width, height, &mut |_, _| base_state.clone());
+        let lookup = tileset
+            .tiles
+            .iter()
+            .enumerate()
+            .map(|(index, tile)| (tile.get_id(), index as i32))
+            .collect();
+
+        Self {
+            wave: WaveBuilder::new(grid).seed(seed).build(),
+            lookup,
+            tile_size: tileset.tiles[0].value.image().dimensions(),
+        }
+    }
+
+    /// Propagates pending changes and collapses one more cell
+    fn tick(&mut self) {
+        self.wave.tick();
+    }
+
+    /// Ticks until every cell is collapsed
+    fn run(&mut self) {
+        while !self.wave.done() {
+            self.wave.tick();
+        }
+    }
+
+    fn done(&self) -> bool {
+        self.wave.done()
+    }
+
+    fn remaining(&self) -> usize {
+        self.wave.remaining()
+    }
+
+    fn rollbacks(&self) -> usize {
+        self.wave.rollbacks()
+    }
+
+    /// (x, y, cells narrowed, reach, contradicted) for every tile the solver picked, in
+    /// the order it picked them
+    fn propagation(&self) -> Vec<(usize, usize, usize, usize, bool)> {
+        self.wave
+            .propagation()
+            .iter()
+            .map(|propagation| {
+                let position = propagation.position;
+
+                (position.x, position.y, propagation.cells, propagation.reach, propagation.contradicted)
+            })
+            .collect()
+    }
+
+    /// Tileset index of every cell as a (height, width) array, -1 where not collapsed
+    fn tile_indexes<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyArray2<i32>>> {
+        let grid = &self.wave.grid;
+        let indexes: Vec<i32> = grid
+            .iter()
+            .map(|(_, _, cell)| match cell.collapsed() {
+                Some(tile) => self.lookup[&tile.get_id()],
+                None => -1,
+            })
+            .collect();
+
+        PyArray1::from_vec(py, indexes).reshape([grid.height(), grid.width()])
+    }
+
+    fn save_png(&self, path: PathBuf) -> PyResult<()> {
+        let (tile_width, tile_height) = self.tile_size;
+
+        Tile::compose(&self.wave.grid, tile_width, tile_height)
+            .save(path)
+            .map_err(|e| PyIOError::new_err(e.to_string()))
+    }
+}
+
+#[pymodule]
+fn wave_function_collapse(module: &Bound<'_, PyModule>) -> PyResult<()> {
+    module.add_class::<PyTileSet>()?;
+    module.add_class::<PyWave>()?;
+
+    Ok(())
+}
@@ -0,0 +1,227 @@
+use std::io::{self, Read, Write};
+
+use crate::grid::Position;
+
+const MAGIC: &[u8; 4] = b"WFCR";
+const VERSION: u8 = 1;
+
+/// A single solver action, tiles are referred to by their dense index in the wave
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Event {
+    /// Tiles removed from a cell by propagation
+    Remove(Position, Vec<usize>),
+    /// Explicit collapse to a single tile
+    Collapse(Position, usize),
+    /// A cell ran out of possible tiles
+    Contradiction(Position),
+    /// Explicit collapses undone by a rollback, followed by the resets it caused
+    Rollback(usize),
+    /// A cell was set back to its base state
+    Reset(Position),
+    /// Every cell was set back to its base state
+    ResetAll,
+    /// A cell was set to exactly these tiles, used when restoring a checkpoint
+    Assign(Position, Vec<usize>),
+}
+
+impl Event {
+    fn tag(&self) -> u8 {
+        match self {
+            Event::Remove(..) => 0,
+            Event::Collapse(..) => 1,
+            Event::Contradiction(..) => 2,
+            Event::Rollback(..) => 3,
+            Event::Reset(..) => 4,
+            Event::ResetAll => 5,
+            Event::Assign(..) => 6,
+        }
+    }
+}
+
+/// Dimensions of the recorded wave, replaying requires the same grid and tileset
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Header {
+    pub width: usize,
+    pub height: usize,
+    pub tiles: usize,
+}
+
+/// Appends events to a compact binary log (LEB128 varints behind a one byte tag).
+/// Write errors stop the recording and are returned by `finish`.
+pub struct Recorder {
+    writer: Box<dyn Write + Send>,
+    error: Option<io::Error>,
+}
+
+impl Recorder {
+    pub fn new(writer: Box<dyn Write + Send>) -> Self {
+        Self {
+            writer,
+            error: None,
+        }
+    }
+
+    pub fn header(&mut self, header: Header) {
+        let mut buffer = MAGIC.to_vec();
+
+        buffer.push(VERSION);
+        write_varint(&mut buffer, header.width);
+        write_varint(&mut buffer, header.height);
+        write_varint(&mut buffer, header.tiles);
+
+        self.write(&buffer);
+    }
+
+    pub fn record(&mut self, event: Event) {
+        let mut buffer = vec![event.tag()];
+
+        match &event {
+            Event::Remove(position, tiles) | Event::Assign(position, tiles) => {
+                write_position(&mut buffer, position);
+                write_varint(&mut buffer, tiles.len());
+
+                for tile in tiles {
+                    write_varint(&mut buffer, *tile);
+                }
+            }
+            Event::Collapse(position, tile) => {
+                write_position(&mut buffer, position);
+                write_varint(&mut buffer, *tile);
+            }
+            Event::Contradiction(position) | Event::Reset(position) => {
+                write_position(&mut buffer, position);
+            }
+            Event::Rollback(steps) => write_varint(&mut buffer, *steps),
+            Event::ResetAll => {}
+        }
+
+        self.write(&buffer);
+    }
+
+    pub fn finish(mut self) -> io::Result<()> {
+        match self.error.take() {
+            Some(error) => Err(error),
+            None => self.writer.flush(),
+        }
+    }
+
+    fn write(&mut self, buffer: &[u8]) {
+        if self.error.is_none() {
+            if let Err(error) = self.writer.write_all(buffer) {
+                self.error = Some(error);
+            }
+        }
+    }
+}
+
+/// Reads back a log written by `Recorder`
+pub struct Replay<R: Read> {
+    reader: R,
+    pub header: Header,
+}
+
+impl<R: Read> Replay<R> {
+    pub fn new(mut reader: R) -> io::Result<Self> {
+        let mut magic = [0; 5];
+
+        reader.read_exact(&mut magic)?;
+
+        if &magic[..4] != MAGIC || magic[4] != VERSION {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "Not a wave recording"));
+        }
+
+        let header = Header {
+            width: read_varint(&mut reader)?,
+            height: read_varint(&mut reader)?,
+            tiles: read_varint(&mut reader)?,
+        };
+
+        Ok(Self { reader, header })
+    }
+
+    fn read_event(&mut self, tag: u8) -> io::Result<Event> {
+        let reader = &mut self.reader;
+
+        Ok(match tag {
+            0 | 6 => {
+                let position = read_position(reader)?;
+                let count = read_varint(reader)?;
+                let tiles = (0..count)
+                    .map(|_| read_varint(reader))
+                    .collect::<io::Result<Vec<usize>>>()?;
+
+                if tag == 0 {
+                    Event::Remove(position, tiles)
+                } else {
+                    Event::Assign(position, tiles)
+                }
+            }
+            1 => Event::Collapse(read_position(reader)?, read_varint(reader)?),
+            2 => Event::Contradiction(read_position(reader)?),
+            3 => Event::Rollback(read_varint(reader)?),
+            4 => Event::Reset(read_position(reader)?),
+            5 => Event::ResetAll,
+            _ => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("Unknown event tag {tag}"),
+                ))
+            }
+        })
+    }
+}
+
+impl<R: Read> Iterator for Replay<R> {
+    type Item = io::Result<Event>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut tag = [0];
+
+        match self.reader.read(&mut tag) {
+            Ok(0) => None,
+            Ok(_) => Some(self.read_event(tag[0])),
+            Err(error) => Some(Err(error)),
+        }
+    }
+}
+
+pub(crate) fn write_position(buffer: &mut Vec<u8>, position: &Position) {
+    write_varint(buffer, position.x);
+    write_varint(buffer, position.y);
+}
+
+pub(crate) fn read_position<R: Read>(reader: &mut R) -> io::Result<Position> {
+    Ok(Position::new(read_varint(reader)?, read_varint(reader)?))
+}
+
+pub(crate) fn write_varint(buffer: &mut Vec<u8>, mut value: usize) {
+    while value >= 0x80 {
+        buffer.push((value as u8) | 0x80);
+        value >>= 7;
+    }
+
+    buffer.push(value as u8);
+}
+
+pub(crate) fn read_varint<R: Read>(reader: &mut R) -> io::Result<usize> {
+    let mut value = 0;
+    let mut shift = 0;
+
+    loop {
+        let mut byte = [0];
+
+        reader.read_exact(&mut byte)?;
+
+        if shift >= usize::BITS {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "Varint too long"));
+        }
+
+        value |= ((byte[0] & 0x7f) as usize) << shift;
+
+        if byte[0] & 0x80 == 0 {
+            return Ok(value);
+        }
+
+        shift += 7;
+    }
+}
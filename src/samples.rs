@@ -0,0 +1,246 @@
+//! Ready-made `Collapsable` types that aren't cut from an image, for the `samples`
+//! feature. They're small enough to read as a reference for implementing the trait on
+//! your own types.
+
+use std::sync::Arc;
+
+use enum_map::Enum;
+
+use crate::constraint::Constraint;
+use crate::grid::{Direction, Grid, Neighbors, Position};
+use crate::superstate::{Collapsable, SuperState};
+use crate::wave::{Set, Wave, WaveBuilder};
+
+/// Terrain for a roguelike map, every kind only borders itself and the kinds one step
+/// up or down, so water never touches grass without a beach in between.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Terrain {
+    Water,
+    Sand,
+    Grass,
+    Forest,
+    Mountain,
+}
+
+impl Terrain {
+    pub const ALL: [Terrain; 5] = [
+        Terrain::Water,
+        Terrain::Sand,
+        Terrain::Grass,
+        Terrain::Forest,
+        Terrain::Mountain,
+    ];
+
+    pub fn symbol(&self) -> char {
+        match self {
+            Terrain::Water => '~',
+            Terrain::Sand => '.',
+            Terrain::Grass => '"',
+            Terrain::Forest => 'T',
+            Terrain::Mountain => '^',
+        }
+    }
+}
+
+impl Collapsable for Terrain {
+    type Identifier = u64;
+
+    fn test(&self, neighbors: &Neighbors<Set<Self::Identifier>>) -> bool {
+        let level = *self as u64;
+
+        neighbors
+            .values()
+            .all(|tiles| tiles.is_empty() || tiles.iter().any(|other| other.abs_diff(level) <= 1))
+    }
+
+    fn get_id(&self) -> Self::Identifier {
+        *self as u64
+    }
+
+    fn get_weight(&self) -> f64 {
+        match self {
+            Terrain::Water => 4.0,
+            Terrain::Sand => 1.0,
+            Terrain::Grass => 4.0,
+            Terrain::Forest => 2.0,
+            Terrain::Mountain => 1.0,
+        }
+    }
+
+    fn index(&self) -> Option<usize> {
+        Some(*self as usize)
+    }
+
+    fn universe_size() -> Option<usize> {
+        Some(Terrain::ALL.len())
+    }
+}
+
+/// A pipe or road piece that opens to some of its sides, open sides have to meet open
+/// sides. Every combination of sides is its own tile, the id is a bit per side.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Pipe {
+    sides: u8,
+}
+
+impl Pipe {
+    /// All 16 pieces, from no openings to a crossing
+    pub fn all() -> Vec<Pipe> {
+        (0..16).map(|sides| Pipe { sides }).collect()
+    }
+
+    pub fn opens(&self, direction: Direction) -> bool {
+        self.sides & (1 << direction.into_usize()) != 0
+    }
+
+    /// Box drawing character for the piece
+    pub fn symbol(&self) -> char {
+        match self.sides {
+            0b0000 => ' ',
+            0b0101 => '│',
+            0b1010 => '─',
+            0b0011 => '└',
+            0b0110 => '┌',
+            0b1100 => '┐',
+            0b1001 => '┘',
+            0b0111 => '├',
+            0b1101 => '┤',
+            0b1011 => '┴',
+            0b1110 => '┬',
+            0b1111 => '┼',
+            // dead ends
+            0b0001 => '╵',
+            0b0010 => '╶',
+            0b0100 => '╷',
+            _ => '╴',
+        }
+    }
+}
+
+impl Collapsable for Pipe {
+    type Identifier = u64;
+
+    fn test(&self, neighbors: &Neighbors<Set<Self::Identifier>>) -> bool {
+        neighbors.iter().all(|(direction, tiles)| {
+            let open = self.opens(direction);
+
+            tiles.is_empty()
+                || tiles
+                    .iter()
+                    .any(|other| Pipe { sides: *other as u8 }.opens(direction.invert()) == open)
+        })
+    }
+
+    fn get_id(&self) -> Self::Identifier {
+        self.sides as u64
+    }
+
+    fn get_weight(&self) -> f64 {
+        match self.sides.count_ones() {
+            // mostly straight pieces and corners, the odd crossing
+            2 => 4.0,
+            0 => 2.0,
+            _ => 1.0,
+        }
+    }
+
+    fn index(&self) -> Option<usize> {
+        Some(self.sides as usize)
+    }
+
+    fn universe_size() -> Option<usize> {
+        Some(16)
+    }
+}
+
+/// A digit in a sudoku. Digits don't care about their direct neighbors, the rules of
+/// the puzzle are in `Sudoku`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Digit(pub u8);
+
+impl Collapsable for Digit {
+    type Identifier = u64;
+
+    fn test(&self, _neighbors: &Neighbors<Set<Self::Identifier>>) -> bool {
+        true
+    }
+
+    fn get_id(&self) -> Self::Identifier {
+        self.0 as u64
+    }
+
+    fn get_weight(&self) -> f64 {
+        1.0
+    }
+
+    fn index(&self) -> Option<usize> {
+        Some(self.0 as usize - 1)
+    }
+
+    fn universe_size() -> Option<usize> {
+        Some(9)
+    }
+}
+
+/// Every digit once per row, column and 3x3 box of a 9x9 grid
+#[derive(Debug, Clone, Default)]
+pub struct Sudoku {
+    /// Digits used per row, column and box, a bit per digit
+    rows: [u16; 9],
+    columns: [u16; 9],
+    boxes: [u16; 9],
+}
+
+impl Sudoku {
+    /// A wave for the puzzle in `givens`, 81 characters in row order with a digit for
+    /// every given and a `.` or `0` for every blank. Whitespace is skipped.
+    pub fn wave(givens: &str, seed: u64) -> Result<Wave<Digit>, String> {
+        let cells: Vec<char> = givens.chars().filter(|c| !c.is_whitespace()).collect();
+
+        if cells.len() != 81 {
+            return Err(format!("Expected 81 cells, got {}", cells.len()));
+        }
+
+        let base = SuperState::new((1..=9).map(|digit| Arc::new(Digit(digit))).collect());
+        let mut wave = WaveBuilder::new(Grid::new(9, 9, &mut |_, _| base.clone()))
+            .seed(seed)
+            .constraint(Sudoku::default())
+            .build();
+
+        for (index, cell) in cells.into_iter().enumerate() {
+            match cell {
+                '.' | '0' => {}
+                '1'..='9' => wave.pin(index % 9, index / 9, cell as u64 - '0' as u64)?,
+                _ => return Err(format!("Unexpected {cell:?} in the puzzle")),
+            }
+        }
+
+        Ok(wave)
+    }
+
+    fn used(&self, position: &Position) -> u16 {
+        self.rows[position.y] | self.columns[position.x] | self.boxes[position.y / 3 * 3 + position.x / 3]
+    }
+}
+
+impl Constraint<Digit> for Sudoku {
+    fn filter(&self, position: &Position, candidates: &mut SuperState<Digit>) {
+        let used = self.used(position);
+
+        if candidates.entropy() > 1 {
+            candidates.retain(|digit| used & (1 << digit.0) == 0);
+        }
+    }
+
+    fn on_collapse(&mut self, position: &Position, tile: &Digit) {
+        let bit = 1 << tile.0;
+
+        self.rows[position.y] |= bit;
+        self.columns[position.x] |= bit;
+        self.boxes[position.y / 3 * 3 + position.x / 3] |= bit;
+    }
+
+    fn reset(&mut self) {
+        *self = Self::default();
+    }
+}
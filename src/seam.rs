@@ -0,0 +1,100 @@
+//! Outputs that continue a grid generated earlier, like the next chunk of a world that's
+//! generated as the player walks. The border cells only get tiles that fit against the
+//! edge of the grid next to them, so the two line up without a seam.
+
+use std::hash::Hash;
+
+use enum_map::EnumMap;
+
+use crate::constraint::Constraint;
+use crate::grid::{Direction, Grid, Neighbors, Position};
+use crate::superstate::{Collapsable, SuperState};
+use crate::wave::Set;
+
+/// Keeps the borders of a grid to the tiles that fit against the grids around it. `I`
+/// identifies tiles.
+#[derive(Debug, Clone)]
+pub struct Seams<I> {
+    width: usize,
+    height: usize,
+    /// Tiles just outside every border, from left to right or from top to bottom
+    edges: EnumMap<Direction, Option<Vec<Option<I>>>>,
+}
+
+impl<I> Seams<I>
+where
+    I: Clone + Eq + Hash,
+{
+    /// No borders matched yet for a grid of `width` by `height`
+    pub fn new(width: usize, height: usize) -> Self {
+        Self {
+            width,
+            height,
+            edges: EnumMap::default(),
+        }
+    }
+
+    /// Matches the border in `direction` against `edge`, the tiles of the grid on that
+    /// side that face this one. None, or an edge that's too short, leaves cells free.
+    pub fn edge(mut self, direction: Direction, edge: Vec<Option<I>>) -> Self {
+        self.edges[direction] = Some(edge);
+        self
+    }
+
+    /// Tile just outside the grid in `direction` of `position`, if that's on a matched
+    /// border
+    fn facing(&self, position: &Position, direction: Direction) -> Option<&I> {
+        let edge = self.edges[direction].as_ref()?;
+        let index = match direction {
+            Direction::Up if position.y == 0 => position.x,
+            Direction::Down if position.y + 1 == self.height => position.x,
+            Direction::Left if position.x == 0 => position.y,
+            Direction::Right if position.x + 1 == self.width => position.y,
+            _ => return None,
+        };
+
+        edge.get(index)?.as_ref()
+    }
+}
+
+impl<I, T> Constraint<T> for Seams<I>
+where
+    I: Clone + Ord + Hash + Send,
+    T: Collapsable<Identifier = I>,
+{
+    fn filter(&self, position: &Position, candidates: &mut SuperState<T>) {
+        let mut neighbors: Neighbors<Set<I>> = Default::default();
+        let mut matched = false;
+
+        for (direction, tiles) in &mut neighbors {
+            if let Some(tile) = self.facing(position, direction) {
+                tiles.insert(tile.clone());
+                matched = true;
+            }
+        }
+
+        if matched {
+            candidates.retain(|tile| tile.test(&neighbors));
+        }
+    }
+}
+
+/// Tiles along the border of `grid` in `direction`, for `Seams::edge` of a grid that
+/// lies on that side of it. Cells that aren't collapsed are None.
+pub fn edge<T>(grid: &Grid<SuperState<T>>, direction: Direction) -> Vec<Option<T::Identifier>>
+where
+    T: Collapsable,
+{
+    let (width, height) = (grid.width(), grid.height());
+    let cells: Vec<(usize, usize)> = match direction {
+        Direction::Up => (0..width).map(|x| (x, 0)).collect(),
+        Direction::Down => (0..width).map(|x| (x, height - 1)).collect(),
+        Direction::Left => (0..height).map(|y| (0, y)).collect(),
+        Direction::Right => (0..height).map(|y| (width - 1, y)).collect(),
+    };
+
+    cells
+        .into_iter()
+        .map(|(x, y)| grid.get(x, y).and_then(|cell| cell.collapsed()).map(|tile| tile.get_id()))
+        .collect()
+}
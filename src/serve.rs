@@ -0,0 +1,423 @@
+use std::collections::{HashMap, VecDeque};
+use std::io::{self, Cursor, Read};
+use std::sync::mpsc::{self, Receiver, TrySendError};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use image::{GenericImageView, ImageOutputFormat};
+use rand::rngs::OsRng;
+use rand::Rng;
+use serde_json::{json, Value};
+use clap::builder::RangedU64ValueParser;
+use clap::Args;
+use tiny_http::{Header, Method, Request, Response, Server};
+use tracing::{error, info, warn};
+
+use wave_function_collapse::grid::{Grid, Size};
+use wave_function_collapse::superstate::{Collapsable, SuperState};
+use wave_function_collapse::tile::Tile;
+use wave_function_collapse::wave::WaveBuilder;
+
+/// Largest accepted upload
+const MAX_BODY: usize = 16 * 1024 * 1024;
+/// Largest output grid side, keeps a single request from taking over the server
+const MAX_SIDE: usize = 512;
+/// Most cells the input may be cut into, extraction and rules grow with the square of
+/// the tiles and can't be interrupted by the timeout
+const MAX_INPUT_CELLS: u32 = 4096;
+/// Finished images kept around for /results
+const MAX_RESULTS: usize = 32;
+/// Requests that may wait for a busy worker, per worker, before the server answers 503
+const QUEUE_PER_WORKER: usize = 2;
+
+#[derive(Debug, Args)]
+pub struct ServeOpt {
+    #[arg(long, default_value = "127.0.0.1:8080", help = "Address to listen on")]
+    bind: String,
+
+    #[arg(long, default_value = "60", help = "Give up on a generation after n seconds")]
+    timeout: u64,
+
+    #[arg(
+        long,
+        default_value = "4",
+        value_parser = RangedU64ValueParser::<usize>::new().range(1..),
+        help = "Requests handled at once, a few more wait and the rest get a 503"
+    )]
+    workers: usize,
+}
+
+/// Generated images by id, oldest are dropped first
+#[derive(Default)]
+struct Results {
+    order: VecDeque<u64>,
+    images: HashMap<u64, Vec<u8>>,
+}
+
+impl Results {
+    fn insert(&mut self, image: Vec<u8>) -> u64 {
+        let id = OsRng.gen();
+
+        if self.order.len() >= MAX_RESULTS {
+            if let Some(old) = self.order.pop_front() {
+                self.images.remove(&old);
+            }
+        }
+
+        self.order.push_back(id);
+        self.images.insert(id, image);
+
+        id
+    }
+}
+
+struct Options {
+    tile_size: usize,
+    size: Size,
+    seed: u64,
+    stream: bool,
+}
+
+impl Options {
+    /// Reads `tile_size`, `size`, `seed` and `stream` from a query string
+    fn parse(query: &str) -> Result<Self, String> {
+        let params: HashMap<&str, &str> = query
+            .split('&')
+            .filter_map(|pair| pair.split_once('='))
+            .collect();
+
+        let tile_size = params
+            .get("tile_size")
+            .ok_or("Missing tile_size")?
+            .parse()
+            .map_err(|_| "Invalid tile_size")?;
+        let size: Size = params.get("size").unwrap_or(&"20x20").parse()?;
+        let seed = match params.get("seed") {
+            Some(seed) => seed.parse().map_err(|_| "Invalid seed")?,
+            None => OsRng.gen(),
+        };
+
+        if tile_size == 0 || size.width == 0 || size.height == 0 {
+            return Err("Sizes must be larger than zero".into());
+        }
+
+        if size.width > MAX_SIDE || size.height > MAX_SIDE {
+            return Err(format!("Output is limited to {MAX_SIDE}x{MAX_SIDE}"));
+        }
+
+        Ok(Self {
+            tile_size,
+            size,
+            seed,
+            stream: params.get("stream").map(|v| *v == "1" || *v == "true").unwrap_or(false),
+        })
+    }
+}
+
+/// Serves generations over HTTP:
+///  - `POST /generate?tile_size=14&size=50x50&seed=1` with an image as body, responds with
+///    the seed, the tile index of every cell and where to fetch the PNG
+///  - the same with `stream=1` responds with server-sent progress events instead, ending
+///    with a `done` event holding the same JSON
+///  - `GET /results/<id>.png` returns a generated image
+///
+/// Tile configs aren't accepted, their tiles name image files that would be read from
+/// the server's disk.
+pub fn run(opt: &ServeOpt) {
+    let server = match Server::http(&opt.bind) {
+        Ok(server) => server,
+        Err(e) => {
+            error!("Failed to listen on {}: {e}", opt.bind);
+            return;
+        }
+    };
+
+    info!("Listening on http://{}", opt.bind);
+
+    let results = Arc::new(Mutex::new(Results::default()));
+    let timeout = Duration::from_secs(opt.timeout);
+    let (sender, receiver) = mpsc::sync_channel::<Request>(opt.workers * QUEUE_PER_WORKER);
+    let receiver = Arc::new(Mutex::new(receiver));
+
+    for _ in 0..opt.workers {
+        let results = results.clone();
+        let receiver = receiver.clone();
+
+        thread::spawn(move || loop {
+            // the lock is only held while waiting, the next worker waits once it's taken
+            let Ok(request) = receiver.lock().unwrap().recv() else {
+                return;
+            };
+
+            handle(request, results.clone(), timeout);
+        });
+    }
+
+    for request in server.incoming_requests() {
+        match sender.try_send(request) {
+            Ok(()) => {}
+            Err(TrySendError::Full(request)) => {
+                warn!("All workers busy, turning away {} {}", request.method(), request.url());
+                respond(request, error_response(503, "Too many requests, try again later"));
+            }
+            Err(TrySendError::Disconnected(_)) => {
+                error!("Every worker stopped");
+                return;
+            }
+        }
+    }
+}
+
+fn handle(mut request: Request, results: Arc<Mutex<Results>>, timeout: Duration) {
+    let url = request.url().to_string();
+    let (path, query) = url.split_once('?').unwrap_or((&url, ""));
+
+    info!("{} {}", request.method(), url);
+
+    let response = match (request.method(), path) {
+        (Method::Post, "/generate") => {
+            let options = match Options::parse(query) {
+                Ok(options) => options,
+                Err(e) => return respond(request, error_response(400, &e)),
+            };
+
+            if is_json(&request) {
+                return respond(request, error_response(415, "Only images are accepted, not tile configs"));
+            }
+
+            let body = match read_body(&mut request) {
+                Ok(body) => body,
+                Err(e) => return respond(request, error_response(400, &e)),
+            };
+
+            if options.stream {
+                return stream(request, body, options, results, timeout);
+            }
+
+            match generate(&body, &options, timeout, |_, _| {}) {
+                Ok((png, report)) => json_response(200, finish(report, png, &results)),
+                Err(e) => error_response(422, &e),
+            }
+        }
+        (Method::Get, path) if path.starts_with("/results/") => {
+            let image = path
+                .trim_start_matches("/results/")
+                .trim_end_matches(".png")
+                .parse::<u64>()
+                .ok()
+                .and_then(|id| results.lock().unwrap().images.get(&id).cloned());
+
+            match image {
+                Some(image) => Response::from_data(image)
+                    .with_header(header("Content-Type", "image/png")),
+                None => error_response(404, "No such result"),
+            }
+        }
+        _ => error_response(404, "Not found"),
+    };
+
+    respond(request, response);
+}
+
+fn respond(request: Request, response: Response<Cursor<Vec<u8>>>) {
+    if let Err(e) = request.respond(response) {
+        warn!("Failed to send response: {e}");
+    }
+}
+
+/// Responds with server-sent events, generating on a separate thread that feeds them
+fn stream(
+    request: Request,
+    body: Vec<u8>,
+    options: Options,
+    results: Arc<Mutex<Results>>,
+    timeout: Duration,
+) {
+    let (sender, receiver) = mpsc::channel();
+
+    thread::spawn(move || {
+        let mut last = Instant::now();
+        let result = generate(&body, &options, timeout, |remaining, total| {
+            // a closed connection doesn't stop the generation, the events just go nowhere
+            if last.elapsed() >= Duration::from_millis(100) {
+                last = Instant::now();
+
+                let event = json!({ "remaining": remaining, "total": total });
+                let _ = sender.send(format!("event: progress\ndata: {event}\n\n"));
+            }
+        });
+
+        let _ = sender.send(match result {
+            Ok((png, report)) => {
+                format!("event: done\ndata: {}\n\n", finish(report, png, &results))
+            }
+            Err(e) => format!("event: error\ndata: {}\n\n", json!({ "error": e })),
+        });
+    });
+
+    let reader = EventReader {
+        receiver,
+        buffer: Cursor::new(Vec::new()),
+    };
+    let response = Response::new(
+        200.into(),
+        vec![
+            header("Content-Type", "text/event-stream"),
+            header("Cache-Control", "no-cache"),
+        ],
+        reader,
+        None,
+        None,
+    );
+
+    if let Err(e) = request.respond(response) {
+        warn!("Failed to send response: {e}");
+    }
+}
+
+/// Reads events as they're sent, ends once the sending side is dropped
+struct EventReader {
+    receiver: Receiver<String>,
+    buffer: Cursor<Vec<u8>>,
+}
+
+impl Read for EventReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        loop {
+            let read = self.buffer.read(buf)?;
+
+            if read > 0 || buf.is_empty() {
+                return Ok(read);
+            }
+
+            match self.receiver.recv() {
+                Ok(event) => self.buffer = Cursor::new(event.into_bytes()),
+                Err(_) => return Ok(0),
+            }
+        }
+    }
+}
+
+fn finish(mut report: Value, png: Vec<u8>, results: &Mutex<Results>) -> Value {
+    let id = results.lock().unwrap().insert(png);
+
+    report["png"] = json!(format!("/results/{id}.png"));
+    report
+}
+
+/// Whether the body is declared as JSON, like a tile config
+fn is_json(request: &Request) -> bool {
+    request
+        .headers()
+        .iter()
+        .any(|header| header.field.equiv("Content-Type") && header.value.as_str().starts_with("application/json"))
+}
+
+fn read_body(request: &mut Request) -> Result<Vec<u8>, String> {
+    if request.body_length().unwrap_or(0) > MAX_BODY {
+        return Err(format!("Body is limited to {MAX_BODY} bytes"));
+    }
+
+    let mut body = Vec::new();
+
+    request
+        .as_reader()
+        .take(MAX_BODY as u64 + 1)
+        .read_to_end(&mut body)
+        .map_err(|e| e.to_string())?;
+
+    if body.len() > MAX_BODY {
+        return Err(format!("Body is limited to {MAX_BODY} bytes"));
+    }
+
+    Ok(body)
+}
+
+/// Generates an image from the uploaded sample, returns the encoded PNG and a report
+/// with the seed and the tile index of every cell (row-major, tiles ordered by id).
+/// The timeout covers reading the sample too.
+fn generate<F: FnMut(usize, usize)>(
+    body: &[u8],
+    options: &Options,
+    timeout: Duration,
+    mut progress: F,
+) -> Result<(Vec<u8>, Value), String> {
+    let start = Instant::now();
+    let timed_out = || Err(format!("Gave up after {} seconds", timeout.as_secs()));
+    let image = image::load_from_memory(body).map_err(|e| format!("Invalid image: {e}"))?;
+    let tile_size = options.tile_size as u32;
+
+    if image.width() < tile_size || image.height() < tile_size {
+        return Err("Image is smaller than a single tile".into());
+    }
+
+    if (image.width() / tile_size) * (image.height() / tile_size) > MAX_INPUT_CELLS {
+        return Err(format!("Image is limited to {MAX_INPUT_CELLS} tiles, use a larger tile_size"));
+    }
+
+    let tiles = Tile::from_image(&image, &Size::uniform(options.tile_size));
+
+    if start.elapsed() > timeout {
+        return timed_out();
+    }
+
+    let (tile_width, tile_height) = tiles[0].value.image().dimensions();
+    let base_state = SuperState::new(tiles.iter().cloned().map(Arc::new).collect());
+    let grid = Grid::new(options.size.width, options.size.height, &mut |_, _| {
+        base_state.clone()
+    });
+
+    let mut wave = WaveBuilder::new(grid).seed(options.seed).build();
+
+    while !wave.done() {
+        if start.elapsed() > timeout {
+            return timed_out();
+        }
+
+        wave.tick();
+        progress(wave.remaining(), wave.grid.size());
+    }
+
+    let lookup: HashMap<u64, usize> = tiles
+        .iter()
+        .enumerate()
+        .map(|(index, tile)| (tile.get_id(), index))
+        .collect();
+    let indexes: Vec<Option<usize>> = wave
+        .grid
+        .iter()
+        .map(|(_, _, cell)| cell.collapsed().map(|tile| lookup[&tile.get_id()]))
+        .collect();
+
+    let mut png = Vec::new();
+
+    Tile::compose(&wave.grid, tile_width, tile_height)
+        .write_to(&mut Cursor::new(&mut png), ImageOutputFormat::Png)
+        .map_err(|e| e.to_string())?;
+
+    let report = json!({
+        "seed": options.seed,
+        "width": options.size.width,
+        "height": options.size.height,
+        "tiles": tiles.len(),
+        "rollbacks": wave.rollbacks(),
+        "cells": indexes,
+    });
+
+    Ok((png, report))
+}
+
+fn header(name: &str, value: &str) -> Header {
+    Header::from_bytes(name.as_bytes(), value.as_bytes()).unwrap()
+}
+
+fn json_response(status: u16, value: Value) -> Response<Cursor<Vec<u8>>> {
+    Response::from_string(value.to_string())
+        .with_status_code(status)
+        .with_header(header("Content-Type", "application/json"))
+}
+
+fn error_response(status: u16, message: &str) -> Response<Cursor<Vec<u8>>> {
+    json_response(status, json!({ "error": message }))
+}
@@ -0,0 +1,142 @@
+use std::io::{self, Read, Write};
+
+use crate::grid::Position;
+use crate::recorder::{read_position, read_varint, write_position, write_varint, Header};
+
+const MAGIC: &[u8; 4] = b"WFCS";
+const VERSION: u8 = 1;
+
+/// Everything needed to pick a wave back up later, see `Wave::session`. Tiles are
+/// referred to by their dense index like in recordings, so a session only loads into a
+/// wave with the same grid size and tileset.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Session {
+    pub header: Header,
+    pub seed: u64,
+    /// Seed the solver rng continues from after loading
+    pub(crate) rng_seed: u64,
+    pub(crate) pins: Vec<(Position, usize)>,
+    /// Collapsed cells in the order they collapsed, true for explicit collapses
+    pub(crate) collapsed: Vec<(Position, bool)>,
+    /// Possible tiles of every cell in row-major order
+    pub(crate) cells: Vec<Vec<usize>>,
+}
+
+impl Session {
+    pub fn write<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        let mut buffer = MAGIC.to_vec();
+
+        buffer.push(VERSION);
+        write_varint(&mut buffer, self.header.width);
+        write_varint(&mut buffer, self.header.height);
+        write_varint(&mut buffer, self.header.tiles);
+        buffer.extend(self.seed.to_le_bytes());
+        buffer.extend(self.rng_seed.to_le_bytes());
+        write_varint(&mut buffer, self.pins.len());
+
+        for (position, tile) in &self.pins {
+            write_position(&mut buffer, position);
+            write_varint(&mut buffer, *tile);
+        }
+
+        write_varint(&mut buffer, self.collapsed.len());
+
+        for (position, explicit) in &self.collapsed {
+            write_position(&mut buffer, position);
+            buffer.push(*explicit as u8);
+        }
+
+        for tiles in &self.cells {
+            write_varint(&mut buffer, tiles.len());
+
+            for tile in tiles {
+                write_varint(&mut buffer, *tile);
+            }
+        }
+
+        writer.write_all(&buffer)?;
+        writer.flush()
+    }
+
+    pub fn read<R: Read>(mut reader: R) -> io::Result<Self> {
+        let mut magic = [0; 5];
+
+        reader.read_exact(&mut magic)?;
+
+        if &magic[..4] != MAGIC || magic[4] != VERSION {
+            return Err(invalid("Not a wave session"));
+        }
+
+        let header = Header {
+            width: read_varint(&mut reader)?,
+            height: read_varint(&mut reader)?,
+            tiles: read_varint(&mut reader)?,
+        };
+        let seed = read_u64(&mut reader)?;
+        let rng_seed = read_u64(&mut reader)?;
+        let inside = |position: Position| position.within(header.width, header.height);
+        let tile = |index: usize| match index < header.tiles {
+            true => Ok(index),
+            false => Err(invalid("Tile index out of range")),
+        };
+
+        let mut pins = Vec::new();
+
+        for _ in 0..read_varint(&mut reader)? {
+            let position = read_position(&mut reader)?;
+
+            if !inside(position) {
+                return Err(invalid("Pin outside the grid"));
+            }
+
+            pins.push((position, tile(read_varint(&mut reader)?)?));
+        }
+
+        let mut collapsed = Vec::new();
+
+        for _ in 0..read_varint(&mut reader)? {
+            let position = read_position(&mut reader)?;
+            let mut explicit = [0];
+
+            reader.read_exact(&mut explicit)?;
+
+            if !inside(position) {
+                return Err(invalid("Collapsed cell outside the grid"));
+            }
+
+            collapsed.push((position, explicit[0] != 0));
+        }
+
+        let mut cells = Vec::with_capacity(header.width * header.height);
+
+        for _ in 0..header.width * header.height {
+            let count = read_varint(&mut reader)?.min(header.tiles);
+            let tiles = (0..count)
+                .map(|_| tile(read_varint(&mut reader)?))
+                .collect::<io::Result<Vec<usize>>>()?;
+
+            cells.push(tiles);
+        }
+
+        Ok(Self {
+            header,
+            seed,
+            rng_seed,
+            pins,
+            collapsed,
+            cells,
+        })
+    }
+}
+
+fn read_u64<R: Read>(reader: &mut R) -> io::Result<u64> {
+    let mut bytes = [0; 8];
+
+    reader.read_exact(&mut bytes)?;
+
+    Ok(u64::from_le_bytes(bytes))
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.to_string())
+}
@@ -1,21 +1,90 @@
-use image::{DynamicImage, GenericImageView, Pixel};
+use image::{DynamicImage, Pixel, Rgb};
 use std::hash::{Hash, Hasher};
-use num_traits::cast::ToPrimitive;
 
+/// FNV-1a parameters, for hashes that have to stay the same between builds and Rust
+/// versions, unlike `DefaultHasher`
+pub const FNV_OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
+pub const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+
+/// The value of an image tile: the pixels cut from the input, their average color and
+/// a hash of them, which doubles as the tile id. Built once at extraction, the image
+/// can't be swapped out afterwards so the rest stays in sync with it.
+///
+/// The hash is FNV-1a over the RGBA bytes of the pixels in row order, so ids don't
+/// change between builds, platforms or Rust versions and exported maps stay valid.
+/// Fully transparent pixels hash as transparent black whatever color they hide, so
+/// tiles that look the same are the same tile and every fully transparent tile is one
+/// empty tile.
 #[derive(Debug, Clone)]
 pub struct Sprite {
-    /// Todo either figure out other purposes or phase out struct
-    pub image: DynamicImage,
+    image: DynamicImage,
+    hash: u64,
+    average_color: Rgb<u8>,
+    average_alpha: u8,
 }
 
-impl Hash for Sprite {
-    fn hash<H: Hasher>(&self, state: &mut H) {
-        for pixel in self.image.pixels() {
-            for channel in pixel.2.channels() {
-                if let Some(value) = channel.to_u8() {
-                    state.write_u8(value)
-                }
+impl Sprite {
+    pub fn new(image: DynamicImage) -> Self {
+        let rgba = image.to_rgba8();
+        let mut hash = FNV_OFFSET;
+        let mut sum = [0u64; 3];
+        let mut alpha = 0u64;
+        let count = rgba.pixels().len().max(1) as u64;
+
+        for pixel in rgba.pixels() {
+            let channels: &[u8] = match pixel[3] {
+                0 => &[0; 4],
+                _ => pixel.channels(),
+            };
+
+            for channel in channels {
+                hash = (hash ^ *channel as u64).wrapping_mul(FNV_PRIME);
+            }
+
+            // weighted by alpha so hidden colors don't tint the average
+            for (total, channel) in sum.iter_mut().zip(pixel.to_rgb().channels()) {
+                *total += *channel as u64 * pixel[3] as u64;
             }
+
+            alpha += pixel[3] as u64;
+        }
+
+        Self {
+            hash,
+            average_color: Rgb(sum.map(|total| (total / alpha.max(1)) as u8)),
+            // rounded up, only empty sprites average to 0
+            average_alpha: alpha.div_ceil(count) as u8,
+            image,
         }
     }
-}
\ No newline at end of file
+
+    pub fn image(&self) -> &DynamicImage {
+        &self.image
+    }
+
+    /// Mean of the pixels, for renderers that draw a tile as a single color
+    pub fn average_color(&self) -> Rgb<u8> {
+        self.average_color
+    }
+
+    /// Mean opacity of the pixels, 0 only when every pixel is fully transparent
+    pub fn average_alpha(&self) -> u8 {
+        self.average_alpha
+    }
+
+    /// Whether every pixel is fully transparent, drawing the sprite changes nothing
+    pub fn is_empty(&self) -> bool {
+        self.average_alpha == 0
+    }
+
+    /// Hash of the pixels, equal sprites have equal ids
+    pub fn id(&self) -> u64 {
+        self.hash
+    }
+}
+
+impl Hash for Sprite {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        state.write_u64(self.hash)
+    }
+}
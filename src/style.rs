@@ -0,0 +1,168 @@
+use std::str::FromStr;
+
+use image::{Rgb, Rgba};
+
+use crate::sprite::Sprite;
+use crate::superstate::SuperState;
+use crate::tile::Tile;
+
+/// How uncollapsed cells are colored, from how far along they are
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Palette {
+    /// Dark to light teal
+    #[default]
+    Teal,
+    /// Perceptually uniform and readable with most forms of color blindness
+    Viridis,
+    /// Black through red and yellow to white
+    Heat,
+    /// Average color of the tiles that are still possible
+    Blend,
+}
+
+impl Palette {
+    pub const NAMES: [&'static str; 4] = ["teal", "viridis", "heat", "blend"];
+}
+
+impl FromStr for Palette {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "teal" => Ok(Palette::Teal),
+            "viridis" => Ok(Palette::Viridis),
+            "heat" => Ok(Palette::Heat),
+            "blend" => Ok(Palette::Blend),
+            _ => Err(format!("Unknown palette {s}, expected one of {:?}", Palette::NAMES)),
+        }
+    }
+}
+
+const VIRIDIS: [[u8; 3]; 5] = [
+    [68, 1, 84],
+    [59, 82, 139],
+    [33, 145, 140],
+    [94, 201, 98],
+    [253, 231, 37],
+];
+const HEAT: [[u8; 3]; 4] = [[0, 0, 0], [180, 0, 0], [255, 160, 0], [255, 255, 200]];
+
+/// Cell colors shared by the renderers, collapsed cells get the average color of
+/// their tile for renderers that can't draw the tile itself
+#[derive(Debug, Clone)]
+pub struct CellStyle {
+    palette: Palette,
+    /// Replaces the palette's contradiction color
+    contradiction: Option<Rgb<u8>>,
+}
+
+impl CellStyle {
+    pub fn new(palette: Palette) -> Self {
+        Self {
+            palette,
+            contradiction: None,
+        }
+    }
+
+    pub fn contradiction_color(mut self, color: Rgb<u8>) -> Self {
+        self.contradiction = Some(color);
+        self
+    }
+
+    pub fn palette(&self) -> Palette {
+        self.palette
+    }
+
+    pub fn color(&self, cell: &SuperState<Tile<Sprite>>) -> Rgb<u8> {
+        if let Some(tile) = cell.collapsed() {
+            return self.tile_color(tile);
+        }
+
+        if cell.entropy() == 0 {
+            return self.contradiction();
+        }
+
+        if self.palette == Palette::Blend {
+            return self.blend(cell);
+        }
+
+        let ratio = cell.entropy() as f32 / cell.base_entropy() as f32;
+
+        self.gradient(1.0 - ratio)
+    }
+
+    pub fn tile_color(&self, tile: &Tile<Sprite>) -> Rgb<u8> {
+        tile.value.average_color()
+    }
+
+    pub fn contradiction(&self) -> Rgb<u8> {
+        if let Some(color) = self.contradiction {
+            return color;
+        }
+
+        match self.palette {
+            // red is hard to tell apart from green for many
+            Palette::Viridis => Rgb([255, 255, 255]),
+            _ => Rgb([255, 0, 0]),
+        }
+    }
+
+    /// Color for a cell that is `progress` (0 to 1) of the way to collapsing. Blend has
+    /// no gradient of its own and uses teal.
+    pub fn gradient(&self, progress: f32) -> Rgb<u8> {
+        let progress = progress.clamp(0.0, 1.0);
+
+        match self.palette {
+            Palette::Teal | Palette::Blend => {
+                let value = (255.0 * progress) as u8;
+
+                Rgb([0, value / 3, value / 2])
+            }
+            Palette::Viridis => interpolate(&VIRIDIS, progress),
+            Palette::Heat => interpolate(&HEAT, progress),
+        }
+    }
+
+    fn blend(&self, cell: &SuperState<Tile<Sprite>>) -> Rgb<u8> {
+        let mut sum = [0u32; 3];
+
+        for tile in cell.possible.iter() {
+            for (total, channel) in sum.iter_mut().zip(self.tile_color(tile).0) {
+                *total += channel as u32;
+            }
+        }
+
+        let count = cell.possible.len().max(1) as u32;
+
+        Rgb(sum.map(|total| (total / count) as u8))
+    }
+}
+
+/// Hex color as `rrggbb` or `rrggbbaa`, with or without a leading `#`
+pub fn parse_color(s: &str) -> Result<Rgba<u8>, String> {
+    let hex = s.strip_prefix('#').unwrap_or(s);
+    let channels: Option<Vec<u8>> = match hex.len() {
+        6 | 8 if hex.is_ascii() => (0..hex.len())
+            .step_by(2)
+            .map(|index| u8::from_str_radix(&hex[index..index + 2], 16).ok())
+            .collect(),
+        _ => None,
+    };
+
+    match channels.as_deref() {
+        Some(&[r, g, b]) => Ok(Rgba([r, g, b, 255])),
+        Some(&[r, g, b, a]) => Ok(Rgba([r, g, b, a])),
+        _ => Err(format!("Invalid color {s}, expected rrggbb or rrggbbaa in hex")),
+    }
+}
+
+fn interpolate(stops: &[[u8; 3]], progress: f32) -> Rgb<u8> {
+    let position = progress * (stops.len() - 1) as f32;
+    let index = (position as usize).min(stops.len() - 2);
+    let fraction = position - index as f32;
+    let (from, to) = (stops[index], stops[index + 1]);
+
+    Rgb([0, 1, 2].map(|channel| {
+        (from[channel] as f32 + (to[channel] as f32 - from[channel] as f32) * fraction) as u8
+    }))
+}
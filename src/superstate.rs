@@ -1,43 +1,50 @@
-use crate::grid::Neighbors;
+use crate::bitset::BitSet;
+use crate::grid::{Direction, Direction8, Neighbors};
 use crate::wave::Set;
-use rand::seq::SliceRandom;
+use rand::distributions::{Distribution, Uniform};
 use rand::RngCore;
-use std::{hash::Hash, sync::Arc};
-
-#[cfg(feature = "threaded")]
-use {
-    rayon::prelude::IntoParallelRefIterator,
-    rayon::prelude::ParallelIterator,
-    log::trace,
-    lazy_static::lazy_static,
+use std::{
+    hash::Hash,
+    sync::{Arc, OnceLock},
 };
 
-#[cfg(feature = "threaded")]
-lazy_static! {
-    static ref PAR_MIN_LEN: usize = {
-        let workload_size: f32 = 20.0; // todo tune
-        let num_threads = rayon::current_num_threads();
-        let min_len = (workload_size * num_threads as f32).ceil() as usize;
-
-        trace!("Min workload size before threading: {min_len}");
-
-        min_len        
-    };
-}
-
 pub trait Collapsable: Clone + Sync + Send {
     type Identifier: Clone + Eq + Hash + Ord + Sync + Send;
     fn test(&self, neighbors: &Neighbors<Set<Self::Identifier>>) -> bool;
+    /// Whether `neighbor` may sit diagonally from this in `direction`, only asked by waves
+    /// that check diagonals
+    fn test_diagonal(&self, _direction: Direction8, _neighbor: &Self::Identifier) -> bool {
+        true
+    }
     fn get_id(&self) -> Self::Identifier;
-    fn get_weight(&self) -> usize;
+    /// Dense index of this state, below `universe_size`. States that have one skip
+    /// hashing their ids when the wave indexes them, None falls back to the ids.
+    fn index(&self) -> Option<usize> {
+        None
+    }
+    /// Amount of dense indexes, has to be Some when `index` is
+    fn universe_size() -> Option<usize> {
+        None
+    }
+    /// How likely this is picked when a cell collapses, relative to the other states
+    fn get_weight(&self) -> f64;
 }
 
+/// The states a cell can still be in. Clones share their states until one of them is
+/// narrowed down, so a grid filled with clones of one state and the copies the wave
+/// keeps to start cells over from hold a single list between them.
 #[derive(Debug, Clone)]
 pub struct SuperState<T>
 where
     T: Collapsable,
 {
-    pub possible: Vec<Arc<T>>,
+    pub possible: Arc<Vec<Arc<T>>>,
+    /// Dense indexes assigned by the wave, in the same order as `possible`
+    indexes: Arc<Vec<usize>>,
+    /// Running totals of the weights `collapse_weights` was given, shared by the clones
+    /// like `possible` and started over whenever the states change. None when the
+    /// weights can't be drawn from.
+    cumulative: Arc<OnceLock<Option<Vec<f64>>>>,
     base_entropy: usize,
     entropy: usize,
 }
@@ -49,11 +56,46 @@ where
     pub fn new(possible: Vec<Arc<T>>) -> Self {
         let base_entropy = possible.len();
 
-        Self {
-            possible,
+        let mut output = Self {
+            possible: Arc::new(possible),
+            indexes: Arc::default(),
+            cumulative: Arc::default(),
             base_entropy,
             entropy: base_entropy,
-        }
+        };
+
+        output.update_entropy();
+        output
+    }
+
+    /// Looks up the dense indexes of the possible states and sorts them by index
+    pub fn assign_indexes<F: Fn(&T) -> usize>(&mut self, lookup: F) {
+        Arc::make_mut(&mut self.possible).sort_by_cached_key(|state| lookup(state));
+        self.indexes = Arc::new(self.possible.iter().map(|state| lookup(state)).collect());
+        self.cumulative = Arc::default();
+        self.update_entropy();
+    }
+
+    pub fn indexes(&self) -> &[usize] {
+        &self.indexes
+    }
+
+    /// Keeps the states that pass `keep`
+    pub fn retain<F: Fn(&T) -> bool>(&mut self, keep: F) {
+        let flags: Vec<bool> = self.possible.iter().map(|state| keep(state)).collect();
+
+        self.keep(&flags);
+    }
+
+    /// Keeps the states whose dense index passes `keep`
+    pub fn retain_indexes<F: Fn(usize) -> bool>(&mut self, keep: F) {
+        let flags: Vec<bool> = self.indexes.iter().map(|index| keep(*index)).collect();
+
+        self.keep(&flags);
+    }
+
+    pub fn mask(&self, size: usize) -> BitSet {
+        BitSet::from_indexes(size, self.indexes.iter().copied())
     }
 
     pub fn base_entropy(&self) -> usize {
@@ -74,51 +116,143 @@ where
         self.entropy = self.possible.len();
     }
 
-    pub fn collapsed(&self) -> Option<Arc<&T>> {
+    pub fn collapsed(&self) -> Option<&T> {
         match self.possible.len() {
-            1 => Some(Arc::new(self.possible.get(0)?.as_ref())),
+            1 => Some(self.possible.first()?.as_ref()),
             _ => None,
         }
     }
 
-    pub fn collapse(&mut self, rng: &mut dyn RngCore) {
+    /// Picks one of the possible states, `weight` gives the odds of a state (by dense index)
+    /// relative to the others. The draw is the same as `WeightedIndex`.
+    pub fn collapse<F: Fn(usize, &T) -> f64>(&mut self, rng: &mut dyn RngCore, weight: F) {
         if self.possible.len() > 1 {
-            self.possible.sort_by_key(|a| a.get_id());
+            let cumulative = cumulative_weights(
+                self.indexes
+                    .iter()
+                    .zip(self.possible.iter())
+                    .map(|(index, state)| weight(*index, state)),
+            );
+            let pos = self.pick(rng, cumulative.as_deref(), weight);
 
-            let chosen_id = self
-                .possible
-                .choose_weighted(rng, |v| v.get_weight())
-                .unwrap()
-                .get_id();
+            self.collapse_to(pos);
+        }
+    }
 
-            let chosen_index = self.possible.iter().position(|v| v.get_id() == chosen_id);
+    /// `collapse` with the weights of every dense index in a table that stays the same.
+    /// The running totals are kept along with the states, so cells sharing them only sum
+    /// them once until the states change.
+    pub fn collapse_weights(&mut self, rng: &mut dyn RngCore, weights: &[f64]) {
+        if self.possible.len() > 1 {
+            let cumulative = self
+                .cumulative
+                .get_or_init(|| cumulative_weights(self.indexes.iter().map(|index| weights[*index])));
+            let pos = self.pick(rng, cumulative.as_deref(), |index, _| weights[index]);
 
-            if let Some(pos) = chosen_index {
-                self.possible = vec![self.possible.swap_remove(pos)];
-            }
+            self.collapse_to(pos);
+        }
+    }
 
-            self.update_entropy();
+    /// Position in `possible` of a state drawn from the running totals
+    fn pick<F: Fn(usize, &T) -> f64>(&self, rng: &mut dyn RngCore, cumulative: Option<&[f64]>, weight: F) -> usize {
+        match cumulative {
+            Some(cumulative) => {
+                let chosen_weight = Uniform::new(0.0, cumulative[cumulative.len() - 1]).sample(rng);
+
+                // the last total is left out, like `WeightedIndex` does
+                cumulative[..cumulative.len() - 1].partition_point(|w| *w <= chosen_weight)
+            }
+            // every weight rounded down to nothing at a low temperature
+            None => self
+                .indexes
+                .iter()
+                .zip(self.possible.iter())
+                .map(|(index, state)| weight(*index, state))
+                .enumerate()
+                .max_by(|a, b| a.1.total_cmp(&b.1))
+                .map(|(pos, _)| pos)
+                .unwrap(),
         }
     }
 
-    pub fn tick(&mut self, neighbors: &Neighbors<Set<T::Identifier>>) {
+    fn collapse_to(&mut self, pos: usize) {
+        self.possible = Arc::new(vec![self.possible[pos].clone()]);
+        self.indexes = Arc::new(vec![self.indexes[pos]]);
+        self.cumulative = Arc::default();
+
+        self.update_entropy();
+    }
+
+    /// Removes states that have no allowed neighbor in a direction, `rules` holds the
+    /// allowed neighbors per direction for every dense index.
+    pub fn tick(&mut self, neighbors: &Neighbors<BitSet>, rules: &[Neighbors<BitSet>]) {
         if self.entropy() > 1 {
-            #[cfg(feature = "threaded")]
-            if self.possible.len() > *PAR_MIN_LEN {
-                self.possible = self
-                    .possible
-                    .par_iter()
-                    .filter(|s| s.test(neighbors))
-                    .cloned()
-                    .collect();
-            } else {
-                self.possible.retain(|s| s.test(neighbors));
-            }
+            let keep = self.allowed(neighbors, rules);
+
+            self.keep(&keep);
+        }
+    }
+
+    /// Which of the possible states `tick` would keep, without changing anything so
+    /// cells can be checked side by side
+    pub fn allowed(&self, neighbors: &Neighbors<BitSet>, rules: &[Neighbors<BitSet>]) -> Vec<bool> {
+        let constraints: Vec<(Direction, &BitSet)> = neighbors
+            .iter()
+            .filter(|(_, tiles)| !tiles.is_empty())
+            .collect();
 
-            #[cfg(not(feature = "threaded"))]
-            self.possible.retain(|s| s.test(neighbors));
+        self.indexes
+            .iter()
+            .map(|index| {
+                constraints
+                    .iter()
+                    .all(|(direction, tiles)| !rules[*index][*direction].is_disjoint(tiles))
+            })
+            .collect()
+    }
+
+    /// Keeps the states flagged in `keep`, in the order of `possible`
+    pub fn keep(&mut self, keep: &[bool]) {
+        // keeping everything shouldn't cost a shared state its sharing
+        if keep.iter().all(|flag| *flag) {
+            return;
+        }
+
+        retain_flagged(&mut self.possible, keep);
+        retain_flagged(&mut self.indexes, keep);
+        self.cumulative = Arc::default();
+
+        self.update_entropy();
+    }
+}
+
+/// Running totals of `weights`, None when one is negative or they add up to nothing
+fn cumulative_weights<I: Iterator<Item = f64>>(weights: I) -> Option<Vec<f64>> {
+    let mut total = 0.0;
+    let mut valid = true;
+    let cumulative: Vec<f64> = weights
+        .map(|weight| {
+            valid &= weight >= 0.0;
+            total += weight;
+            total
+        })
+        .collect();
+
+    (valid && total > 0.0).then_some(cumulative)
+}
+
+/// Keeps the items flagged in `keep`, a shared list is copied with just those
+fn retain_flagged<V: Clone>(items: &mut Arc<Vec<V>>, keep: &[bool]) {
+    match Arc::get_mut(items) {
+        Some(items) => {
+            let mut flags = keep.iter();
+
+            items.retain(|_| *flags.next().unwrap());
+        }
+        None => {
+            let kept = items.iter().zip(keep).filter(|(_, flag)| **flag).map(|(item, _)| item.clone());
 
-            self.update_entropy();
+            *items = Arc::new(kept.collect());
         }
     }
 }
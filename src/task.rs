@@ -0,0 +1,84 @@
+//! Solving on an async runtime without tying up a thread for every generation. The
+//! wave is ticked a slice at a time on the task that awaits it and yields back to the
+//! runtime in between, so dropping the future (`tokio::select!`, a timeout, aborting
+//! the task) is enough to cancel it. A slice is a number of `Wave::tick_once` calls,
+//! each propagates a single cell or collapses one, unlike `Wave::tick` which works
+//! through a whole propagation front that can span the grid.
+
+use tokio::sync::watch;
+use tokio_stream::wrappers::WatchStream;
+
+use crate::superstate::Collapsable;
+use crate::wave::{Progress, Wave};
+
+/// Cells ticked before yielding to the runtime by default, well under a millisecond on
+/// most tilesets
+const TICKS_PER_YIELD: usize = 1024;
+
+/// A wave to be solved on an async runtime, see `run`
+pub struct WaveTask<T>
+where
+    T: Collapsable,
+{
+    wave: Wave<T>,
+    ticks_per_yield: usize,
+    max_rollbacks: Option<usize>,
+    progress: watch::Sender<Progress>,
+}
+
+impl<T> WaveTask<T>
+where
+    T: Collapsable,
+{
+    pub fn new(wave: Wave<T>) -> Self {
+        let (progress, _) = watch::channel(wave.progress());
+
+        Self {
+            wave,
+            ticks_per_yield: TICKS_PER_YIELD,
+            max_rollbacks: None,
+            progress,
+        }
+    }
+
+    /// Lower yields more often, which keeps other tasks responsive at some overhead
+    pub fn ticks_per_yield(mut self, ticks: usize) -> Self {
+        self.ticks_per_yield = ticks.max(1);
+        self
+    }
+
+    /// Gives up once there were more than `limit` contradictions instead of trying forever
+    pub fn max_rollbacks(mut self, limit: usize) -> Self {
+        self.max_rollbacks = Some(limit);
+        self
+    }
+
+    /// Progress every time the task yields, slow readers only get the latest. The stream
+    /// ends once the task is done or dropped.
+    pub fn progress(&self) -> WatchStream<Progress> {
+        WatchStream::new(self.progress.subscribe())
+    }
+
+    /// Solves the wave, the wave comes back either way. Err means it gave up after
+    /// more than `max_rollbacks` contradictions.
+    pub async fn run(mut self) -> Result<Wave<T>, Wave<T>> {
+        while !self.wave.done() {
+            if self.max_rollbacks.is_some_and(|limit| self.wave.rollbacks() > limit) {
+                return Err(self.wave);
+            }
+
+            for _ in 0..self.ticks_per_yield {
+                if self.wave.done() {
+                    break;
+                }
+
+                self.wave.tick_once();
+            }
+
+            self.progress.send_replace(self.wave.progress());
+            tokio::task::yield_now().await;
+        }
+
+        Ok(self.wave)
+    }
+}
@@ -1,64 +1,251 @@
 use crate::grid::Direction;
-use crate::grid::Grid;
+use crate::grid::Direction8;
 use crate::grid::Neighbors;
-use crate::grid::Size;
+use crate::grid::Neighbors8;
+use crate::grid::Position;
 use crate::superstate::Collapsable;
 use crate::wave::Set;
 
-use enum_map::enum_map;
-use log::debug;
+use enum_map::Enum;
+use std::collections::HashSet;
+use std::path::PathBuf;
+use std::str::FromStr;
 
 #[cfg(feature = "image")]
 mod image_imports {
+    pub use crate::grid::{Grid, Size};
     pub use crate::sprite::Sprite;
     pub use image::io::Reader as ImageReader;
     pub use image::DynamicImage;
     pub use image::GenericImageView;
     pub use image::ImageBuffer;
+    pub use image::RgbaImage;
+    pub use crate::superstate::SuperState;
     pub use serde::Deserialize;
-    pub use std::collections::hash_map::DefaultHasher;
+    pub use std::borrow::Cow;
     pub use std::collections::HashMap;
-    pub use std::hash::Hash;
-    pub use std::hash::Hasher;
-    pub use std::path::PathBuf;
+    pub use std::ops::Range;
+    pub use std::path::Path;
+    pub use enum_map::enum_map;
+    pub use tracing::debug;
 }
 
 #[cfg(feature = "image")]
 use image_imports::*;
+#[cfg(feature = "image")]
+use crate::sprite::{FNV_OFFSET, FNV_PRIME};
 
 #[derive(Debug, Clone)]
 pub struct Tile<T> {
     pub value: Box<T>,
     /// todo: neighbours per side
     pub neighbors: Neighbors<Set<u64>>,
+    /// Tiles seen on the corners, only the diagonals are used. An empty set allows any tile.
+    pub diagonals: Neighbors8<Set<u64>>,
 
     id: u64,
     pub weight: usize,
 }
 
+/// Corner labels clockwise from the top left, see `Tile::link_corners`
+pub type Corners<L> = [L; 4];
+
+/// What tiles along the edges of an input learn about the other side of the edge
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Boundary {
+    /// Nothing, edge tiles only get neighbors from inside the input. Tiles that only
+    /// show up on an edge can end up without neighbors on that side.
+    #[default]
+    Drop,
+    /// The input is reflected at its borders, the cell past an edge is the one next to
+    /// the edge tile on the inside
+    Mirror,
+}
+
+/// Rotations and reflections of the input that tiles are learned from as well. Every
+/// transform of the input adds its tiles and the neighbors they have there.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Symmetry {
+    /// The input as it is
+    #[default]
+    None,
+    /// The input and its mirror image
+    Mirror,
+    /// The input turned a quarter at a time, needs square tiles
+    Rotate,
+    /// Every rotation of the input and of its mirror image, needs square tiles
+    All,
+}
+
+impl Symmetry {
+    pub const NAMES: [&'static str; 4] = ["none", "mirror", "rotate", "all"];
+}
+
+impl FromStr for Symmetry {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "none" => Ok(Symmetry::None),
+            "mirror" => Ok(Symmetry::Mirror),
+            "rotate" => Ok(Symmetry::Rotate),
+            "all" => Ok(Symmetry::All),
+            _ => Err(format!("Unknown symmetry {s}, expected one of {:?}", Symmetry::NAMES)),
+        }
+    }
+}
+
+/// How tiles are learned from an input image, see `Tile::from_image_with`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Extraction {
+    pub boundary: Boundary,
+    pub symmetry: Symmetry,
+    /// Every variant of a tile weighs as much as the tile did in the input, instead of
+    /// how often the variant came up. Otherwise a tile that looks the same turned
+    /// around is counted once for every transform and picked up to 8 times as often.
+    pub shared_weights: bool,
+}
+
+#[cfg(feature = "image")]
+impl Extraction {
+    fn transforms(&self, tile_size: &Size) -> Vec<Transform> {
+        let (flips, turns): (&[bool], u8) = match self.symmetry {
+            Symmetry::None => (&[false], 1),
+            Symmetry::Mirror => (&[false, true], 1),
+            Symmetry::Rotate => (&[false], 4),
+            Symmetry::All => (&[false, true], 4),
+        };
+
+        assert!(
+            turns == 1 || tile_size.width == tile_size.height,
+            "Tiles have to be square to be rotated"
+        );
+
+        flips
+            .iter()
+            .flat_map(|flip| (0..turns).map(move |turns| Transform { flip: *flip, turns }))
+            .collect()
+    }
+}
+
+/// A mirror image if `flip`, then `turns` quarter turns clockwise
 #[cfg(feature = "image")]
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Transform {
+    flip: bool,
+    turns: u8,
+}
+
+#[cfg(feature = "image")]
+impl Transform {
+    fn apply<'a>(&self, image: &'a DynamicImage) -> Cow<'a, DynamicImage> {
+        if !self.flip && self.turns == 0 {
+            return Cow::Borrowed(image);
+        }
+
+        let mut image = match self.flip {
+            true => image.fliph(),
+            false => image.clone(),
+        };
+
+        for _ in 0..self.turns {
+            image = image.rotate90();
+        }
+
+        Cow::Owned(image)
+    }
+
+    fn apply_grid<T: Clone>(&self, grid: &Grid<T>) -> Grid<T> {
+        let mut grid = match self.flip {
+            true => grid.flip_horizontal(),
+            false => grid.clone(),
+        };
+
+        for _ in 0..self.turns {
+            grid = grid.rotate90();
+        }
+
+        grid
+    }
+}
+
+/// Where a tile was learned from, so an odd cell of an output can be traced back to the
+/// spot of the input that made it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Provenance {
+    /// File the tile came from
+    pub source: Option<PathBuf>,
+    /// Cell of the input in tiles, before it was turned. `None` for tiles that are a
+    /// whole file.
+    pub position: Option<Position>,
+    /// Quarter turns clockwise, after the mirroring
+    pub turns: u8,
+    /// Mirrored left to right
+    pub flipped: bool,
+}
+
+/// Cells of `tile_size` that fit in the image
+#[cfg(feature = "image")]
+fn grid_size(image: &DynamicImage, tile_size: &Size) -> (usize, usize) {
+    let (image_width, image_height) = image.dimensions();
+
+    (image_width as usize / tile_size.width, image_height as usize / tile_size.height)
+}
+
+/// Id of an animated tile, FNV-1a over the ids of its frames like `Sprite` does over pixels
+#[cfg(feature = "image")]
+fn cycle_id(ids: impl Iterator<Item = u64>) -> u64 {
+    ids.fold(FNV_OFFSET, |hash, id| (hash ^ id).wrapping_mul(FNV_PRIME))
+}
+
+/// Frames of animated tiles by the id of the tile that stands for them, see
+/// `Tile::from_frames`. Tiles that don't change aren't in it.
+#[cfg(feature = "image")]
+pub type Variants = HashMap<u64, Vec<Sprite>>;
+
+#[cfg(feature = "image")]
+#[derive(Debug, Clone, Deserialize)]
 pub struct TileConfig {
     image: PathBuf,
+    /// Edge sockets clockwise from the top, ignored when `corners` is set
+    #[serde(default)]
     slots: Vec<String>,
+    /// Corner labels instead of edge sockets, only matches other tiles with corners
+    corners: Option<Corners<String>>,
+    /// Names to pick tiles by on the command line, like `mountain`
+    #[serde(default)]
+    tags: Vec<String>,
+}
+
+#[cfg(feature = "image")]
+impl TileConfig {
+    pub fn image(&self) -> &Path {
+        &self.image
+    }
+
+    pub fn tags(&self) -> &[String] {
+        &self.tags
+    }
 }
 
 #[cfg(feature = "image")]
 impl Tile<Sprite> {
     pub fn from_config(configs: &[TileConfig]) -> Vec<Self> {
         let mut output = Vec::new();
-        let mut slots: Vec<(u64, Neighbors<String>)> = Vec::new();
+        let mut slots: Vec<(u64, Option<Neighbors<String>>)> = Vec::new();
 
         output.reserve_exact(configs.len());
         slots.reserve_exact(configs.len());
 
         for config in configs {
-            let neighbors = enum_map! {
-                Direction::Up => config.slots[0].clone(),
-                Direction::Right => config.slots[1].clone(),
-                Direction::Down => config.slots[2].clone(),
-                Direction::Left => config.slots[3].clone(),
-            };
+            let neighbors = config.corners.is_none().then(|| {
+                enum_map! {
+                    Direction::Up => config.slots[0].clone(),
+                    Direction::Right => config.slots[1].clone(),
+                    Direction::Down => config.slots[2].clone(),
+                    Direction::Left => config.slots[3].clone(),
+                }
+            });
 
             let image = ImageReader::open(config.image.as_path())
                 .unwrap()
@@ -71,10 +258,13 @@ impl Tile<Sprite> {
         }
 
         for index in 0..slots.len() {
+            let Some(own) = &slots[index].1 else {
+                continue;
+            };
+
             for (id, neighbors) in &slots {
-                for (direction, key) in neighbors {
-                    let rev_key: String =
-                        slots[index].1[direction.invert()].chars().rev().collect();
+                for (direction, key) in neighbors.iter().flatten() {
+                    let rev_key: String = own[direction.invert()].chars().rev().collect();
 
                     if *key == rev_key {
                         output[index].neighbors[direction].insert(*id);
@@ -83,41 +273,164 @@ impl Tile<Sprite> {
             }
         }
 
+        for (tile, config) in output.iter_mut().zip(configs) {
+            let Some(corners) = &config.corners else {
+                continue;
+            };
+
+            for ((id, _), other) in slots.iter().zip(configs) {
+                for direction in (0..Direction::LENGTH).map(Direction::from_usize) {
+                    if other.corners.as_ref().is_some_and(|other| corners_fit(corners, other, direction)) {
+                        tile.neighbors[direction].insert(*id);
+                    }
+                }
+            }
+        }
+
         output
     }
 
     pub fn from_image(image: &DynamicImage, tile_size: &Size) -> Vec<Self> {
-        let (image_width, image_height) = image.dimensions();
-        let grid_width = image_width as usize / tile_size.width;
-        let grid_height = image_height as usize / tile_size.height;
+        Self::from_image_with(image, tile_size, &Extraction::default())
+    }
+
+    /// Like `from_image` with the edges and symmetry of `extraction`
+    pub fn from_image_with(image: &DynamicImage, tile_size: &Size, extraction: &Extraction) -> Vec<Self> {
+        let learned = extraction
+            .transforms(tile_size)
+            .iter()
+            .map(|transform| {
+                let image = transform.apply(image);
+                let (grid_width, grid_height) = grid_size(&image, tile_size);
+
+                Self::from_cells(grid_width, grid_height, extraction.boundary, |x, y| {
+                    Self::extract(&image, tile_size, x, y)
+                })
+            })
+            .collect();
+
+        Self::merge(learned, extraction, tile_size, |tile, transform| {
+            Sprite::new(transform.apply(tile.value.image()).into_owned()).id()
+        })
+    }
+
+    /// Tiles of an animation, the frames of every cell of the input are one tile that
+    /// learns its neighbors like `from_image` does. Cells that look the same in one frame
+    /// but move differently are different tiles. The tiles show their first frame, the
+    /// frames of the ones that change are returned as variants.
+    pub fn from_frames(frames: &[DynamicImage], tile_size: &Size, extraction: &Extraction) -> (Vec<Self>, Variants) {
+        let mut variants = Variants::new();
+        let learned = extraction
+            .transforms(tile_size)
+            .iter()
+            .map(|transform| {
+                let frames: Vec<DynamicImage> = frames.iter().map(|frame| transform.apply(frame).into_owned()).collect();
+                let (grid_width, grid_height) = grid_size(&frames[0], tile_size);
+
+                Self::from_cells(grid_width, grid_height, extraction.boundary, |x, y| {
+                    let cycle: Vec<Sprite> = frames
+                        .iter()
+                        .map(|frame| *Self::extract(frame, tile_size, x, y).value)
+                        .collect();
+                    let id = cycle_id(cycle.iter().map(Sprite::id));
+
+                    if cycle.iter().any(|sprite| sprite.id() != cycle[0].id()) {
+                        variants.entry(id).or_insert_with(|| cycle.clone());
+                    }
+
+                    Self::new(id, cycle[0].clone())
+                })
+            })
+            .collect();
+
+        let tiles = Self::merge(learned, extraction, tile_size, |tile, transform| {
+            let transformed = |sprite: &Sprite| Sprite::new(transform.apply(sprite.image()).into_owned()).id();
+
+            match variants.get(&tile.get_id()) {
+                Some(cycle) => cycle_id(cycle.iter().map(transformed)),
+                None => cycle_id(std::iter::repeat_n(transformed(&tile.value), frames.len())),
+            }
+        });
+
+        (tiles, variants)
+    }
+
+    /// Joins the tiles learned from every transform of the input, a tile seen in more
+    /// than one keeps the neighbors of all of them. `transformed_id` is the id a tile
+    /// would have after a transform, for telling how many transforms leave it as it is.
+    fn merge(
+        learned: Vec<Vec<Self>>,
+        extraction: &Extraction,
+        tile_size: &Size,
+        transformed_id: impl Fn(&Self, Transform) -> u64,
+    ) -> Vec<Self> {
+        if learned.len() == 1 {
+            return learned.into_iter().next().unwrap();
+        }
+
+        let mut unique: HashMap<u64, Self> = HashMap::new();
+
+        for tile in learned.into_iter().flatten() {
+            let Some(known) = unique.get_mut(&tile.id) else {
+                unique.insert(tile.id, tile);
+                continue;
+            };
+
+            known.weight += tile.weight;
+
+            for (direction, ids) in tile.neighbors {
+                known.neighbors[direction].extend(ids);
+            }
+
+            for (direction, ids) in tile.diagonals {
+                known.diagonals[direction].extend(ids);
+            }
+        }
+
+        if extraction.shared_weights {
+            let transforms = extraction.transforms(tile_size);
+
+            // a tile turns up once for every transform that leaves it as it is, every
+            // variant is counted once per place its group takes up in the input instead
+            for tile in unique.values_mut() {
+                let unchanged = transforms
+                    .iter()
+                    .filter(|transform| transformed_id(tile, **transform) == tile.id)
+                    .count();
+
+                tile.weight = (tile.weight / unchanged.max(1)).max(1);
+            }
+        }
+
+        let mut output: Vec<Self> = unique.into_values().collect();
+
+        output.sort_by_key(|t| t.id);
+
+        output
+    }
 
+    fn from_cells(
+        grid_width: usize,
+        grid_height: usize,
+        boundary: Boundary,
+        mut extract: impl FnMut(usize, usize) -> Self,
+    ) -> Vec<Self> {
         let mut unique: HashMap<u64, Self> = Default::default();
 
         debug!("Input grid: {grid_width}x{grid_height}");
 
         debug!("Generating tiles");
         let grid = Grid::new(grid_width, grid_height, &mut |x, y| {
-            let view = image.view(
-                x as u32 * tile_size.width as u32,
-                y as u32 * tile_size.height as u32,
-                tile_size.width as u32,
-                tile_size.height as u32,
-            );
-
-            let buffer =
-                ImageBuffer::from_fn(tile_size.width as u32, tile_size.height as u32, |ix, iy| {
-                    view.get_pixel(ix, iy)
-                });
-
-            let new_tile = Tile::new_image_tile(DynamicImage::from(buffer));
+            let new_tile = extract(x, y);
             let tile_id = new_tile.get_id();
 
-            unique.insert(tile_id, new_tile);
+            // every occurrence counts towards the weight, not just the last one
+            unique
+                .entry(tile_id)
+                .and_modify(|tile| tile.weight += 1)
+                .or_insert(new_tile);
 
-            unique.get_mut(&tile_id).unwrap().weight += 1;
-
-            assert_ne!(unique.get(&tile_id).unwrap().get_weight(), 1);
-            unique.get(&tile_id).unwrap().get_id()
+            tile_id
         });
 
         debug!("Populating neighbors");
@@ -133,35 +446,313 @@ impl Tile<Sprite> {
             }
 
             assert!(tile.neighbors.len() > 0);
+
+            for direction in Direction8::DIAGONALS {
+                if let Some(position) = grid.get_neighbor_position8(x, y, direction) {
+                    tile.diagonals[direction].insert(*grid.get(position.x, position.y).unwrap());
+                }
+            }
+        }
+
+        if boundary == Boundary::Mirror {
+            debug!("Mirroring the edges");
+
+            for (x, y, tile_id) in &grid {
+                for (direction, maybe) in grid.get_neighbors(x, y) {
+                    // past the edge is the cell on the other side, the rule goes both ways
+                    // so the other tile also accepts this one on that side
+                    let mirrored = match maybe {
+                        Some(_) => continue,
+                        None => grid.get_neighbor(x, y, direction.invert()),
+                    };
+
+                    if let Some(mirrored) = mirrored {
+                        unique.get_mut(tile_id).unwrap().neighbors[direction].insert(*mirrored);
+                        unique.get_mut(mirrored).unwrap().neighbors[direction.invert()].insert(*tile_id);
+                    }
+                }
+            }
         }
 
-        let output: Vec<Self> = unique.values().cloned().collect::<Vec<Self>>();
+        let mut output: Vec<Self> = unique.values().cloned().collect::<Vec<Self>>();
+
+        // hash map order differs between runs, keep the output stable
+        output.sort_by_key(|t| t.id);
 
         for tile in output.iter() {
             assert!(tile.neighbors.len() > 0);
         }
 
-        // todo: Keep track of rotation
-
         output
     }
 
+    /// Where each tile of `from_image_with` first shows up in the input, and how the input
+    /// was turned for it. The source is left for the caller to fill in.
+    pub fn provenance(image: &DynamicImage, tile_size: &Size, extraction: &Extraction) -> HashMap<u64, Provenance> {
+        Self::trace(std::slice::from_ref(image), tile_size, extraction, false)
+    }
+
+    /// Like `provenance` for the tiles of `from_frames`
+    pub fn frame_provenance(
+        frames: &[DynamicImage],
+        tile_size: &Size,
+        extraction: &Extraction,
+    ) -> HashMap<u64, Provenance> {
+        Self::trace(frames, tile_size, extraction, true)
+    }
+
+    /// Walks the cells of every transform of the input in the order they're learned
+    fn trace(
+        frames: &[DynamicImage],
+        tile_size: &Size,
+        extraction: &Extraction,
+        animated: bool,
+    ) -> HashMap<u64, Provenance> {
+        let (grid_width, grid_height) = grid_size(&frames[0], tile_size);
+        let positions = Grid::new(grid_width, grid_height, &mut |x, y| Position::new(x, y));
+        let mut provenance = HashMap::new();
+
+        for transform in extraction.transforms(tile_size) {
+            let frames: Vec<Cow<DynamicImage>> = frames.iter().map(|frame| transform.apply(frame)).collect();
+            let id = |x, y| {
+                let mut ids = frames.iter().map(|frame| Self::extract(frame, tile_size, x, y).get_id());
+
+                match animated {
+                    true => cycle_id(ids),
+                    false => ids.next().unwrap(),
+                }
+            };
+
+            // the cells get moved around the same way the pixels do
+            for (x, y, position) in &transform.apply_grid(&positions) {
+                provenance.entry(id(x, y)).or_insert(Provenance {
+                    source: None,
+                    position: Some(*position),
+                    turns: transform.turns,
+                    flipped: transform.flip,
+                });
+            }
+        }
+
+        provenance
+    }
+
+    /// Id of the tile in every cell of `image`, for reading an output back into tiles
+    pub fn ids(image: &DynamicImage, tile_size: &Size) -> Grid<u64> {
+        let (image_width, image_height) = image.dimensions();
+
+        Grid::new(
+            image_width as usize / tile_size.width,
+            image_height as usize / tile_size.height,
+            &mut |x, y| Self::extract(image, tile_size, x, y).get_id(),
+        )
+    }
+
+    /// Pairs of tile ids found in the same place in `base` and `overlay`, tells which
+    /// tiles of an overlay drawn over the input go on which tiles of the input
+    pub fn pairs(base: &DynamicImage, overlay: &DynamicImage, tile_size: &Size) -> Result<HashSet<(u64, u64)>, String> {
+        if base.dimensions() != overlay.dimensions() {
+            return Err(format!(
+                "The overlay is {:?} but the input is {:?}, they need to line up",
+                overlay.dimensions(),
+                base.dimensions()
+            ));
+        }
+
+        let (image_width, image_height) = base.dimensions();
+        let mut pairs = HashSet::new();
+
+        for y in 0..image_height as usize / tile_size.height {
+            for x in 0..image_width as usize / tile_size.width {
+                pairs.insert((
+                    Self::extract(base, tile_size, x, y).get_id(),
+                    Self::extract(overlay, tile_size, x, y).get_id(),
+                ));
+            }
+        }
+
+        Ok(pairs)
+    }
+
+    fn extract(image: &DynamicImage, tile_size: &Size, x: usize, y: usize) -> Self {
+        let view = image.view(
+            x as u32 * tile_size.width as u32,
+            y as u32 * tile_size.height as u32,
+            tile_size.width as u32,
+            tile_size.height as u32,
+        );
+
+        let buffer =
+            ImageBuffer::from_fn(tile_size.width as u32, tile_size.height as u32, |ix, iy| {
+                view.get_pixel(ix, iy)
+            });
+
+        Tile::new_image_tile(DynamicImage::from(buffer))
+    }
+
+    /// Draws the collapsed cells of a grid keeping their alpha, cells that aren't collapsed
+    /// are left transparent
+    pub fn compose(grid: &Grid<SuperState<Self>>, tile_width: u32, tile_height: u32) -> RgbaImage {
+        Self::compose_rows(grid, 0..grid.height(), tile_width, tile_height)
+    }
+
+    /// Draws the collapsed cells of `rows` only, the top of the image is the top of the
+    /// first row. Huge outputs can be drawn a band at a time this way.
+    pub fn compose_rows(
+        grid: &Grid<SuperState<Self>>,
+        rows: Range<usize>,
+        tile_width: u32,
+        tile_height: u32,
+    ) -> RgbaImage {
+        Self::draw_rows(grid, rows, tile_width, tile_height, |tile| &tile.value)
+    }
+
+    /// Draws `frame` of an animated output, tiles with variants show that frame of their
+    /// cycle (wrapping around when it's shorter) and the rest their only one
+    pub fn compose_frame(
+        grid: &Grid<SuperState<Self>>,
+        variants: &Variants,
+        frame: usize,
+        tile_width: u32,
+        tile_height: u32,
+    ) -> RgbaImage {
+        Self::draw_rows(grid, 0..grid.height(), tile_width, tile_height, |tile| {
+            match variants.get(&tile.get_id()) {
+                Some(cycle) => &cycle[frame % cycle.len()],
+                None => &tile.value,
+            }
+        })
+    }
+
+    fn draw_rows<'a>(
+        grid: &'a Grid<SuperState<Self>>,
+        rows: Range<usize>,
+        tile_width: u32,
+        tile_height: u32,
+        sprite: impl Fn(&'a Self) -> &'a Sprite,
+    ) -> RgbaImage {
+        let mut canvas = RgbaImage::new(
+            grid.width() as u32 * tile_width,
+            rows.len() as u32 * tile_height,
+        );
+
+        for y in rows.clone() {
+            for x in 0..grid.width() {
+                let tile = grid.get(x, y).and_then(|cell| cell.collapsed());
+
+                // empty tiles leave the canvas transparent as it is
+                if let Some(t) = tile.map(&sprite).filter(|t| !t.is_empty()) {
+                    image::imageops::overlay(
+                        &mut canvas,
+                        t.image(),
+                        x as i64 * tile_width as i64,
+                        (y - rows.start) as i64 * tile_height as i64,
+                    );
+                }
+            }
+        }
+
+        canvas
+    }
+
     pub fn new_image_tile(image: DynamicImage) -> Self {
-        let mut hasher = DefaultHasher::new();
-        let sprite = Sprite { image };
+        let sprite = Sprite::new(image);
 
-        sprite.hash(&mut hasher);
+        Self::new(sprite.id(), sprite)
+    }
+}
 
-        Self::new(hasher.finish(), sprite)
+/// Something in a tileset that keeps tiles from being placed or outputs from solving
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TileIssue {
+    /// Nothing may sit next to the tile in this direction, it only fits against that border
+    NoNeighbors(u64, Direction),
+    /// The tile allows a neighbor that isn't part of the tileset
+    UnknownNeighbor(u64, Direction, u64),
+    /// No tile allows a neighbor in this direction, so only outputs one cell wide (or
+    /// high) can be solved
+    Unsolvable(Direction),
+}
+
+impl TileIssue {
+    /// Describes the issue, `name` turns tile ids into something the user can find
+    pub fn describe<F: Fn(u64) -> String>(&self, name: F) -> String {
+        match self {
+            TileIssue::NoNeighbors(tile, direction) => format!(
+                "{} has no allowed neighbors {direction:?}, it can only be placed at that border",
+                name(*tile)
+            ),
+            TileIssue::UnknownNeighbor(tile, direction, neighbor) => format!(
+                "{} allows {neighbor} {direction:?}, which is not in the tileset",
+                name(*tile)
+            ),
+            TileIssue::Unsolvable(direction) => format!(
+                "No tile allows a neighbor {direction:?}, only single cell wide outputs can be solved"
+            ),
+        }
     }
 }
 
 impl<T> Tile<T> {
+    /// Fills in the neighbors of tiles that match on their corners rather than their
+    /// sides, like marching squares tilesets, `corners` goes with `tiles` by index. Every
+    /// tile meeting at a corner has to agree on its label. Tiles side by side share two
+    /// corners, so agreeing on those for every side already makes all four tiles around
+    /// a corner agree, and the corners turn into regular neighbor rules.
+    pub fn link_corners<L: Eq>(tiles: &mut [Self], corners: &[Corners<L>]) {
+        let ids: Vec<u64> = tiles.iter().map(|tile| tile.id).collect();
+
+        for (tile, own) in tiles.iter_mut().zip(corners) {
+            for (id, other) in ids.iter().zip(corners) {
+                for direction in (0..Direction::LENGTH).map(Direction::from_usize) {
+                    if corners_fit(own, other, direction) {
+                        tile.neighbors[direction].insert(*id);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Checks for tiles that can't be placed everywhere and tilesets that can't be solved
+    pub fn validate(tiles: &[Self]) -> Vec<TileIssue> {
+        let ids: HashSet<u64> = tiles.iter().map(|tile| tile.id).collect();
+        let mut issues = Vec::new();
+
+        for tile in tiles {
+            for (direction, neighbors) in &tile.neighbors {
+                if neighbors.is_empty() {
+                    issues.push(TileIssue::NoNeighbors(tile.id, direction));
+                }
+
+                let mut unknown: Vec<u64> =
+                    neighbors.iter().filter(|id| !ids.contains(id)).copied().collect();
+
+                unknown.sort();
+                issues.extend(
+                    unknown
+                        .into_iter()
+                        .map(|neighbor| TileIssue::UnknownNeighbor(tile.id, direction, neighbor)),
+                );
+            }
+        }
+
+        for index in 0..Direction::LENGTH {
+            let direction = Direction::from_usize(index);
+
+            if tiles.iter().all(|tile| tile.neighbors[direction].is_empty()) {
+                issues.push(TileIssue::Unsolvable(direction));
+            }
+        }
+
+        issues
+    }
+
     pub fn new(id: u64, value: T) -> Self {
         Self {
             id,
             value: Box::new(value),
             neighbors: Default::default(),
+            diagonals: Default::default(),
             weight: 1,
         }
     }
@@ -186,11 +777,30 @@ impl<T: Clone + Sync + Send> Collapsable for Tile<T> {
         true
     }
 
+    fn test_diagonal(&self, direction: Direction8, neighbor: &Self::Identifier) -> bool {
+        let possible = &self.diagonals[direction];
+
+        possible.is_empty() || possible.contains(neighbor)
+    }
+
     fn get_id(&self) -> Self::Identifier {
         self.id
     }
 
-    fn get_weight(&self) -> usize {
-        self.weight
+    fn get_weight(&self) -> f64 {
+        self.weight as f64
+    }
+}
+
+/// Whether a tile with the corners `other` may sit in `direction` of one with `corners`
+fn corners_fit<L: Eq>(corners: &Corners<L>, other: &Corners<L>, direction: Direction) -> bool {
+    let [up_left, up_right, down_right, down_left] = corners;
+    let [other_up_left, other_up_right, other_down_right, other_down_left] = other;
+
+    match direction {
+        Direction::Up => up_left == other_down_left && up_right == other_down_right,
+        Direction::Right => up_right == other_up_left && down_right == other_down_left,
+        Direction::Down => down_left == other_up_left && down_right == other_up_right,
+        Direction::Left => up_left == other_up_right && down_left == other_down_right,
     }
 }
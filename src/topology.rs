@@ -0,0 +1,413 @@
+//! Solving over any graph of cells rather than just a rectangle, like irregular meshes,
+//! voronoi cells or navmesh polygons. Cells are numbered nodes joined by labeled edges,
+//! the labels take the place of directions. `solve` searches any topology, `Wave` runs
+//! over topologies labeled with directions, see `WaveBuilder::with_topology`.
+
+use rand::Rng;
+
+use crate::bitset::BitSet;
+use crate::grid::{Direction, Grid};
+
+/// Nodes and the labeled edges between them
+pub trait Topology {
+    type Label: Copy + Eq;
+
+    fn nodes(&self) -> usize;
+    /// Neighbors of `node` with the label of the edge leading to them
+    fn neighbors(&self, node: usize) -> Vec<(Self::Label, usize)>;
+
+    /// The neighbor across the edge of `node` with `label`, the first one if there are
+    /// several. Propagating asks for every side of every cell it visits, so topologies
+    /// that can answer without listing every neighbor should.
+    fn neighbor(&self, node: usize, label: Self::Label) -> Option<usize> {
+        self.neighbors(node)
+            .into_iter()
+            .find(|(other, _)| *other == label)
+            .map(|(_, neighbor)| neighbor)
+    }
+}
+
+/// Nodes are the cells in row-major order, edges are labeled by their direction
+impl<T> Topology for Grid<T>
+where
+    T: Clone,
+{
+    type Label = Direction;
+
+    fn nodes(&self) -> usize {
+        self.size()
+    }
+
+    fn neighbors(&self, node: usize) -> Vec<(Direction, usize)> {
+        let width = self.width();
+
+        self.get_neighbor_positions(node % width, node / width)
+            .into_iter()
+            .filter_map(|(direction, position)| position.map(|position| (direction, position.index(width))))
+            .collect()
+    }
+
+    fn neighbor(&self, node: usize, direction: Direction) -> Option<usize> {
+        let width = self.width();
+
+        self.get_neighbor_position(node % width, node / width, direction)
+            .map(|position| position.index(width))
+    }
+}
+
+/// Graph built edge by edge
+#[derive(Debug, Clone)]
+pub struct Graph<L> {
+    edges: Vec<Vec<(L, usize)>>,
+}
+
+impl<L> Graph<L>
+where
+    L: Copy + Eq,
+{
+    pub fn new(nodes: usize) -> Self {
+        Self {
+            edges: vec![Vec::new(); nodes],
+        }
+    }
+
+    /// Adds an edge from `from` to `to`. Edges only go one way, constraints only spread
+    /// along them, so most graphs want an edge back as well.
+    pub fn connect(&mut self, from: usize, to: usize, label: L) {
+        self.edges[from].push((label, to));
+    }
+}
+
+impl<L> Topology for Graph<L>
+where
+    L: Copy + Eq,
+{
+    type Label = L;
+
+    fn nodes(&self) -> usize {
+        self.edges.len()
+    }
+
+    fn neighbors(&self, node: usize) -> Vec<(L, usize)> {
+        self.edges[node].clone()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Options {
+    /// Assignments tried before giving up
+    pub nodes: usize,
+    /// Most cells a tile may take up as (dense index, count), on top of the neighbor rules.
+    /// Only decided cells count, a search that would go over backtracks.
+    pub max_counts: Vec<(usize, usize)>,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            nodes: 1_000_000,
+            max_counts: Vec::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Failure {
+    /// Every assignment was ruled out, the graph can't be solved
+    Unsolvable,
+    /// Ran out of its budget (nodes, or conflicts for the SAT backend) before finding a solution
+    GaveUp,
+}
+
+struct Search<'a> {
+    /// Edges per node as (label index, neighbor)
+    edges: Vec<Vec<(usize, usize)>>,
+    /// Tiles allowed across an edge per label index and tile
+    allowed: Vec<Vec<BitSet>>,
+    weights: &'a [f64],
+    max_counts: Vec<usize>,
+    nodes: usize,
+}
+
+/// Picks a tile for every node out of `domains`, the possible tiles per node, with a
+/// depth first search that keeps every edge arc consistent. Tiles are indexes into
+/// `weights`, `fits(tile, label, neighbor)` tells whether `neighbor` may be across an
+/// edge with `label` from `tile`. Values are tried in a weighted random order so
+/// different rngs give different solutions.
+pub fn solve<G, F, R>(
+    topology: &G,
+    domains: Vec<Vec<usize>>,
+    weights: &[f64],
+    fits: F,
+    options: &Options,
+    rng: &mut R,
+) -> Result<Vec<usize>, Failure>
+where
+    G: Topology,
+    F: Fn(usize, G::Label, usize) -> bool,
+    R: Rng,
+{
+    let mut labels: Vec<G::Label> = Vec::new();
+    let edges = (0..topology.nodes())
+        .map(|node| {
+            topology
+                .neighbors(node)
+                .into_iter()
+                .map(|(label, neighbor)| {
+                    let index = labels.iter().position(|other| *other == label).unwrap_or_else(|| {
+                        labels.push(label);
+                        labels.len() - 1
+                    });
+
+                    (index, neighbor)
+                })
+                .collect()
+        })
+        .collect();
+    let tiles = weights.len();
+    let allowed = labels
+        .iter()
+        .map(|label| {
+            (0..tiles)
+                .map(|tile| BitSet::from_indexes(tiles, (0..tiles).filter(|other| fits(tile, *label, *other))))
+                .collect()
+        })
+        .collect();
+    let mut max_counts = vec![usize::MAX; tiles];
+
+    for (index, count) in &options.max_counts {
+        max_counts[*index] = max_counts[*index].min(*count);
+    }
+
+    let mut search = Search {
+        edges,
+        allowed,
+        weights,
+        max_counts,
+        nodes: options.nodes,
+    };
+    let mut cells = domains;
+
+    if !search.propagate(&mut cells, (0..topology.nodes()).collect()) {
+        return Err(Failure::Unsolvable);
+    }
+
+    search.search(cells, rng)?.ok_or(Failure::Unsolvable)
+}
+
+impl Search<'_> {
+    /// Removes values without a fitting neighbor value until nothing changes, starting
+    /// from the cells in `queue`. False when a cell runs out of values or a tile is used
+    /// more often than allowed.
+    fn propagate(&self, cells: &mut [Vec<usize>], mut queue: Vec<usize>) -> bool {
+        loop {
+            while let Some(cell) = queue.pop() {
+                for &(label, neighbor) in &self.edges[cell] {
+                    if neighbor == cell {
+                        continue;
+                    }
+
+                    let before = cells[neighbor].len();
+                    let (current, other) = pair(cells, cell, neighbor);
+
+                    other.retain(|value| current.iter().any(|tile| self.allowed[label][*tile].contains(*value)));
+
+                    if other.is_empty() {
+                        return false;
+                    }
+
+                    if other.len() != before && !queue.contains(&neighbor) {
+                        queue.push(neighbor);
+                    }
+                }
+            }
+
+            let mut counts = vec![0; self.weights.len()];
+
+            for cell in cells.iter().filter(|cell| cell.len() == 1) {
+                counts[cell[0]] += 1;
+            }
+
+            if counts.iter().zip(&self.max_counts).any(|(count, max)| count > max) {
+                return false;
+            }
+
+            // tiles at their limit can't go anywhere else
+            for (index, cell) in cells.iter_mut().enumerate() {
+                if cell.len() > 1 {
+                    cell.retain(|tile| counts[*tile] < self.max_counts[*tile]);
+
+                    if cell.is_empty() {
+                        return false;
+                    }
+
+                    if cell.len() == 1 {
+                        queue.push(index);
+                    }
+                }
+            }
+
+            if queue.is_empty() {
+                return true;
+            }
+        }
+    }
+
+    /// Depth first over the undecided cells, with the choices still to try for every
+    /// level kept on an explicit stack so a deep search can't overflow the call stack
+    fn search<R: Rng>(&mut self, cells: Vec<Vec<usize>>, rng: &mut R) -> Result<Option<Vec<usize>>, Failure> {
+        let mut stack = match self.branch(cells, rng) {
+            Branch::Solved(solution) => return Ok(Some(solution)),
+            Branch::Open(choice) => vec![choice],
+        };
+
+        while let Some(choice) = stack.last_mut() {
+            let Some(tile) = choice.order.pop() else {
+                stack.pop();
+                continue;
+            };
+
+            if self.nodes == 0 {
+                return Err(Failure::GaveUp);
+            }
+
+            self.nodes -= 1;
+
+            let mut attempt = choice.cells.clone();
+            let index = choice.index;
+
+            attempt[index] = vec![tile];
+
+            if self.propagate(&mut attempt, vec![index]) {
+                match self.branch(attempt, rng) {
+                    Branch::Solved(solution) => return Ok(Some(solution)),
+                    Branch::Open(choice) => stack.push(choice),
+                }
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// Picks the undecided cell with the fewest values left and the order to try them
+    /// in, weighted random so heavier tiles tend to come first
+    fn branch<R: Rng>(&self, cells: Vec<Vec<usize>>, rng: &mut R) -> Branch {
+        let next = cells
+            .iter()
+            .enumerate()
+            .filter(|(_, cell)| cell.len() > 1)
+            .min_by_key(|(_, cell)| cell.len())
+            .map(|(index, _)| index);
+
+        let Some(index) = next else {
+            return Branch::Solved(cells.into_iter().map(|cell| cell[0]).collect());
+        };
+
+        let mut order: Vec<(f64, usize)> = cells[index]
+            .iter()
+            .map(|tile| (rng.gen::<f64>().powf(1.0 / self.weights[*tile].max(f64::MIN_POSITIVE)), *tile))
+            .collect();
+
+        // popped from the back, the heaviest draw goes last
+        order.sort_by(|a, b| b.0.total_cmp(&a.0));
+        order.reverse();
+
+        Branch::Open(Choice {
+            cells,
+            index,
+            order: order.into_iter().map(|(_, tile)| tile).collect(),
+        })
+    }
+}
+
+enum Branch {
+    /// Every cell is decided
+    Solved(Vec<usize>),
+    Open(Choice),
+}
+
+/// A level of the search, the cells as they were before `index` was decided and the
+/// values it has left to try
+struct Choice {
+    cells: Vec<Vec<usize>>,
+    index: usize,
+    order: Vec<usize>,
+}
+
+/// Borrows two different cells at once
+fn pair(cells: &mut [Vec<usize>], first: usize, second: usize) -> (&Vec<usize>, &mut Vec<usize>) {
+    if first < second {
+        let (left, right) = cells.split_at_mut(second);
+
+        (&left[first], &mut right[0])
+    } else {
+        let (left, right) = cells.split_at_mut(first);
+
+        (&right[0], &mut left[second])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+    use rand_xorshift::XorShiftRng;
+
+    use super::*;
+
+    #[test]
+    fn search_backtracks_until_every_option_is_ruled_out() {
+        // every node of four next to each other, arc consistency alone can't tell three
+        // colors aren't enough
+        let mut graph = Graph::new(4);
+
+        for from in 0..4 {
+            for to in (0..4).filter(|to| *to != from) {
+                graph.connect(from, to, ());
+            }
+        }
+
+        let mut rng = XorShiftRng::seed_from_u64(0);
+        let differ = |tile: usize, _: (), other: usize| tile != other;
+        let three = solve(&graph, vec![vec![0, 1, 2]; 4], &[1.0; 3], differ, &Options::default(), &mut rng);
+
+        assert_eq!(three, Err(Failure::Unsolvable));
+
+        let mut four = solve(&graph, vec![vec![0, 1, 2, 3]; 4], &[1.0; 4], differ, &Options::default(), &mut rng).unwrap();
+
+        four.sort();
+
+        assert_eq!(four, vec![0, 1, 2, 3]);
+    }
+
+    #[test]
+    fn max_counts_limit_how_often_a_tile_is_used() {
+        // two tiles that fit anywhere, the first one is far more likely
+        let grid = Grid::new(4, 4, &mut |_, _| ());
+        let domains = vec![vec![0, 1]; 16];
+        let weights = [100.0, 1.0];
+        let mut rng = XorShiftRng::seed_from_u64(0);
+        let count = |solution: &[usize]| solution.iter().filter(|tile| **tile == 0).count();
+
+        let free = solve(&grid, domains.clone(), &weights, |_, _, _| true, &Options::default(), &mut rng).unwrap();
+
+        assert!(count(&free) > 3);
+
+        let options = Options {
+            max_counts: vec![(0, 3)],
+            ..Options::default()
+        };
+        let limited = solve(&grid, domains.clone(), &weights, |_, _, _| true, &options, &mut rng).unwrap();
+
+        assert!(count(&limited) <= 3);
+
+        let options = Options {
+            max_counts: vec![(0, 3), (1, 12)],
+            ..Options::default()
+        };
+
+        assert_eq!(
+            solve(&grid, domains, &weights, |_, _, _| true, &options, &mut rng),
+            Err(Failure::Unsolvable)
+        );
+    }
+}
@@ -0,0 +1,263 @@
+use std::io;
+use std::time::{Duration, Instant};
+
+use ratatui::buffer::Buffer;
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyModifiers};
+use ratatui::layout::{Constraint, Layout, Rect};
+use ratatui::style::Color;
+use ratatui::text::Line;
+use ratatui::widgets::{Block, Paragraph, Sparkline, Widget};
+use ratatui::DefaultTerminal;
+
+use wave_function_collapse::grid::Grid;
+use wave_function_collapse::sprite::Sprite;
+use wave_function_collapse::style::CellStyle;
+use wave_function_collapse::superstate::SuperState;
+use wave_function_collapse::tile::Tile;
+use wave_function_collapse::wave::Wave;
+
+/// Minimum time between two redraws, input is only read this often while running
+const DRAW_INTERVAL: Duration = Duration::from_millis(50);
+const RATE_INTERVAL: Duration = Duration::from_millis(500);
+const PANEL_WIDTH: u16 = 34;
+
+/// What the main loop should do next
+pub enum Action {
+    Continue,
+    Restart,
+    Quit,
+}
+
+/// Terminal preview of the grid with a stats panel, each cell is drawn as half a
+/// character in its `CellStyle` color
+pub struct Tui {
+    terminal: DefaultTerminal,
+    style: CellStyle,
+    seed: u64,
+    paused: bool,
+    started: Instant,
+    last_draw: Instant,
+    last_sample: (Instant, usize),
+    rate: f64,
+}
+
+impl Tui {
+    pub fn start(style: CellStyle, seed: u64) -> Self {
+        Self {
+            terminal: ratatui::init(),
+            style,
+            seed,
+            paused: false,
+            started: Instant::now(),
+            last_draw: Instant::now() - DRAW_INTERVAL,
+            last_sample: (Instant::now(), 0),
+            rate: 0.0,
+        }
+    }
+
+    pub fn restart(&mut self, seed: u64) {
+        self.seed = seed;
+        self.started = Instant::now();
+        self.last_sample = (Instant::now(), 0);
+        self.rate = 0.0;
+    }
+
+    /// Redraws and handles input at most every `DRAW_INTERVAL`, blocks while paused
+    pub fn update(&mut self, wfc: &Wave<Tile<Sprite>>) -> io::Result<Action> {
+        loop {
+            if !self.paused && self.last_draw.elapsed() < DRAW_INTERVAL {
+                return Ok(Action::Continue);
+            }
+
+            self.draw(wfc, if self.paused { "Paused" } else { "Running" })?;
+
+            while event::poll(Duration::ZERO)? {
+                match read_key()? {
+                    Some(KeyCode::Char('q') | KeyCode::Esc) => return Ok(Action::Quit),
+                    Some(KeyCode::Char('r')) => return Ok(Action::Restart),
+                    Some(KeyCode::Char(' ') | KeyCode::Char('p')) => self.paused = !self.paused,
+                    Some(KeyCode::Char('s') | KeyCode::Right) if self.paused => {
+                        return Ok(Action::Continue)
+                    }
+                    _ => {}
+                }
+            }
+
+            if !self.paused {
+                return Ok(Action::Continue);
+            }
+
+            event::poll(DRAW_INTERVAL)?;
+        }
+    }
+
+    /// Shows the finished grid until the user quits or restarts
+    pub fn finish(&mut self, wfc: &Wave<Tile<Sprite>>) -> io::Result<Action> {
+        self.paused = false;
+
+        loop {
+            self.draw(wfc, "Done")?;
+
+            if event::poll(DRAW_INTERVAL)? {
+                match read_key()? {
+                    Some(KeyCode::Char('q') | KeyCode::Esc | KeyCode::Enter) => {
+                        return Ok(Action::Quit)
+                    }
+                    Some(KeyCode::Char('r')) => return Ok(Action::Restart),
+                    _ => {}
+                }
+            }
+        }
+    }
+
+    fn draw(&mut self, wfc: &Wave<Tile<Sprite>>, state: &str) -> io::Result<()> {
+        let total = wfc.grid.size();
+        let collapsed = total - wfc.remaining();
+        let (sampled_at, sampled) = self.last_sample;
+
+        if sampled_at.elapsed() >= RATE_INTERVAL {
+            // rollbacks can undo more than was collapsed in between
+            let delta = collapsed.saturating_sub(sampled);
+
+            self.rate = delta as f64 / sampled_at.elapsed().as_secs_f64();
+            self.last_sample = (Instant::now(), collapsed);
+        }
+
+        let stats = vec![
+            Line::from(format!("State      {state}")),
+            Line::from(format!("Seed       {}", self.seed)),
+            Line::from(format!("Size       {}x{}", wfc.grid.width(), wfc.grid.height())),
+            Line::from(format!(
+                "Collapsed  {collapsed} ({:.1}%)",
+                collapsed as f64 * 100.0 / total.max(1) as f64
+            )),
+            Line::from(format!("Remaining  {}", wfc.remaining())),
+            Line::from(format!("Rollbacks  {}", wfc.rollbacks())),
+            Line::from(format!("Rate       {:.0} cells/s", self.rate)),
+            Line::from(format!("Elapsed    {:.1}s", self.started.elapsed().as_secs_f64())),
+            Line::from(""),
+            Line::from("space  pause/resume"),
+            Line::from("s      step while paused"),
+            Line::from("r      restart, new seed"),
+            Line::from("q      quit"),
+        ];
+
+        let entropy = entropy_bars(wfc.entropy_histogram(), PANEL_WIDTH as usize - 2);
+        let entropy_title = format!(" Entropy 2-{} ", wfc.entropy_histogram().len().saturating_sub(2));
+        let stats_height = stats.len() as u16 + 2;
+
+        let preview = GridPreview {
+            grid: &wfc.grid,
+            style: &self.style,
+        };
+
+        self.terminal.draw(|frame| {
+            let [left, right] =
+                Layout::horizontal([Constraint::Min(0), Constraint::Length(PANEL_WIDTH)])
+                    .areas(frame.area());
+            let block = Block::bordered().title(" Wave Function Collapse ");
+
+            frame.render_widget(preview, block.inner(left));
+            frame.render_widget(block, left);
+            let [stats_area, entropy_area] =
+                Layout::vertical([Constraint::Length(stats_height), Constraint::Min(0)]).areas(right);
+
+            frame.render_widget(Paragraph::new(stats).block(Block::bordered().title(" Stats ")), stats_area);
+            frame.render_widget(
+                Sparkline::default()
+                    .block(Block::bordered().title(entropy_title))
+                    .data(entropy),
+                entropy_area,
+            );
+        })?;
+
+        self.last_draw = Instant::now();
+
+        Ok(())
+    }
+}
+
+impl Drop for Tui {
+    fn drop(&mut self) {
+        ratatui::restore();
+    }
+}
+
+/// Cells that are being narrowed down per entropy in at most `width` bars. Collapsed
+/// cells and the ones nothing was taken from yet are left out, they'd dwarf the rest.
+fn entropy_bars(histogram: &[usize], width: usize) -> Vec<u64> {
+    let open = histogram.get(2..histogram.len().saturating_sub(1)).unwrap_or_default();
+    let per_bar = open.len().div_ceil(width.max(1)).max(1);
+
+    open.chunks(per_bar).map(|chunk| chunk.iter().sum::<usize>() as u64).collect()
+}
+
+/// Raw mode swallows ctrl-c, so it's mapped to quitting here
+fn read_key() -> io::Result<Option<KeyCode>> {
+    Ok(match event::read()? {
+        Event::Key(key) if key.kind == KeyEventKind::Press => {
+            if key.modifiers.contains(KeyModifiers::CONTROL) && key.code == KeyCode::Char('c') {
+                Some(KeyCode::Esc)
+            } else {
+                Some(key.code)
+            }
+        }
+        _ => None,
+    })
+}
+
+/// Draws two grid rows per terminal row using upper half blocks. Grids larger than
+/// the area are scaled down by skipping cells.
+struct GridPreview<'a> {
+    grid: &'a Grid<SuperState<Tile<Sprite>>>,
+    style: &'a CellStyle,
+}
+
+impl GridPreview<'_> {
+    fn color(&self, x: usize, y: usize) -> Option<Color> {
+        if x >= self.grid.width() {
+            return None;
+        }
+
+        let cell = self.grid.get(x, y)?;
+
+        let [r, g, b] = self.style.color(cell).0;
+
+        Some(Color::Rgb(r, g, b))
+    }
+}
+
+impl Widget for GridPreview<'_> {
+    fn render(self, area: Rect, buffer: &mut Buffer) {
+        let width = area.width as usize;
+        let height = area.height as usize * 2;
+
+        if width == 0 || height == 0 {
+            return;
+        }
+
+        let scale = self
+            .grid
+            .width()
+            .div_ceil(width)
+            .max(self.grid.height().div_ceil(height))
+            .max(1);
+
+        for row in 0..area.height {
+            for column in 0..area.width {
+                let x = column as usize * scale;
+                let y = row as usize * 2 * scale;
+
+                let Some(top) = self.color(x, y) else {
+                    continue;
+                };
+                let bottom = self.color(x, y + scale).unwrap_or(Color::Reset);
+
+                buffer[(area.x + column, area.y + row)]
+                    .set_char('▀')
+                    .set_fg(top)
+                    .set_bg(bottom);
+            }
+        }
+    }
+}
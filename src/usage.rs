@@ -0,0 +1,111 @@
+//! Which neighbor rules an output relied on. Rules that never come up across a few
+//! outputs are worth a look, they tend to come from a stray pixel that split one tile
+//! into two or from a corner of the input that was drawn only once.
+
+use std::collections::HashMap;
+
+use enum_map::Enum;
+
+use crate::grid::{Direction, Grid};
+use crate::superstate::{Collapsable, SuperState};
+use crate::tile::Tile;
+
+/// A tile next to another in one direction
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RuleUsage {
+    pub tile: u64,
+    pub direction: Direction,
+    pub neighbor: u64,
+    /// Times `neighbor` sits in `direction` of `tile` in the output
+    pub count: usize,
+    /// Whether the tileset allows it, the solver only ever uses allowed rules but an
+    /// image edited by hand may not
+    pub allowed: bool,
+}
+
+/// Every allowed rule of a tileset and every pair found in an output, ordered by tile,
+/// direction and neighbor
+#[derive(Debug, Clone, Default)]
+pub struct AdjacencyUsage {
+    pub rules: Vec<RuleUsage>,
+}
+
+impl AdjacencyUsage {
+    /// Counts the neighbors of every cell of `cells` against the rules of `tiles`, cells
+    /// that are None are skipped
+    pub fn new<T>(cells: &Grid<Option<u64>>, tiles: &[Tile<T>]) -> Self
+    where
+        T: Clone + Sync + Send,
+    {
+        let mut counts: HashMap<(u64, Direction, u64), usize> = HashMap::new();
+
+        for (x, y, cell) in cells {
+            let Some(tile) = cell else {
+                continue;
+            };
+
+            for (direction, neighbor) in cells.get_neighbors(x, y) {
+                if let Some(Some(neighbor)) = neighbor {
+                    *counts.entry((*tile, direction, *neighbor)).or_insert(0) += 1;
+                }
+            }
+        }
+
+        let mut rules: Vec<RuleUsage> = Vec::new();
+
+        for tile in tiles {
+            for (direction, neighbors) in &tile.neighbors {
+                for neighbor in neighbors {
+                    rules.push(RuleUsage {
+                        tile: tile.get_id(),
+                        direction,
+                        neighbor: *neighbor,
+                        count: counts.remove(&(tile.get_id(), direction, *neighbor)).unwrap_or(0),
+                        allowed: true,
+                    });
+                }
+            }
+        }
+
+        // whatever is left isn't allowed by any tile
+        rules.extend(counts.into_iter().map(|((tile, direction, neighbor), count)| RuleUsage {
+            tile,
+            direction,
+            neighbor,
+            count,
+            allowed: false,
+        }));
+        rules.sort_by_key(|rule| (rule.tile, rule.direction.into_usize(), rule.neighbor));
+
+        Self { rules }
+    }
+
+    /// Like `new` for the collapsed cells of a wave's grid
+    pub fn of_grid<T>(grid: &Grid<SuperState<Tile<T>>>, tiles: &[Tile<T>]) -> Self
+    where
+        T: Clone + Sync + Send,
+    {
+        let cells = Grid::new(grid.width(), grid.height(), &mut |x, y| {
+            grid.get(x, y)
+                .and_then(|cell| cell.collapsed())
+                .map(|tile| tile.get_id())
+        });
+
+        Self::new(&cells, tiles)
+    }
+
+    /// Allowed rules the output used at least once
+    pub fn used(&self) -> impl Iterator<Item = &RuleUsage> {
+        self.rules.iter().filter(|rule| rule.allowed && rule.count > 0)
+    }
+
+    /// Allowed rules the output never used
+    pub fn dead(&self) -> impl Iterator<Item = &RuleUsage> {
+        self.rules.iter().filter(|rule| rule.allowed && rule.count == 0)
+    }
+
+    /// Pairs in the output that no rule allows
+    pub fn disallowed(&self) -> impl Iterator<Item = &RuleUsage> {
+        self.rules.iter().filter(|rule| !rule.allowed)
+    }
+}
@@ -0,0 +1,879 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter};
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+use image::{GenericImageView, Rgba};
+use sdl2::event::{Event, WindowEvent};
+use sdl2::keyboard::{Keycode, Mod};
+use sdl2::mouse::MouseButton;
+use sdl2::pixels::{Color, PixelFormatEnum};
+use sdl2::rect::{Point, Rect};
+use sdl2::render::{Canvas, Texture};
+use sdl2::video::{FullscreenType, Window};
+use sdl2::EventPump;
+use tracing::{debug, error, info, warn};
+
+use wave_function_collapse::grid::{Position, Size};
+use wave_function_collapse::history::EditHistory;
+use wave_function_collapse::overlay::{Overlay, Text};
+use wave_function_collapse::profiling::{self, Phase};
+use wave_function_collapse::session::Session;
+use wave_function_collapse::sprite::Sprite;
+use wave_function_collapse::style::CellStyle;
+use wave_function_collapse::superstate::{Collapsable, SuperState};
+use wave_function_collapse::tile::{Provenance, Tile};
+use wave_function_collapse::wave::{Rollback, Wave};
+
+use crate::generate::{Settings, Tileset};
+use crate::Opt;
+
+/// Edits the visual keeps around for undoing
+#[cfg(feature = "sdl2")]
+pub const EDIT_HISTORY: usize = 64;
+
+/// How long the visual highlights the cells a rollback touched
+#[cfg(feature = "sdl2")]
+const FLASH_TIME: Duration = Duration::from_millis(400);
+
+/// Time between two frames of the visual, the solver keeps going in between
+#[cfg(feature = "sdl2")]
+pub const FRAME_TIME: Duration = Duration::from_millis(16);
+
+#[cfg(feature = "sdl2")]
+fn save_session(wfc: &Wave<Tile<Sprite>>, path: &PathBuf) -> io::Result<()> {
+    wfc.session().write(BufWriter::new(File::create(path)?))
+}
+
+#[cfg(feature = "sdl2")]
+fn load_session(path: &PathBuf) -> io::Result<Session> {
+    Session::read(BufReader::new(File::open(path)?))
+}
+
+#[cfg(feature = "sdl2")]
+pub enum SdlAction {
+    Quit,
+    /// Left click, pins the cell to its most likely tile
+    Pin(usize, usize),
+    /// Right click
+    Unpin(usize, usize),
+    /// Ctrl+z
+    Undo,
+    /// Ctrl+y or ctrl+shift+z
+    Redo,
+    /// Ctrl+s
+    Save,
+    /// Ctrl+o
+    Load,
+    /// F1, shows or hides the stats
+    ToggleStats,
+}
+
+/// Where and how the window opens, see `SdlDraw::new`
+#[cfg(feature = "sdl2")]
+struct WindowOptions {
+    vsync: bool,
+    fullscreen: bool,
+    /// Fullscreen at the resolution the desktop is at, without switching video modes
+    borderless: bool,
+    /// Index of the display to open on, 0 is the primary one
+    display: i32,
+    /// Top left corner relative to the display, centered when None
+    position: Option<Position>,
+}
+
+/// Colors of the window that don't come from the cell style
+#[cfg(feature = "sdl2")]
+struct Theme {
+    background: Color,
+    /// Outline around collapsed cells
+    border: Option<Color>,
+}
+
+#[cfg(feature = "sdl2")]
+fn sdl_color(Rgba([r, g, b, a]): Rgba<u8>) -> Color {
+    Color::RGBA(r, g, b, a)
+}
+
+#[cfg(feature = "sdl2")]
+pub struct SdlDraw {
+    canvas: Canvas<Window>,
+    events: EventPump,
+    pub textures: HashMap<u64, Texture>,
+    tile_size: (u32, u32),
+    style: CellStyle,
+    overlay: Overlay,
+    theme: Theme,
+    /// Rollbacks still highlighted and when they happened
+    flashes: Vec<(Rollback, Instant)>,
+    /// Seed, time and rates in the top left corner, and where the tile under the mouse
+    /// came from
+    stats: bool,
+    /// Mouse position in the window while it's over it
+    hover: Option<(i32, i32)>,
+    provenance: HashMap<u64, Provenance>,
+}
+
+#[cfg(feature = "sdl2")]
+impl SdlDraw {
+    fn new(
+        size: Size,
+        tiles: &[Tile<Sprite>],
+        style: CellStyle,
+        overlay: Overlay,
+        options: WindowOptions,
+        theme: Theme,
+    ) -> Self {
+        let context = sdl2::init().unwrap();
+        let video = context.video().unwrap();
+        let displays = video.num_video_displays().unwrap();
+
+        for index in 0..displays {
+            debug!("Display {index}: {}", video.display_name(index).unwrap_or_default());
+        }
+
+        let display = match options.display < displays {
+            true => options.display,
+            false => {
+                warn!("There's no display {}, opening on the primary one", options.display);
+                0
+            }
+        };
+        let bounds = video.display_bounds(display).unwrap();
+        let (width, height) = (size.width as i32, size.height as i32);
+        let (x, y) = match options.position {
+            Some(position) => (bounds.x() + position.x as i32, bounds.y() + position.y as i32),
+            None => (
+                bounds.x() + (bounds.width() as i32 - width) / 2,
+                bounds.y() + (bounds.height() as i32 - height) / 2,
+            ),
+        };
+
+        let mut window = video
+            .window("Wave Function Collapse", width as u32, height as u32)
+            .position(x, y)
+            .build()
+            .map_err(|e| e.to_string())
+            .unwrap();
+
+        // fullscreen goes to the display the window is on
+        if options.fullscreen {
+            window.set_fullscreen(FullscreenType::True).unwrap();
+        } else if options.borderless {
+            window.set_fullscreen(FullscreenType::Desktop).unwrap();
+        }
+
+        if window.fullscreen_state() != FullscreenType::Off {
+            context.mouse().show_cursor(false);
+        }
+
+        let mut builder = window.into_canvas().target_texture();
+
+        if options.vsync {
+            builder = builder.present_vsync();
+        }
+
+        let canvas = builder.build().map_err(|e| e.to_string()).unwrap();
+
+        let events = context.event_pump().unwrap();
+        let mut draw = Self {
+            canvas,
+            events,
+            textures: HashMap::new(),
+            tile_size: (1, 1),
+            style,
+            overlay,
+            theme,
+            flashes: Vec::new(),
+            stats: false,
+            hover: None,
+            provenance: HashMap::new(),
+        };
+
+        draw.load_tiles(tiles, None);
+        draw
+    }
+
+    /// Highlights the cells of `rollbacks` for `FLASH_TIME`, the contradicting cells in
+    /// the contradiction color
+    pub fn flash(&mut self, rollbacks: Vec<Rollback>) {
+        let now = Instant::now();
+
+        self.flashes.extend(rollbacks.into_iter().map(|rollback| (rollback, now)));
+    }
+
+    /// Shows what was drawn since the last call
+    pub fn present(&mut self) {
+        self.canvas.present();
+    }
+
+    /// Replaces the tile textures, and the style when given
+    pub fn load_tiles(&mut self, tiles: &[Tile<Sprite>], style: Option<CellStyle>) {
+        let texture_creator = self.canvas.texture_creator();
+
+        for (_, texture) in self.textures.drain() {
+            // nothing else holds on to the textures, they're only drawn from here
+            unsafe { texture.destroy() };
+        }
+
+        for tile in tiles {
+            if self.textures.contains_key(&tile.get_id()) {
+                continue;
+            }
+
+            let rgba = tile.value.image().to_rgba8();
+            let (width, height) = tile.value.image().dimensions();
+
+            let mut texture = texture_creator
+                .create_texture_streaming(PixelFormatEnum::RGBA32, width, height)
+                .map_err(|e| e.to_string())
+                .unwrap();
+
+            texture
+                .with_lock(None, |buffer: &mut [u8], _: usize| {
+                    buffer.copy_from_slice(&rgba);
+                })
+                .unwrap();
+
+            self.textures.insert(tile.get_id(), texture);
+        }
+
+        if let Some(tile) = tiles.first() {
+            self.tile_size = tile.value.image().dimensions();
+        }
+
+        if let Some(style) = style {
+            self.style = style;
+        }
+    }
+
+    /// Drains pending events into the edits they ask for
+    pub fn actions(&mut self) -> Vec<SdlAction> {
+        let (tile_width, tile_height) = self.tile_size;
+        let control = Mod::LCTRLMOD | Mod::RCTRLMOD;
+        let shift = Mod::LSHIFTMOD | Mod::RSHIFTMOD;
+
+        self.events
+            .poll_iter()
+            .filter_map(|event| match event {
+                Event::Quit { .. }
+                | Event::KeyDown {
+                    keycode: Some(Keycode::Escape),
+                    ..
+                } => Some(SdlAction::Quit),
+                Event::KeyDown {
+                    keycode: Some(Keycode::Z),
+                    keymod,
+                    ..
+                } if keymod.intersects(control) => Some(match keymod.intersects(shift) {
+                    true => SdlAction::Redo,
+                    false => SdlAction::Undo,
+                }),
+                Event::KeyDown {
+                    keycode: Some(Keycode::Y),
+                    keymod,
+                    ..
+                } if keymod.intersects(control) => Some(SdlAction::Redo),
+                Event::KeyDown {
+                    keycode: Some(Keycode::S),
+                    keymod,
+                    ..
+                } if keymod.intersects(control) => Some(SdlAction::Save),
+                Event::KeyDown {
+                    keycode: Some(Keycode::O),
+                    keymod,
+                    ..
+                } if keymod.intersects(control) => Some(SdlAction::Load),
+                Event::KeyDown {
+                    keycode: Some(Keycode::F1),
+                    ..
+                } => Some(SdlAction::ToggleStats),
+                Event::MouseMotion { x, y, .. } => {
+                    self.hover = Some((x, y));
+
+                    None
+                }
+                Event::Window {
+                    win_event: WindowEvent::Leave,
+                    ..
+                } => {
+                    self.hover = None;
+
+                    None
+                }
+                Event::MouseButtonDown {
+                    mouse_btn, x, y, ..
+                } if x >= 0 && y >= 0 => {
+                    let cell = (
+                        (x as u32 / tile_width) as usize,
+                        (y as u32 / tile_height) as usize,
+                    );
+
+                    match mouse_btn {
+                        MouseButton::Left => Some(SdlAction::Pin(cell.0, cell.1)),
+                        MouseButton::Right => Some(SdlAction::Unpin(cell.0, cell.1)),
+                        _ => None,
+                    }
+                }
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Plays back a finished wave in the order its cells collapsed until the window is
+    /// closed or escape is pressed, see `--scrub`
+    pub fn scrub(&mut self, wfc: &Wave<Tile<Sprite>>) {
+        let timeline: Vec<Position> = wfc.collapse_order().map(|(position, _)| position).collect();
+        let row = wfc.grid.width();
+        let mut step = timeline.len();
+        let mut playing = false;
+
+        loop {
+            let events: Vec<Event> = self.events.poll_iter().collect();
+
+            for event in events {
+                let (keycode, keymod) = match event {
+                    Event::Quit { .. }
+                    | Event::KeyDown {
+                        keycode: Some(Keycode::Escape),
+                        ..
+                    } => return,
+                    Event::KeyDown {
+                        keycode: Some(keycode),
+                        keymod,
+                        ..
+                    } => (keycode, keymod),
+                    _ => continue,
+                };
+                let stride = match keymod.intersects(Mod::LSHIFTMOD | Mod::RSHIFTMOD) {
+                    true => row,
+                    false => 1,
+                };
+
+                match keycode {
+                    Keycode::Left => step = step.saturating_sub(stride),
+                    Keycode::Right => step = (step + stride).min(timeline.len()),
+                    Keycode::Home => step = 0,
+                    Keycode::End => step = timeline.len(),
+                    // playing from the end starts over
+                    Keycode::Space if step == timeline.len() => {
+                        step = 0;
+                        playing = true;
+                    }
+                    Keycode::Space => playing = !playing,
+                    _ => {}
+                }
+            }
+
+            if playing {
+                step = (step + 1).min(timeline.len());
+                playing = step < timeline.len();
+            }
+
+            draw_timeline(wfc, self, &timeline[..step]);
+            std::thread::sleep(FRAME_TIME);
+        }
+    }
+
+    /// Drains pending events, true once the window was closed or escape was pressed
+    pub fn quit_requested(&mut self) -> bool {
+        self.events.poll_iter().any(|event| {
+            matches!(
+                event,
+                Event::Quit { .. }
+                    | Event::KeyDown {
+                        keycode: Some(Keycode::Escape),
+                        ..
+                    }
+            )
+        })
+    }
+}
+
+/// Opens the window for `--visual`, or shows the new tiles in the one left open by the
+/// last generation
+#[cfg(feature = "sdl2")]
+pub fn open_window(opt: &Opt, settings: &Settings, tileset: &Tileset, style: &CellStyle, sdl_draw: &mut Option<SdlDraw>) {
+    let tiles = &tileset.tiles;
+
+    if let Some(draw) = sdl_draw.as_mut() {
+        draw.load_tiles(tiles, Some(style.clone()));
+    } else if opt.visual {
+        let (tile_width, tile_height) = tiles[0].value.image().dimensions();
+        let mut size = opt.output_size;
+
+        assert_eq!(tile_width, tile_height);
+
+        size.scale(tile_width.try_into().unwrap());
+
+        let window = WindowOptions {
+            vsync: opt.vsync,
+            fullscreen: opt.fullscreen,
+            borderless: opt.borderless,
+            display: opt.display,
+            position: opt.window_position,
+        };
+        let theme = Theme {
+            background: sdl_color(opt.background),
+            border: opt.border_color.map(sdl_color),
+        };
+
+        *sdl_draw = Some(SdlDraw::new(size, tiles, style.clone(), settings.overlay, window, theme));
+    }
+
+    if let Some(draw) = sdl_draw.as_mut() {
+        draw.provenance = tileset.provenance.clone();
+    }
+}
+
+/// Applies the edits made in the window since the last frame, whether any went through
+/// or None once it's closed
+#[cfg(feature = "sdl2")]
+pub fn apply_actions(
+    opt: &Opt,
+    draw: &mut SdlDraw,
+    wfc: &mut Wave<Tile<Sprite>>,
+    history: &mut EditHistory<Tile<Sprite>>,
+    replaying: bool,
+    editing: &mut bool,
+) -> Option<bool> {
+    let mut edited_any = false;
+
+    for action in draw.actions() {
+        let edited = match action {
+            // closing the window finishes the session as usual
+            SdlAction::Quit if *editing && wfc.done() => {
+                *editing = false;
+
+                Ok(())
+            }
+            SdlAction::Quit => return None,
+            SdlAction::ToggleStats => {
+                draw.stats = !draw.stats;
+
+                Ok(())
+            }
+            // a replay only shows what happened, it can't be edited
+            _ if replaying => Ok(()),
+            SdlAction::Pin(x, y) => match wfc.possibilities(x, y).max_by_key(|tile| tile.weight) {
+                Some(tile) => {
+                    let id = tile.get_id();
+
+                    history.pin(wfc, x, y, id)
+                }
+                None => Err("nothing to pin"),
+            },
+            SdlAction::Unpin(x, y) => history.unpin(wfc, x, y),
+            SdlAction::Undo => history.undo(wfc).map(|_| ()).ok_or("nothing to undo"),
+            SdlAction::Redo => history.redo(wfc).map(|_| ()).ok_or("nothing to redo"),
+            SdlAction::Save => match &opt.session {
+                Some(path) => {
+                    match save_session(wfc, path) {
+                        Ok(()) => info!("Saved session to {}", path.display()),
+                        Err(e) => error!("Failed to save session: {e}"),
+                    }
+
+                    Ok(())
+                }
+                None => Err("no --session to save to"),
+            },
+            SdlAction::Load => match &opt.session {
+                Some(path) => match load_session(path) {
+                    Ok(session) => history.load(wfc, session),
+                    Err(e) => {
+                        error!("Failed to load session: {e}");
+
+                        Ok(())
+                    }
+                },
+                None => Err("no --session to load from"),
+            },
+        };
+
+        match edited {
+            Ok(()) => edited_any = true,
+            Err(e) => debug!("Edit ignored, {e}"),
+        }
+    }
+
+    Some(edited_any)
+}
+
+/// Draws the wave and shows it right away
+#[cfg(feature = "sdl2")]
+pub fn update_canvas(wfc: &Wave<Tile<Sprite>>, context: &mut SdlDraw) {
+    draw_canvas(wfc, context);
+    context.canvas.present();
+}
+
+/// Draws the wave without showing it, presenting can wait for vsync and doesn't need
+/// the wave anymore
+// todo only draw updated
+#[cfg(feature = "sdl2")]
+pub fn draw_canvas(wfc: &Wave<Tile<Sprite>>, context: &mut SdlDraw) {
+    use sdl2::render::BlendMode;
+
+    let _timer = profiling::time(Phase::Rendering);
+
+    let (tile_width, tile_height) = wfc
+        .possibilities(0, 0)
+        .next()
+        .unwrap()
+        .value
+        .image()
+        .dimensions();
+
+    context.canvas.set_draw_color(context.theme.background);
+    context.canvas.clear();
+    context.canvas.set_blend_mode(BlendMode::Blend);
+
+    let (output_width, output_height) = context.canvas.output_size().unwrap();
+    let visible = (
+        (output_width / tile_width) as usize,
+        (output_height / tile_height) as usize,
+    );
+    let mut minimap = (visible.0 < wfc.grid.width() || visible.1 < wfc.grid.height())
+        .then(|| Minimap::new(wfc.grid.width(), wfc.grid.height()));
+    let mut borders = Vec::new();
+
+    for (x, y, cell) in &wfc.grid {
+        if let Some(minimap) = minimap.as_mut() {
+            minimap.count(x, y, cell);
+        }
+
+        let rect = Rect::new(
+            x as i32 * tile_width as i32,
+            y as i32 * tile_height as i32,
+            tile_width,
+            tile_height,
+        );
+
+        if let Some(tile) = cell.collapsed() {
+            // todo streamline
+            let texture = context.textures.get(&tile.get_id()).unwrap();
+
+            context.canvas.set_draw_color(context.theme.background);
+            context.canvas.fill_rect(rect).unwrap();
+            context.canvas.copy(texture, None, Some(rect)).unwrap();
+            borders.push(rect);
+        } else {
+            let [r, g, b] = context.style.color(cell).0;
+            let mut color = Color::RGB(r, g, b);
+
+            if wfc.data.get(x, y).map(|x| x.is_some()).unwrap_or(false) {
+                color.r = 40;
+            }
+
+            context.canvas.set_draw_color(color);
+            context.canvas.fill_rect(rect).unwrap();
+        }
+    }
+
+    if let Some(border) = context.theme.border {
+        context.canvas.set_draw_color(border);
+        context.canvas.draw_rects(&borders).unwrap();
+    }
+
+    draw_overlay(wfc, context, tile_width, tile_height);
+    draw_flashes(context, tile_width, tile_height);
+
+    if let Some(minimap) = minimap {
+        minimap.draw(&mut context.canvas, visible);
+    }
+
+    if context.stats {
+        draw_stats(wfc, &mut context.canvas);
+        draw_inspector(wfc, context);
+    }
+}
+
+/// Draws the tiles of the cells in `shown`, the rest of the grid stays empty
+#[cfg(feature = "sdl2")]
+fn draw_timeline(wfc: &Wave<Tile<Sprite>>, context: &mut SdlDraw, shown: &[Position]) {
+    let (tile_width, tile_height) = context.tile_size;
+
+    context.canvas.set_draw_color(context.theme.background);
+    context.canvas.clear();
+
+    for position in shown {
+        let Some(tile) = wfc.grid.get(position.x, position.y).and_then(|cell| cell.collapsed()) else {
+            continue;
+        };
+        let rect = Rect::new(
+            position.x as i32 * tile_width as i32,
+            position.y as i32 * tile_height as i32,
+            tile_width,
+            tile_height,
+        );
+
+        context.canvas.copy(&context.textures[&tile.get_id()], None, Some(rect)).unwrap();
+    }
+
+    context.canvas.present();
+}
+
+#[cfg(feature = "sdl2")]
+fn draw_flashes(context: &mut SdlDraw, tile_width: u32, tile_height: u32) {
+    let SdlDraw {
+        canvas, flashes, style, ..
+    } = context;
+    let rect = |position: &Position| {
+        Rect::new(
+            position.x as i32 * tile_width as i32,
+            position.y as i32 * tile_height as i32,
+            tile_width,
+            tile_height,
+        )
+    };
+
+    flashes.retain(|(_, since)| since.elapsed() < FLASH_TIME);
+
+    for (rollback, since) in flashes.iter() {
+        let fade = 1.0 - since.elapsed().as_secs_f32() / FLASH_TIME.as_secs_f32();
+        let cells: Vec<Rect> = rollback.cells.iter().map(rect).collect();
+        let [r, g, b] = style.contradiction().0;
+
+        canvas.set_draw_color(Color::RGBA(255, 255, 255, (160.0 * fade) as u8));
+        canvas.fill_rects(&cells).unwrap();
+        canvas.set_draw_color(Color::RGBA(r, g, b, (255.0 * fade) as u8));
+        canvas.fill_rect(rect(&rollback.contradiction)).unwrap();
+    }
+}
+
+#[cfg(feature = "sdl2")]
+fn draw_overlay(wfc: &Wave<Tile<Sprite>>, context: &mut SdlDraw, tile_width: u32, tile_height: u32) {
+    let (width, height) = (
+        wfc.grid.width() as i32 * tile_width as i32,
+        wfc.grid.height() as i32 * tile_height as i32,
+    );
+    let canvas = &mut context.canvas;
+
+    if context.overlay.grid_lines {
+        canvas.set_draw_color(Color::RGBA(0, 0, 0, 96));
+
+        for x in (tile_width as i32..width).step_by(tile_width as usize) {
+            canvas.draw_line((x, 0), (x, height - 1)).unwrap();
+        }
+
+        for y in (tile_height as i32..height).step_by(tile_height as usize) {
+            canvas.draw_line((0, y), (width - 1, y)).unwrap();
+        }
+    }
+
+    if context.overlay.labels {
+        for (x, y, cell) in &wfc.grid {
+            let Some(label) = Text::label(cell, tile_width, tile_height) else {
+                continue;
+            };
+            let left = x as i32 * tile_width as i32;
+            let top = y as i32 * tile_height as i32;
+            let (label_width, label_height) = label.size();
+            let pixels: Vec<Point> = label
+                .pixels()
+                .map(|(dx, dy)| Point::new(left + dx as i32, top + dy as i32))
+                .collect();
+
+            canvas.set_draw_color(Color::RGBA(0, 0, 0, 160));
+            canvas.fill_rect(Rect::new(left, top, label_width, label_height)).unwrap();
+            canvas.set_draw_color(Color::WHITE);
+            canvas.draw_points(pixels.as_slice()).unwrap();
+        }
+    }
+}
+
+/// Seed, elapsed time, remaining cells, collapse rate and rollbacks in the top left
+/// corner, toggled with F1
+#[cfg(feature = "sdl2")]
+fn draw_stats(wfc: &Wave<Tile<Sprite>>, canvas: &mut Canvas<Window>) {
+    const SCALE: u32 = 2;
+    const MARGIN: i32 = 8;
+
+    let progress = wfc.progress();
+    let lines = [
+        format!("seed {}", wfc.seed()),
+        format!("time {:.1}s", progress.elapsed.as_secs_f64()),
+        format!("left {}", wfc.remaining()),
+        format!("rate {:.0}/s", progress.net_rate),
+        format!("rollbacks {}", progress.rollbacks),
+    ]
+    .map(|line| Text::new(&line, SCALE));
+
+    draw_lines(canvas, &lines, MARGIN, MARGIN);
+}
+
+/// Where the tile under the mouse was learned from, next to the mouse. Shown along
+/// with the stats.
+#[cfg(feature = "sdl2")]
+fn draw_inspector(wfc: &Wave<Tile<Sprite>>, context: &mut SdlDraw) {
+    const SCALE: u32 = 2;
+    const OFFSET: i32 = 16;
+
+    let (tile_width, tile_height) = context.tile_size;
+    let Some((x, y)) = context.hover.filter(|(x, y)| *x >= 0 && *y >= 0) else {
+        return;
+    };
+    let tile = wfc
+        .grid
+        .get(x as usize / tile_width as usize, y as usize / tile_height as usize)
+        .and_then(|cell| cell.collapsed());
+    let Some(origin) = tile.and_then(|tile| context.provenance.get(&tile.get_id())) else {
+        return;
+    };
+    let mut lines = Vec::new();
+
+    if let Some(source) = origin.source.as_ref().and_then(|source| source.file_name()) {
+        lines.push(source.to_string_lossy().into_owned());
+    }
+
+    if let Some(Position { x, y }) = origin.position {
+        lines.push(format!("column {x} row {y}"));
+    }
+
+    if origin.flipped {
+        lines.push("mirrored".to_string());
+    }
+
+    if origin.turns > 0 {
+        lines.push(format!("turned {}", origin.turns as u32 * 90));
+    }
+
+    let lines: Vec<Text> = lines.iter().map(|line| Text::new(line, SCALE)).collect();
+
+    draw_lines(&mut context.canvas, &lines, x + OFFSET, y + OFFSET);
+}
+
+/// White lines of text on a dark box with its top left at `left`, `top`
+#[cfg(feature = "sdl2")]
+fn draw_lines(canvas: &mut Canvas<Window>, lines: &[Text], left: i32, mut top: i32) {
+    let width = lines.iter().map(|line| line.size().0).max().unwrap_or(0);
+    let height: u32 = lines.iter().map(|line| line.size().1).sum();
+    let mut pixels = Vec::new();
+
+    if width == 0 || height == 0 {
+        return;
+    }
+
+    canvas.set_draw_color(Color::RGBA(0, 0, 0, 160));
+    canvas.fill_rect(Rect::new(left, top, width, height)).unwrap();
+
+    for line in lines {
+        pixels.extend(line.pixels().map(|(dx, dy)| Point::new(left + dx as i32, top + dy as i32)));
+        top += line.size().1 as i32;
+    }
+
+    canvas.set_draw_color(Color::WHITE);
+    canvas.draw_points(pixels.as_slice()).unwrap();
+}
+
+/// Overview of the whole grid for when it doesn't fit the window, every pixel covers a
+/// block of cells and shows whether most of them are collapsed, collapsing or untouched.
+/// Collapsed blocks take the average color of their tiles.
+#[cfg(feature = "sdl2")]
+struct Minimap {
+    scale: usize,
+    columns: usize,
+    rows: usize,
+    counts: Vec<[u32; 3]>,
+    /// Summed colors of the collapsed tiles per block
+    colors: Vec<[u32; 3]>,
+}
+
+#[cfg(feature = "sdl2")]
+impl Minimap {
+    /// Longest side in pixels
+    const SIZE: usize = 160;
+    const MARGIN: i32 = 8;
+    const COLORS: [Color; 3] = [
+        Color::RGB(200, 200, 200),
+        Color::RGB(0, 85, 128),
+        Color::RGB(20, 20, 20),
+    ];
+
+    fn new(width: usize, height: usize) -> Self {
+        let scale = width.max(height).div_ceil(Self::SIZE).max(1);
+        let columns = width.div_ceil(scale);
+        let rows = height.div_ceil(scale);
+
+        Self {
+            scale,
+            columns,
+            rows,
+            counts: vec![[0; 3]; columns * rows],
+            colors: vec![[0; 3]; columns * rows],
+        }
+    }
+
+    fn count(&mut self, x: usize, y: usize, cell: &SuperState<Tile<Sprite>>) {
+        let index = (y / self.scale) * self.columns + x / self.scale;
+        let state = if let Some(tile) = cell.collapsed() {
+            for (total, channel) in self.colors[index].iter_mut().zip(tile.value.average_color().0) {
+                *total += channel as u32;
+            }
+
+            0
+        } else if cell.entropy() < cell.base_entropy() {
+            1
+        } else {
+            2
+        };
+
+        self.counts[index][state] += 1;
+    }
+
+    /// Draws in the bottom right corner, `visible` is the amount of cells in view
+    fn draw(&self, canvas: &mut Canvas<Window>, visible: (usize, usize)) {
+        let (output_width, output_height) = canvas.output_size().unwrap();
+        let left = output_width as i32 - self.columns as i32 - Self::MARGIN;
+        let top = output_height as i32 - self.rows as i32 - Self::MARGIN;
+        let mut points: [Vec<Point>; 3] = Default::default();
+
+        let mut collapsed = Vec::new();
+
+        for (index, counts) in self.counts.iter().enumerate() {
+            let state = (0..3).max_by_key(|state| counts[*state]).unwrap();
+            let point = Point::new(
+                left + (index % self.columns) as i32,
+                top + (index / self.columns) as i32,
+            );
+
+            if state == 0 {
+                let [r, g, b] = self.colors[index].map(|total| (total / counts[0]) as u8);
+
+                collapsed.push((point, Color::RGB(r, g, b)));
+            } else {
+                points[state].push(point);
+            }
+        }
+
+        canvas.set_draw_color(Color::BLACK);
+        canvas
+            .fill_rect(Rect::new(
+                left - 1,
+                top - 1,
+                self.columns as u32 + 2,
+                self.rows as u32 + 2,
+            ))
+            .unwrap();
+
+        for (color, points) in Self::COLORS.iter().zip(&points) {
+            canvas.set_draw_color(*color);
+            canvas.draw_points(points.as_slice()).unwrap();
+        }
+
+        for (point, color) in collapsed {
+            canvas.set_draw_color(color);
+            canvas.draw_point(point).unwrap();
+        }
+
+        canvas.set_draw_color(Color::YELLOW);
+        canvas
+            .draw_rect(Rect::new(
+                left,
+                top,
+                visible.0.div_ceil(self.scale).clamp(1, self.columns) as u32,
+                visible.1.div_ceil(self.scale).clamp(1, self.rows) as u32,
+            ))
+            .unwrap();
+    }
+}
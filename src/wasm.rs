@@ -0,0 +1,181 @@
+//! WebAssembly bindings, built with the `wasm` feature for `wasm32-unknown-unknown` and
+//! run through `wasm-bindgen` (see the README). The page draws the cells itself, from
+//! `cells` and the tiles in `atlas_png`.
+//!
+//! In a Web Worker `run_for` solves in slices short enough for the worker to keep
+//! answering messages, and `changes` is what it posts back after each one. Both return
+//! typed arrays with their own buffer, they can be transferred instead of copied.
+
+use std::collections::HashMap;
+use std::io::Cursor;
+use std::sync::Arc;
+use std::time::Duration;
+
+use image::{GenericImageView, ImageOutputFormat, RgbaImage};
+use wasm_bindgen::prelude::*;
+use web_time::Instant;
+
+use crate::grid::{Grid, Size};
+use crate::sprite::Sprite;
+use crate::superstate::{Collapsable, SuperState};
+use crate::tile::Tile;
+use crate::wave::{Wave, WaveBuilder};
+
+/// How `WfcHandle` cuts up its input and what it generates
+#[wasm_bindgen]
+#[derive(Debug, Clone, Copy)]
+pub struct WfcOptions {
+    /// Width and height of a tile in the input, in pixels
+    pub tile_size: usize,
+    /// Output size in tiles
+    pub width: usize,
+    pub height: usize,
+    pub seed: u64,
+}
+
+#[wasm_bindgen]
+impl WfcOptions {
+    #[wasm_bindgen(constructor)]
+    pub fn new(tile_size: usize, width: usize, height: usize, seed: u64) -> Self {
+        Self {
+            tile_size,
+            width,
+            height,
+            seed,
+        }
+    }
+}
+
+/// A wave over the tiles of an input image, tiles are referred to by their position in
+/// the atlas
+#[wasm_bindgen]
+pub struct WfcHandle {
+    wave: Wave<Tile<Sprite>>,
+    tiles: Vec<Tile<Sprite>>,
+    lookup: HashMap<u64, i32>,
+    tile_size: (u32, u32),
+    /// `cells` as of the last `changes`
+    sent: Vec<i32>,
+}
+
+#[wasm_bindgen]
+impl WfcHandle {
+    /// Extracts the tiles of `image_bytes`, any format the image crate reads
+    #[wasm_bindgen(constructor)]
+    pub fn new(image_bytes: &[u8], options: &WfcOptions) -> Result<WfcHandle, JsError> {
+        let image = image::load_from_memory(image_bytes)?;
+        let tiles = Tile::from_image(&image, &Size::uniform(options.tile_size));
+
+        if tiles.is_empty() {
+            return Err(JsError::new("No tiles found"));
+        }
+
+        let base_state = SuperState::new(tiles.iter().cloned().map(Arc::new).collect());
+        let grid = Grid::new(options.width, options.height, &mut |_, _| base_state.clone());
+        let lookup = tiles
+            .iter()
+            .enumerate()
+            .map(|(index, tile)| (tile.get_id(), index as i32))
+            .collect();
+
+        Ok(Self {
+            sent: vec![-1; grid.size()],
+            wave: WaveBuilder::new(grid).seed(options.seed).build(),
+            tile_size: tiles[0].value.image().dimensions(),
+            tiles,
+            lookup,
+        })
+    }
+
+    /// Propagates pending changes and collapses one more cell, true once every cell is
+    /// collapsed
+    pub fn step(&mut self) -> bool {
+        if !self.wave.done() {
+            self.wave.tick();
+        }
+
+        self.wave.done()
+    }
+
+    /// Steps until the wave is done or `millis` milliseconds went by, true once every
+    /// cell is collapsed. A step can take longer on its own, the budget is only checked
+    /// in between.
+    pub fn run_for(&mut self, millis: f64) -> bool {
+        let start = Instant::now();
+        let budget = Duration::from_secs_f64(millis.max(0.0) / 1000.0);
+
+        while !self.wave.done() && start.elapsed() < budget {
+            self.wave.tick();
+        }
+
+        self.wave.done()
+    }
+
+    pub fn done(&self) -> bool {
+        self.wave.done()
+    }
+
+    pub fn remaining(&self) -> usize {
+        self.wave.remaining()
+    }
+
+    pub fn rollbacks(&self) -> usize {
+        self.wave.rollbacks()
+    }
+
+    /// Atlas index of every cell row by row, -1 where not collapsed
+    pub fn cells(&self) -> Vec<i32> {
+        self.wave
+            .grid
+            .iter()
+            .map(|(_, _, cell)| match cell.collapsed() {
+                Some(tile) => self.lookup[&tile.get_id()],
+                None => -1,
+            })
+            .collect()
+    }
+
+    /// The cells that changed since the last call as (cell index, atlas index) pairs
+    /// one after the other, -1 for cells that aren't collapsed anymore. The first call
+    /// has every collapsed cell.
+    pub fn changes(&mut self) -> Vec<i32> {
+        let cells = self.cells();
+        let mut changes = Vec::new();
+
+        for (index, (cell, sent)) in cells.iter().zip(&mut self.sent).enumerate() {
+            if cell != sent {
+                changes.extend([index as i32, *cell]);
+                *sent = *cell;
+            }
+        }
+
+        changes
+    }
+
+    /// Every tile side by side in a single row as a PNG
+    pub fn atlas_png(&self) -> Result<Vec<u8>, JsError> {
+        let (tile_width, tile_height) = self.tile_size;
+        let mut atlas = RgbaImage::new(tile_width * self.tiles.len() as u32, tile_height);
+
+        for (index, tile) in self.tiles.iter().enumerate() {
+            image::imageops::overlay(&mut atlas, tile.value.image(), index as i64 * tile_width as i64, 0);
+        }
+
+        png(&atlas)
+    }
+
+    /// The output as a PNG, cells that aren't collapsed yet are transparent
+    pub fn result_png(&self) -> Result<Vec<u8>, JsError> {
+        let (tile_width, tile_height) = self.tile_size;
+
+        png(&Tile::compose(&self.wave.grid, tile_width, tile_height))
+    }
+}
+
+fn png(image: &RgbaImage) -> Result<Vec<u8>, JsError> {
+    let mut bytes = Vec::new();
+
+    image.write_to(&mut Cursor::new(&mut bytes), ImageOutputFormat::Png)?;
+
+    Ok(bytes)
+}
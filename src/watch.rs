@@ -0,0 +1,75 @@
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use tracing::{error, info};
+
+use crate::generate::{Generator, Outcome};
+use crate::{load_input, Input};
+
+/// `--watch`, generates from the input over again on every change to its files until
+/// a generation stops early or the window is closed
+pub fn run(mut generator: Generator, mut input: Input, source: &Path) -> Outcome {
+    loop {
+        let files = watched_files(source, &input);
+        let outcome = generator.run(&input);
+
+        if outcome != Outcome::Written {
+            return outcome;
+        }
+
+        info!("Watching {} file(s) for changes", files.len());
+
+        input = match wait_for_change(&files, source, &mut generator) {
+            Some(input) => input,
+            None => return Outcome::Finished,
+        };
+
+        info!("Input changed, generating again");
+    }
+}
+
+/// Waits until the input files change and loads them again. None when the window is
+/// closed while waiting.
+fn wait_for_change(files: &[PathBuf], source: &Path, generator: &mut Generator) -> Option<Input> {
+    let mut modified = modified_times(files);
+
+    loop {
+        std::thread::sleep(Duration::from_millis(250));
+
+        if generator.closed() {
+            return None;
+        }
+
+        let current = modified_times(files);
+
+        if current == modified {
+            continue;
+        }
+
+        // editors may still be writing, a failed load is retried on the next change
+        modified = current;
+
+        match load_input(&source.to_string_lossy()) {
+            Ok(input) => return Some(input),
+            Err(e) => error!("Failed to reload input: {e}"),
+        }
+    }
+}
+
+/// Files to watch for changes, a config also depends on its tile images
+fn watched_files(source: &Path, input: &Input) -> Vec<PathBuf> {
+    let mut files = vec![source.to_path_buf()];
+
+    if let Input::Config(configs) = input {
+        files.extend(configs.iter().map(|config| config.image().to_path_buf()));
+    }
+
+    files
+}
+
+fn modified_times(files: &[PathBuf]) -> Vec<Option<SystemTime>> {
+    files
+        .iter()
+        .map(|file| std::fs::metadata(file).and_then(|meta| meta.modified()).ok())
+        .collect()
+}
@@ -1,81 +1,672 @@
-use std::collections::{HashSet, VecDeque};
-use std::hash::{BuildHasher, Hasher};
-
-use log::{trace, warn};
+use std::collections::{BTreeMap, BTreeSet, HashMap, VecDeque};
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::io;
+use std::sync::Arc;
+use std::time::Duration;
+#[cfg(not(feature = "wasm"))]
+use std::time::Instant;
+// std's Instant panics in the browser
+#[cfg(feature = "wasm")]
+use web_time::Instant;
+
+use enum_map::{enum_map, Enum};
 use rand::seq::IteratorRandom;
 use rand::{RngCore, SeedableRng};
 use rand_xorshift::XorShiftRng;
-
-use crate::grid::{Direction, Grid, Neighbors, Position};
+#[cfg(feature = "threaded")]
+use rayon::{
+    prelude::{IntoParallelRefIterator, ParallelIterator},
+    ThreadPool,
+};
+use tracing::{instrument, trace, warn};
+
+use crate::bitset::BitSet;
+use crate::constraint::Constraint;
+#[cfg(feature = "exact")]
+use crate::exact;
+#[cfg(feature = "gpu")]
+use crate::gpu;
+use crate::grid::{Direction, Direction8, Grid, Neighbors, Neighbors8, Position};
+use crate::profiling::{self, Phase};
+use crate::recorder::{Event, Header, Recorder};
+use crate::session::Session;
+use crate::sortedset::SortedSet;
 use crate::superstate::{Collapsable, SuperState};
+use crate::topology::Topology;
+
+type CellNeighbors = Option<Neighbors<BitSet>>;
+/// Neighbor sets handed to `Collapsable::test`
+pub type Set<T> = SortedSet<T>;
+/// Replaces the weight of a tile in a cell when it collapses, gets the cell and the tile
+pub type WeightModifier<T> = Box<dyn Fn(&Position, &T) -> f64 + Send + Sync>;
+
+/// Minimum amount of explicit collapses between two checkpoints
+const CHECKPOINT_INTERVAL: usize = 16;
+/// Upper bound on checkpoints taken over a full grid, larger grids space them out
+const CHECKPOINTS_PER_GRID: usize = 64;
+/// Maximum amount of checkpoints kept around, older ones are dropped first
+const MAX_CHECKPOINTS: usize = 8;
+/// Smallest wavefront that's checked in parallel, smaller ones aren't worth the threads
+#[cfg(feature = "threaded")]
+const PARALLEL_FRONT: usize = 64;
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+enum CollapseReason {
+    Implicit,
+    Explicit,
+}
 
-/// https://github.com/chris-morgan/anymap/blob/2e9a5704/src/lib.rs#L599
-#[derive(Debug, Clone, Copy, Default)]
-pub struct NoOpHasher(u64);
+/// Controls how many explicit collapses are undone on a contradiction
+#[derive(Debug, Clone, Copy)]
+pub struct RollbackSchedule {
+    /// Collapses undone on the first contradiction after making progress
+    pub initial_step: usize,
+    /// Hundredths of a collapse added to the rollback for every contradiction without
+    /// progress, independent of `initial_step`. The growth is linear, the default of 50
+    /// undoes one more collapse every other contradiction.
+    pub growth_percent: usize,
+    /// Rollbacks growing past this restore a checkpoint or reset instead
+    pub reset_threshold: Option<usize>,
+    /// Start over with a fresh seed after this many contradictions times the next
+    /// number of the Luby sequence (1, 1, 2, 1, 1, 2, 4, ...)
+    pub restart_unit: Option<usize>,
+    /// Min-conflicts moves tried on the contradicting cells before restoring a
+    /// checkpoint or resetting, keeping the rest of the grid
+    pub repair_steps: Option<usize>,
+}
 
-impl Hasher for NoOpHasher {
-    fn write(&mut self, _bytes: &[u8]) {
-        unimplemented!("This NoOpHasher can only handle u64s")
+impl Default for RollbackSchedule {
+    fn default() -> Self {
+        Self {
+            initial_step: 1,
+            growth_percent: 50,
+            reset_threshold: None,
+            restart_unit: None,
+            repair_steps: None,
+        }
     }
+}
+
+/// How a cell differs from this wave to the other one, see `Wave::diff`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Change<I> {
+    /// Open here, collapsed to this tile in the other wave
+    Collapsed(I),
+    /// Collapsed to this tile here, open in the other wave
+    Uncollapsed(I),
+    /// Collapsed to different tiles, this wave's tile first
+    Replaced(I, I),
+    /// Open in both with a different number of possible tiles, this wave's first
+    Entropy(usize, usize),
+}
 
-    fn write_u64(&mut self, i: u64) {
-        self.0 = i;
+/// How far along a wave is, see `Wave::progress`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Progress {
+    pub collapsed: usize,
+    pub total: usize,
+    /// Contradictions run into so far
+    pub rollbacks: usize,
+    /// Cells collapsed per second since the wave was built, minus the ones rollbacks
+    /// took back
+    pub net_rate: f64,
+    /// Time since the wave was built
+    pub elapsed: Duration,
+}
+
+impl Progress {
+    pub fn fraction(&self) -> f64 {
+        self.collapsed as f64 / self.total.max(1) as f64
     }
 
-    fn finish(&self) -> u64 {
-        self.0
+    /// Time left at the net rate, None until the wave made some progress
+    pub fn eta(&self) -> Option<Duration> {
+        (self.net_rate > 0.0).then(|| Duration::from_secs_f64((self.total - self.collapsed) as f64 / self.net_rate))
     }
 }
 
-impl BuildHasher for NoOpHasher {
-    type Hasher = Self;
+/// Cells a contradiction undid, see `Wave::take_rollbacks`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Rollback {
+    /// The cell that ran out of tiles
+    pub contradiction: Position,
+    /// Cells that were reset or changed to get out of it, every cell when the whole grid
+    /// was reset or a checkpoint restored
+    pub cells: Vec<Position>,
+}
 
-    fn build_hasher(&self) -> Self::Hasher {
-        *self
-    }
+/// What picking a tile for a cell did to the rest of the grid, see `Wave::propagation`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Propagation {
+    /// The cell a tile was picked for
+    pub position: Position,
+    /// Other cells that lost possible tiles before the grid settled
+    pub cells: usize,
+    /// Steps from `position` to the furthest of those cells, counted along rows and
+    /// columns
+    pub reach: usize,
+    /// Whether it ended in a contradiction, `cells` and `reach` stop counting there
+    pub contradicted: bool,
 }
 
-type CellNeighbors<T> = Option<Neighbors<Set<<T as Collapsable>::Identifier>>>;
-pub type Set<T> = HashSet<T, NoOpHasher>;
+/// Solver state to go back to, see `Wave::snapshot`
+pub struct Snapshot<T>
+where
+    T: Collapsable,
+{
+    grid: Grid<SuperState<T>>,
+    collapsed: Vec<(Position, CollapseReason)>,
+    pins: BTreeMap<Position, T::Identifier>,
+    rng: XorShiftRng,
+    area_rngs: HashMap<usize, XorShiftRng>,
+}
 
-#[derive(Debug, PartialEq, Eq)]
-enum CollapseReason {
-    Implicit,
-    Explicit,
+/// Snapshot of a settled wave (empty stack) that can be restored cheaply
+struct Checkpoint<T>
+where
+    T: Collapsable,
+{
+    grid: Grid<SuperState<T>>,
+    collapsed: Vec<(Position, CollapseReason)>,
+    unsettled: Grid<bool>,
+    explicit: usize,
+    rng: XorShiftRng,
 }
 
-pub struct Wave<T>
+/// Solver over the cells of `grid`. Which cells neighbor each other comes from `G`, the
+/// rectangle of the grid unless the wave is built `WaveBuilder::with_topology`.
+pub struct Wave<T, G = Grid<()>>
 where
     T: Collapsable,
 {
     pub grid: Grid<SuperState<T>>,
+    /// Neighbors of every cell, the cells are its nodes in row-major order
+    topology: G,
     grid_base: Grid<SuperState<T>>,
     stack: VecDeque<Position>,
+    /// Row-major indexes still to be visited by a sweep after a rollback
+    sweep: BTreeSet<usize>,
+    sweep_next: Option<usize>,
+    /// Cells that haven't been checked against all of their neighbors since their last reset
+    unsettled: Grid<bool>,
     // todo tmp pub
-    pub data: Grid<CellNeighbors<T>>,
+    pub data: Grid<CellNeighbors>,
+    /// Allowed neighbors per direction for every dense tile index
+    rules: Vec<Neighbors<BitSet>>,
+    /// Cells per entropy, kept up to date as cells change
+    entropies: Vec<usize>,
+    /// Allowed diagonal neighbors like `rules`, None unless diagonals are checked
+    diagonal_rules: Option<Vec<Neighbors8<BitSet>>>,
     collapsed: Vec<(Position, CollapseReason)>,
-    rng: Box<dyn RngCore>,
+    rng: XorShiftRng,
+    seed: u64,
+    /// Sharpens (below 1) or flattens (above 1) the tile weights when collapsing
+    temperature: f64,
+    /// Weight of every dense index with the temperature applied, looked up when there's
+    /// no weight modifier
+    weights: Vec<f64>,
+    weight_modifier: Option<WeightModifier<T>>,
+    constraints: Vec<Box<dyn Constraint<T>>>,
+    /// Cells the user fixed to a tile, kept through rollbacks, restores and resets
+    pins: BTreeMap<Position, T::Identifier>,
+    /// Key of the area every cell starts out in, see `index_areas`. None unless areas
+    /// get their own rng.
+    area_keys: Option<Grid<usize>>,
+    area_rngs: HashMap<usize, XorShiftRng>,
     last_rollback: usize,
-    rollback_penalty: f64,
+    /// Contradictions since the last time progress was made
+    rollback_penalty: usize,
+    rollback_schedule: RollbackSchedule,
+    checkpoints: Vec<Checkpoint<T>>,
+    checkpoint_interval: usize,
+    checkpoint_floor: usize,
+    restores: usize,
+    rollbacks: usize,
+    restarts: usize,
+    /// Contradictions since the last restart
+    restart_penalty: usize,
+    recorder: Option<Recorder>,
+    /// Pool wavefronts are checked on, None keeps propagation on the calling thread
+    #[cfg(feature = "threaded")]
+    pool: Option<Arc<ThreadPool>>,
+    /// Sweeps the grid once `gpu_front` cells are queued, None propagates on the CPU
+    #[cfg(feature = "gpu")]
+    gpu: Option<gpu::Propagator>,
+    #[cfg(feature = "gpu")]
+    gpu_front: usize,
+    /// Set when the GPU ran into a contradiction, the CPU propagates until the queue is
+    /// short again
+    #[cfg(feature = "gpu")]
+    gpu_paused: bool,
+    started: Instant,
+    /// Contradictions per cell, kept through rollbacks and restarts
+    contradictions: Grid<usize>,
+    last_contradiction: Option<Position>,
+    /// Every explicit collapse, kept through rollbacks and restarts
+    propagation: Vec<Propagation>,
+    /// Whether the last of `propagation` is still spreading
+    propagating: bool,
+    /// Length of `propagation` when every cell was last counted towards a pick, so a
+    /// cell that's narrowed down more than once counts once
+    propagation_marks: Grid<usize>,
+    /// Rollbacks not yet taken by `take_rollbacks`, None unless they're tracked
+    rollback_log: Option<Vec<Rollback>>,
+    rolling_back: bool,
     // tracker: PropegationTracker,
 }
 
+pub struct WaveBuilder<T, G = Grid<()>>
+where
+    T: Collapsable,
+{
+    grid: Grid<SuperState<T>>,
+    topology: G,
+    seed: u64,
+    area_seeds: bool,
+    diagonals: bool,
+    track_rollbacks: bool,
+    temperature: f64,
+    weight_modifier: Option<WeightModifier<T>>,
+    constraints: Vec<Box<dyn Constraint<T>>>,
+    rollback_schedule: RollbackSchedule,
+    recorder: Option<Recorder>,
+    #[cfg(feature = "threaded")]
+    pool: Option<Arc<ThreadPool>>,
+    #[cfg(feature = "gpu")]
+    gpu: Option<usize>,
+}
+
+impl<T> WaveBuilder<T>
+where
+    T: Collapsable,
+{
+    pub fn new(grid: Grid<SuperState<T>>) -> Self {
+        let topology = Grid::new(grid.width(), grid.height(), &mut |_, _| ());
+
+        Self::with_topology(grid, topology)
+    }
+
+    /// Propagates on the GPU once `min_front` cells are queued, `gpu::MIN_FRONT` is a
+    /// good start. The wave keeps propagating on the CPU when no GPU can be set up. The
+    /// shader only knows rectangles, so waves with their own topology can't use it.
+    #[cfg(feature = "gpu")]
+    pub fn gpu(mut self, min_front: usize) -> Self {
+        self.gpu = Some(min_front);
+        self
+    }
+}
+
+impl<T, G> WaveBuilder<T, G>
+where
+    T: Collapsable,
+    G: Topology<Label = Direction>,
+{
+    /// Solves over `topology`, a node for every cell of `grid` in row-major order, rather
+    /// than the rectangle of the grid. Cells are still stored, drawn and constrained by
+    /// their place in the grid, only propagation follows the topology, so a torus,
+    /// portals or a mesh with its faces laid out in a grid all work. Every edge needs
+    /// one back labeled with the inverted direction, the rules are checked from both
+    /// sides. Diagonals are taken from the layout of the grid.
+    pub fn with_topology(grid: Grid<SuperState<T>>, topology: G) -> Self {
+        assert_eq!(topology.nodes(), grid.size(), "the topology needs a node for every cell");
+
+        Self {
+            grid,
+            topology,
+            seed: 0,
+            area_seeds: false,
+            diagonals: false,
+            track_rollbacks: false,
+            temperature: 1.0,
+            weight_modifier: None,
+            constraints: Vec::new(),
+            rollback_schedule: Default::default(),
+            recorder: None,
+            #[cfg(feature = "threaded")]
+            pool: None,
+            #[cfg(feature = "gpu")]
+            gpu: None,
+        }
+    }
+
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    /// Gives every disconnected area of uncollapsed cells its own rng, derived from the
+    /// seed and the area, so changes in one area don't change the outcome of the others
+    pub fn area_seeds(mut self, enabled: bool) -> Self {
+        self.area_seeds = enabled;
+        self
+    }
+
+    /// Also checks the corners of every cell against `Collapsable::test_diagonal`. The
+    /// repair step and the exact backend only look at the sides.
+    pub fn diagonals(mut self, enabled: bool) -> Self {
+        self.diagonals = enabled;
+        self
+    }
+
+    /// Keeps the cells every rollback touched until they're taken with
+    /// `Wave::take_rollbacks`, for renderers that show where the tileset struggles
+    pub fn track_rollbacks(mut self, enabled: bool) -> Self {
+        self.track_rollbacks = enabled;
+        self
+    }
+
+    /// Raises the tile weights to the power of `1 / temperature` when collapsing. Near 0
+    /// the heaviest tile nearly always wins, 1 follows the sample and higher values give
+    /// every tile closer to the same odds.
+    pub fn temperature(mut self, temperature: f64) -> Self {
+        assert!(temperature > 0.0, "temperature has to be above zero");
+
+        self.temperature = temperature;
+        self
+    }
+
+    /// Asks `modifier` for the weight of every tile a cell can still be when it collapses,
+    /// instead of `Collapsable::get_weight`. Gradients, biome maps and the like can
+    /// depend on the position this way. The temperature is applied afterwards.
+    pub fn weight_modifier<F>(mut self, modifier: F) -> Self
+    where
+        F: Fn(&Position, &T) -> f64 + Send + Sync + 'static,
+    {
+        self.weight_modifier = Some(Box::new(modifier));
+        self
+    }
+
+    /// Adds a rule on top of the neighbor rules, see `Constraint`. The exact backend and
+    /// the repair step don't know about constraints.
+    pub fn constraint<C: Constraint<T> + 'static>(mut self, constraint: C) -> Self {
+        self.constraints.push(Box::new(constraint));
+        self
+    }
+
+    pub fn rollback_schedule(mut self, schedule: RollbackSchedule) -> Self {
+        self.rollback_schedule = schedule;
+        self
+    }
+
+    /// Record every solver action, see `Wave::apply` for replaying them
+    pub fn recorder(mut self, recorder: Recorder) -> Self {
+        self.recorder = Some(recorder);
+        self
+    }
+
+    /// Checks large wavefronts on `pool`. Without one the wave never touches rayon's
+    /// global pool and propagates on the thread that ticks it, hosts decide how many
+    /// threads the solver gets. Waves can share a pool.
+    #[cfg(feature = "threaded")]
+    pub fn thread_pool(mut self, pool: Arc<ThreadPool>) -> Self {
+        self.pool = Some(pool);
+        self
+    }
+
+    pub fn build(self) -> Wave<T, G> {
+        let mut wave = Wave::with_topology(self.grid, self.topology, self.seed);
+
+        wave.rollback_schedule = self.rollback_schedule;
+        wave.area_keys = self.area_seeds.then(|| wave.index_areas());
+        wave.diagonal_rules = self.diagonals.then(|| wave.index_diagonals());
+        wave.temperature = self.temperature;
+        wave.weights = wave.index_weights();
+        wave.rollback_log = self.track_rollbacks.then(Vec::new);
+        wave.weight_modifier = self.weight_modifier;
+        wave.constraints = self.constraints;
+        wave.recorder = self.recorder;
+        #[cfg(feature = "threaded")]
+        {
+            wave.pool = self.pool;
+        }
+        #[cfg(feature = "gpu")]
+        if let Some(min_front) = self.gpu {
+            match gpu::Propagator::new(&wave.rules, wave.grid.width(), wave.grid.height()) {
+                Ok(propagator) => {
+                    wave.gpu = Some(propagator);
+                    wave.gpu_front = min_front;
+                }
+                Err(e) => warn!("Propagating on the CPU, the GPU can't be used: {e}"),
+            }
+        }
+        wave.queue_constraints();
+
+        let header = wave.header();
+
+        if let Some(recorder) = wave.recorder.as_mut() {
+            recorder.header(header);
+        }
+
+        wave
+    }
+}
+
 impl<T> Wave<T>
 where
     T: Collapsable,
 {
     pub fn new(grid: Grid<SuperState<T>>, seed: u64) -> Self {
-        Self {
+        let topology = Grid::new(grid.width(), grid.height(), &mut |_, _| ());
+
+        Self::with_topology(grid, topology, seed)
+    }
+}
+
+impl<T, G> Wave<T, G>
+where
+    T: Collapsable,
+    G: Topology<Label = Direction>,
+{
+    /// Like `new` with the neighbors of every cell taken from `topology`, see
+    /// `WaveBuilder::with_topology`
+    pub fn with_topology(mut grid: Grid<SuperState<T>>, topology: G, seed: u64) -> Self {
+        let rules = Self::index_tiles(&mut grid);
+        let entropies = count_entropies(&grid, rules.len());
+
+        let mut wave = Self {
+            topology,
+            rules,
+            entropies,
+            diagonal_rules: None,
             stack: VecDeque::with_capacity(grid.size()),
+            sweep: BTreeSet::new(),
+            sweep_next: None,
+            unsettled: Grid::new(grid.width(), grid.height(), &mut |_, _| true),
+            contradictions: Grid::new(grid.width(), grid.height(), &mut |_, _| 0),
+            propagation_marks: Grid::new(grid.width(), grid.height(), &mut |_, _| 0),
             collapsed: Vec::with_capacity(grid.size()),
+            checkpoint_interval: CHECKPOINT_INTERVAL.max(grid.size() / CHECKPOINTS_PER_GRID),
             data: Grid::new(grid.width(), grid.height(), &mut |_, _| Default::default()),
             grid_base: grid.clone(),
             grid,
-            rng: Box::new(XorShiftRng::seed_from_u64(seed)),
+            rng: XorShiftRng::seed_from_u64(seed),
+            seed,
+            temperature: 1.0,
+            weights: Vec::new(),
+            weight_modifier: None,
+            constraints: Vec::new(),
+            pins: BTreeMap::new(),
+            area_keys: None,
+            area_rngs: HashMap::new(),
             last_rollback: 0,
-            rollback_penalty: 0.0,
+            rollback_penalty: 0,
+            rollback_schedule: Default::default(),
+            checkpoints: Vec::new(),
+            checkpoint_floor: 0,
+            restores: 0,
+            rollbacks: 0,
+            restarts: 0,
+            restart_penalty: 0,
+            recorder: None,
+            #[cfg(feature = "threaded")]
+            pool: None,
+            #[cfg(feature = "gpu")]
+            gpu: None,
+            #[cfg(feature = "gpu")]
+            gpu_front: usize::MAX,
+            #[cfg(feature = "gpu")]
+            gpu_paused: false,
+            started: Instant::now(),
+            last_contradiction: None,
+            propagation: Vec::new(),
+            propagating: false,
+            rollback_log: None,
+            rolling_back: false,
             // tracker: Default::default(),
+        };
+
+        wave.weights = wave.index_weights();
+        wave
+    }
+
+    /// Maps tile ids to dense indexes (in id order) and turns `Collapsable::test` into
+    /// bitsets of allowed neighbors, so propagation doesn't have to hash any ids. Tiles
+    /// with their own dense index (`Collapsable::index`) keep it.
+    fn index_tiles(grid: &mut Grid<SuperState<T>>) -> Vec<Neighbors<BitSet>> {
+        let tiles: Vec<Option<Arc<T>>> = match T::universe_size() {
+            Some(size) => {
+                let mut tiles = vec![None; size];
+
+                Self::assign_indexes(grid, |tile| tile.index().expect("tiles with a universe have an index"));
+
+                for (_, _, cell) in grid.iter() {
+                    for (tile, index) in cell.possible.iter().zip(cell.indexes()) {
+                        tiles[*index].get_or_insert_with(|| tile.clone());
+                    }
+                }
+
+                tiles
+            }
+            None => {
+                let mut tiles: Vec<Arc<T>> = Vec::new();
+                let mut lookup: HashMap<T::Identifier, usize> = HashMap::new();
+
+                for (_, _, cell) in grid.iter() {
+                    for tile in cell.possible.iter() {
+                        lookup.entry(tile.get_id()).or_insert_with(|| {
+                            tiles.push(tile.clone());
+                            0
+                        });
+                    }
+                }
+
+                tiles.sort_by_key(|t| t.get_id());
+
+                for (index, tile) in tiles.iter().enumerate() {
+                    lookup.insert(tile.get_id(), index);
+                }
+
+                Self::assign_indexes(grid, |tile| lookup[&tile.get_id()]);
+
+                tiles.into_iter().map(Some).collect()
+            }
+        };
+
+        let empty: Neighbors<BitSet> = enum_map! { _ => BitSet::new(tiles.len()) };
+        let mut rules = vec![empty; tiles.len()];
+
+        for (index, tile) in tiles.iter().enumerate() {
+            let Some(tile) = tile else {
+                continue;
+            };
+
+            for direction in (0..Direction::LENGTH).map(Direction::from_usize) {
+                let mut neighbors: Neighbors<Set<T::Identifier>> = Default::default();
+
+                neighbors[direction].insert(tile.get_id());
+
+                for (other, rule) in tiles.iter().zip(rules.iter_mut()) {
+                    if other.as_ref().is_some_and(|other| other.test(&neighbors)) {
+                        rule[direction].insert(index);
+                    }
+                }
+            }
+        }
+
+        rules
+    }
+
+    /// `SuperState::assign_indexes` for every cell, cells that share a state get one
+    /// indexed copy to share in its place
+    fn assign_indexes<F: Fn(&T) -> usize>(grid: &mut Grid<SuperState<T>>, lookup: F) {
+        // by the address of the shared states, the originals are kept so it can't be reused
+        let mut indexed: HashMap<usize, (SuperState<T>, SuperState<T>)> = HashMap::new();
+
+        for cell in grid.rows_mut().flatten() {
+            let key = Arc::as_ptr(&cell.possible) as usize;
+
+            match indexed.get(&key) {
+                Some((_, done)) => *cell = done.clone(),
+                None => {
+                    let original = cell.clone();
+
+                    cell.assign_indexes(&lookup);
+                    indexed.insert(key, (original, cell.clone()));
+                }
+            }
+        }
+    }
+
+    /// `Collapsable::get_weight` of every dense index raised by the temperature, so
+    /// collapsing doesn't do it for every candidate
+    fn index_weights(&self) -> Vec<f64> {
+        let mut weights = vec![0.0; self.rules.len()];
+
+        for (_, _, cell) in &self.grid_base {
+            for (tile, index) in cell.possible.iter().zip(cell.indexes()) {
+                weights[*index] = temper(tile.get_weight(), self.temperature);
+            }
+        }
+
+        weights
+    }
+
+    /// Turns `Collapsable::test_diagonal` into bitsets, like `index_tiles` does for the sides
+    fn index_diagonals(&self) -> Vec<Neighbors8<BitSet>> {
+        let mut tiles: Vec<Option<Arc<T>>> = vec![None; self.rules.len()];
+
+        for (_, _, cell) in &self.grid_base {
+            for (tile, index) in cell.possible.iter().zip(cell.indexes()) {
+                tiles[*index] = Some(tile.clone());
+            }
+        }
+
+        let empty: Neighbors8<BitSet> = enum_map! { _ => BitSet::new(tiles.len()) };
+        let mut rules = vec![empty; tiles.len()];
+
+        // a tileset with its own dense indexes can leave some of them unused
+        for (tile, rule) in tiles.iter().zip(rules.iter_mut()) {
+            let Some(tile) = tile else {
+                continue;
+            };
+
+            for direction in Direction8::DIAGONALS {
+                for (index, other) in tiles.iter().enumerate() {
+                    if other.as_ref().is_some_and(|other| tile.test_diagonal(direction, &other.get_id())) {
+                        rule[direction].insert(index);
+                    }
+                }
+            }
         }
+
+        rules
+    }
+
+    /// Possible tiles of the diagonal neighbors, None unless diagonals are checked
+    fn diagonal_sets(&self, x: usize, y: usize) -> Option<Vec<(Direction8, BitSet)>> {
+        self.diagonal_rules.as_ref()?;
+
+        let sets = Direction8::DIAGONALS
+            .into_iter()
+            .filter_map(|direction| {
+                let neighbor = self.grid.get_neighbor_position8(x, y, direction)?;
+
+                Some((direction, self.grid.get(neighbor.x, neighbor.y).unwrap().mask(self.rules.len())))
+            })
+            .collect();
+
+        Some(sets)
     }
 
     pub fn done(&self) -> bool {
@@ -86,189 +677,1373 @@ where
         self.grid.size() - self.collapsed.len()
     }
 
-    pub fn tick(&mut self) -> bool {
-        let mut worked = false;
+    /// Unlike `remaining` the rate accounts for rollbacks, it doesn't get ahead of
+    /// itself on a tileset that keeps running into contradictions
+    pub fn progress(&self) -> Progress {
+        let collapsed = self.collapsed.len();
+        let elapsed = self.started.elapsed();
+
+        Progress {
+            collapsed,
+            total: self.grid.size(),
+            rollbacks: self.rollbacks,
+            net_rate: collapsed as f64 / elapsed.as_secs_f64().max(f64::EPSILON),
+            elapsed,
+        }
+    }
+
+    /// Hash of the size and the tile in every cell, equal for waves that solved to the
+    /// same output. Nothing until the wave is done. Only meant for comparing waves within
+    /// one run, the hash may change between builds.
+    pub fn fingerprint(&self) -> Option<u64> {
+        if !self.done() {
+            return None;
+        }
+
+        let mut hasher = DefaultHasher::new();
+
+        (self.grid.width(), self.grid.height()).hash(&mut hasher);
+
+        for (_, _, cell) in &self.grid {
+            cell.collapsed()?.get_id().hash(&mut hasher);
+        }
+
+        Some(hasher.finish())
+    }
+
+    /// How many cells every tile is placed in so far, tiles without any are left out
+    pub fn placements(&self) -> BTreeMap<T::Identifier, usize> {
+        let mut placements = BTreeMap::new();
+
+        for (_, _, cell) in &self.grid {
+            if let Some(tile) = cell.collapsed() {
+                *placements.entry(tile.get_id()).or_insert(0) += 1;
+            }
+        }
+
+        placements
+    }
+
+    /// Cells per entropy, the count of cells with `n` possible tiles is at `n`. Kept up
+    /// to date as the wave goes, so it's cheap to ask for every frame.
+    pub fn entropy_histogram(&self) -> &[usize] {
+        &self.entropies
+    }
+
+    /// Tiles still possible in the cell at `x`, `y`, nothing when it's outside the grid
+    pub fn possibilities(&self, x: usize, y: usize) -> impl Iterator<Item = &T> {
+        self.cell(x, y)
+            .into_iter()
+            .flat_map(|cell| cell.possible.iter().map(|tile| tile.as_ref()))
+    }
+
+    /// Whether the cell at `x`, `y` is down to a single tile, false outside the grid
+    pub fn is_collapsed(&self, x: usize, y: usize) -> bool {
+        self.cell(x, y).is_some_and(|cell| cell.entropy() == 1)
+    }
+
+    /// Fixes the cell to the tile with `id` until it's unpinned, the solver works around
+    /// it. Fails when the tile isn't possible in the cell.
+    pub fn pin(&mut self, x: usize, y: usize, id: T::Identifier) -> Result<(), &'static str> {
+        let cell = self.cell(x, y).ok_or("position is outside the grid")?;
+
+        if !cell.possible.iter().any(|tile| tile.get_id() == id) {
+            return Err("tile isn't possible in this cell");
+        }
+
+        self.pins.insert(Position::new(x, y), id);
+        self.propagating = false;
+        self.apply_pin(x, y);
+        self.reindex_areas();
+
+        Ok(())
+    }
+
+    /// Lets the solver pick the tile of a pinned cell again, the cells it constrained
+    /// are opened up and propagated again
+    pub fn unpin(&mut self, x: usize, y: usize) -> Result<(), &'static str> {
+        let position = Position::new(x, y);
+
+        self.pins.remove(&position).ok_or("cell isn't pinned")?;
+        self.propagating = false;
+        self.collapsed.retain(|(collapsed, _)| *collapsed != position);
+        // checkpoints taken since still have the cell collapsed and count it as explicit
+        self.checkpoints
+            .retain(|checkpoint| checkpoint.collapsed.iter().all(|(collapsed, _)| *collapsed != position));
+        self.data = Grid::new(self.grid.width(), self.grid.height(), &mut |_, _| Default::default());
+        self.rollback_propegate(x, y);
+        self.requeue_frontier();
+        self.sync_constraints();
+        self.reindex_areas();
+
+        Ok(())
+    }
+
+    pub fn pins(&self) -> impl Iterator<Item = (Position, &T::Identifier)> {
+        self.pins.iter().map(|(position, id)| (*position, id))
+    }
+
+    /// Copy of the solver state for `restore`, costs about as much as a copy of the grid
+    pub fn snapshot(&self) -> Snapshot<T> {
+        Snapshot {
+            grid: self.grid.clone(),
+            collapsed: self.collapsed.clone(),
+            pins: self.pins.clone(),
+            rng: self.rng.clone(),
+            area_rngs: self.area_rngs.clone(),
+        }
+    }
+
+    /// Goes back to a snapshot. It may have been taken halfway through propagating, so
+    /// every cell is checked against its neighbors again.
+    pub fn restore(&mut self, snapshot: Snapshot<T>) {
+        self.grid = snapshot.grid;
+        self.entropies = count_entropies(&self.grid, self.rules.len());
+
+        if let Some(recorder) = self.recorder.as_mut() {
+            for (x, y, cell) in &self.grid {
+                recorder.record(Event::Assign(Position::new(x, y), cell.indexes().to_vec()));
+            }
+        }
+
+        self.collapsed = snapshot.collapsed;
+        self.propagating = false;
+        self.pins = snapshot.pins;
+        self.rng = snapshot.rng;
+        self.area_rngs = snapshot.area_rngs;
+        self.reindex_areas();
+        self.checkpoints.clear();
+        self.checkpoint_floor = 0;
+        self.data = Grid::new(self.grid.width(), self.grid.height(), &mut |_, _| Default::default());
+        self.unsettled = Grid::new(self.grid.width(), self.grid.height(), &mut |_, _| true);
+        self.clear_queue();
+        self.requeue_frontier();
+        self.rollback_penalty = 0;
+        self.last_rollback = self.collapsed.len();
+        self.sync_constraints();
+    }
+
+    /// Everything needed to continue from here in a later run, see `load_session`
+    pub fn session(&self) -> Session {
+        let dense = |position: &Position, id: &T::Identifier| {
+            let base = self.grid_base.get(position.x, position.y).unwrap();
+            let index = base.possible.iter().position(|tile| tile.get_id() == *id).unwrap();
+
+            base.indexes()[index]
+        };
+
+        Session {
+            header: self.header(),
+            seed: self.seed,
+            rng_seed: self.rng.clone().next_u64(),
+            pins: self.pins.iter().map(|(position, id)| (*position, dense(position, id))).collect(),
+            collapsed: self
+                .collapsed
+                .iter()
+                .map(|(position, reason)| (*position, *reason == CollapseReason::Explicit))
+                .collect(),
+            cells: self.grid.iter().map(|(_, _, cell)| cell.indexes().to_vec()).collect(),
+        }
+    }
+
+    /// Continues from a saved session, which has to come from a wave with the same grid
+    /// size and tileset
+    pub fn load_session(&mut self, session: Session) -> Result<(), &'static str> {
+        if session.header != self.header() {
+            return Err("session is for a different grid size or tileset");
+        }
+
+        let mut pins = BTreeMap::new();
+
+        for (pinned, index) in session.pins {
+            let base = self.grid_base.get(pinned.x, pinned.y).unwrap();
+            let position = base
+                .indexes()
+                .iter()
+                .position(|other| *other == index)
+                .ok_or("pinned tile isn't possible in its cell")?;
+
+            pins.insert(pinned, base.possible[position].get_id());
+        }
+
+        let mut cells = session.cells.into_iter();
+        let grid = Grid::new(self.grid.width(), self.grid.height(), &mut |x, y| {
+            let tiles = cells.next().unwrap();
+            let mut cell = self.grid_base.get(x, y).unwrap().clone();
+
+            cell.retain_indexes(|index| tiles.binary_search(&index).is_ok());
+            cell
+        });
+        let collapsed = session
+            .collapsed
+            .into_iter()
+            .map(|(position, explicit)| match explicit {
+                true => (position, CollapseReason::Explicit),
+                false => (position, CollapseReason::Implicit),
+            })
+            .collect();
+
+        self.seed = session.seed;
+        self.restore(Snapshot {
+            grid,
+            collapsed,
+            pins,
+            rng: XorShiftRng::seed_from_u64(session.rng_seed),
+            area_rngs: HashMap::new(),
+        });
+
+        Ok(())
+    }
+
+    /// Narrows a pinned cell down to its tile and propagates that
+    fn apply_pin(&mut self, x: usize, y: usize) {
+        let id = &self.pins[&Position::new(x, y)];
+        let base = self.grid_base.get(x, y).unwrap();
+        let Some(index) = base
+            .possible
+            .iter()
+            .position(|tile| tile.get_id() == *id)
+            .map(|position| base.indexes()[position])
+        else {
+            return;
+        };
+        let cell = self.grid.get(x, y).unwrap();
+
+        if cell.entropy() == 1 && cell.indexes()[0] == index {
+            return;
+        }
+
+        if cell.entropy() > 1 {
+            self.collapsed.push((Position::new(x, y), CollapseReason::Explicit));
+        }
+
+        let mut cell = base.clone();
+
+        cell.retain_indexes(|other| other == index);
+        self.set_cell(x, y, cell);
+        self.record(|| Event::Assign(Position::new(x, y), vec![index]));
+        self.notify_collapse(x, y);
+        self.mark(x, y);
+    }
+
+    fn apply_pins(&mut self) {
+        let pinned: Vec<Position> = self.pins.keys().copied().collect();
+
+        for Position { x, y } in pinned {
+            self.apply_pin(x, y);
+        }
+    }
+
+    /// Cells that differ between the two waves in row-major order, tiles are compared
+    /// by id so waves with different tilesets can be compared too. Only the area both
+    /// grids cover is compared.
+    pub fn diff(&self, other: &Self) -> Vec<(Position, Change<T::Identifier>)> {
+        let mut changes = Vec::new();
+
+        for y in 0..self.grid.height().min(other.grid.height()) {
+            for x in 0..self.grid.width().min(other.grid.width()) {
+                let (before, after) = (self.cell(x, y).unwrap(), other.cell(x, y).unwrap());
+                let change = match (before.collapsed(), after.collapsed()) {
+                    (None, Some(tile)) => Change::Collapsed(tile.get_id()),
+                    (Some(tile), None) => Change::Uncollapsed(tile.get_id()),
+                    (Some(a), Some(b)) if a.get_id() != b.get_id() => {
+                        Change::Replaced(a.get_id(), b.get_id())
+                    }
+                    (None, None) if before.entropy() != after.entropy() => {
+                        Change::Entropy(before.entropy(), after.entropy())
+                    }
+                    _ => continue,
+                };
+
+                changes.push((Position::new(x, y), change));
+            }
+        }
+
+        changes
+    }
+
+    /// `Grid::get` only checks the index, so x past the width would wrap to the next row
+    fn cell(&self, x: usize, y: usize) -> Option<&SuperState<T>> {
+        (x < self.grid.width()).then(|| self.grid.get(x, y)).flatten()
+    }
+
+    /// Solves the grid from scratch with the exact backend, on success the solution
+    /// replaces the grid and the wave is done
+    #[cfg(feature = "exact")]
+    pub fn solve_exact(&mut self, options: &exact::Options) -> Result<(), exact::Failure> {
+        let mut weights = vec![0.0; self.rules.len()];
+
+        for (_, _, cell) in &self.grid_base {
+            for (tile, index) in cell.possible.iter().zip(cell.indexes()) {
+                weights[*index] = tile.get_weight();
+            }
+        }
+
+        let domains = Grid::new(self.grid.width(), self.grid.height(), &mut |x, y| {
+            let base = self.grid_base.get(x, y).unwrap();
+            let pin = self.pins.get(&Position::new(x, y));
+
+            base.possible
+                .iter()
+                .zip(base.indexes())
+                .filter(|(tile, _)| pin.is_none_or(|id| tile.get_id() == *id))
+                .map(|(_, index)| *index)
+                .collect()
+        });
+        let solution = exact::solve(domains, &self.rules, &weights, options, &mut self.rng)?;
+
+        self.clear_queue();
+        self.collapsed.clear();
+        self.checkpoints.clear();
+
+        for (x, y, tile) in &solution {
+            let mut cell = self.grid_base.get(x, y).unwrap().clone();
+
+            cell.retain_indexes(|index| index == *tile);
+            self.set_cell(x, y, cell);
+            self.data.set(x, y, None).unwrap();
+            self.unsettled.set(x, y, false).unwrap();
+            self.collapsed.push((Position::new(x, y), CollapseReason::Explicit));
+            self.record(|| Event::Assign(Position::new(x, y), vec![*tile]));
+        }
+
+        self.sync_constraints();
+
+        Ok(())
+    }
+
+    /// Contradictions run into so far
+    pub fn rollbacks(&self) -> usize {
+        self.rollbacks
+    }
+
+    /// Rollbacks since the last call, always empty unless `WaveBuilder::track_rollbacks`
+    pub fn take_rollbacks(&mut self) -> Vec<Rollback> {
+        self.rollback_log.as_mut().map(std::mem::take).unwrap_or_default()
+    }
+
+    /// How often every cell ran out of tiles, over everything the wave tried
+    pub fn contradictions(&self) -> &Grid<usize> {
+        &self.contradictions
+    }
+
+    pub fn last_contradiction(&self) -> Option<Position> {
+        self.last_contradiction
+    }
+
+    /// How far the consequences of every tile the solver picked spread, in the order it
+    /// picked them. Unlike `collapse_order` this keeps the picks rollbacks took back, they
+    /// show how stiff the constraints are just the same.
+    pub fn propagation(&self) -> &[Propagation] {
+        &self.propagation
+    }
+
+    /// Cells that are down to a single tile in the order they got there, true for cells
+    /// the solver picked a tile for and false for cells propagating left with one
+    pub fn collapse_order(&self) -> impl DoubleEndedIterator<Item = (Position, bool)> + '_ {
+        self.collapsed
+            .iter()
+            .map(|(position, reason)| (*position, *reason == CollapseReason::Explicit))
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    pub fn header(&self) -> Header {
+        Header {
+            width: self.grid.width(),
+            height: self.grid.height(),
+            tiles: self.rules.len(),
+        }
+    }
+
+    /// Records everything the wave does from here on, in place of an earlier recording
+    pub fn start_recording(&mut self, mut recorder: Recorder) {
+        recorder.header(self.header());
+        self.recorder = Some(recorder);
+    }
+
+    /// Flushes the recording, returning the first error hit while recording
+    pub fn finish_recording(&mut self) -> io::Result<()> {
+        match self.recorder.take() {
+            Some(recorder) => recorder.finish(),
+            None => Ok(()),
+        }
+    }
+
+    fn record<F: FnOnce() -> Event>(&mut self, event: F) {
+        if let Some(recorder) = self.recorder.as_mut() {
+            recorder.record(event());
+        }
+    }
+
+    /// Applies a recorded event to the grid. Only the cells are updated, the solver
+    /// state (collapse history, queues) is left alone so a replayed wave can only be
+    /// rendered, not solved further.
+    pub fn apply(&mut self, event: &Event) {
+        match event {
+            Event::Remove(Position { x, y }, tiles) => {
+                let mut cell = self.grid.get(*x, *y).unwrap().clone();
+
+                cell.retain_indexes(|index| tiles.binary_search(&index).is_err());
+                self.set_cell(*x, *y, cell);
+            }
+            Event::Collapse(Position { x, y }, tile) => {
+                let mut cell = self.grid.get(*x, *y).unwrap().clone();
+
+                cell.retain_indexes(|index| index == *tile);
+                self.set_cell(*x, *y, cell);
+            }
+            Event::Reset(Position { x, y }) => {
+                let base = self.grid_base.get(*x, *y).unwrap().clone();
+
+                self.set_cell(*x, *y, base);
+            }
+            Event::ResetAll => {
+                self.grid = self.grid_base.clone();
+                self.entropies = count_entropies(&self.grid, self.rules.len());
+            }
+            Event::Assign(Position { x, y }, tiles) => {
+                let mut base = self.grid_base.get(*x, *y).unwrap().clone();
+
+                base.retain_indexes(|index| tiles.binary_search(&index).is_ok());
+                self.set_cell(*x, *y, base);
+            }
+            Event::Contradiction(_) | Event::Rollback(_) => {}
+        }
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    pub fn tick(&mut self) -> bool {
+        let mut worked = false;
+        let rollbacks = self.rollbacks;
+
+        #[cfg(feature = "threaded")]
+        while self.rollbacks == rollbacks && (self.gpu_sweep() || self.tick_front()) {
+            worked = true;
+        }
+
+        #[cfg(not(feature = "threaded"))]
+        loop {
+            if self.gpu_sweep() {
+                worked = true;
+                break;
+            }
+
+            let Some(Position { x, y }) = self.next_queued() else {
+                break;
+            };
+
+            self.tick_cell(x, y);
+            worked = true;
+
+            // a constraint that can't be met refills the queue after every reset, hand
+            // control back so the caller gets to give up
+            if self.rollbacks != rollbacks {
+                break;
+            }
+        }
+
+        worked || self.maybe_collapse().is_none()
+    }
+
+    pub fn tick_once(&mut self) -> Option<Position> {
+        if let Some(Position { x, y }) = self.next_queued() {
+            self.tick_cell(x, y);
+
+            Some(Position::new(x, y))
+        } else {
+            self.maybe_collapse()
+        }
+    }
+
+    #[instrument(level = "trace", skip(self))]
+    fn tick_cell(&mut self, x: usize, y: usize) {
+        let _timer = profiling::time(Phase::Propagation);
+
+        let Some(neighbors) = self.take_neighbors(x, y) else {
+            return;
+        };
+        let keep = self.grid.get(x, y).unwrap().allowed(&neighbors, &self.rules);
+
+        self.settle_cell(x, y, keep);
+    }
+
+    /// Ticks every queued cell as one wavefront. The cells are checked against the grid as
+    /// it was, in parallel when there's a pool, and then settled one by one in queue
+    /// order, so the outcome doesn't depend on the threads. Cells that changed under a neighbor in the same
+    /// front get queued again. Returns false if nothing was queued.
+    #[cfg(feature = "threaded")]
+    #[instrument(level = "trace", skip(self))]
+    fn tick_front(&mut self) -> bool {
+        let front = self.take_front();
+
+        if front.is_empty() {
+            return false;
+        }
+
+        let _timer = profiling::time(Phase::Propagation);
+        let front: Vec<(Position, Neighbors<BitSet>)> = front
+            .into_iter()
+            .filter_map(|position| Some((position, self.take_neighbors(position.x, position.y)?)))
+            .collect();
+        let (grid, rules) = (&self.grid, &self.rules);
+        let allowed = |(position, neighbors): &(Position, Neighbors<BitSet>)| {
+            grid.get(position.x, position.y).unwrap().allowed(neighbors, rules)
+        };
+        let checked: Vec<Vec<bool>> = match &self.pool {
+            Some(pool) if front.len() >= PARALLEL_FRONT => pool.install(|| front.par_iter().map(allowed).collect()),
+            _ => front.iter().map(allowed).collect(),
+        };
+        let rollbacks = self.rollbacks;
+        let mut cells = front.into_iter().zip(checked);
+
+        for ((Position { x, y }, _), keep) in cells.by_ref() {
+            self.settle_cell(x, y, keep);
+
+            if self.rollbacks != rollbacks {
+                break;
+            }
+        }
+
+        // the grid jumped back under the rest of the front, they're checked from scratch
+        let width = self.grid.width();
+
+        self.sweep.extend(cells.map(|((position, _), _)| position.index(width)));
+
+        true
+    }
+
+    /// Propagates everything that's queued on the GPU once at least `gpu_front` cells
+    /// are, returns true if it did. The sweep narrows cells down as far as propagating
+    /// on the CPU would. Constraints, diagonals and recordings are only handled on the
+    /// CPU, and a sweep that runs into a contradiction is dropped so the CPU rolls back
+    /// from the contradiction it finds first, like it does without a GPU.
+    #[cfg(feature = "gpu")]
+    fn gpu_sweep(&mut self) -> bool {
+        let queued = self.stack.len() + self.sweep.len();
+
+        if self.gpu.is_none() || queued < self.gpu_front {
+            self.gpu_paused = false;
+            return false;
+        }
+
+        if self.gpu_paused || !self.constraints.is_empty() || self.diagonal_rules.is_some() || self.recorder.is_some() {
+            return false;
+        }
+
+        let _timer = profiling::time(Phase::Propagation);
+        let gpu = self.gpu.as_mut().unwrap();
+        let words = gpu.words();
+        let mut masks = vec![0u32; self.grid.size() * words];
+
+        for (index, (_, _, cell)) in self.grid.iter().enumerate() {
+            for tile in cell.indexes() {
+                masks[index * words + tile / 32] |= 1 << (tile % 32);
+            }
+        }
+
+        if let Err(e) = gpu.propagate(&mut masks) {
+            warn!("Propagating on the CPU from here on, the GPU failed: {e}");
+            self.gpu = None;
+            return false;
+        }
+
+        if masks.chunks_exact(words).any(|mask| mask.iter().all(|word| *word == 0)) {
+            self.gpu_paused = true;
+            return false;
+        }
+
+        let width = self.grid.width();
+
+        for (index, mask) in masks.chunks_exact(words).enumerate() {
+            let (x, y) = (index % width, index / width);
+            let mut cell = self.grid.get(x, y).unwrap().clone();
+            let keep: Vec<bool> = cell
+                .indexes()
+                .iter()
+                .map(|tile| mask[tile / 32] & (1 << (tile % 32)) != 0)
+                .collect();
+
+            if keep.iter().all(|kept| *kept) {
+                continue;
+            }
+
+            cell.keep(&keep);
+
+            let entropy = cell.entropy();
+
+            self.set_cell(x, y, cell);
+            self.propagated(x, y);
+
+            if entropy == 1 {
+                self.collapsed.push((Position::new(x, y), CollapseReason::Implicit));
+                self.notify_collapse(x, y);
+            }
+        }
+
+        // every cell was checked against its neighbors as they ended up
+        self.clear_queue();
+        self.data = Grid::new(self.grid.width(), self.grid.height(), &mut |_, _| Default::default());
+        self.unsettled = Grid::new(self.grid.width(), self.grid.height(), &mut |_, _| false);
+
+        true
+    }
+
+    #[cfg(not(feature = "gpu"))]
+    fn gpu_sweep(&mut self) -> bool {
+        false
+    }
+
+    /// Everything that's queued in the order `next_queued` would hand it out, without
+    /// the cells that are queued twice
+    #[cfg(feature = "threaded")]
+    fn take_front(&mut self) -> Vec<Position> {
+        let width = self.grid.width();
+        let mut seen = BTreeSet::new();
+        let swept: Vec<Position> = std::mem::take(&mut self.sweep)
+            .into_iter()
+            .map(|index| Position::new(index % width, index / width))
+            .collect();
+
+        self.sweep_next = None;
+
+        swept
+            .into_iter()
+            .chain(self.stack.drain(..))
+            .filter(|position| seen.insert(*position))
+            .collect()
+    }
+
+    /// Neighbors a queued cell has to be checked against, None for cells that are already
+    /// down to one tile
+    fn take_neighbors(&mut self, x: usize, y: usize) -> Option<Neighbors<BitSet>> {
+        if self.grid.get(x, y).unwrap().entropy() == 1 {
+            self.unsettled.set(x, y, false).unwrap();
+            return None;
+        }
+
+        if self.data.get(x, y).unwrap().is_none() {
+            let data = self.neighbor_sets(x, y);
+
+            self.unsettled.set(x, y, false).unwrap();
+            self.data.set(x, y, Some(data)).unwrap();
+        }
+
+        self.data.replace(x, y, None).unwrap()
+    }
+
+    /// Narrows a cell down to the tiles flagged in `keep` (see `SuperState::allowed`) and
+    /// the diagonals and constraints, then propagates the change
+    fn settle_cell(&mut self, x: usize, y: usize, keep: Vec<bool>) {
+        let diagonals = self.diagonal_sets(x, y);
+        let cell = self.grid.get_mut(x, y).unwrap();
+        let old_entropy = cell.entropy();
+        let before = self.recorder.as_ref().map(|_| cell.indexes().to_vec());
+
+        cell.keep(&keep);
+
+        if let (Some(rules), Some(diagonals)) = (&self.diagonal_rules, &diagonals) {
+            cell.retain_indexes(|index| {
+                diagonals
+                    .iter()
+                    .all(|(direction, tiles)| tiles.is_empty() || !rules[index][*direction].is_disjoint(tiles))
+            });
+        }
+
+        for constraint in &self.constraints {
+            constraint.filter(&Position::new(x, y), cell);
+        }
+
+        if let (Some(recorder), Some(mut removed)) = (self.recorder.as_mut(), before) {
+            removed.retain(|index| cell.indexes().binary_search(index).is_err());
+
+            if !removed.is_empty() {
+                recorder.record(Event::Remove(Position::new(x, y), removed));
+            }
+
+            if cell.entropy() == 0 {
+                recorder.record(Event::Contradiction(Position::new(x, y)));
+            }
+        }
+
+        let entropy = cell.entropy();
+        let collapsing = cell.collapsing();
+
+        self.count_entropy(old_entropy, entropy);
+
+        if old_entropy != entropy {
+            self.propagated(x, y);
+        }
+
+        if entropy <= 1 {
+            self.collapsed.push((Position::new(x, y), CollapseReason::Implicit));
+        }
+
+        if entropy == 0 {
+            self.contradiction(x, y);
+        } else if old_entropy != entropy {
+            if entropy == 1 {
+                self.notify_collapse(x, y);
+            }
+
+            // a constraint can take a cell down to one tile before its neighbors changed
+            if collapsing
+                && entropy > 1
+                && self
+                    .neighbor_positions(x, y)
+                    .values()
+                    .all(|position| position.is_none_or(|p| !self.grid.get(p.x, p.y).unwrap().collapsing()))
+            {
+                self.collapse(x, y);
+            } else {
+                self.mark(x, y);
+            }
+        }
+    }
+
+    #[instrument(level = "trace", skip(self))]
+    fn collapse(&mut self, x: usize, y: usize) {
+        let _timer = profiling::time(Phase::Collapse);
+        let position = Position::new(x, y);
+
+        if !self.constraints.is_empty() {
+            let cell = self.grid.get_mut(x, y).unwrap();
+            let old_entropy = cell.entropy();
+
+            for constraint in &self.constraints {
+                constraint.filter(&position, cell);
+            }
+
+            let entropy = cell.entropy();
+
+            self.count_entropy(old_entropy, entropy);
+
+            if entropy == 0 {
+                // like in tick_cell, the rollback resets the cell along with the others
+                self.collapsed.push((position, CollapseReason::Implicit));
+                self.record(|| Event::Contradiction(position));
+                self.contradiction(x, y);
+
+                return;
+            }
+        }
+
+        let rng = match &self.area_keys {
+            Some(keys) => {
+                let key = *keys.get(x, y).unwrap();
+
+                self.area_rngs
+                    .entry(key)
+                    .or_insert_with(|| area_rng(self.seed, key))
+            }
+            None => &mut self.rng,
+        };
+        let temperature = self.temperature;
+        let cell = self.grid.get_mut(x, y).unwrap();
+        let old_entropy = cell.entropy();
+
+        match &self.weight_modifier {
+            Some(modifier) => cell.collapse(rng, |_, tile| temper(modifier(&position, tile), temperature)),
+            None => cell.collapse_weights(rng, &self.weights),
+        }
+
+        let tile = cell.indexes()[0];
+        let entropy = cell.entropy();
+
+        self.count_entropy(old_entropy, entropy);
+
+        self.record(|| Event::Collapse(position, tile));
+        self.collapsed.push((position, CollapseReason::Explicit));
+        self.propagation.push(Propagation {
+            position,
+            cells: 0,
+            reach: 0,
+            contradicted: false,
+        });
+        self.propagating = true;
+        // self.tracker.next(x, y);
+        self.notify_collapse(x, y);
+        self.mark(x, y);
+    }
+
+    /// Rolls back from a cell that ran out of tiles
+    fn contradiction(&mut self, x: usize, y: usize) {
+        let position = Position::new(x, y);
+
+        *self.contradictions.get_mut(x, y).unwrap() += 1;
+        self.last_contradiction = Some(position);
+
+        if let Some(propagation) = self.propagation.last_mut().filter(|_| self.propagating) {
+            propagation.contradicted = true;
+        }
+
+        // cells narrowed while the grid is put back together belong to no pick
+        self.propagating = false;
+
+        if let Some(log) = self.rollback_log.as_mut() {
+            log.push(Rollback {
+                contradiction: position,
+                cells: Vec::new(),
+            });
+        }
+
+        self.rolling_back = true;
+        self.smart_rollback();
+        self.rolling_back = false;
+        self.sync_constraints();
+    }
+
+    /// Puts `cell` at `x`, `y`, keeping `entropies` up to date
+    fn set_cell(&mut self, x: usize, y: usize, cell: SuperState<T>) {
+        let entropy = cell.entropy();
+        let old_entropy = self.grid.replace(x, y, cell).unwrap().entropy();
+
+        self.count_entropy(old_entropy, entropy);
+    }
+
+    /// Moves a cell between the counts of `entropies`
+    #[inline]
+    fn count_entropy(&mut self, old_entropy: usize, entropy: usize) {
+        if old_entropy != entropy {
+            self.entropies[old_entropy] -= 1;
+            self.entropies[entropy] += 1;
+        }
+    }
+
+    /// Counts a cell that lost tiles towards the last pick, if it's still spreading
+    fn propagated(&mut self, x: usize, y: usize) {
+        let mark = self.propagation.len();
+
+        if let Some(propagation) = self.propagation.last_mut().filter(|_| self.propagating) {
+            if self.propagation_marks.replace(x, y, mark).unwrap() == mark {
+                return;
+            }
+
+            let distance = propagation.position.x.abs_diff(x) + propagation.position.y.abs_diff(y);
+
+            propagation.cells += 1;
+            propagation.reach = propagation.reach.max(distance);
+        }
+    }
+
+    /// Adds a cell to the rollback that's happening, if any
+    fn touched(&mut self, x: usize, y: usize) {
+        if let Some(rollback) = self.rollback_log.as_mut().and_then(|log| log.last_mut()) {
+            if self.rolling_back {
+                rollback.cells.push(Position::new(x, y));
+            }
+        }
+    }
+
+    /// Adds every cell to the rollback that's happening, if any
+    fn touched_all(&mut self) {
+        if let Some(rollback) = self.rollback_log.as_mut().and_then(|log| log.last_mut()) {
+            if self.rolling_back {
+                rollback.cells = self.grid.iter().map(|(x, y, _)| Position::new(x, y)).collect();
+            }
+        }
+    }
+
+    /// Tells the constraints about a cell that's down to a single tile
+    fn notify_collapse(&mut self, x: usize, y: usize) {
+        let Some(tile) = self.grid.get(x, y).unwrap().collapsed() else {
+            return;
+        };
+
+        for constraint in &mut self.constraints {
+            constraint.on_collapse(&Position::new(x, y), tile);
+        }
+    }
+
+    /// Brings the constraints up to date with the grid after jumping to another state
+    fn sync_constraints(&mut self) {
+        if self.constraints.is_empty() {
+            return;
+        }
+
+        for constraint in &mut self.constraints {
+            constraint.reset();
+        }
+
+        for (x, y, cell) in &self.grid {
+            if let Some(tile) = cell.collapsed() {
+                for constraint in &mut self.constraints {
+                    constraint.on_collapse(&Position::new(x, y), tile);
+                }
+            }
+        }
+    }
+
+    pub fn maybe_collapse(&mut self) -> Option<Position> {
+        self.maybe_checkpoint();
+
+        let timer = profiling::time(Phase::Selection);
+        let mut options = Vec::new();
+        let mut lowest_entropy = usize::MAX;
+        let areas = self.collapsable_areas();
+
+        for &Position { x, y } in areas.first().unwrap() {
+            let cell = self.grid.get(x, y).unwrap();
+            // for (x, y, cell) in &self.grid {
+            let entropy = cell.entropy();
+
+            if entropy <= 1 {
+                continue;
+            }
+
+            if entropy < lowest_entropy {
+                options.clear();
+                lowest_entropy = entropy;
+            }
+
+            if entropy == lowest_entropy {
+                options.push((x, y));
+            }
+        }
+
+        let maybe = match &self.area_keys {
+            Some(keys) => {
+                let key = *keys.get(areas[0][0].x, areas[0][0].y).unwrap();
+                let rng = self
+                    .area_rngs
+                    .entry(key)
+                    .or_insert_with(|| area_rng(self.seed, key));
+
+                options.into_iter().choose_stable(rng)
+            }
+            None => options.into_iter().choose_stable(&mut self.rng),
+        };
+
+        drop(timer);
+
+        match maybe {
+            Some((x, y)) => {
+                self.collapse(x, y);
+                Some(Position::new(x, y))
+            }
+            None => None,
+        }
+    }
+
+    fn mark(&mut self, cx: usize, cy: usize) {
+        let possible_states = self.grid.get(cx, cy).unwrap().mask(self.rules.len());
+
+        for (direction, pos) in self.neighbor_positions(cx, cy) {
+            if pos.is_none() {
+                continue;
+            }
+
+            let Position { x, y } = pos.unwrap();
+            match self.data.get_mut(x, y).unwrap() {
+                None => {
+                    let mut neighbors = Neighbors::default();
+
+                    neighbors[direction.invert()] = possible_states.clone();
+
+                    self.enqueue(x, y, neighbors);
+                }
+                Some(neighbors) => {
+                    neighbors[direction.invert()] = possible_states.clone();
+                }
+            }
+        }
+
+        // diagonals are read straight from the grid when ticking, the corners only need
+        // to be queued
+        if self.diagonal_rules.is_some() {
+            for direction in Direction8::DIAGONALS {
+                let Some(Position { x, y }) = self.data.get_neighbor_position8(cx, cy, direction) else {
+                    continue;
+                };
+
+                if self.data.get(x, y).unwrap().is_none() {
+                    let neighbors = self.neighbor_sets(x, y);
+
+                    self.enqueue(x, y, neighbors);
+                }
+            }
+        }
+    }
+
+    fn enqueue(&mut self, x: usize, y: usize, neighbors: Neighbors<BitSet>) {
+        self.data.set(x, y, Some(neighbors)).unwrap();
+
+        self.stack.push_back(Position::new(x, y));
+
+        if let Some(next) = self.sweep_next {
+            let index = x + y * self.grid.width();
+
+            if index >= next {
+                self.sweep.insert(index);
+            }
+        }
+    }
+
+    fn explicit_count(&self) -> usize {
+        self.collapsed
+            .iter()
+            .filter(|(_, c)| *c == CollapseReason::Explicit)
+            .count()
+    }
+
+    /// Take a checkpoint every `checkpoint_interval` explicit collapses.
+    /// Only called while the stack is empty, so `data` does not need to be saved.
+    fn maybe_checkpoint(&mut self) {
+        let explicit = self.explicit_count();
+
+        if explicit <= self.checkpoint_floor || !explicit.is_multiple_of(self.checkpoint_interval) {
+            return;
+        }
+
+        if let Some(last) = self.checkpoints.last() {
+            if last.explicit >= explicit {
+                return;
+            }
+        }
+
+        trace!("Checkpoint at {explicit} explicit collapses");
+
+        if self.checkpoints.len() >= MAX_CHECKPOINTS {
+            self.checkpoints.remove(0);
+        }
+
+        self.checkpoints.push(Checkpoint {
+            grid: self.grid.clone(),
+            collapsed: self.collapsed.clone(),
+            unsettled: self.unsettled.clone(),
+            explicit,
+            rng: self.rng.clone(),
+        });
+    }
+
+    /// Restore the most recent checkpoint, returns false if there are none left.
+    /// A checkpoint is only restored once, repeated storms fall back to older ones.
+    fn restore_checkpoint(&mut self) -> bool {
+        let checkpoint = match self.checkpoints.pop() {
+            Some(checkpoint) => checkpoint,
+            None => return false,
+        };
+
+        warn!("Unable to solve, restoring checkpoint...");
+
+        self.grid = checkpoint.grid;
+        self.entropies = count_entropies(&self.grid, self.rules.len());
+        self.touched_all();
+
+        if let Some(recorder) = self.recorder.as_mut() {
+            for (x, y, cell) in &self.grid {
+                recorder.record(Event::Assign(Position::new(x, y), cell.indexes().to_vec()));
+            }
+        }
+
+        self.collapsed = checkpoint.collapsed;
+        self.unsettled = checkpoint.unsettled;
+        self.checkpoint_floor = checkpoint.explicit;
+        self.rng = checkpoint.rng;
+
+        // skip ahead so the restored run doesn't replay into the same contradiction
+        self.restores += 1;
+        for _ in 0..self.restores {
+            self.rng.next_u64();
+        }
+
+        for (x, y, _) in &self.grid {
+            self.data.set(x, y, None).unwrap();
+        }
+
+        self.clear_queue();
+        self.rollback_penalty = 0;
+        self.last_rollback = self.collapsed.len();
+        self.apply_pins();
+
+        true
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    fn smart_rollback(&mut self) {
+        let _timer = profiling::time(Phase::Rollback);
+
+        self.rollbacks += 1;
+
+        if self.maybe_restart() {
+            return;
+        }
+
+        let collapsed_count = self.grid.size() - self.remaining();
+
+        trace!("Collapsed: {}", collapsed_count);
 
-        while let Some((x, y)) = self.stack.pop_front() {
-            self.tick_cell(x, y);
-            worked = true;
+        if collapsed_count <= self.last_rollback {
+            self.rollback_penalty += 1;
+        } else {
+            self.last_rollback = collapsed_count;
+            self.rollback_penalty = 0;
         }
 
-        worked || self.maybe_collapse().is_none()
-    }
+        let steps = self.rollback_steps();
+        let over_threshold = self
+            .rollback_schedule
+            .reset_threshold
+            .map(|threshold| steps > threshold)
+            .unwrap_or(false);
 
-    pub fn tick_once(&mut self) -> Option<Position> {
-        if let Some((x, y)) = self.stack.pop_front() {
-            self.tick_cell(x, y);
+        // only count collapses since the last checkpoint, rolling back past it is a storm
+        let collapsed_count = self.explicit_count()
+            - self.checkpoints.last().map(|c| c.explicit).unwrap_or(0);
+
+        if collapsed_count < steps || over_threshold {
+            if self.repair() || self.restore_checkpoint() {
+                return;
+            }
 
-            Some((x, y))
-        } else if let Some(value) = self.maybe_collapse() {
-            return Some(value);
+            warn!("Unable to solve, resetting...");
+            self.reset();
         } else {
-            None
+            self.record(|| Event::Rollback(steps));
+            self.rollback(steps);
+            self.requeue_frontier();
         }
     }
 
-    fn tick_cell(&mut self, x: usize, y: usize) {
-        if self.grid.get(x, y).unwrap().entropy() == 1 {
-            return;
-        }
+    /// Restarts from scratch with a seed drawn from the current one once the Luby
+    /// sequence says so, returns true if it did
+    fn maybe_restart(&mut self) -> bool {
+        let Some(unit) = self.rollback_schedule.restart_unit else {
+            return false;
+        };
 
-        if self.data.get(x, y).unwrap().is_none() {
-            let data = self.grid.get_neighbors(x, y).map(|_, v| match v {
-                None => Set::default(),
-                Some(neighbor) => Set::from_iter(neighbor.possible.iter().map(|x| x.get_id())),
-            });
+        self.restart_penalty += 1;
 
-            self.data.set(x, y, Some(data)).unwrap();
+        if self.restart_penalty < luby(self.restarts + 1) * unit.max(1) {
+            return false;
         }
 
-        let cell = self.grid.get_mut(x, y).unwrap();
+        warn!("Restarting after {} contradictions...", self.restart_penalty);
 
-        let neighbors = self.data.replace(x, y, None).unwrap().unwrap();
+        self.restarts += 1;
+        self.restart_penalty = 0;
+        self.rng = XorShiftRng::seed_from_u64(self.rng.next_u64());
+        self.checkpoints.clear();
+        self.reset();
 
-        self.data.set(x, y, None).unwrap();
-        let old_entropy = cell.entropy();
+        true
+    }
 
-        cell.tick(&neighbors);
+    /// Min-conflicts local search, returns false if conflicts are left after
+    /// `repair_steps` moves. A contradicting cell gets the tile that disagrees with the
+    /// fewest collapsed neighbors, the neighbors it disagrees with are repaired in turn.
+    /// Cells that aren't collapsed are reset and propagated again afterwards.
+    fn repair(&mut self) -> bool {
+        let Some(steps) = self.rollback_schedule.repair_steps else {
+            return false;
+        };
+        let mut conflicts: Vec<Position> = self
+            .grid
+            .iter()
+            .filter(|(_, _, cell)| cell.entropy() == 0)
+            .map(|(x, y, _)| Position::new(x, y))
+            .collect();
 
-        if cell.entropy() <= 1 {
-            self.collapsed.push(((x, y), CollapseReason::Implicit));
-        }
+        for _ in 0..steps {
+            if conflicts.is_empty() {
+                break;
+            }
 
-        if cell.entropy() == 0 {
-            self.smart_rollback();
-        } else if old_entropy != cell.entropy() {
-            if cell.collapsing()
-                && self
-                    .grid
-                    .get_neighbors(x, y)
-                    .values()
-                    .all(|v| v.map(|v| !v.collapsing()).unwrap_or(true))
-            {
-                self.collapse(x, y);
-            } else {
-                self.mark(x, y);
+            let Position { x, y } = conflicts.swap_remove(self.rng.next_u64() as usize % conflicts.len());
+            let neighbors: Vec<(Direction, Position, usize)> = self
+                .neighbor_positions(x, y)
+                .into_iter()
+                .filter_map(|(direction, position)| {
+                    let position = position?;
+                    let cell = self.grid.get(position.x, position.y).unwrap();
+
+                    (cell.entropy() == 1).then(|| (direction, position, cell.indexes()[0]))
+                })
+                .collect();
+            let disagrees = |tile: usize, direction: Direction, neighbor: usize| {
+                !self.rules[tile][direction].contains(neighbor)
+                    || !self.rules[neighbor][direction.invert()].contains(tile)
+            };
+            let base = self.grid_base.get(x, y).unwrap();
+            let mut options = Vec::new();
+            let mut fewest = usize::MAX;
+
+            for &tile in base.indexes() {
+                let count = neighbors
+                    .iter()
+                    .filter(|(direction, _, neighbor)| disagrees(tile, *direction, *neighbor))
+                    .count();
+
+                if count < fewest {
+                    options.clear();
+                    fewest = count;
+                }
+
+                if count == fewest {
+                    options.push(tile);
+                }
             }
-        }
-    }
 
-    fn collapse(&mut self, x: usize, y: usize) {
-        self.grid.get_mut(x, y).unwrap().collapse(&mut self.rng);
-        self.collapsed.push(((x, y), CollapseReason::Explicit));
-        // self.tracker.next(x, y);
-        self.mark(x, y);
-    }
+            let Some(tile) = options.into_iter().choose_stable(&mut self.rng) else {
+                return false;
+            };
 
-    pub fn maybe_collapse(&mut self) -> Option<Position> {
-        let mut options = Vec::new();
-        let mut lowest_entropy = usize::MAX;
-        let areas = self.collapsable_areas();
+            for (direction, position, neighbor) in neighbors {
+                if !disagrees(tile, direction, neighbor) {
+                    continue;
+                }
 
-        for &(x, y) in areas.first().unwrap() {
-            let cell = self.grid.get(x, y).unwrap();
-            // for (x, y, cell) in &self.grid {
-            let entropy = cell.entropy();
+                // pinned cells stay, the cell has to try again instead
+                let position = match self.pins.contains_key(&position) {
+                    true => Position::new(x, y),
+                    false => position,
+                };
 
-            if entropy <= 1 {
-                continue;
+                if !conflicts.contains(&position) {
+                    conflicts.push(position);
+                }
             }
 
-            if entropy < lowest_entropy {
-                options.clear();
-                lowest_entropy = entropy;
-            }
+            let mut cell = base.clone();
 
-            if entropy == lowest_entropy {
-                options.push((x, y));
-            }
+            cell.retain_indexes(|index| index == tile);
+            self.set_cell(x, y, cell);
+            self.record(|| Event::Assign(Position::new(x, y), vec![tile]));
+            self.touched(x, y);
         }
 
-        let maybe = options.into_iter().choose_stable(&mut self.rng);
-
-        match maybe {
-            Some((x, y)) => {
-                self.collapse(x, y);
-                Some((x, y))
-            }
-            None => None,
+        if !conflicts.is_empty() {
+            return false;
         }
-    }
 
-    fn mark(&mut self, cx: usize, cy: usize) {
-        let raw_possible_states: Vec<T::Identifier> = self
+        warn!("Unable to solve, repaired the conflicting cells...");
+
+        let open: Vec<Position> = self
             .grid
-            .get(cx, cy)
-            .unwrap()
-            .possible
             .iter()
-            .map(|t| t.get_id())
+            .filter(|(_, _, cell)| cell.entropy() != 1)
+            .map(|(x, y, _)| Position::new(x, y))
             .collect();
 
-        let possible_states: Set<T::Identifier> = raw_possible_states.into_iter().collect();
+        for Position { x, y } in open {
+            self.set_cell(x, y, self.grid_base.get(x, y).unwrap().clone());
+            self.record(|| Event::Reset(Position::new(x, y)));
+            self.touched(x, y);
+        }
 
-        for (direction, pos) in self.data.get_neighbor_positions(cx, cy) {
-            if pos.is_none() {
-                continue;
-            }
+        let grid = &self.grid;
 
-            let (x, y) = pos.unwrap();
-            match self.data.get_mut(x, y).unwrap() {
-                None => {
-                    let mut neighbors = Neighbors::default();
+        self.collapsed.retain(|(Position { x, y }, _)| grid.get(*x, *y).unwrap().entropy() == 1);
+        self.data = Grid::new(grid.width(), grid.height(), &mut |_, _| Default::default());
+        self.unsettled = Grid::new(grid.width(), grid.height(), &mut |_, _| true);
+        self.clear_queue();
+        self.requeue_frontier();
+        self.rollback_penalty = 0;
+        self.last_rollback = self.collapsed.len();
 
-                    neighbors[direction.invert()] = possible_states.clone();
+        true
+    }
 
-                    self.data.set(x, y, Some(neighbors)).unwrap();
+    /// Back to the initial grid, the rng carries on
+    fn reset(&mut self) {
+        self.record(|| Event::ResetAll);
+        self.touched_all();
 
-                    self.stack.push_back((x, y));
-                }
-                Some(neighbors) => {
-                    neighbors[direction.invert()] = possible_states.clone();
-                }
-            }
+        for (x, y, cell) in &self.grid_base {
+            self.grid.set(x, y, cell.clone()).unwrap();
+            self.data.set(x, y, None).unwrap();
         }
+
+        self.entropies = count_entropies(&self.grid, self.rules.len());
+
+        self.collapsed.clear();
+        self.unsettled = Grid::new(self.grid.width(), self.grid.height(), &mut |_, _| true);
+        self.clear_queue();
+        self.rollback_penalty = 0;
+        self.last_rollback = 0;
+        self.checkpoint_floor = 0;
+        self.apply_pins();
+        self.queue_constraints();
     }
 
-    fn smart_rollback(&mut self) {
-        let collapsed_count = self.grid.size() - self.remaining();
+    /// After a rollback only the cells left on the stack (reverted cells and cells that
+    /// were still pending) and unsettled cells can disagree with their neighbors, so
+    /// instead of queueing the whole grid those are swept in row-major order. Cells marked
+    /// ahead of the sweep join it, which visits cells in the same order a full pass over
+    /// the grid would.
+    fn requeue_frontier(&mut self) {
+        let width = self.grid.width();
+        let pending: Vec<Position> = self.stack.drain(..).collect();
+
+        self.sweep.extend(pending.into_iter().map(|position| position.index(width)));
+        self.sweep.extend(
+            self.unsettled
+                .iter()
+                .filter(|(_, _, unsettled)| **unsettled)
+                .map(|(x, y, _)| x + y * width),
+        );
+        self.sweep_next = Some(0);
+    }
 
-        trace!("Collapsed: {}", collapsed_count);
+    fn next_queued(&mut self) -> Option<Position> {
+        if let Some(index) = self.sweep.pop_first() {
+            let width = self.grid.width();
 
-        if collapsed_count <= self.last_rollback {
-            self.rollback_penalty += 0.5;
-        } else {
-            self.last_rollback = collapsed_count;
-            self.rollback_penalty = 0.5;
+            self.sweep_next = Some(index + 1);
+
+            return Some(Position::new(index % width, index / width));
         }
 
-        let collapsed_count = self
-            .collapsed
-            .iter()
-            .filter(|((_, _), c)| *c == CollapseReason::Explicit)
-            .count();
+        self.sweep_next = None;
+        self.stack.pop_front()
+    }
 
-        if collapsed_count < self.rollback_penalty.ceil() as usize {
-            warn!("Unable to solve, resetting...");
-            for (x, y, cell) in &self.grid_base {
-                self.grid.set(x, y, cell.clone()).unwrap();
-                self.data.set(x, y, None).unwrap();
-            }
+    /// Sweeps the whole grid when there are constraints, so they narrow every cell before
+    /// the first collapse instead of once propagation gets there. Rules on the border of
+    /// the grid would otherwise only show up after the inside is settled.
+    fn queue_constraints(&mut self) {
+        if self.constraints.is_empty() {
+            return;
+        }
 
-            self.collapsed.clear();
-            self.stack.clear();
-            self.rollback_penalty = 0.5;
-            self.last_rollback = 0;
-        } else {
-            self.rollback(self.rollback_penalty.ceil() as usize);
+        self.sweep.extend(0..self.grid.size());
+        self.sweep_next = Some(0);
+    }
 
-            // tmp hack, shouldn't have to do this...
-            self.stack.clear();
-            for (x, y, _) in &self.grid {
-                self.data.set(x, y, None).unwrap();
-                self.stack.push_back((x, y));
-            }
-        }
+    fn clear_queue(&mut self) {
+        self.stack.clear();
+        self.sweep.clear();
+        self.sweep_next = None;
+    }
+
+    fn neighbor_sets(&self, x: usize, y: usize) -> Neighbors<BitSet> {
+        self.neighbor_positions(x, y).map(|_, position| match position {
+            None => BitSet::default(),
+            Some(Position { x, y }) => self.grid.get(x, y).unwrap().mask(self.rules.len()),
+        })
+    }
+
+    /// The cell next to `x`, `y` in `direction` as the topology has it
+    fn neighbor_position(&self, x: usize, y: usize, direction: Direction) -> Option<Position> {
+        let width = self.grid.width();
+        let node = self.topology.neighbor(Position::new(x, y).index(width), direction)?;
+
+        Some(Position::new(node % width, node / width))
+    }
+
+    fn neighbor_positions(&self, x: usize, y: usize) -> Neighbors<Option<Position>> {
+        enum_map! { direction => self.neighbor_position(x, y, direction) }
+    }
+
+    /// `initial_step` plus `growth_percent` hundredths of a collapse for every
+    /// contradiction since the last progress, rounded down
+    fn rollback_steps(&self) -> usize {
+        let schedule = &self.rollback_schedule;
+        let growth = self.rollback_penalty * schedule.growth_percent / 100;
+
+        schedule.initial_step.max(1) + growth
     }
 
     fn rollback(&mut self, mut count: usize) {
@@ -284,11 +2059,18 @@ where
             Default::default()
         });
 
+        let mut pinned = Vec::new();
+
         // revert last step of collapse stack
-        while let Some(((x, y), reason)) = self.collapsed.pop() {
-            self.rollback_propegate(x, y, None);
+        while let Some((position, reason)) = self.collapsed.pop() {
+            if self.pins.contains_key(&position) {
+                pinned.push((position, reason));
+                continue;
+            }
+
+            self.rollback_propegate(position.x, position.y);
 
-            self.stack.push_front((x, y));
+            self.stack.push_front(position);
 
             if reason == CollapseReason::Explicit {
                 count -= 1;
@@ -299,26 +2081,40 @@ where
                 }
             }
         }
-    }
 
-    fn rollback_propegate(&mut self, x: usize, y: usize, from: Option<Direction>) {
-        // set state to base state
-        let base = self.grid_base.get(x, y).unwrap().clone();
-        self.grid.set(x, y, base).unwrap();
-        self.stack.push_back((x, y));
+        self.collapsed.extend(pinned.into_iter().rev());
+    }
 
+    fn rollback_propegate(&mut self, x: usize, y: usize) {
         // for each neighbor (skipping "from" direction)
         //  - get entropy
         //  - set to base
         //  - tick
-        //  - if entropy changed recurse
+        //  - if entropy changed visit it next
+        //
+        // Frames keep the direction to check next so cells are visited in the same
+        // depth-first order a recursive walk would, without growing the call stack.
+        let mut frames: Vec<(Position, Option<Direction>, usize)> = Vec::new();
+
+        self.reset_cell(x, y);
+        frames.push((Position::new(x, y), None, 0));
+
+        while let Some(frame) = frames.last_mut() {
+            if frame.2 >= Direction::LENGTH {
+                frames.pop();
+                continue;
+            }
+
+            let (Position { x, y }, from) = (frame.0, frame.1);
+            let direction = Direction::from_usize(frame.2);
+
+            frame.2 += 1;
 
-        for (direction, value) in self.grid.get_neighbor_positions(x, y) {
-            if direction == from.unwrap_or(direction.invert()) {
+            if Some(direction) == from {
                 continue;
             }
 
-            if let Some((nx, ny)) = value {
+            if let Some(Position { x: nx, y: ny }) = self.neighbor_position(x, y, direction) {
                 let cell = self.grid.get(nx, ny).unwrap();
                 let entropy = cell.entropy();
 
@@ -328,31 +2124,70 @@ where
 
                 let mut base = self.grid_base.get(nx, ny).unwrap().clone();
 
-                let neighbors = self.grid.get_neighbors(nx, ny).map(|_, v| match v {
-                    None => Set::default(),
-                    Some(neighbor) => Set::from_iter(neighbor.possible.iter().map(|x| x.get_id())),
-                });
-
-                base.tick(&neighbors);
+                base.tick(&self.neighbor_sets(nx, ny), &self.rules);
 
                 let new_entropy = base.entropy();
 
                 if entropy != new_entropy {
-                    // todo: Remove recursion
-                    self.rollback_propegate(nx, ny, Some(direction.invert()));
+                    self.reset_cell(nx, ny);
+                    frames.push((Position::new(nx, ny), Some(direction.invert()), 0));
                 }
             }
         }
     }
 
+    fn reset_cell(&mut self, x: usize, y: usize) {
+        // set state to base state
+        let base = self.grid_base.get(x, y).unwrap().clone();
+        self.set_cell(x, y, base);
+        self.record(|| Event::Reset(Position::new(x, y)));
+        self.touched(x, y);
+        self.stack.push_back(Position::new(x, y));
+        self.unsettled.set(x, y, true).unwrap();
+    }
+
     fn collapsable_areas(&self) -> Vec<Vec<Position>> {
-        let mut board = Grid::<bool>::new(self.grid.width(), self.grid.height(), &mut |x, y| {
+        self.areas(|x, y| {
             let item = self.grid.get(x, y).unwrap();
 
             item.entropy() == 1
             // item.entropy() == 1 || self.tracker.seen(x, y)
+        })
+    }
+
+    /// Keys every cell by the area it starts out in, the lowest row-major index of the
+    /// area. Areas are split by the cells that start collapsed and by the pins, so a key
+    /// stays the same while the cells of its area collapse and edits elsewhere don't
+    /// touch it.
+    fn index_areas(&self) -> Grid<usize> {
+        let width = self.grid.width();
+        let mut keys = Grid::new(width, self.grid.height(), &mut |_, _| 0);
+        let areas = self.areas(|x, y| {
+            self.grid_base.get(x, y).unwrap().entropy() == 1 || self.pins.contains_key(&Position::new(x, y))
         });
 
+        for area in areas {
+            let key = area.iter().map(|position| position.index(width)).min().unwrap();
+
+            for Position { x, y } in area {
+                keys.set(x, y, key).unwrap();
+            }
+        }
+
+        keys
+    }
+
+    /// `index_areas` again after the pins changed, if areas have their own rng
+    fn reindex_areas(&mut self) {
+        if self.area_keys.is_some() {
+            self.area_keys = Some(self.index_areas());
+        }
+    }
+
+    /// Groups of connected cells that aren't `collapsed`, smallest first
+    fn areas<F: Fn(usize, usize) -> bool>(&self, collapsed: F) -> Vec<Vec<Position>> {
+        let mut board = Grid::<bool>::new(self.grid.width(), self.grid.height(), &mut |x, y| collapsed(x, y));
+
         let mut stack: Vec<Position> = Default::default();
         let mut output: Vec<Vec<Position>> = Default::default();
 
@@ -362,32 +2197,313 @@ where
                     continue;
                 }
 
-                stack.push((bx, by));
+                stack.push(Position::new(bx, by));
 
                 let mut area: Vec<Position> = Vec::new();
 
-                while let Some((x, y)) = stack.pop() {
+                while let Some(Position { x, y }) = stack.pop() {
                     if *board.get(x, y).unwrap_or(&true) {
                         continue;
                     }
 
                     board.set(x, y, true).unwrap();
 
-                    board
-                        .get_neighbor_positions(x, y)
+                    self.neighbor_positions(x, y)
                         .values()
                         .filter_map(|v| *v)
                         .for_each(|v| stack.push(v));
 
-                    area.push((x, y));
+                    area.push(Position::new(x, y));
                 }
 
                 output.push(area);
             }
         }
 
-        output.sort_by(|a, b| a.len().cmp(&b.len()));
+        output.sort_by_key(|a| a.len());
 
         output
     }
 }
+
+/// Luby sequence, 1-based: 1, 1, 2, 1, 1, 2, 4, 1, 1, 2, 1, 1, 2, 4, 8, ...
+fn luby(mut index: usize) -> usize {
+    loop {
+        let mut length = 1;
+
+        while length < index {
+            length = length * 2 + 1;
+        }
+
+        if length == index {
+            return length.div_ceil(2);
+        }
+
+        index -= length / 2;
+    }
+}
+
+/// `weight` raised to the power of `1 / temperature`, see `WaveBuilder::temperature`
+fn temper(weight: f64, temperature: f64) -> f64 {
+    if temperature == 1.0 {
+        weight
+    } else {
+        weight.powf(temperature.recip())
+    }
+}
+
+/// Rng for the area with `key`. The stream carries on through rollbacks and resets, so
+/// the area doesn't replay into the same contradiction.
+fn area_rng(seed: u64, key: usize) -> XorShiftRng {
+    XorShiftRng::seed_from_u64(mix(seed ^ mix(key as u64)))
+}
+
+/// splitmix64 finalizer, spreads nearby inputs over the whole range
+fn mix(value: u64) -> u64 {
+    let mut value = value.wrapping_add(0x9E37_79B9_7F4A_7C15);
+
+    value = (value ^ (value >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    value = (value ^ (value >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+
+    value ^ (value >> 31)
+}
+
+/// Cells per entropy of `grid`, no cell has more than `tiles` possible tiles
+fn count_entropies<T: Collapsable>(grid: &Grid<SuperState<T>>, tiles: usize) -> Vec<usize> {
+    let mut entropies = vec![0; tiles + 1];
+
+    for (_, _, cell) in grid {
+        entropies[cell.entropy()] += 1;
+    }
+
+    entropies
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tile::Tile;
+    use crate::topology::Graph;
+
+    /// Tiles `0..count` that allow every tile next to them when `distinct` is false,
+    /// and every tile but themselves when it's true
+    fn tileset(count: u64, distinct: bool) -> Vec<Arc<Tile<()>>> {
+        (0..count)
+            .map(|id| {
+                let mut tile = Tile::new(id, ());
+                let neighbors: Set<u64> = (0..count).filter(|other| !distinct || *other != id).collect();
+
+                for (_, allowed) in tile.neighbors.iter_mut() {
+                    *allowed = neighbors.clone();
+                }
+
+                Arc::new(tile)
+            })
+            .collect()
+    }
+
+    fn wave(tiles: Vec<Arc<Tile<()>>>, width: usize, height: usize, seed: u64) -> Wave<Tile<()>> {
+        let base = SuperState::new(tiles);
+
+        Wave::new(Grid::new(width, height, &mut |_, _| base.clone()), seed)
+    }
+
+    /// Ids of the tiles in every cell, None for cells that aren't collapsed
+    fn tiles<G>(wave: &Wave<Tile<()>, G>) -> Vec<Option<u64>> {
+        wave.grid.iter().map(|(_, _, cell)| cell.collapsed().map(|tile| tile.get_id())).collect()
+    }
+
+    #[test]
+    fn propagation_follows_the_topology() {
+        const SIZE: usize = 6;
+
+        // a torus, the edges of the grid wrap around to the other side
+        let mut torus = Graph::new(SIZE * SIZE);
+
+        for y in 0..SIZE {
+            for x in 0..SIZE {
+                let node = x + y * SIZE;
+
+                torus.connect(node, (x + 1) % SIZE + y * SIZE, Direction::Right);
+                torus.connect(node, (x + SIZE - 1) % SIZE + y * SIZE, Direction::Left);
+                torus.connect(node, x + (y + 1) % SIZE * SIZE, Direction::Down);
+                torus.connect(node, x + (y + SIZE - 1) % SIZE * SIZE, Direction::Up);
+            }
+        }
+
+        for seed in 0..8 {
+            let base = SuperState::new(tileset(3, true));
+            let grid = Grid::new(SIZE, SIZE, &mut |_, _| base.clone());
+            let mut wave = WaveBuilder::with_topology(grid, torus.clone()).seed(seed).build();
+
+            for _ in 0..1000 {
+                if wave.done() {
+                    break;
+                }
+
+                wave.tick();
+            }
+
+            assert!(wave.done());
+
+            let tiles = tiles(&wave);
+
+            for index in 0..SIZE {
+                // the first and last of every row and column are neighbors on a torus
+                assert_ne!(tiles[index * SIZE], tiles[index * SIZE + SIZE - 1]);
+                assert_ne!(tiles[index], tiles[index + (SIZE - 1) * SIZE]);
+            }
+        }
+    }
+
+    #[test]
+    fn rollback_spreads_over_a_large_grid() {
+        const SIZE: usize = 500;
+
+        let mut wave = wave(tileset(3, false), SIZE, SIZE, 0);
+
+        // every cell narrowed down while its neighbors allow anything, so resetting one
+        // resets its neighbor and so on, a walk as long as the grid is large
+        for y in 0..SIZE {
+            for x in 0..SIZE {
+                let mut cell = wave.grid.get(x, y).unwrap().clone();
+
+                cell.retain_indexes(|index| index != 2);
+                wave.set_cell(x, y, cell);
+            }
+        }
+
+        wave.rollback_propegate(0, 0);
+
+        assert!(wave.grid.iter().all(|(_, _, cell)| cell.entropy() == 3));
+    }
+
+    #[test]
+    fn frontier_requeue_matches_a_full_sweep() {
+        let (mut frontier_ticks, mut full_ticks, mut rollbacks) = (0, 0, 0);
+
+        for seed in 0..8 {
+            let mut frontier = wave(tileset(3, true), 16, 16, seed);
+            let mut full = wave(tileset(3, true), 16, 16, seed);
+
+            while !frontier.done() {
+                frontier.tick_once();
+                frontier_ticks += 1;
+            }
+
+            while !full.done() {
+                let before = full.rollbacks();
+
+                full.tick_once();
+                full_ticks += 1;
+
+                // how rollbacks were propagated before, the whole grid queued again
+                if full.rollbacks() != before {
+                    full.sweep.extend(0..full.grid.size());
+                    full.sweep_next = Some(0);
+                }
+            }
+
+            assert_eq!(tiles(&frontier), tiles(&full), "seed {seed}");
+            rollbacks += frontier.rollbacks();
+        }
+
+        assert!(rollbacks > 0, "no seed ran into a contradiction");
+        assert!(frontier_ticks < full_ticks, "{frontier_ticks} ticks, {full_ticks} with full sweeps");
+    }
+
+    #[test]
+    fn unpin_drops_the_checkpoints_taken_since() {
+        let mut wave = wave(tileset(3, false), 8, 8, 0);
+
+        wave.pin(0, 0, 1).unwrap();
+
+        while wave.checkpoints.is_empty() && !wave.done() {
+            wave.tick_once();
+        }
+
+        assert_eq!(wave.checkpoints.len(), 1);
+
+        wave.unpin(0, 0).unwrap();
+
+        assert!(wave.checkpoints.is_empty());
+
+        // counts the collapses since the last checkpoint, which can't be more than now
+        wave.smart_rollback();
+        wave.tick();
+    }
+
+    #[test]
+    fn area_seeds_keep_edits_to_their_area() {
+        let base = SuperState::new(tileset(4, false));
+        let grid = Grid::new(7, 4, &mut |_, _| base.clone());
+
+        for seed in 0..8 {
+            let mut edited = WaveBuilder::new(grid.clone()).seed(seed).area_seeds(true).build();
+            let mut untouched = WaveBuilder::new(grid.clone()).seed(seed).area_seeds(true).build();
+
+            // a column of pins splits the grid in two areas
+            for wave in [&mut edited, &mut untouched] {
+                for y in 0..4 {
+                    wave.pin(3, y, 0).unwrap();
+                }
+            }
+
+            edited.pin(5, 1, 2).unwrap();
+
+            while !edited.done() {
+                edited.tick();
+            }
+
+            while !untouched.done() {
+                untouched.tick();
+            }
+
+            let left = |wave: &Wave<Tile<()>>| -> Vec<Option<u64>> {
+                tiles(wave).into_iter().enumerate().filter(|(index, _)| index % 7 < 3).map(|(_, tile)| tile).collect()
+            };
+
+            assert_eq!(left(&edited), left(&untouched), "seed {seed}");
+        }
+    }
+
+    #[cfg(feature = "gpu")]
+    #[test]
+    fn gpu_sweeps_match_the_cpu() {
+        // tiles next to each other are at most one apart, which never contradicts
+        let banded: Vec<Arc<Tile<()>>> = (0..40u64)
+            .map(|id| {
+                let mut tile = Tile::new(id, ());
+                let neighbors: Set<u64> = (id.saturating_sub(1)..=id + 1).filter(|other| *other < 40).collect();
+
+                for (_, allowed) in tile.neighbors.iter_mut() {
+                    *allowed = neighbors.clone();
+                }
+
+                Arc::new(tile)
+            })
+            .collect();
+        let base = SuperState::new(banded);
+        let grid = Grid::new(24, 24, &mut |_, _| base.clone());
+        let mut cpu = WaveBuilder::new(grid.clone()).seed(7).build();
+        // every propagation goes to the GPU
+        let mut gpu = WaveBuilder::new(grid).seed(7).gpu(1).build();
+
+        if gpu.gpu.is_none() {
+            eprintln!("no GPU to test on");
+            return;
+        }
+
+        while !cpu.done() {
+            cpu.tick();
+        }
+
+        while !gpu.done() {
+            gpu.tick();
+        }
+
+        assert_eq!(cpu.rollbacks(), 0);
+        assert!(gpu.gpu.is_some(), "the GPU failed halfway");
+        assert_eq!(tiles(&cpu), tiles(&gpu));
+    }
+}
@@ -0,0 +1,127 @@
+//! Solves every type in `samples` end to end, through the same public API a library
+//! user would go through.
+
+use std::sync::Arc;
+
+use wave_function_collapse::grid::{Direction, Grid};
+use wave_function_collapse::samples::{Pipe, Sudoku, Terrain};
+use wave_function_collapse::superstate::{Collapsable, SuperState};
+use wave_function_collapse::wave::Wave;
+
+/// Ticks `wave` until it's done, panics if that takes more than `limit` ticks
+fn solve<T: Collapsable>(wave: &mut Wave<T>, limit: usize) {
+    for _ in 0..limit {
+        if wave.done() {
+            return;
+        }
+
+        wave.tick();
+    }
+
+    assert!(wave.done(), "{} cells left after {limit} ticks", wave.remaining());
+}
+
+fn tile<T: Collapsable>(wave: &Wave<T>, x: usize, y: usize) -> &T {
+    let mut tiles = wave.possibilities(x, y);
+    let tile = tiles.next().expect("an empty cell");
+
+    assert!(tiles.next().is_none(), "({x}, {y}) isn't collapsed");
+
+    tile
+}
+
+fn wave<T: Collapsable>(tiles: Vec<T>, width: usize, height: usize, seed: u64) -> Wave<T> {
+    let base = SuperState::new(tiles.into_iter().map(Arc::new).collect());
+
+    Wave::new(Grid::new(width, height, &mut |_, _| base.clone()), seed)
+}
+
+#[test]
+fn terrain_only_borders_the_next_kind() {
+    const SIZE: usize = 16;
+
+    for seed in 0..4 {
+        let mut wave = wave(Terrain::ALL.to_vec(), SIZE, SIZE, seed);
+
+        solve(&mut wave, 10_000);
+
+        for y in 0..SIZE {
+            for x in 0..SIZE {
+                let level = *tile(&wave, x, y) as i32;
+
+                if x + 1 < SIZE {
+                    assert!((*tile(&wave, x + 1, y) as i32 - level).abs() <= 1);
+                }
+
+                if y + 1 < SIZE {
+                    assert!((*tile(&wave, x, y + 1) as i32 - level).abs() <= 1);
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn pipes_connect_open_sides() {
+    const SIZE: usize = 12;
+
+    for seed in 0..4 {
+        let mut wave = wave(Pipe::all(), SIZE, SIZE, seed);
+
+        solve(&mut wave, 10_000);
+
+        for y in 0..SIZE {
+            for x in 0..SIZE {
+                let pipe = tile(&wave, x, y);
+
+                if x + 1 < SIZE {
+                    let right = tile(&wave, x + 1, y);
+
+                    assert_eq!(pipe.opens(Direction::Right), right.opens(Direction::Left));
+                }
+
+                if y + 1 < SIZE {
+                    let below = tile(&wave, x, y + 1);
+
+                    assert_eq!(pipe.opens(Direction::Down), below.opens(Direction::Up));
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn sudoku_keeps_the_givens_and_the_rules() {
+    const PUZZLE: &str = "
+        53..7.... 6..195... .98....6.
+        8...6...3 4..8.3..1 7...2...6
+        .6....28. ...419..5 ....8..79";
+
+    let givens: Vec<char> = PUZZLE.chars().filter(|c| !c.is_whitespace()).collect();
+    let mut wave = Sudoku::wave(PUZZLE, 1).unwrap();
+
+    solve(&mut wave, 100_000);
+
+    let digits: Vec<u8> = (0..81).map(|index| tile(&wave, index % 9, index / 9).0).collect();
+
+    for (given, digit) in givens.iter().zip(&digits) {
+        if let Some(given) = given.to_digit(10) {
+            assert_eq!(given as u8, *digit);
+        }
+    }
+
+    for group in 0..9 {
+        let row: Vec<u8> = (0..9).map(|x| digits[group * 9 + x]).collect();
+        let column: Vec<u8> = (0..9).map(|y| digits[y * 9 + group]).collect();
+        let square: Vec<u8> = (0..9)
+            .map(|cell| digits[(group / 3 * 3 + cell / 3) * 9 + group % 3 * 3 + cell % 3])
+            .collect();
+
+        for digits in [row, column, square] {
+            let mut digits = digits;
+
+            digits.sort_unstable();
+            assert_eq!(digits, (1..=9).collect::<Vec<u8>>());
+        }
+    }
+}